@@ -1,28 +1,213 @@
 //! A simple free-fly camera plugin for Bevy. It borrow heavily from the bevy plugin
 //! `bevy_flycam` but use the newest Bevy engine version and is integrated with the
 //! Amethyst engine.
+use crate::cursor::CursorControl;
+use crate::touch::TouchControlsPlugin;
+use amethyst_render::bounds::Frustum;
+use amethyst_render::dof::DofProjection;
+use amethyst_render::view::RenderView;
 use bevy::ecs::event::{EventCursor, Events};
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
+use serde::{Deserialize, Serialize};
 
-/// A simple 3D camera
-#[derive(Default, Debug, Component)]
+/// A simple 3D camera with a perspective projection
+#[derive(Debug, Component)]
 pub struct Camera3D {
     pub transform: Transform,
+
+    /// The vertical field of view, in radians.
+    pub fov: f32,
+
+    /// The distance of the near clipping plane. Must be greater than 0.0.
+    pub near: f32,
+
+    /// The distance of the far clipping plane. Must be greater than [`Camera3D::near`].
+    pub far: f32,
+
+    /// An aspect ratio (width / height) that overrides the one of the primary window. This
+    /// is useful when rendering to a target that does not have the same size as the window.
+    pub aspect_override: Option<f32>,
+
+    /// The aspect ratio of the primary window, kept in sync with its size by the
+    /// [`CameraPlugin`]. Use [`Camera3D::aspect_override`] to render with another aspect ratio.
+    pub window_aspect: f32,
 }
 
+impl Default for Camera3D {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            fov: std::f32::consts::FRAC_PI_4,
+            near: 0.1,
+            far: 1000.0,
+            aspect_override: None,
+            window_aspect: 1.0,
+        }
+    }
+}
+
+impl Camera3D {
+    /// Returns the aspect ratio used by the projection, which is either the aspect override
+    /// or the aspect ratio of the primary window.
+    #[must_use]
+    pub fn aspect(&self) -> f32 {
+        self.aspect_override.unwrap_or(self.window_aspect)
+    }
+
+    /// Returns the view matrix of the camera, which transforms world space coordinates into
+    /// the camera space.
+    #[must_use]
+    pub fn view_matrix(&self) -> Mat4 {
+        self.transform.compute_matrix().inverse()
+    }
+
+    /// Returns the perspective projection matrix of the camera. The matrix follows the Vulkan
+    /// conventions: the Y axis points down in clip space and the depth range is `[0, 1]`.
+    #[must_use]
+    pub fn projection_matrix(&self) -> Mat4 {
+        let mut projection = Mat4::perspective_rh(self.fov, self.aspect(), self.near, self.far);
+
+        // Flip the Y axis, since Vulkan clip space has its Y axis pointing down.
+        projection.y_axis.y = -projection.y_axis.y;
+        projection
+    }
+
+    /// Returns the combined view and projection matrix of the camera.
+    #[must_use]
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// Returns the view frustum of the camera, which can be used to check whether an object
+    /// is visible or not.
+    #[must_use]
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_view_projection(self.view_projection())
+    }
+
+    /// Returns the projection parameters used by the depth of field pass.
+    #[must_use]
+    pub const fn dof_projection(&self) -> DofProjection {
+        DofProjection {
+            fov: self.fov,
+            near: self.near,
+            far: self.far,
+        }
+    }
+}
+
+/// An orthographic camera, used for 2D and isometric games. The camera looks along its local
+/// `-Z` axis, so an isometric view can be obtained by simply rotating its transform.
+#[derive(Debug, Component)]
+pub struct Camera2D {
+    pub transform: Transform,
+
+    /// The zoom factor of the camera. A value greater than 1.0 zooms in, and a value lower
+    /// than 1.0 zooms out.
+    pub zoom: f32,
+
+    /// The minimum and maximum zoom factor allowed by [`Camera2D::zoom_by`].
+    pub zoom_limits: (f32, f32),
+
+    /// The number of screen pixels used to display one world unit when the zoom is 1.0. This
+    /// allows pixel-art games to work with world units that map to an exact number of pixels.
+    pub pixel_scale: f32,
+
+    /// The distance of the near clipping plane.
+    pub near: f32,
+
+    /// The distance of the far clipping plane.
+    pub far: f32,
+}
+
+impl Default for Camera2D {
+    fn default() -> Self {
+        Self {
+            transform: Transform::default(),
+            zoom: 1.0,
+            zoom_limits: (0.1, 10.0),
+            pixel_scale: 1.0,
+            near: -1000.0,
+            far: 1000.0,
+        }
+    }
+}
+
+impl Camera2D {
+    /// Multiply the zoom of the camera by the given factor, clamped to the zoom limits.
+    pub fn zoom_by(&mut self, factor: f32) {
+        self.zoom = (self.zoom * factor).clamp(self.zoom_limits.0, self.zoom_limits.1);
+    }
+
+    /// Returns the size of the area visible by the camera, in world units, for a viewport of
+    /// the given size in pixels.
+    #[must_use]
+    pub fn visible_area(&self, viewport: Vec2) -> Vec2 {
+        viewport / (self.pixel_scale * self.zoom)
+    }
+
+    /// Returns the view matrix of the camera, which transforms world space coordinates into
+    /// the camera space.
+    #[must_use]
+    pub fn view_matrix(&self) -> Mat4 {
+        self.transform.compute_matrix().inverse()
+    }
+
+    /// Returns the orthographic projection matrix of the camera for a viewport of the given
+    /// size in pixels. The matrix follows the Vulkan conventions: the Y axis points down in
+    /// clip space and the depth range is `[0, 1]`.
+    #[must_use]
+    pub fn projection_matrix(&self, viewport: Vec2) -> Mat4 {
+        let half = self.visible_area(viewport) * 0.5;
+
+        // Bottom and top are swapped to flip the Y axis, since Vulkan clip space has
+        // its Y axis pointing down.
+        Mat4::orthographic_rh(-half.x, half.x, half.y, -half.y, self.near, self.far)
+    }
+
+    /// Returns the view frustum of the camera for a viewport of the given size in pixels.
+    #[must_use]
+    pub fn frustum(&self, viewport: Vec2) -> Frustum {
+        Frustum::from_view_projection(self.projection_matrix(viewport) * self.view_matrix())
+    }
+}
+
+/// The transform a [`Camera3D`] is moving towards. Camera controllers write into this component
+/// instead of the camera transform, and the camera transform is then smoothly interpolated
+/// towards it, making the motion independent of the frame rate. This component is automatically
+/// added to every [`Camera3D`].
+#[derive(Debug, Clone, Copy, Component)]
+pub struct CameraTarget(pub Transform);
+
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource, Default)]
 struct InputState {
     reader_motion: EventCursor<MouseMotion>,
 }
 
-/// Mouse sensitivity and movement speed
-#[derive(Resource)]
+/// Mouse sensitivity, gamepad settings and movement speed
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MovementSettings {
     pub sensitivity: f32,
     pub speed: f32,
+
+    /// The look speed when using a gamepad stick, in radians per second at full deflection.
+    pub gamepad_sensitivity: f32,
+
+    /// The radial dead-zone applied to the gamepad sticks, between 0.0 and 1.0. Any stick
+    /// deflection below this value is ignored to avoid drifting.
+    pub gamepad_dead_zone: f32,
+
+    /// The exponential smoothing rate applied to the camera position, per second. Higher
+    /// values make the camera snappier, and 0.0 disables the smoothing entirely.
+    pub position_smoothing: f32,
+
+    /// The exponential smoothing rate applied to the camera rotation, per second. Higher
+    /// values make the camera snappier, and 0.0 disables the smoothing entirely.
+    pub rotation_smoothing: f32,
 }
 
 impl Default for MovementSettings {
@@ -30,12 +215,17 @@ impl Default for MovementSettings {
         Self {
             sensitivity: 0.00012,
             speed: 12.,
+            gamepad_sensitivity: 2.5,
+            gamepad_dead_zone: 0.15,
+            position_smoothing: 20.0,
+            rotation_smoothing: 30.0,
         }
     }
 }
 
 /// Key configuration
-#[derive(Resource)]
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct KeyBindings {
     pub move_forward: KeyCode,
     pub move_backward: KeyCode,
@@ -60,33 +250,117 @@ impl Default for KeyBindings {
     }
 }
 
-/// A marker component used in queries when you want flycams and not other cameras
-#[derive(Component)]
-pub struct FlyCam;
+/// An action that can be bound to a key in [`KeyBindings`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum KeyAction {
+    MoveForward,
+    MoveBackward,
+    MoveLeft,
+    MoveRight,
+    MoveAscend,
+    MoveDescend,
+    ToggleGrabCursor,
+}
 
-/// Grabs/ungrabs mouse cursor
-fn toggle_grab_cursor(window: &mut Window) {
-    match window.cursor_options.grab_mode {
-        CursorGrabMode::None => {
-            window.cursor_options.grab_mode = CursorGrabMode::Confined;
-            window.cursor_options.visible = false;
+impl KeyBindings {
+    /// Returns the key bound to the given action.
+    #[must_use]
+    pub fn get(&self, action: KeyAction) -> KeyCode {
+        match action {
+            KeyAction::MoveForward => self.move_forward,
+            KeyAction::MoveBackward => self.move_backward,
+            KeyAction::MoveLeft => self.move_left,
+            KeyAction::MoveRight => self.move_right,
+            KeyAction::MoveAscend => self.move_ascend,
+            KeyAction::MoveDescend => self.move_descend,
+            KeyAction::ToggleGrabCursor => self.toggle_grab_cursor,
         }
-        _ => {
-            window.cursor_options.grab_mode = CursorGrabMode::None;
-            window.cursor_options.visible = true;
+    }
+
+    /// Bind the given key to the given action. If the key was already bound to another action,
+    /// the two actions swap their keys so that no key is bound twice.
+    pub fn rebind(&mut self, action: KeyAction, key: KeyCode) {
+        let previous = self.get(action);
+        if let Some(other) = self.action_of(key) {
+            *self.slot_mut(other) = previous;
+        }
+        *self.slot_mut(action) = key;
+    }
+
+    /// Returns the action bound to the given key, if any.
+    #[must_use]
+    pub fn action_of(&self, key: KeyCode) -> Option<KeyAction> {
+        KeyAction::ALL
+            .into_iter()
+            .find(|&action| self.get(action) == key)
+    }
+
+    /// Returns a mutable reference to the key bound to the given action.
+    fn slot_mut(&mut self, action: KeyAction) -> &mut KeyCode {
+        match action {
+            KeyAction::MoveForward => &mut self.move_forward,
+            KeyAction::MoveBackward => &mut self.move_backward,
+            KeyAction::MoveLeft => &mut self.move_left,
+            KeyAction::MoveRight => &mut self.move_right,
+            KeyAction::MoveAscend => &mut self.move_ascend,
+            KeyAction::MoveDescend => &mut self.move_descend,
+            KeyAction::ToggleGrabCursor => &mut self.toggle_grab_cursor,
         }
     }
 }
 
-/// Grabs the cursor when game first starts
-fn initial_grab_cursor(mut primary_window: Query<&mut Window, With<PrimaryWindow>>) {
-    if let Ok(mut window) = primary_window.get_single_mut() {
-        toggle_grab_cursor(&mut window);
-    } else {
-        warn!("Primary window not found for `initial_grab_cursor`!");
+impl KeyAction {
+    /// All the actions that can be bound to a key.
+    pub const ALL: [KeyAction; 7] = [
+        KeyAction::MoveForward,
+        KeyAction::MoveBackward,
+        KeyAction::MoveLeft,
+        KeyAction::MoveRight,
+        KeyAction::MoveAscend,
+        KeyAction::MoveDescend,
+        KeyAction::ToggleGrabCursor,
+    ];
+}
+
+/// When set to an action, the next key pressed by the user is bound to this action and the
+/// resource is reset to `None`. This allows games to implement a "press a key" rebinding menu.
+#[derive(Resource, Debug, Default)]
+pub struct PendingRebind(pub Option<KeyAction>);
+
+/// Gamepad configuration
+#[derive(Resource, Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamepadBindings {
+    pub move_x: GamepadAxis,
+    pub move_y: GamepadAxis,
+    pub look_x: GamepadAxis,
+    pub look_y: GamepadAxis,
+    pub move_ascend: GamepadButton,
+    pub move_descend: GamepadButton,
+}
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self {
+            move_x: GamepadAxis::LeftStickX,
+            move_y: GamepadAxis::LeftStickY,
+            look_x: GamepadAxis::RightStickX,
+            look_y: GamepadAxis::RightStickY,
+            move_ascend: GamepadButton::RightTrigger,
+            move_descend: GamepadButton::LeftTrigger,
+        }
     }
 }
 
+/// A marker component used in queries when you want flycams and not other cameras
+#[derive(Component)]
+pub struct FlyCam;
+
+/// Grabs the cursor when game first starts
+fn initial_grab_cursor(mut cursor: CursorControl) {
+    cursor.toggle_grab();
+}
+
 /// Spawns the `Camera3D` with a `FlyCam` marker
 fn setup_player(mut commands: Commands) {
     commands.spawn((
@@ -105,12 +379,12 @@ fn player_move(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     settings: Res<MovementSettings>,
     key_bindings: Res<KeyBindings>,
-    mut query: Query<&mut Camera3D, With<FlyCam>>,
+    mut query: Query<&mut CameraTarget, With<FlyCam>>,
 ) {
     if let Ok(window) = primary_window.get_single() {
-        for mut camera in query.iter_mut() {
+        for mut target in query.iter_mut() {
             let mut velocity = Vec3::ZERO;
-            let local_z = camera.transform.local_z();
+            let local_z = target.0.local_z();
             let forward = -Vec3::new(local_z.x, 0., local_z.z);
             let right = Vec3::new(local_z.z, 0., -local_z.x);
 
@@ -137,7 +411,7 @@ fn player_move(
             }
 
             velocity = velocity.normalize_or_zero();
-            camera.transform.translation += velocity * time.delta_secs() * settings.speed
+            target.0.translation += velocity * time.delta_secs() * settings.speed
         }
     } else {
         warn!("Primary window not found for `player_move`!");
@@ -150,12 +424,12 @@ fn player_look(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     mut state: ResMut<InputState>,
     motion: Res<Events<MouseMotion>>,
-    mut query: Query<&mut Camera3D, With<FlyCam>>,
+    mut query: Query<&mut CameraTarget, With<FlyCam>>,
 ) {
     if let Ok(window) = primary_window.get_single() {
-        for mut camera in query.iter_mut() {
+        for mut target in query.iter_mut() {
             for ev in state.reader_motion.read(&motion) {
-                let (mut yaw, mut pitch, _) = camera.transform.rotation.to_euler(EulerRot::YXZ);
+                let (mut yaw, mut pitch, _) = target.0.rotation.to_euler(EulerRot::YXZ);
                 match window.cursor_options.grab_mode {
                     CursorGrabMode::None => (),
                     _ => {
@@ -170,7 +444,7 @@ fn player_look(
                 pitch = pitch.clamp(-1.54, 1.54);
 
                 // Order is important to prevent unintended roll
-                camera.transform.rotation =
+                target.0.rotation =
                     Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
             }
         }
@@ -179,33 +453,248 @@ fn player_look(
     }
 }
 
+/// Apply a radial dead-zone to a stick value and rescale the remaining range so the output
+/// still goes smoothly from 0.0 to 1.0.
+fn apply_dead_zone(stick: Vec2, dead_zone: f32) -> Vec2 {
+    let length = stick.length();
+    if length <= dead_zone {
+        return Vec2::ZERO;
+    }
+
+    let scaled = ((length - dead_zone) / (1.0 - dead_zone)).min(1.0);
+    stick * (scaled / length)
+}
+
+/// Read the two axes of a stick from a gamepad
+fn read_stick(gamepad: &Gamepad, x: GamepadAxis, y: GamepadAxis) -> Vec2 {
+    Vec2::new(gamepad.get(x).unwrap_or(0.0), gamepad.get(y).unwrap_or(0.0))
+}
+
+/// Returns the input with the largest magnitude, or zero if there is none. This is used to
+/// follow a single gamepad when several are connected, instead of adding up their inputs.
+fn strongest_input(inputs: impl IntoIterator<Item = Vec3>) -> Vec3 {
+    inputs
+        .into_iter()
+        .max_by(|a, b| a.length_squared().total_cmp(&b.length_squared()))
+        .unwrap_or(Vec3::ZERO)
+}
+
+/// Handles gamepad movement
+fn player_gamepad_move(
+    time: Res<Time>,
+    settings: Res<MovementSettings>,
+    bindings: Res<GamepadBindings>,
+    gamepads: Query<&Gamepad>,
+    mut query: Query<&mut CameraTarget, With<FlyCam>>,
+) {
+    // The input of each gamepad is stored as (right, up, forward)
+    let input = strongest_input(gamepads.iter().map(|gamepad| {
+        let stick = read_stick(gamepad, bindings.move_x, bindings.move_y);
+        let stick = apply_dead_zone(stick, settings.gamepad_dead_zone);
+
+        let mut vertical = 0.0;
+        if gamepad.pressed(bindings.move_ascend) {
+            vertical += 1.0;
+        }
+        if gamepad.pressed(bindings.move_descend) {
+            vertical -= 1.0;
+        }
+        Vec3::new(stick.x, vertical, stick.y)
+    }));
+
+    if input == Vec3::ZERO {
+        return;
+    }
+
+    for mut target in query.iter_mut() {
+        let local_z = target.0.local_z();
+        let forward = -Vec3::new(local_z.x, 0., local_z.z).normalize_or_zero();
+        let right = Vec3::new(local_z.z, 0., -local_z.x).normalize_or_zero();
+
+        // Do not normalize the velocity to keep the analog precision of the stick, but
+        // clamp it to avoid moving faster than with the keyboard.
+        let velocity = forward * input.z + right * input.x + Vec3::Y * input.y;
+        let velocity = velocity.clamp_length_max(1.0);
+        target.0.translation += velocity * time.delta_secs() * settings.speed;
+    }
+}
+
+/// Handles gamepad looking around
+fn player_gamepad_look(
+    time: Res<Time>,
+    settings: Res<MovementSettings>,
+    bindings: Res<GamepadBindings>,
+    gamepads: Query<&Gamepad>,
+    mut query: Query<&mut CameraTarget, With<FlyCam>>,
+) {
+    let stick = strongest_input(gamepads.iter().map(|gamepad| {
+        let stick = read_stick(gamepad, bindings.look_x, bindings.look_y);
+        apply_dead_zone(stick, settings.gamepad_dead_zone).extend(0.0)
+    }))
+    .truncate();
+
+    if stick == Vec2::ZERO {
+        return;
+    }
+
+    for mut target in query.iter_mut() {
+        let (mut yaw, mut pitch, _) = target.0.rotation.to_euler(EulerRot::YXZ);
+        yaw -= stick.x * settings.gamepad_sensitivity * time.delta_secs();
+        pitch += stick.y * settings.gamepad_sensitivity * time.delta_secs();
+        pitch = pitch.clamp(-1.54, 1.54);
+
+        // Order is important to prevent unintended roll
+        target.0.rotation =
+            Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+    }
+}
+
+/// Binds the next pressed key to the pending rebind action, if any. The key press is consumed,
+/// so binding a key that already triggers an action (like toggling the cursor grab) does not
+/// also trigger it during the same frame.
+fn capture_rebind(
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    mut pending: ResMut<PendingRebind>,
+    mut key_bindings: ResMut<KeyBindings>,
+) {
+    let Some(action) = pending.0 else {
+        return;
+    };
+
+    let pressed = keys.get_just_pressed().next().copied();
+    if let Some(key) = pressed {
+        keys.clear_just_pressed(key);
+        key_bindings.rebind(action, key);
+        pending.0 = None;
+    }
+}
+
 fn cursor_grab(
     keys: Res<ButtonInput<KeyCode>>,
     key_bindings: Res<KeyBindings>,
-    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut cursor: CursorControl,
 ) {
-    if let Ok(mut window) = primary_window.get_single_mut() {
-        if keys.just_pressed(key_bindings.toggle_grab_cursor) {
-            toggle_grab_cursor(&mut window);
-        }
-    } else {
-        warn!("Primary window not found for `cursor_grab`!");
+    if keys.just_pressed(key_bindings.toggle_grab_cursor) {
+        cursor.toggle_grab();
     }
 }
 
 // Grab cursor when an entity with FlyCam is added
 fn initial_grab_on_flycam_spawn(
-    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+    mut cursor: CursorControl,
     query_added: Query<Entity, Added<FlyCam>>,
 ) {
     if query_added.is_empty() {
         return;
     }
 
-    if let Ok(window) = &mut primary_window.get_single_mut() {
-        toggle_grab_cursor(window);
-    } else {
-        warn!("Primary window not found for `initial_grab_cursor`!");
+    cursor.toggle_grab();
+}
+
+/// Keeps the aspect ratio of the cameras in sync with the primary window size
+fn update_camera_aspect(
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut query: Query<&mut Camera3D>,
+) {
+    let Ok(window) = primary_window.get_single() else {
+        return;
+    };
+
+    // A minimized window may have a null height: keep the previous aspect ratio
+    // in that case instead of producing an infinite one.
+    if window.width() <= 0.0 || window.height() <= 0.0 {
+        return;
+    }
+
+    let aspect = window.width() / window.height();
+    for mut camera in query.iter_mut() {
+        if camera.window_aspect != aspect {
+            camera.window_aspect = aspect;
+        }
+    }
+}
+
+/// Adds a [`CameraTarget`] to every new camera, initialized with the current camera transform
+fn init_camera_target(
+    mut commands: Commands,
+    query: Query<(Entity, &Camera3D), Without<CameraTarget>>,
+) {
+    for (entity, camera) in query.iter() {
+        commands
+            .entity(entity)
+            .insert(CameraTarget(camera.transform));
+    }
+}
+
+/// Moves the cameras towards their target using a frame-rate independent exponential smoothing
+fn smooth_camera(
+    time: Res<Time>,
+    settings: Res<MovementSettings>,
+    mut query: Query<(&mut Camera3D, &CameraTarget)>,
+) {
+    let factor = |rate: f32| {
+        if rate > 0.0 {
+            1.0 - (-rate * time.delta_secs()).exp()
+        } else {
+            1.0
+        }
+    };
+
+    let position_factor = factor(settings.position_smoothing);
+    let rotation_factor = factor(settings.rotation_smoothing);
+
+    for (mut camera, target) in query.iter_mut() {
+        let transform = &mut camera.transform;
+        transform.translation = transform
+            .translation
+            .lerp(target.0.translation, position_factor);
+        transform.rotation = transform.rotation.slerp(target.0.rotation, rotation_factor);
+    }
+}
+
+/// Render the scene through the first [`Camera3D`], using its view and projection matrices
+/// once its transform has been smoothed for the frame. Scenes without any 3D camera are
+/// rendered through the first [`Camera2D`], whose projection covers the primary window.
+fn extract_camera_view(
+    cameras: Query<&Camera3D>,
+    cameras_2d: Query<&Camera2D>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut view: ResMut<RenderView>,
+) {
+    if let Some(camera) = cameras.iter().next() {
+        *view = RenderView {
+            view: camera.view_matrix(),
+            projection: camera.projection_matrix(),
+        };
+    } else if let Some(camera) = cameras_2d.iter().next() {
+        // Keep the previous view while the window is minimized, since an empty viewport
+        // would produce an infinite projection.
+        let Ok(window) = primary_window.get_single() else {
+            return;
+        };
+        let viewport = window.physical_size().as_vec2();
+        if viewport.x <= 0.0 || viewport.y <= 0.0 {
+            return;
+        }
+
+        *view = RenderView {
+            view: camera.view_matrix(),
+            projection: camera.projection_matrix(viewport),
+        };
+    }
+}
+
+/// The core camera systems shared by every camera controller: keeps the [`Camera3D`] aspect
+/// ratio in sync with the primary window and its transform in sync with its [`CameraTarget`],
+/// with optional smoothing, and renders the scene through the first camera. This plugin is
+/// automatically added by the camera controller plugins.
+pub struct CameraPlugin;
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MovementSettings>()
+            .init_resource::<RenderView>()
+            .add_systems(PreUpdate, (init_camera_target, update_camera_aspect))
+            .add_systems(PostUpdate, (smooth_camera, extract_camera_view).chain());
     }
 }
 
@@ -216,11 +705,23 @@ impl Plugin for PlayerPlugin {
         app.init_resource::<InputState>()
             .init_resource::<MovementSettings>()
             .init_resource::<KeyBindings>()
+            .init_resource::<GamepadBindings>()
+            .init_resource::<PendingRebind>()
             .add_systems(Startup, setup_player)
             .add_systems(Startup, initial_grab_cursor)
             .add_systems(Update, player_move)
             .add_systems(Update, player_look)
+            .add_systems(Update, player_gamepad_move)
+            .add_systems(Update, player_gamepad_look)
+            .add_systems(Update, capture_rebind.before(cursor_grab))
             .add_systems(Update, cursor_grab);
+
+        if !app.is_plugin_added::<CameraPlugin>() {
+            app.add_plugins(CameraPlugin);
+        }
+        if !app.is_plugin_added::<TouchControlsPlugin>() {
+            app.add_plugins(TouchControlsPlugin);
+        }
     }
 }
 
@@ -231,10 +732,133 @@ impl Plugin for NoCameraPlayerPlugin {
         app.init_resource::<InputState>()
             .init_resource::<MovementSettings>()
             .init_resource::<KeyBindings>()
+            .init_resource::<GamepadBindings>()
+            .init_resource::<PendingRebind>()
             .add_systems(Startup, initial_grab_cursor)
             .add_systems(Startup, initial_grab_on_flycam_spawn)
             .add_systems(Update, player_move)
             .add_systems(Update, player_look)
+            .add_systems(Update, player_gamepad_move)
+            .add_systems(Update, player_gamepad_look)
+            .add_systems(Update, capture_rebind.before(cursor_grab))
             .add_systems(Update, cursor_grab);
+
+        if !app.is_plugin_added::<CameraPlugin>() {
+            app.add_plugins(CameraPlugin);
+        }
+        if !app.is_plugin_added::<TouchControlsPlugin>() {
+            app.add_plugins(TouchControlsPlugin);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rebind_to_a_free_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(KeyAction::MoveForward, KeyCode::ArrowUp);
+
+        assert_eq!(bindings.get(KeyAction::MoveForward), KeyCode::ArrowUp);
+        assert_eq!(bindings.action_of(KeyCode::KeyW), None);
+    }
+
+    #[test]
+    fn rebind_to_a_bound_key_swaps_the_actions() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(KeyAction::MoveForward, KeyCode::KeyS);
+
+        assert_eq!(bindings.get(KeyAction::MoveForward), KeyCode::KeyS);
+        assert_eq!(bindings.get(KeyAction::MoveBackward), KeyCode::KeyW);
+    }
+
+    #[test]
+    fn rebind_to_the_same_key() {
+        let mut bindings = KeyBindings::default();
+        bindings.rebind(KeyAction::MoveLeft, KeyCode::KeyA);
+
+        assert_eq!(bindings.get(KeyAction::MoveLeft), KeyCode::KeyA);
+        assert_eq!(bindings.action_of(KeyCode::KeyA), Some(KeyAction::MoveLeft));
+    }
+
+    #[test]
+    fn strongest_input_of_several_gamepads() {
+        let inputs = [
+            Vec3::new(0.5, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -0.8),
+            Vec3::ZERO,
+        ];
+        assert_eq!(strongest_input(inputs), Vec3::new(0.0, 0.0, -0.8));
+    }
+
+    #[test]
+    fn strongest_input_without_gamepads() {
+        assert_eq!(strongest_input([]), Vec3::ZERO);
+    }
+
+    #[test]
+    fn extract_camera_2d_view() {
+        let mut app = App::new();
+        app.init_resource::<RenderView>()
+            .add_systems(Update, extract_camera_view);
+        let camera = Camera2D {
+            transform: Transform::from_xyz(3.0, 4.0, 0.0),
+            zoom: 2.0,
+            ..Default::default()
+        };
+        let expected = RenderView {
+            view: camera.view_matrix(),
+            projection: camera.projection_matrix(Vec2::new(1280.0, 720.0)),
+        };
+        app.world_mut().spawn((Window::default(), PrimaryWindow));
+        app.world_mut().spawn(camera);
+        app.update();
+
+        assert_eq!(*app.world().resource::<RenderView>(), expected);
+    }
+
+    #[test]
+    fn extract_camera_3d_view_first() {
+        let mut app = App::new();
+        app.init_resource::<RenderView>()
+            .add_systems(Update, extract_camera_view);
+        let camera = Camera3D {
+            transform: Transform::from_xyz(0.0, 2.0, 5.0),
+            ..Default::default()
+        };
+        let expected = RenderView {
+            view: camera.view_matrix(),
+            projection: camera.projection_matrix(),
+        };
+        app.world_mut().spawn((Window::default(), PrimaryWindow));
+        app.world_mut().spawn(Camera2D::default());
+        app.world_mut().spawn(camera);
+        app.update();
+
+        assert_eq!(*app.world().resource::<RenderView>(), expected);
+    }
+
+    #[test]
+    fn capture_rebind_consumes_the_key() {
+        let mut app = App::new();
+        app.init_resource::<ButtonInput<KeyCode>>()
+            .init_resource::<KeyBindings>()
+            .insert_resource(PendingRebind(Some(KeyAction::MoveForward)))
+            .add_systems(Update, capture_rebind);
+        app.world_mut()
+            .resource_mut::<ButtonInput<KeyCode>>()
+            .press(KeyCode::Escape);
+        app.update();
+
+        let bindings = app.world().resource::<KeyBindings>();
+        assert_eq!(bindings.get(KeyAction::MoveForward), KeyCode::Escape);
+        assert_eq!(bindings.get(KeyAction::ToggleGrabCursor), KeyCode::KeyW);
+        assert!(app.world().resource::<PendingRebind>().0.is_none());
+        assert!(!app
+            .world()
+            .resource::<ButtonInput<KeyCode>>()
+            .just_pressed(KeyCode::Escape));
     }
 }
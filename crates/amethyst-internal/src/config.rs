@@ -0,0 +1,63 @@
+//! Loading and saving of user-editable configuration files, such as key bindings or movement
+//! settings. Configuration files are stored in the RON format, which is human-readable and easy
+//! to edit by hand.
+use crate::camera::{GamepadBindings, KeyBindings, MovementSettings};
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::Path;
+
+/// An error that can occur when loading or saving a configuration file.
+#[derive(Debug, thiserror::Error)]
+pub enum ConfigError {
+    /// The configuration file could not be read or written.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The configuration file contains invalid data.
+    #[error("Invalid configuration: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+
+    /// The configuration could not be serialized.
+    #[error("Failed to serialize configuration: {0}")]
+    Serialize(#[from] ron::Error),
+}
+
+/// A configuration that can be loaded from and saved to a file. This trait is implemented by
+/// the settings resources of the camera controllers, and can be implemented by the settings of
+/// the application with an empty `impl` block.
+pub trait ConfigFile: Serialize + DeserializeOwned {
+    /// Load the configuration from the file at the given path.
+    ///
+    /// # Errors
+    /// This function returns an error if the file cannot be read or if its content is not a
+    /// valid configuration.
+    fn load_from(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(ron::from_str(&content)?)
+    }
+
+    /// Load the configuration from the file at the given path, or return the default
+    /// configuration if the file does not exist or is invalid.
+    #[must_use]
+    fn load_or_default(path: impl AsRef<Path>) -> Self
+    where
+        Self: Default,
+    {
+        Self::load_from(path).unwrap_or_default()
+    }
+
+    /// Save the configuration to the file at the given path, overwriting it if it already
+    /// exists.
+    ///
+    /// # Errors
+    /// This function returns an error if the configuration cannot be serialized or if the
+    /// file cannot be written.
+    fn save_to(&self, path: impl AsRef<Path>) -> Result<(), ConfigError> {
+        let content = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, content)?;
+        Ok(())
+    }
+}
+
+impl ConfigFile for MovementSettings {}
+impl ConfigFile for KeyBindings {}
+impl ConfigFile for GamepadBindings {}
@@ -0,0 +1,159 @@
+//! Engine-level cursor control. This module exposes a small API to change the cursor icon, hide
+//! or show it, move it and confine or lock it inside the primary window. It is independent from
+//! any camera controller and can be used by any game state (menus, RTS edge scrolling, ...).
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+use bevy::winit::cursor::CursorIcon;
+
+/// A system parameter that gives access to the cursor of the primary window. All the methods
+/// silently do nothing (except logging a warning) if there is no primary window.
+#[derive(SystemParam)]
+pub struct CursorControl<'w, 's> {
+    commands: Commands<'w, 's>,
+    windows: Query<'w, 's, (Entity, &'static mut Window), With<PrimaryWindow>>,
+}
+
+impl CursorControl<'_, '_> {
+    /// Set the icon of the cursor when it is over the primary window. This accepts a
+    /// [`bevy::window::SystemCursorIcon`] or any other type that can be converted into
+    /// a [`CursorIcon`].
+    pub fn set_icon(&mut self, icon: impl Into<CursorIcon>) {
+        if let Ok((entity, _)) = self.windows.get_single() {
+            self.commands.entity(entity).insert(icon.into());
+        } else {
+            warn!("Primary window not found for `CursorControl::set_icon`!");
+        }
+    }
+
+    /// Show the cursor.
+    pub fn show(&mut self) {
+        self.set_visible(true);
+    }
+
+    /// Hide the cursor. The cursor is still able to move and interact with the window, it is
+    /// simply not drawn anymore.
+    pub fn hide(&mut self) {
+        self.set_visible(false);
+    }
+
+    /// Show or hide the cursor depending on the `visible` parameter.
+    pub fn set_visible(&mut self, visible: bool) {
+        if let Some(mut window) = self.window_mut() {
+            set_visible(&mut window, visible);
+        }
+    }
+
+    /// Returns whether the cursor is visible or not. If there is no primary window, this
+    /// function returns `false`.
+    #[must_use]
+    pub fn is_visible(&self) -> bool {
+        self.windows
+            .get_single()
+            .is_ok_and(|(_, window)| window.cursor_options.visible)
+    }
+
+    /// Move the cursor to the given position, in logical pixels relative to the top-left
+    /// corner of the primary window.
+    pub fn set_position(&mut self, position: Vec2) {
+        if let Some(mut window) = self.window_mut() {
+            window.set_cursor_position(Some(position));
+        }
+    }
+
+    /// Returns the position of the cursor in logical pixels relative to the top-left corner
+    /// of the primary window, or `None` if the cursor is outside of the window or if there
+    /// is no primary window.
+    #[must_use]
+    pub fn position(&self) -> Option<Vec2> {
+        self.windows
+            .get_single()
+            .ok()
+            .and_then(|(_, window)| window.cursor_position())
+    }
+
+    /// Confine the cursor inside the primary window. The cursor can still move freely inside
+    /// the window, which is what is needed for RTS-like edge scrolling.
+    pub fn confine(&mut self) {
+        if let Some(mut window) = self.window_mut() {
+            set_grab_mode(&mut window, CursorGrabMode::Confined);
+        }
+    }
+
+    /// Lock the cursor at its current position. Mouse motion events are still emitted, which
+    /// is what is needed for first-person cameras.
+    pub fn lock(&mut self) {
+        if let Some(mut window) = self.window_mut() {
+            set_grab_mode(&mut window, CursorGrabMode::Locked);
+        }
+    }
+
+    /// Release the cursor so it can freely leave the primary window.
+    pub fn release(&mut self) {
+        if let Some(mut window) = self.window_mut() {
+            set_grab_mode(&mut window, CursorGrabMode::None);
+        }
+    }
+
+    /// Returns the current grab mode of the cursor. If there is no primary window, this
+    /// function returns [`CursorGrabMode::None`].
+    #[must_use]
+    pub fn grab_mode(&self) -> CursorGrabMode {
+        self.windows
+            .get_single()
+            .map_or(CursorGrabMode::None, |(_, window)| {
+                window.cursor_options.grab_mode
+            })
+    }
+
+    /// Returns whether the cursor is currently confined or locked in the primary window.
+    #[must_use]
+    pub fn is_grabbed(&self) -> bool {
+        self.grab_mode() != CursorGrabMode::None
+    }
+
+    /// Grab and hide the cursor if it was free, or release and show it otherwise. This is
+    /// the behavior expected by most first-person controllers.
+    pub fn toggle_grab(&mut self) {
+        if let Some(mut window) = self.window_mut() {
+            toggle_grab(&mut window);
+        }
+    }
+
+    /// Returns a mutable reference to the primary window, or log a warning and return `None`
+    /// if there is no primary window.
+    fn window_mut(&mut self) -> Option<Mut<'_, Window>> {
+        match self.windows.get_single_mut() {
+            Ok((_, window)) => Some(window),
+            Err(_) => {
+                warn!("Primary window not found for `CursorControl`!");
+                None
+            }
+        }
+    }
+}
+
+/// Show or hide the cursor of the given window.
+pub fn set_visible(window: &mut Window, visible: bool) {
+    window.cursor_options.visible = visible;
+}
+
+/// Set the grab mode of the cursor of the given window.
+pub fn set_grab_mode(window: &mut Window, mode: CursorGrabMode) {
+    window.cursor_options.grab_mode = mode;
+}
+
+/// Grab and hide the cursor of the given window if it was free, or release and show it
+/// otherwise.
+pub fn toggle_grab(window: &mut Window) {
+    match window.cursor_options.grab_mode {
+        CursorGrabMode::None => {
+            set_grab_mode(window, CursorGrabMode::Confined);
+            set_visible(window, false);
+        }
+        _ => {
+            set_grab_mode(window, CursorGrabMode::None);
+            set_visible(window, true);
+        }
+    }
+}
@@ -1,4 +1,9 @@
 pub mod camera;
+pub mod config;
+pub mod cursor;
+pub mod orbit;
+pub mod prefab;
+pub mod touch;
 
 pub mod render {
     pub use amethyst_render::*;
@@ -9,5 +14,10 @@ pub mod vulkan {
 }
 
 pub mod prelude {
-    pub use crate::camera::{Camera3D, FlyCam, PlayerPlugin};
+    pub use crate::camera::{Camera2D, Camera3D, FlyCam, PlayerPlugin};
+    pub use crate::config::ConfigFile;
+    pub use crate::cursor::CursorControl;
+    pub use crate::orbit::{OrbitCam, OrbitCameraPlugin, OrbitObstacle};
+    pub use crate::prefab::{Prefab, PrefabPlugin, SpawnPrefab};
+    pub use crate::touch::TouchControlsPlugin;
 }
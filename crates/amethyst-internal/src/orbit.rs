@@ -0,0 +1,214 @@
+//! A third-person orbit camera controller. The camera orbits around a target entity (or a fixed
+//! point) at a given distance, and automatically zooms in when an obstacle is between the target
+//! and the camera.
+use crate::camera::{CameraPlugin, CameraTarget, MovementSettings};
+use crate::cursor::CursorControl;
+use crate::touch::TouchControlsPlugin;
+use bevy::input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+/// A component that turns a [`Camera3D`](crate::camera::Camera3D) into a third-person camera orbiting around a target.
+#[derive(Debug, Component)]
+pub struct OrbitCam {
+    /// The entity to follow. The camera orbits around the translation of the entity `Transform`
+    /// offset by [`OrbitCam::offset`]. If `None`, the camera orbits around [`OrbitCam::focus`].
+    pub target: Option<Entity>,
+
+    /// The point the camera orbits around. This is updated every frame when following a target.
+    pub focus: Vec3,
+
+    /// An offset added to the target translation, for example to look at the head of a
+    /// character instead of its feet.
+    pub offset: Vec3,
+
+    /// The desired distance between the camera and the focus point.
+    pub distance: f32,
+
+    /// The minimum and maximum distance allowed when zooming.
+    pub distance_limits: (f32, f32),
+
+    /// The rotation around the vertical axis, in radians.
+    pub yaw: f32,
+
+    /// The rotation around the horizontal axis, in radians.
+    pub pitch: f32,
+
+    /// The minimum and maximum pitch allowed, in radians.
+    pub pitch_limits: (f32, f32),
+
+    /// How much the distance changes for each mouse wheel line.
+    pub zoom_speed: f32,
+
+    /// The radius of the camera used when checking for obstacles. The camera is moved closer
+    /// to the focus point so that this sphere never goes through an [`OrbitObstacle`].
+    pub collision_radius: f32,
+}
+
+impl Default for OrbitCam {
+    fn default() -> Self {
+        Self {
+            target: None,
+            focus: Vec3::ZERO,
+            offset: Vec3::ZERO,
+            distance: 8.0,
+            distance_limits: (1.0, 50.0),
+            yaw: 0.0,
+            pitch: -0.4,
+            pitch_limits: (-1.54, 1.54),
+            zoom_speed: 1.0,
+            collision_radius: 0.2,
+        }
+    }
+}
+
+impl OrbitCam {
+    /// Create a new orbit camera following the given entity.
+    #[must_use]
+    pub fn following(target: Entity) -> Self {
+        Self {
+            target: Some(target),
+            ..Default::default()
+        }
+    }
+
+    /// Returns the rotation of the camera computed from its yaw and pitch.
+    #[must_use]
+    pub fn rotation(&self) -> Quat {
+        // Order is important to prevent unintended roll
+        Quat::from_axis_angle(Vec3::Y, self.yaw) * Quat::from_axis_angle(Vec3::X, self.pitch)
+    }
+}
+
+/// A spherical obstacle that the orbit camera cannot go through. The center of the sphere
+/// is the translation of the entity `Transform`.
+#[derive(Debug, Clone, Copy, Component)]
+pub struct OrbitObstacle {
+    pub radius: f32,
+}
+
+/// Handles orbiting around the focus point with the mouse if the cursor is grabbed
+fn orbit_look(
+    settings: Res<MovementSettings>,
+    cursor: CursorControl,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut motion: EventReader<MouseMotion>,
+    mut query: Query<&mut OrbitCam>,
+) {
+    let Ok(window) = primary_window.get_single() else {
+        motion.clear();
+        return;
+    };
+
+    if !cursor.is_grabbed() {
+        motion.clear();
+        return;
+    }
+
+    // Scale the sensitivity the same way as the fly camera, so that both cameras turn by the
+    // same angle for the same mouse motion.
+    let window_scale = window.height().min(window.width());
+    let delta = motion.read().map(|ev| ev.delta).sum::<Vec2>();
+    for mut orbit in query.iter_mut() {
+        orbit.yaw -= (settings.sensitivity * delta.x * window_scale).to_radians();
+        orbit.pitch -= (settings.sensitivity * delta.y * window_scale).to_radians();
+        orbit.pitch = orbit
+            .pitch
+            .clamp(orbit.pitch_limits.0, orbit.pitch_limits.1);
+    }
+}
+
+/// Handles zooming in and out with the mouse wheel
+fn orbit_zoom(mut wheel: EventReader<MouseWheel>, mut query: Query<&mut OrbitCam>) {
+    let lines = wheel
+        .read()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / 16.0,
+        })
+        .sum::<f32>();
+
+    if lines == 0.0 {
+        return;
+    }
+
+    for mut orbit in query.iter_mut() {
+        let (min, max) = orbit.distance_limits;
+        orbit.distance = (orbit.distance - lines * orbit.zoom_speed).clamp(min, max);
+    }
+}
+
+/// Updates the focus point of the orbit cameras following a target
+fn orbit_follow(mut query: Query<&mut OrbitCam>, targets: Query<&Transform>) {
+    for mut orbit in query.iter_mut() {
+        if let Some(target) = orbit.target {
+            if let Ok(transform) = targets.get(target) {
+                orbit.focus = transform.translation + orbit.offset;
+            }
+        }
+    }
+}
+
+/// Places the camera around the focus point, moving it closer if an obstacle is in the way
+fn orbit_place(
+    mut query: Query<(&OrbitCam, &mut CameraTarget)>,
+    obstacles: Query<(&Transform, &OrbitObstacle)>,
+) {
+    for (orbit, mut target) in query.iter_mut() {
+        let rotation = orbit.rotation();
+        let direction = rotation * Vec3::Z;
+
+        // Find the closest obstacle between the focus point and the camera, and shorten
+        // the distance so the camera stays in front of it.
+        let distance = obstacles
+            .iter()
+            .filter_map(|(transform, obstacle)| {
+                ray_sphere(
+                    orbit.focus,
+                    direction,
+                    transform.translation,
+                    obstacle.radius + orbit.collision_radius,
+                )
+            })
+            .fold(orbit.distance, f32::min)
+            .max(orbit.distance_limits.0.min(orbit.distance));
+
+        target.0.translation = orbit.focus + direction * distance;
+        target.0.rotation = rotation;
+    }
+}
+
+/// Returns the distance along the ray to the first intersection with the sphere, or `None`
+/// if the ray does not hit the sphere. The ray direction must be normalized.
+fn ray_sphere(origin: Vec3, direction: Vec3, center: Vec3, radius: f32) -> Option<f32> {
+    let oc = origin - center;
+    let b = oc.dot(direction);
+    let c = oc.length_squared() - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let t = -b - discriminant.sqrt();
+    (t >= 0.0).then_some(t)
+}
+
+/// Contains everything needed to add third-person orbit camera behavior to your game. The
+/// camera must be spawned by the user with a [`Camera3D`](crate::camera::Camera3D) and an
+/// [`OrbitCam`] component.
+pub struct OrbitCameraPlugin;
+impl Plugin for OrbitCameraPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MovementSettings>().add_systems(
+            Update,
+            (orbit_look, orbit_zoom, orbit_follow, orbit_place).chain(),
+        );
+
+        if !app.is_plugin_added::<CameraPlugin>() {
+            app.add_plugins(CameraPlugin);
+        }
+        if !app.is_plugin_added::<TouchControlsPlugin>() {
+            app.add_plugins(TouchControlsPlugin);
+        }
+    }
+}
@@ -0,0 +1,384 @@
+//! Prefabs: reusable entity templates stored as assets. A prefab describes an entity with its
+//! transform, mesh, material and gameplay parameters, and its children. Prefabs are written in
+//! the RON format in files with the `.prefab.ron` extension:
+//!
+//! ```ron
+//! (
+//!     name: Some("crate"),
+//!     mesh: Some(Cube(half_size: 0.5)),
+//!     material: Some((roughness: 0.8)),
+//!     parameters: {"health": Float(100.0)},
+//!     children: [
+//!         (transform: (translation: (0.0, 0.75, 0.0)), mesh: Some(Cube(half_size: 0.25))),
+//!     ],
+//! )
+//! ```
+//!
+//! A prefab is spawned by inserting a [`SpawnPrefab`] component on an entity, which becomes the
+//! root of the prefab once it is loaded. Fields of the root can be overridden at spawn time.
+use amethyst_render::{material::Material, mesh::Mesh};
+use bevy::asset::{io::Reader, AssetLoader, LoadContext, LoadState};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// An error that can occur when loading a prefab.
+#[derive(Debug, thiserror::Error)]
+pub enum PrefabError {
+    /// The prefab file could not be read.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The prefab file contains invalid data.
+    #[error("Invalid prefab: {0}")]
+    Parse(#[from] ron::error::SpannedError),
+}
+
+/// A reusable entity template.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, Asset, TypePath)]
+#[serde(default)]
+pub struct Prefab {
+    /// The name of the entity.
+    pub name: Option<String>,
+
+    /// The transform of the entity, relative to its parent.
+    pub transform: PrefabTransform,
+
+    /// The mesh of the entity.
+    pub mesh: Option<PrefabMesh>,
+
+    /// The material of the entity.
+    pub material: Option<Material>,
+
+    /// Gameplay parameters, stored in the [`PrefabParameters`] component of the entity.
+    pub parameters: HashMap<String, PrefabValue>,
+
+    /// The children of the entity.
+    pub children: Vec<Prefab>,
+}
+
+/// The transform of a prefab. Unlike [`Transform`], each field can be omitted in the prefab
+/// file, and defaults to the identity.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrefabTransform {
+    /// The position of the entity.
+    pub translation: Vec3,
+
+    /// The rotation of the entity, as a quaternion `(x, y, z, w)`.
+    pub rotation: Quat,
+
+    /// The scale of the entity.
+    pub scale: Vec3,
+}
+
+impl Default for PrefabTransform {
+    fn default() -> Self {
+        Transform::IDENTITY.into()
+    }
+}
+
+impl From<Transform> for PrefabTransform {
+    fn from(transform: Transform) -> Self {
+        Self {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+}
+
+impl From<PrefabTransform> for Transform {
+    fn from(transform: PrefabTransform) -> Self {
+        Self {
+            translation: transform.translation,
+            rotation: transform.rotation,
+            scale: transform.scale,
+        }
+    }
+}
+
+/// The mesh of a prefab.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PrefabMesh {
+    /// A cube centered on the origin, see [`Mesh::cube`].
+    Cube { half_size: f32 },
+
+    /// A triangle mesh given by its vertex positions and triangle indices.
+    Triangles {
+        positions: Vec<Vec3>,
+        indices: Vec<u32>,
+    },
+}
+
+impl PrefabMesh {
+    /// Build the mesh described by the prefab.
+    #[must_use]
+    pub fn build(&self) -> Mesh {
+        match self {
+            PrefabMesh::Cube { half_size } => Mesh::cube(*half_size),
+            PrefabMesh::Triangles { positions, indices } => {
+                Mesh::new(positions.clone(), indices.clone())
+            }
+        }
+    }
+}
+
+/// The value of a gameplay parameter of a prefab.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum PrefabValue {
+    /// A boolean value.
+    Bool(bool),
+
+    /// An integer value.
+    Int(i64),
+
+    /// A floating-point value.
+    Float(f64),
+
+    /// A string value.
+    String(String),
+
+    /// A vector, such as a position or a color.
+    Vec3(Vec3),
+}
+
+/// The gameplay parameters of an entity spawned from a prefab.
+#[derive(Debug, Clone, Default, PartialEq, Component)]
+pub struct PrefabParameters(pub HashMap<String, PrefabValue>);
+
+impl PrefabParameters {
+    /// Returns the value of the given parameter, if it exists.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&PrefabValue> {
+        self.0.get(name)
+    }
+
+    /// Returns the given parameter as a float, if it exists and is a number.
+    #[must_use]
+    pub fn float(&self, name: &str) -> Option<f64> {
+        match self.get(name)? {
+            PrefabValue::Float(value) => Some(*value),
+            PrefabValue::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    /// Returns the given parameter as a boolean, if it exists and is a boolean.
+    #[must_use]
+    pub fn bool(&self, name: &str) -> Option<bool> {
+        match self.get(name)? {
+            PrefabValue::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Fields of the root entity of a prefab overridden at spawn time.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PrefabOverrides {
+    /// Replaces the transform of the prefab.
+    pub transform: Option<Transform>,
+
+    /// Replaces the material of the prefab.
+    pub material: Option<Material>,
+
+    /// Added to the parameters of the prefab, replacing parameters with the same name.
+    pub parameters: HashMap<String, PrefabValue>,
+}
+
+/// Spawns a prefab on this entity once it is loaded. The component is then replaced by a
+/// [`PrefabInstance`] component. If the prefab fails to load, the component is removed and
+/// nothing is spawned.
+#[derive(Debug, Clone, Component)]
+pub struct SpawnPrefab {
+    /// The prefab to spawn.
+    pub prefab: Handle<Prefab>,
+
+    /// The fields of the root entity to override.
+    pub overrides: PrefabOverrides,
+}
+
+impl SpawnPrefab {
+    /// Spawn the given prefab without any override.
+    #[must_use]
+    pub fn new(prefab: Handle<Prefab>) -> Self {
+        Self {
+            prefab,
+            overrides: PrefabOverrides::default(),
+        }
+    }
+
+    /// Override the transform of the prefab.
+    #[must_use]
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.overrides.transform = Some(transform);
+        self
+    }
+
+    /// Override the material of the prefab.
+    #[must_use]
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.overrides.material = Some(material);
+        self
+    }
+
+    /// Override a parameter of the prefab.
+    #[must_use]
+    pub fn with_parameter(mut self, name: impl Into<String>, value: PrefabValue) -> Self {
+        self.overrides.parameters.insert(name.into(), value);
+        self
+    }
+}
+
+/// The prefab an entity was spawned from.
+#[derive(Debug, Clone, Component)]
+pub struct PrefabInstance(pub Handle<Prefab>);
+
+/// Loads prefabs from `.prefab.ron` files.
+#[derive(Debug, Default)]
+pub struct PrefabLoader;
+
+impl AssetLoader for PrefabLoader {
+    type Asset = Prefab;
+    type Settings = ();
+    type Error = PrefabError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _context: &mut LoadContext<'_>,
+    ) -> Result<Prefab, PrefabError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["prefab.ron"]
+    }
+}
+
+/// Adds the prefab asset and spawns the prefabs requested with [`SpawnPrefab`].
+pub struct PrefabPlugin;
+
+impl Plugin for PrefabPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<Prefab>()
+            .init_asset_loader::<PrefabLoader>()
+            .add_systems(PreUpdate, spawn_prefabs);
+    }
+}
+
+/// Spawn the content of the loaded prefabs on the entities with a [`SpawnPrefab`] component.
+fn spawn_prefabs(
+    mut commands: Commands,
+    server: Res<AssetServer>,
+    prefabs: Res<Assets<Prefab>>,
+    pending: Query<(Entity, &SpawnPrefab)>,
+) {
+    for (entity, spawn) in &pending {
+        let Some(prefab) = prefabs.get(&spawn.prefab) else {
+            if let Some(LoadState::Failed(error)) = server.get_load_state(&spawn.prefab) {
+                error!("Failed to spawn prefab: {error}");
+                commands.entity(entity).remove::<SpawnPrefab>();
+            }
+            continue;
+        };
+
+        insert_prefab(&mut commands, entity, prefab);
+
+        let overrides = &spawn.overrides;
+        let mut root = commands.entity(entity);
+        root.remove::<SpawnPrefab>()
+            .insert(PrefabInstance(spawn.prefab.clone()));
+        if let Some(transform) = overrides.transform {
+            root.insert(transform);
+        }
+        if let Some(material) = overrides.material {
+            root.insert(material);
+        }
+        if !overrides.parameters.is_empty() {
+            let mut parameters = prefab.parameters.clone();
+            parameters.extend(overrides.parameters.clone());
+            root.insert(PrefabParameters(parameters));
+        }
+    }
+}
+
+/// Insert the components of the prefab on the entity, and spawn its children recursively.
+fn insert_prefab(commands: &mut Commands, entity: Entity, prefab: &Prefab) {
+    let mut target = commands.entity(entity);
+    target.insert(Transform::from(prefab.transform));
+    if let Some(name) = &prefab.name {
+        target.insert(Name::new(name.clone()));
+    }
+    if let Some(mesh) = &prefab.mesh {
+        target.insert(mesh.build());
+    }
+    if let Some(material) = prefab.material {
+        target.insert(material);
+    }
+    if !prefab.parameters.is_empty() {
+        target.insert(PrefabParameters(prefab.parameters.clone()));
+    }
+
+    for child in &prefab.children {
+        let child_entity = commands.spawn_empty().id();
+        commands.entity(entity).add_child(child_entity);
+        insert_prefab(commands, child_entity, child);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The prefab of the module documentation.
+    const CRATE_PREFAB: &str = r#"(
+        name: Some("crate"),
+        mesh: Some(Cube(half_size: 0.5)),
+        material: Some((roughness: 0.8)),
+        parameters: {"health": Float(100.0)},
+        children: [
+            (transform: (translation: (0.0, 0.75, 0.0)), mesh: Some(Cube(half_size: 0.25))),
+        ],
+    )"#;
+
+    #[test]
+    fn parse_documented_prefab() {
+        let prefab: Prefab = ron::from_str(CRATE_PREFAB).expect("Invalid prefab");
+
+        assert_eq!(prefab.name.as_deref(), Some("crate"));
+        assert_eq!(prefab.transform, PrefabTransform::default());
+        assert_eq!(prefab.mesh, Some(PrefabMesh::Cube { half_size: 0.5 }));
+        assert_eq!(
+            prefab.material.map(|material| material.roughness),
+            Some(0.8)
+        );
+        assert_eq!(
+            PrefabParameters(prefab.parameters).float("health"),
+            Some(100.0)
+        );
+
+        let [child] = prefab.children.as_slice() else {
+            panic!("Expected a single child");
+        };
+        assert_eq!(
+            Transform::from(child.transform),
+            Transform::from_xyz(0.0, 0.75, 0.0)
+        );
+        assert_eq!(child.mesh, Some(PrefabMesh::Cube { half_size: 0.25 }));
+    }
+
+    #[test]
+    fn parse_partial_transform() {
+        let transform: PrefabTransform = ron::from_str("(scale: (2.0, 2.0, 2.0))").unwrap();
+
+        assert_eq!(
+            Transform::from(transform),
+            Transform::from_scale(Vec3::splat(2.0))
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! Touchscreen camera controls for mobile targets. The left half of the screen acts as a virtual
+//! joystick used to move the fly camera, the right half is used to look around by dragging, and
+//! pinching with two fingers zooms in and out.
+use crate::camera::{Camera3D, CameraTarget, FlyCam, MovementSettings};
+use crate::orbit::OrbitCam;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+
+/// Touch controls configuration
+#[derive(Resource, Debug, Clone)]
+pub struct TouchSettings {
+    /// The radius of the virtual joystick, in logical pixels. Moving the finger further than
+    /// this radius from where the touch started results in full speed movement.
+    pub joystick_radius: f32,
+
+    /// The look speed when dragging, in radians per logical pixel.
+    pub look_sensitivity: f32,
+
+    /// The field of view change of a fly camera when pinching, in radians per logical pixel
+    /// between the two fingers.
+    pub pinch_sensitivity: f32,
+
+    /// How much an orbit camera zooms when the distance between two fingers changes by one
+    /// logical pixel, in mouse wheel lines.
+    pub pinch_zoom_sensitivity: f32,
+
+    /// The minimum and maximum field of view (in radians) reachable by pinching with a fly camera.
+    pub fov_limits: (f32, f32),
+}
+
+impl Default for TouchSettings {
+    fn default() -> Self {
+        Self {
+            joystick_radius: 80.0,
+            look_sensitivity: 0.005,
+            pinch_sensitivity: 0.002,
+            pinch_zoom_sensitivity: 0.02,
+            fov_limits: (0.3, 1.5),
+        }
+    }
+}
+
+/// Keeps track of which touch is used for which control
+#[derive(Resource, Default)]
+struct TouchState {
+    /// The touch used as a virtual joystick, with the position where it started.
+    joystick: Option<(u64, Vec2)>,
+
+    /// The distance between the two fingers used to pinch during the previous frame.
+    pinch_distance: Option<f32>,
+}
+
+/// The per-frame result of the touch controls, shared by the fly and orbit cameras.
+#[derive(Default)]
+struct TouchFrame {
+    movement: Vec2,
+    look: Vec2,
+    /// The change of the distance between the two pinching fingers, in logical pixels.
+    pinch: f32,
+}
+
+/// Reads the touches and converts them into movement, look and pinch values
+fn read_touches(
+    touches: &Touches,
+    window: &Window,
+    settings: &TouchSettings,
+    state: &mut TouchState,
+) -> TouchFrame {
+    let mut frame = TouchFrame::default();
+    let half_width = window.width() * 0.5;
+
+    // Release the joystick when its finger is lifted, and assign a new joystick
+    // when a touch starts on the left half of the screen.
+    if let Some((id, _)) = state.joystick {
+        if touches.get_pressed(id).is_none() {
+            state.joystick = None;
+        }
+    }
+    if state.joystick.is_none() {
+        state.joystick = touches
+            .iter_just_pressed()
+            .find(|touch| touch.start_position().x < half_width)
+            .map(|touch| (touch.id(), touch.start_position()));
+    }
+
+    if let Some((id, origin)) = state.joystick {
+        if let Some(touch) = touches.get_pressed(id) {
+            let offset = (touch.position() - origin) / settings.joystick_radius;
+            frame.movement = offset.clamp_length_max(1.0);
+        }
+    }
+
+    // All the other touches are used to look around (one finger) or to pinch (two fingers)
+    let joystick = state.joystick.map(|(id, _)| id);
+    let others = touches
+        .iter()
+        .filter(|touch| Some(touch.id()) != joystick)
+        .collect::<Vec<_>>();
+
+    match others.as_slice() {
+        [touch] => {
+            frame.look = touch.delta();
+            state.pinch_distance = None;
+        }
+        [a, b, ..] => {
+            let distance = a.position().distance(b.position());
+            if let Some(previous) = state.pinch_distance {
+                frame.pinch = distance - previous;
+            }
+            state.pinch_distance = Some(distance);
+        }
+        [] => state.pinch_distance = None,
+    }
+
+    frame
+}
+
+/// Applies the touch controls to the fly and orbit cameras
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn touch_controls(
+    time: Res<Time>,
+    touches: Res<Touches>,
+    settings: Res<TouchSettings>,
+    movement: Res<MovementSettings>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut state: ResMut<TouchState>,
+    mut flycams: Query<(&mut Camera3D, &mut CameraTarget), (With<FlyCam>, Without<OrbitCam>)>,
+    mut orbits: Query<&mut OrbitCam>,
+) {
+    let Ok(window) = primary_window.get_single() else {
+        return;
+    };
+
+    let frame = read_touches(&touches, window, &settings, &mut state);
+
+    for (mut camera, mut target) in flycams.iter_mut() {
+        let local_z = target.0.local_z();
+        let forward = -Vec3::new(local_z.x, 0., local_z.z).normalize_or_zero();
+        let right = Vec3::new(local_z.z, 0., -local_z.x).normalize_or_zero();
+
+        // The screen Y axis points down, so moving the finger up moves forward
+        let velocity = forward * -frame.movement.y + right * frame.movement.x;
+        target.0.translation += velocity * time.delta_secs() * movement.speed;
+
+        let (mut yaw, mut pitch, _) = target.0.rotation.to_euler(EulerRot::YXZ);
+        yaw -= frame.look.x * settings.look_sensitivity;
+        pitch -= frame.look.y * settings.look_sensitivity;
+        pitch = pitch.clamp(-1.54, 1.54);
+
+        // Order is important to prevent unintended roll
+        target.0.rotation =
+            Quat::from_axis_angle(Vec3::Y, yaw) * Quat::from_axis_angle(Vec3::X, pitch);
+
+        if frame.pinch != 0.0 {
+            let (min, max) = settings.fov_limits;
+            camera.fov = (camera.fov - frame.pinch * settings.pinch_sensitivity).clamp(min, max);
+        }
+    }
+
+    for mut orbit in orbits.iter_mut() {
+        orbit.yaw -= frame.look.x * settings.look_sensitivity;
+        orbit.pitch -= frame.look.y * settings.look_sensitivity;
+        orbit.pitch = orbit
+            .pitch
+            .clamp(orbit.pitch_limits.0, orbit.pitch_limits.1);
+
+        let (min, max) = orbit.distance_limits;
+        let lines = frame.pinch * settings.pinch_zoom_sensitivity;
+        orbit.distance = (orbit.distance - lines * orbit.zoom_speed).clamp(min, max);
+    }
+}
+
+/// Adds touchscreen controls to the fly and orbit cameras.
+pub struct TouchControlsPlugin;
+impl Plugin for TouchControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TouchSettings>()
+            .init_resource::<TouchState>()
+            .init_resource::<MovementSettings>()
+            .add_systems(Update, touch_controls);
+    }
+}
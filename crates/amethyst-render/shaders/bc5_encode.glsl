@@ -0,0 +1,66 @@
+#version 450
+
+// A BC5 encoder, compressing the red and green channels of each 4x4 block as two independent
+// BC4 blocks. This is the usual format for normal maps, whose blue channel is reconstructed in
+// the shaders. Each invocation encodes a 4x4 block.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) readonly buffer Pixels { uint values[]; } pixels;
+layout(set = 0, binding = 1) writeonly buffer Blocks { uvec4 values[]; } blocks;
+
+layout(push_constant) uniform Encode {
+    uint width;
+    uint height;
+} encode;
+
+// Encode a single channel as a BC4 block, using the mode with 8 interpolated values.
+uvec2 encode_channel(float values[16]) {
+    float low = 255.0;
+    float high = 0.0;
+    for (uint i = 0; i < 16; i++) {
+        low = min(low, values[i]);
+        high = max(high, values[i]);
+    }
+
+    // The first endpoint must be greater than the second one to select the 8 values mode. The
+    // palette goes from the first endpoint (index 0) to the second one (index 1), with the
+    // interpolated values at indices 2 to 7.
+    uvec2 block = uvec2(uint(high) | (uint(low) << 8), 0);
+    uint position = 16;
+    float range = max(high - low, 1e-6);
+    for (uint i = 0; i < 16; i++) {
+        uint step = uint(round((values[i] - low) / range * 7.0));
+        uint index = step == 7 ? 0 : (step == 0 ? 1 : 8 - step);
+        if (high == low) {
+            index = 0;
+        }
+
+        for (uint bit = 0; bit < 3; bit++) {
+            uint word = (position + bit) / 32;
+            uint offset = (position + bit) % 32;
+            block[word] |= ((index >> bit) & 1) << offset;
+        }
+        position += 3;
+    }
+
+    return block;
+}
+
+void main() {
+    uvec2 block = gl_GlobalInvocationID.xy;
+    uvec2 count = (uvec2(encode.width, encode.height) + 3) / 4;
+    if (any(greaterThanEqual(block, count))) {
+        return;
+    }
+
+    float red[16];
+    float green[16];
+    for (uint i = 0; i < 16; i++) {
+        uvec2 pixel = min(block * 4 + uvec2(i % 4, i / 4), uvec2(encode.width, encode.height) - 1);
+        uint value = pixels.values[pixel.y * encode.width + pixel.x];
+        red[i] = float(value & 0xff);
+        green[i] = float((value >> 8) & 0xff);
+    }
+
+    blocks.values[block.y * count.x + block.x] = uvec4(encode_channel(red), encode_channel(green));
+}
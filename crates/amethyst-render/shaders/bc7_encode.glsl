@@ -0,0 +1,111 @@
+#version 450
+
+// A fast BC7 encoder, using only the mode 6 of BC7 (a single subset, RGBA endpoints with 7 bits
+// per channel and a shared p-bit, and 4-bit indices). Each invocation encodes a 4x4 block. The
+// endpoints are the corners of the bounding box of the block colors, which gives a good quality
+// for a fraction of the cost of an exhaustive search.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) readonly buffer Pixels { uint values[]; } pixels;
+layout(set = 0, binding = 1) writeonly buffer Blocks { uvec4 values[]; } blocks;
+
+layout(push_constant) uniform Encode {
+    uint width;
+    uint height;
+} encode;
+
+const uint WEIGHTS[16] = uint[](0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64);
+
+uint bits[4];
+
+void write_bits(inout uint position, uint value, uint count) {
+    for (uint i = 0; i < count; i++) {
+        uint bit = (value >> i) & 1;
+        bits[position / 32] |= bit << (position % 32);
+        position++;
+    }
+}
+
+// Quantize an endpoint to 7 bits per channel with the given p-bit, and returns the 7-bit values.
+uvec4 quantize(vec4 color, uint p) {
+    return uvec4(clamp(round((color - float(p)) / 2.0), 0.0, 127.0));
+}
+
+vec4 dequantize(uvec4 color, uint p) {
+    return vec4((color << 1) | p);
+}
+
+void main() {
+    uvec2 block = gl_GlobalInvocationID.xy;
+    uvec2 count = (uvec2(encode.width, encode.height) + 3) / 4;
+    if (any(greaterThanEqual(block, count))) {
+        return;
+    }
+
+    // Load the pixels of the block, repeating the last row and column for the blocks that
+    // are partially outside of the image.
+    vec4 colors[16];
+    vec4 low = vec4(255.0);
+    vec4 high = vec4(0.0);
+    for (uint i = 0; i < 16; i++) {
+        uvec2 pixel = min(block * 4 + uvec2(i % 4, i / 4), uvec2(encode.width, encode.height) - 1);
+        uint value = pixels.values[pixel.y * encode.width + pixel.x];
+        colors[i] = vec4(value & 0xff, (value >> 8) & 0xff, (value >> 16) & 0xff, value >> 24);
+        low = min(low, colors[i]);
+        high = max(high, colors[i]);
+    }
+
+    // Choose the p-bit of each endpoint minimizing its quantization error.
+    uint p0 = distance(dequantize(quantize(low, 0), 0), low)
+        <= distance(dequantize(quantize(low, 1), 1), low) ? 0 : 1;
+    uint p1 = distance(dequantize(quantize(high, 0), 0), high)
+        <= distance(dequantize(quantize(high, 1), 1), high) ? 0 : 1;
+    uvec4 e0 = quantize(low, p0);
+    uvec4 e1 = quantize(high, p1);
+    vec4 c0 = dequantize(e0, p0);
+    vec4 c1 = dequantize(e1, p1);
+
+    // Find the closest palette entry of each pixel by projecting it on the endpoints axis.
+    vec4 axis = c1 - c0;
+    float length2 = max(dot(axis, axis), 1e-6);
+    uint indices[16];
+    for (uint i = 0; i < 16; i++) {
+        float t = clamp(dot(colors[i] - c0, axis) / length2, 0.0, 1.0);
+        uint best = 0;
+        float best_error = 1e30;
+        for (uint j = 0; j < 16; j++) {
+            float error = abs(float(WEIGHTS[j]) / 64.0 - t);
+            if (error < best_error) {
+                best_error = error;
+                best = j;
+            }
+        }
+        indices[i] = best;
+    }
+
+    // The most significant bit of the first index is implicit and must be zero: swap the
+    // endpoints and invert the indices if needed.
+    if (indices[0] >= 8) {
+        uvec4 e = e0; e0 = e1; e1 = e;
+        uint p = p0; p0 = p1; p1 = p;
+        for (uint i = 0; i < 16; i++) {
+            indices[i] = 15 - indices[i];
+        }
+    }
+
+    bits = uint[4](0, 0, 0, 0);
+    uint position = 0;
+    write_bits(position, 1 << 6, 7);
+    for (uint channel = 0; channel < 4; channel++) {
+        write_bits(position, e0[channel], 7);
+        write_bits(position, e1[channel], 7);
+    }
+    write_bits(position, p0, 1);
+    write_bits(position, p1, 1);
+    write_bits(position, indices[0], 3);
+    for (uint i = 1; i < 16; i++) {
+        write_bits(position, indices[i], 4);
+    }
+
+    blocks.values[block.y * count.x + block.x] = uvec4(bits[0], bits[1], bits[2], bits[3]);
+}
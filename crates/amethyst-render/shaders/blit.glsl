@@ -0,0 +1,50 @@
+#version 450
+
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform Blit {
+    // The area covered by the source image in the target image, in texture coordinates.
+    vec2 scale;
+    vec2 offset;
+
+    // The color written outside of the area covered by the source image.
+    vec4 border_color;
+
+    // The gamma conversion applied to the sampled color, see `GammaConversion`.
+    uint gamma;
+} blit;
+
+const uint GAMMA_LINEAR_TO_SRGB = 1;
+const uint GAMMA_SRGB_TO_LINEAR = 2;
+
+vec3 linear_to_srgb(vec3 color) {
+    vec3 low = color * 12.92;
+    vec3 high = 1.055 * pow(color, vec3(1.0 / 2.4)) - 0.055;
+    return mix(low, high, step(vec3(0.0031308), color));
+}
+
+vec3 srgb_to_linear(vec3 color) {
+    vec3 low = color / 12.92;
+    vec3 high = pow((color + 0.055) / 1.055, vec3(2.4));
+    return mix(low, high, step(vec3(0.04045), color));
+}
+
+void main() {
+    vec2 source_uv = (uv - blit.offset) / blit.scale;
+    if (any(lessThan(source_uv, vec2(0.0))) || any(greaterThan(source_uv, vec2(1.0)))) {
+        out_color = blit.border_color;
+        return;
+    }
+
+    vec4 color = texture(source, source_uv);
+    if (blit.gamma == GAMMA_LINEAR_TO_SRGB) {
+        color.rgb = linear_to_srgb(color.rgb);
+    } else if (blit.gamma == GAMMA_SRGB_TO_LINEAR) {
+        color.rgb = srgb_to_linear(color.rgb);
+    }
+
+    out_color = color;
+}
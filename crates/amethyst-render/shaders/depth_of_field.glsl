@@ -0,0 +1,69 @@
+#version 450
+
+// A bokeh depth of field using scatter-as-gather: each pixel gathers the samples of a spiral
+// around it whose circle of confusion is large enough to cover it, which approximates the
+// scattering of each pixel over its circle of confusion. Samples behind the pixel cannot spread
+// more than twice its own circle of confusion, so sharp objects do not get covered by the
+// blurry background.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+layout(set = 0, binding = 1) uniform sampler2D depth;
+layout(set = 0, binding = 2, rgba16f) uniform writeonly image2D target;
+
+layout(push_constant) uniform DepthOfField {
+    float near;
+    float far;
+    float focus_distance;
+    float coc_scale;
+    float max_coc;
+    float radius_step;
+} dof;
+
+const float GOLDEN_ANGLE = 2.39996323;
+
+// Returns the distance to the camera of the surface at the given texture coordinates.
+float linear_depth(vec2 uv) {
+    float d = textureLod(depth, uv, 0.0).r;
+    return dof.near * dof.far / (dof.far - d * (dof.far - dof.near));
+}
+
+// Returns the radius of the circle of confusion of a surface at the given distance, in pixels.
+float circle_of_confusion(float distance) {
+    float coc = dof.coc_scale * abs(distance - dof.focus_distance) / distance;
+    return min(coc, dof.max_coc);
+}
+
+void main() {
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(target);
+    if (any(greaterThanEqual(pixel, size))) {
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(size);
+    vec2 uv = (vec2(pixel) + 0.5) * texel;
+
+    float center_depth = linear_depth(uv);
+    float center_coc = circle_of_confusion(center_depth);
+    vec3 color = textureLod(source, uv, 0.0).rgb;
+    float total = 1.0;
+
+    float radius = dof.radius_step;
+    for (float angle = 0.0; radius < dof.max_coc; angle += GOLDEN_ANGLE) {
+        vec2 sample_uv = uv + vec2(cos(angle), sin(angle)) * texel * radius;
+        vec3 sample_color = textureLod(source, sample_uv, 0.0).rgb;
+        float sample_depth = linear_depth(sample_uv);
+        float sample_coc = circle_of_confusion(sample_depth);
+        if (sample_depth > center_depth) {
+            sample_coc = clamp(sample_coc, 0.0, center_coc * 2.0);
+        }
+
+        float coverage = smoothstep(radius - 0.5, radius + 0.5, sample_coc);
+        color += mix(color / total, sample_color, coverage);
+        total += 1.0;
+        radius += dof.radius_step / radius;
+    }
+
+    imageStore(target, pixel, vec4(color / total, 1.0));
+}
@@ -0,0 +1,88 @@
+#version 450
+
+// Compute the average luminance of the histogram, ignoring the darkest and brightest pixels,
+// and adapt the exposure toward it exponentially. The histogram is cleared for the next frame.
+layout(local_size_x = 256) in;
+
+layout(set = 0, binding = 1) buffer Histogram {
+    uint bins[256];
+} histogram;
+
+layout(set = 0, binding = 2) buffer Exposure {
+    // The current exposure value at ISO 100.
+    float ev100;
+
+    // The factor applied to the HDR colors before tonemapping.
+    float exposure;
+} state;
+
+layout(push_constant) uniform Parameters {
+    float min_log_luminance;
+    float log_luminance_range;
+    float delta_time;
+    float speed_up;
+    float speed_down;
+    float low_percent;
+    float high_percent;
+    float compensation;
+    float min_ev;
+    float max_ev;
+    uint reset;
+} parameters;
+
+shared uint counts[256];
+
+void main() {
+    uint index = gl_LocalInvocationIndex;
+    counts[index] = histogram.bins[index];
+    histogram.bins[index] = 0u;
+    barrier();
+
+    if (index != 0u) {
+        return;
+    }
+
+    // Black pixels of bin 0 are ignored, so a mostly black screen does not over-expose the
+    // rest of the image.
+    float total = 0.0;
+    for (uint i = 1u; i < 256u; i++) {
+        total += float(counts[i]);
+    }
+
+    float low = total * parameters.low_percent;
+    float high = total * parameters.high_percent;
+    float accumulated = 0.0;
+    float sum = 0.0;
+    float weight = 0.0;
+    for (uint i = 1u; i < 256u; i++) {
+        float count = float(counts[i]);
+        float start = accumulated;
+        accumulated += count;
+
+        // Only keep the part of the bin between the low and high percentiles.
+        float kept = max(min(accumulated, high) - max(start, low), 0.0);
+        float log_luminance = (float(i) - 0.5) / 254.0 * parameters.log_luminance_range
+            + parameters.min_log_luminance;
+        sum += log_luminance * kept;
+        weight += kept;
+    }
+
+    if (weight <= 0.0) {
+        return;
+    }
+
+    // EV100 = log2(L * S / K) with a sensor sensitivity S of 100 and a calibration K of 12.5.
+    float target = sum / weight + 3.0 - parameters.compensation;
+    target = clamp(target, parameters.min_ev, parameters.max_ev);
+
+    float ev = state.ev100;
+    if (parameters.reset != 0u) {
+        ev = target;
+    } else {
+        float speed = target > ev ? parameters.speed_up : parameters.speed_down;
+        ev += (target - ev) * (1.0 - exp(-parameters.delta_time * speed));
+    }
+
+    state.ev100 = ev;
+    state.exposure = 1.0 / (1.2 * exp2(ev));
+}
@@ -0,0 +1,44 @@
+#version 450
+
+// Build a histogram of the log2 luminance of the HDR image. Bin 0 counts the pixels darker
+// than the range, the other bins split the range evenly. Each work group accumulates its
+// pixels in shared memory before adding them to the global histogram.
+layout(local_size_x = 16, local_size_y = 16) in;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+layout(set = 0, binding = 1) buffer Histogram {
+    uint bins[256];
+} histogram;
+
+layout(push_constant) uniform Parameters {
+    float min_log_luminance;
+    float inverse_log_luminance_range;
+} parameters;
+
+shared uint local_bins[256];
+
+uint bin(vec3 color) {
+    float luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    if (luminance < 0.0001) {
+        return 0u;
+    }
+
+    float position = (log2(luminance) - parameters.min_log_luminance)
+        * parameters.inverse_log_luminance_range;
+    return uint(clamp(position, 0.0, 1.0) * 254.0 + 1.0);
+}
+
+void main() {
+    local_bins[gl_LocalInvocationIndex] = 0u;
+    barrier();
+
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = textureSize(source, 0);
+    if (all(lessThan(pixel, size))) {
+        vec3 color = texelFetch(source, pixel, 0).rgb;
+        atomicAdd(local_bins[bin(color)], 1u);
+    }
+
+    barrier();
+    atomicAdd(histogram.bins[gl_LocalInvocationIndex], local_bins[gl_LocalInvocationIndex]);
+}
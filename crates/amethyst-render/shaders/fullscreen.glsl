@@ -0,0 +1,11 @@
+#version 450
+
+// A vertex shader that generates a triangle covering the whole screen from the vertex
+// index alone, without any vertex buffer. It must be drawn with 3 vertices. The triangle
+// is larger than the screen and is clipped, which avoids the diagonal seam of a quad.
+layout(location = 0) out vec2 uv;
+
+void main() {
+    uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(uv * 2.0 - 1.0, 0.0, 1.0);
+}
@@ -0,0 +1,35 @@
+#version 450
+
+// One direction of a separable gaussian blur. The blur is applied horizontally then vertically
+// by running this shader twice with a different direction.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+layout(set = 0, binding = 1, rgba16f) uniform writeonly image2D target;
+
+layout(push_constant) uniform Blur {
+    vec2 direction;
+    float sigma;
+    int radius;
+} blur;
+
+void main() {
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(target);
+    if (any(greaterThanEqual(pixel, size))) {
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(size);
+    vec2 uv = (vec2(pixel) + 0.5) * texel;
+
+    vec4 sum = vec4(0.0);
+    float total = 0.0;
+    for (int i = -blur.radius; i <= blur.radius; i++) {
+        float weight = exp(-float(i * i) / (2.0 * blur.sigma * blur.sigma));
+        sum += texture(source, uv + blur.direction * texel * float(i)) * weight;
+        total += weight;
+    }
+
+    imageStore(target, pixel, sum / total);
+}
@@ -0,0 +1,32 @@
+#version 450
+
+// One iteration of a Kawase blur: each pixel is the average of four bilinear samples taken
+// diagonally at the given distance. Running several iterations with an increasing distance
+// approximates a large gaussian blur for a fraction of its cost.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+layout(set = 0, binding = 1, rgba16f) uniform writeonly image2D target;
+
+layout(push_constant) uniform Kawase {
+    float distance;
+} kawase;
+
+void main() {
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(target);
+    if (any(greaterThanEqual(pixel, size))) {
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(size);
+    vec2 uv = (vec2(pixel) + 0.5) * texel;
+    vec2 offset = (kawase.distance + 0.5) * texel;
+
+    vec4 color = texture(source, uv + vec2(-offset.x, -offset.y));
+    color += texture(source, uv + vec2(offset.x, -offset.y));
+    color += texture(source, uv + vec2(-offset.x, offset.y));
+    color += texture(source, uv + vec2(offset.x, offset.y));
+
+    imageStore(target, pixel, color * 0.25);
+}
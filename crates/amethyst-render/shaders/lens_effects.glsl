@@ -0,0 +1,57 @@
+#version 450
+
+// The final lens effects: chromatic aberration, vignette and film grain, applied in this order
+// like on a real camera where the lens comes before the sensor.
+layout(location = 0) in vec2 uv;
+layout(location = 0) out vec4 out_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform LensEffects {
+    vec4 vignette_color;
+    float vignette_intensity;
+    float vignette_smoothness;
+    float vignette_roundness;
+    float grain_intensity;
+    float grain_size;
+    float chromatic_aberration;
+    float aspect;
+    float time;
+} lens;
+
+// A cheap hash returning a pseudo-random value in [0, 1) for each point.
+float hash(vec2 p) {
+    vec3 p3 = fract(vec3(p.xyx) * 0.1031);
+    p3 += dot(p3, p3.yzx + 33.33);
+    return fract((p3.x + p3.y) * p3.z);
+}
+
+void main() {
+    vec2 centered = uv - 0.5;
+
+    // Chromatic aberration: the red and blue channels are scaled away from the center in
+    // opposite directions, more strongly near the edges.
+    vec2 shift = centered * dot(centered, centered) * lens.chromatic_aberration;
+    vec4 color = texture(source, uv);
+    color.r = texture(source, uv - shift).r;
+    color.b = texture(source, uv + shift).b;
+
+    // Vignette: darken toward the vignette color with the distance to the center. A roundness
+    // of 1.0 gives a circle, and 0.0 follows the aspect ratio of the image.
+    vec2 distance = centered * vec2(mix(1.0, lens.aspect, lens.vignette_roundness), 1.0);
+    float falloff = smoothstep(
+        0.5 - lens.vignette_smoothness * 0.5,
+        0.5 + lens.vignette_smoothness * 0.5,
+        length(distance) * 2.0 * lens.vignette_intensity
+    );
+    color.rgb = mix(color.rgb, lens.vignette_color.rgb, falloff * lens.vignette_color.a);
+
+    // Film grain: animated noise, stronger in the mid tones where it is the most visible.
+    vec2 cell = floor(gl_FragCoord.xy / max(lens.grain_size, 1.0));
+    float noise = hash(cell + fract(lens.time) * 1000.0) - 0.5;
+    float luminance = dot(color.rgb, vec3(0.2126, 0.7152, 0.0722));
+    float response = 1.0 - abs(clamp(luminance, 0.0, 1.0) * 2.0 - 1.0);
+    color.rgb += noise * lens.grain_intensity * mix(0.5, 1.0, response);
+
+    out_color = vec4(max(color.rgb, vec3(0.0)), color.a);
+}
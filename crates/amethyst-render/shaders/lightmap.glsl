@@ -0,0 +1,9 @@
+// Sampling of the baked lightmap atlas, to be included in the lit fragment shaders before their
+// main function. The lightmap texture coordinates of the mesh are placed in the atlas with the
+// scale and offset of its `Lightmap` component.
+
+// Returns the baked irradiance at the given lightmap texture coordinates. The diffuse lighting
+// of static surfaces is this irradiance multiplied by their albedo.
+vec3 sample_lightmap(sampler2D atlas, vec2 lightmap_uv, vec2 scale, vec2 offset) {
+    return texture(atlas, lightmap_uv * scale + offset).rgb;
+}
@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) in vec3 frag_normal;
+layout(location = 1) in vec2 frag_uv;
+
+layout(location = 0) out vec4 out_color;
+
+void main() {
+    // A fixed directional light, until the meshes have materials. Meshes without normals are
+    // drawn with a flat color.
+    vec3 light = normalize(vec3(0.4, 1.0, 0.6));
+    float lambert = length(frag_normal) > 0.0
+        ? max(dot(normalize(frag_normal), light), 0.0)
+        : 1.0;
+    out_color = vec4(vec3(0.8) * (0.2 + 0.8 * lambert), 1.0);
+}
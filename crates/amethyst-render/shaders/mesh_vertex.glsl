@@ -0,0 +1,27 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+
+layout(location = 0) out vec3 frag_normal;
+layout(location = 1) out vec2 frag_uv;
+
+layout(set = 0, binding = 0) uniform View {
+    mat4 view;
+    mat4 projection;
+} camera;
+
+layout(push_constant) uniform Object {
+    // The transform of the mesh, from its local space to world space.
+    mat4 model;
+} object;
+
+void main() {
+    gl_Position = camera.projection * camera.view * object.model * vec4(position, 1.0);
+
+    // The normal matrix is only needed for non-uniform scales, which are rare enough to be
+    // ignored until the meshes have materials.
+    frag_normal = mat3(object.model) * normal;
+    frag_uv = uv;
+}
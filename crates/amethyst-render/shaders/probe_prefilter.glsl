@@ -0,0 +1,90 @@
+#version 450
+
+// Prefilter one mip level of a reflection probe for the GGX specular lobe of the given
+// roughness, by importance sampling the radiance cube map. The six faces are processed at
+// once, one per work group layer along Z.
+layout(local_size_x = 8, local_size_y = 8, local_size_z = 1) in;
+
+layout(set = 0, binding = 0) uniform samplerCube radiance;
+layout(set = 0, binding = 1, rgba16f) uniform writeonly image2DArray target;
+
+layout(push_constant) uniform Prefilter {
+    float roughness;
+    uint samples;
+} prefilter;
+
+const float PI = 3.14159265359;
+
+// Returns the direction of the center of a texel of a cube face, following the cube map
+// conventions of the Vulkan specification.
+vec3 texel_direction(ivec3 texel, ivec2 size) {
+    vec2 uv = (vec2(texel.xy) + 0.5) / vec2(size) * 2.0 - 1.0;
+    switch (texel.z) {
+        case 0: return normalize(vec3(1.0, -uv.y, -uv.x));
+        case 1: return normalize(vec3(-1.0, -uv.y, uv.x));
+        case 2: return normalize(vec3(uv.x, 1.0, uv.y));
+        case 3: return normalize(vec3(uv.x, -1.0, -uv.y));
+        case 4: return normalize(vec3(uv.x, -uv.y, 1.0));
+        default: return normalize(vec3(-uv.x, -uv.y, -1.0));
+    }
+}
+
+vec2 hammersley(uint i, uint count) {
+    uint bits = bitfieldReverse(i);
+    return vec2(float(i) / float(count), float(bits) * 2.3283064365386963e-10);
+}
+
+vec3 importance_sample_ggx(vec2 xi, vec3 normal, float alpha) {
+    float phi = 2.0 * PI * xi.x;
+    float cos_theta = sqrt((1.0 - xi.y) / (1.0 + (alpha * alpha - 1.0) * xi.y));
+    float sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+    vec3 h = vec3(cos(phi) * sin_theta, sin(phi) * sin_theta, cos_theta);
+
+    vec3 up = abs(normal.z) < 0.999 ? vec3(0.0, 0.0, 1.0) : vec3(1.0, 0.0, 0.0);
+    vec3 tangent = normalize(cross(up, normal));
+    vec3 bitangent = cross(normal, tangent);
+    return normalize(tangent * h.x + bitangent * h.y + normal * h.z);
+}
+
+void main() {
+    ivec3 texel = ivec3(gl_GlobalInvocationID);
+    ivec2 size = imageSize(target).xy;
+    if (any(greaterThanEqual(texel.xy, size))) {
+        return;
+    }
+
+    // Assume that the view direction is the normal, as usual for split-sum prefiltering.
+    vec3 normal = texel_direction(texel, size);
+    if (prefilter.roughness <= 0.0) {
+        imageStore(target, texel, vec4(textureLod(radiance, normal, 0.0).rgb, 1.0));
+        return;
+    }
+
+    // Sample the mip level of the radiance whose texel solid angle matches the solid angle of
+    // each sample, to avoid bright speckles with few samples.
+    float alpha = prefilter.roughness * prefilter.roughness;
+    float resolution = float(textureSize(radiance, 0).x);
+    float texel_angle = 4.0 * PI / (6.0 * resolution * resolution);
+
+    vec3 color = vec3(0.0);
+    float weight = 0.0;
+    for (uint i = 0u; i < prefilter.samples; i++) {
+        vec3 h = importance_sample_ggx(hammersley(i, prefilter.samples), normal, alpha);
+        vec3 l = normalize(2.0 * dot(normal, h) * h - normal);
+        float n_dot_l = dot(normal, l);
+        if (n_dot_l <= 0.0) {
+            continue;
+        }
+
+        float n_dot_h = max(dot(normal, h), 0.0);
+        float d = alpha * alpha / (PI * pow(n_dot_h * n_dot_h * (alpha * alpha - 1.0) + 1.0, 2.0));
+        float pdf = d / 4.0 + 0.0001;
+        float sample_angle = 1.0 / (float(prefilter.samples) * pdf);
+        float lod = 0.5 * log2(sample_angle / texel_angle) + 1.0;
+
+        color += textureLod(radiance, l, max(lod, 0.0)).rgb * n_dot_l;
+        weight += n_dot_l;
+    }
+
+    imageStore(target, texel, vec4(color / max(weight, 0.0001), 1.0));
+}
@@ -0,0 +1,35 @@
+#version 450
+
+// First step of a radix sort pass: counts the number of keys of each digit in each block. The
+// histogram is stored digit-major, so its exclusive prefix sum gives the position of the first
+// key of each digit and block in the sorted output.
+layout(local_size_x = 256) in;
+
+layout(set = 0, binding = 0) readonly buffer Keys { uint values[]; } keys;
+layout(set = 0, binding = 4) writeonly buffer Histogram { uint values[]; } histogram;
+
+layout(push_constant) uniform Radix {
+    uint count;
+    uint shift;
+    uint blocks;
+} radix;
+
+shared uint counts[16];
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    uint local = gl_LocalInvocationID.x;
+    if (local < 16) {
+        counts[local] = 0;
+    }
+    barrier();
+
+    if (index < radix.count) {
+        atomicAdd(counts[(keys.values[index] >> radix.shift) & 15], 1);
+    }
+    barrier();
+
+    if (local < 16) {
+        histogram.values[local * radix.blocks + gl_WorkGroupID.x] = counts[local];
+    }
+}
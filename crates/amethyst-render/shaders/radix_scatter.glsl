@@ -0,0 +1,44 @@
+#version 450
+
+// Last step of a radix sort pass: writes each key and its value at its sorted position. The
+// rank of a key among the keys of the same digit in its block preserves the order of the keys,
+// so the sort is stable.
+layout(local_size_x = 256) in;
+
+layout(set = 0, binding = 0) readonly buffer KeysIn { uint values[]; } keys_in;
+layout(set = 0, binding = 1) readonly buffer ValuesIn { uint values[]; } values_in;
+layout(set = 0, binding = 2) writeonly buffer KeysOut { uint values[]; } keys_out;
+layout(set = 0, binding = 3) writeonly buffer ValuesOut { uint values[]; } values_out;
+layout(set = 0, binding = 4) readonly buffer Offsets { uint values[]; } offsets;
+
+layout(push_constant) uniform Radix {
+    uint count;
+    uint shift;
+    uint blocks;
+} radix;
+
+shared uint digits[256];
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    uint local = gl_LocalInvocationID.x;
+
+    // Elements past the end get an invalid digit so they are never counted.
+    uint key = index < radix.count ? keys_in.values[index] : 0;
+    uint digit = index < radix.count ? (key >> radix.shift) & 15 : 16;
+    digits[local] = digit;
+    barrier();
+
+    if (index >= radix.count) {
+        return;
+    }
+
+    uint rank = 0;
+    for (uint i = 0; i < local; i++) {
+        rank += digits[i] == digit ? 1 : 0;
+    }
+
+    uint position = offsets.values[digit * radix.blocks + gl_WorkGroupID.x] + rank;
+    keys_out.values[position] = key;
+    values_out.values[position] = values_in.values[index];
+}
@@ -0,0 +1,26 @@
+// Sampling of the reflection probes, to be included in the lit fragment shaders before their
+// main function. The two nearest probes of the object are bound with their blend weights (see
+// `ReflectionProbeBlend`), and `environment` is used where the probes do not cover the object.
+
+// Returns the prefiltered radiance reflected along `r` for the given perceptual roughness, from
+// a probe whose mip levels were prefiltered for increasing roughness.
+vec3 sample_probe(samplerCube probe, vec3 r, float roughness) {
+    float max_lod = float(textureQueryLevels(probe) - 1);
+    return textureLod(probe, r, roughness * max_lod).rgb;
+}
+
+// Returns the specular radiance of the environment reflected along `r`, blending the two
+// nearest probes and the environment.
+vec3 sample_reflection_probes(
+    samplerCube first,
+    samplerCube second,
+    samplerCube environment,
+    vec2 weights,
+    vec3 r,
+    float roughness
+) {
+    vec3 color = sample_probe(first, r, roughness) * weights.x
+        + sample_probe(second, r, roughness) * weights.y;
+    float remaining = max(1.0 - weights.x - weights.y, 0.0);
+    return color + sample_probe(environment, r, roughness) * remaining;
+}
@@ -0,0 +1,19 @@
+#version 450
+
+// Last step of an exclusive prefix sum: adds the scanned sum of the previous blocks to each
+// element of a block.
+layout(local_size_x = 256) in;
+
+layout(set = 0, binding = 0) buffer Data { uint values[]; } data;
+layout(set = 0, binding = 2) readonly buffer Sums { uint values[]; } sums;
+
+layout(push_constant) uniform Scan {
+    uint count;
+} scan;
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    if (index < scan.count) {
+        data.values[index] += sums.values[gl_WorkGroupID.x];
+    }
+}
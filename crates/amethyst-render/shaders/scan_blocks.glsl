@@ -0,0 +1,39 @@
+#version 450
+
+// First step of an exclusive prefix sum: each work group scans a block of elements in shared
+// memory, writes the exclusive scan of the block and the sum of the block. The input and the
+// output can be the same buffer.
+layout(local_size_x = 256) in;
+
+layout(set = 0, binding = 0) readonly buffer Input { uint values[]; } source;
+layout(set = 0, binding = 1) writeonly buffer Output { uint values[]; } target;
+layout(set = 0, binding = 2) writeonly buffer Sums { uint values[]; } sums;
+
+layout(push_constant) uniform Scan {
+    uint count;
+} scan;
+
+shared uint data[256];
+
+void main() {
+    uint index = gl_GlobalInvocationID.x;
+    uint local = gl_LocalInvocationID.x;
+    uint value = index < scan.count ? source.values[index] : 0;
+    data[local] = value;
+    barrier();
+
+    // Hillis-Steele inclusive scan of the block.
+    for (uint offset = 1; offset < 256; offset <<= 1) {
+        uint previous = local >= offset ? data[local - offset] : 0;
+        barrier();
+        data[local] += previous;
+        barrier();
+    }
+
+    if (index < scan.count) {
+        target.values[index] = data[local] - value;
+    }
+    if (local == 255) {
+        sums.values[gl_WorkGroupID.x] = data[255];
+    }
+}
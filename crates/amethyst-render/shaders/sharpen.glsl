@@ -0,0 +1,31 @@
+#version 450
+
+// A sharpen filter, subtracting the four direct neighbours of each pixel from the pixel itself.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+layout(set = 0, binding = 1, rgba16f) uniform writeonly image2D target;
+
+layout(push_constant) uniform Sharpen {
+    float strength;
+} sharpen;
+
+void main() {
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(target);
+    if (any(greaterThanEqual(pixel, size))) {
+        return;
+    }
+
+    vec2 texel = 1.0 / vec2(size);
+    vec2 uv = (vec2(pixel) + 0.5) * texel;
+
+    vec4 center = texture(source, uv);
+    vec4 neighbours = texture(source, uv + vec2(texel.x, 0.0))
+        + texture(source, uv - vec2(texel.x, 0.0))
+        + texture(source, uv + vec2(0.0, texel.y))
+        + texture(source, uv - vec2(0.0, texel.y));
+
+    vec4 color = center * (1.0 + 4.0 * sharpen.strength) - neighbours * sharpen.strength;
+    imageStore(target, pixel, vec4(max(color.rgb, vec3(0.0)), center.a));
+}
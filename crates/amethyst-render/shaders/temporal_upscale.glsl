@@ -0,0 +1,120 @@
+#version 450
+
+// Reconstruct a full resolution image from a jittered image rendered at a lower resolution
+// and the history of the previous frames. Each output pixel accumulates the render samples
+// close to its center, weighted by their distance, and blends them with the reprojected
+// history. The history is clipped to the color range of the neighborhood of the current
+// samples, which rejects most of the ghosting left by disocclusions and moving objects.
+layout(local_size_x = 8, local_size_y = 8) in;
+
+layout(set = 0, binding = 0) uniform sampler2D color;
+layout(set = 0, binding = 1) uniform sampler2D depth;
+layout(set = 0, binding = 2) uniform sampler2D motion;
+layout(set = 0, binding = 3) uniform sampler2D history;
+layout(set = 0, binding = 4, rgba16f) uniform writeonly image2D target;
+
+layout(push_constant) uniform Upscale {
+    // The jitter offset of the current frame, in render pixels.
+    vec2 jitter;
+
+    // The maximum number of frames accumulated by the history.
+    float max_accumulation;
+
+    // Whether the history is invalid and must be ignored.
+    uint reset;
+} upscale;
+
+vec3 rgb_to_ycocg(vec3 rgb) {
+    return vec3(
+        dot(rgb, vec3(0.25, 0.5, 0.25)),
+        dot(rgb, vec3(0.5, 0.0, -0.5)),
+        dot(rgb, vec3(-0.25, 0.5, -0.25)));
+}
+
+vec3 ycocg_to_rgb(vec3 ycocg) {
+    return vec3(
+        ycocg.x + ycocg.y - ycocg.z,
+        ycocg.x + ycocg.z,
+        ycocg.x - ycocg.y - ycocg.z);
+}
+
+// Clip the history color toward the center of the neighborhood box, which keeps its hue
+// better than clamping each channel independently.
+vec3 clip_to_box(vec3 history_color, vec3 minimum, vec3 maximum) {
+    vec3 center = 0.5 * (maximum + minimum);
+    vec3 extents = 0.5 * (maximum - minimum) + 0.0001;
+    vec3 offset = history_color - center;
+    vec3 units = abs(offset / extents);
+    float scale = max(units.x, max(units.y, units.z));
+    return scale > 1.0 ? center + offset / scale : history_color;
+}
+
+void main() {
+    ivec2 pixel = ivec2(gl_GlobalInvocationID.xy);
+    ivec2 size = imageSize(target);
+    if (any(greaterThanEqual(pixel, size))) {
+        return;
+    }
+
+    ivec2 render_size = textureSize(color, 0);
+    vec2 uv = (vec2(pixel) + 0.5) / vec2(size);
+
+    // The position of the output pixel center in the render image, without the jitter.
+    vec2 render_position = uv * vec2(render_size) - upscale.jitter;
+    ivec2 render_pixel = ivec2(floor(render_position));
+
+    vec3 sum = vec3(0.0);
+    float total = 0.0;
+    vec3 moment1 = vec3(0.0);
+    vec3 moment2 = vec3(0.0);
+    float nearest_depth = 1.0;
+    ivec2 nearest_pixel = render_pixel;
+
+    for (int y = -1; y <= 1; y++) {
+        for (int x = -1; x <= 1; x++) {
+            ivec2 neighbor = clamp(render_pixel + ivec2(x, y), ivec2(0), render_size - 1);
+            vec3 sample_color = rgb_to_ycocg(texelFetch(color, neighbor, 0).rgb);
+
+            // A gaussian approximation of a Lanczos filter, centered on the output pixel.
+            vec2 distance = vec2(neighbor) + 0.5 - render_position;
+            float weight = exp(-2.29 * dot(distance, distance));
+            sum += sample_color * weight;
+            total += weight;
+
+            moment1 += sample_color;
+            moment2 += sample_color * sample_color;
+
+            // Use the motion of the closest surface, so the edges of moving objects are
+            // reprojected with them instead of with the background.
+            float sample_depth = texelFetch(depth, neighbor, 0).r;
+            if (sample_depth < nearest_depth) {
+                nearest_depth = sample_depth;
+                nearest_pixel = neighbor;
+            }
+        }
+    }
+
+    vec3 current = sum / max(total, 0.0001);
+    vec3 mean = moment1 / 9.0;
+    vec3 deviation = sqrt(max(moment2 / 9.0 - mean * mean, 0.0));
+    vec3 minimum = mean - 1.25 * deviation;
+    vec3 maximum = mean + 1.25 * deviation;
+
+    vec2 previous_uv = uv - texelFetch(motion, nearest_pixel, 0).rg;
+    bool valid = upscale.reset == 0u
+        && all(greaterThanEqual(previous_uv, vec2(0.0)))
+        && all(lessThanEqual(previous_uv, vec2(1.0)));
+
+    vec4 previous = textureLod(history, previous_uv, 0.0);
+    float accumulated = valid ? previous.a : 0.0;
+    vec3 history_color = clip_to_box(rgb_to_ycocg(previous.rgb), minimum, maximum);
+
+    // The confidence of the current samples is higher when one of them is close to the center
+    // of the output pixel, so they are accumulated proportionally to their best weight.
+    float confidence = clamp(total / 2.0, 0.05, 1.0);
+    float history_weight = min(accumulated, upscale.max_accumulation);
+    vec3 result = (history_color * history_weight + current * confidence)
+        / (history_weight + confidence);
+
+    imageStore(target, pixel, vec4(ycocg_to_rgb(result), history_weight + confidence));
+}
@@ -0,0 +1,114 @@
+//! A thin abstraction between the render logic and the GPU. The render logic (extraction,
+//! culling, ordering...) only produces a [`DrawList`] made of plain data, which is then executed
+//! by a [`RenderBackend`]. The Vulkan renderer is one backend, and the [`NullBackend`] is another
+//! one that simply records the draw lists it receives, so the render logic can be unit tested
+//! on machines without any Vulkan driver.
+use crate::view::RenderView;
+use bevy::prelude::*;
+use std::path::PathBuf;
+use vulkanalia::prelude::v1_3::*;
+
+/// A single draw call, described with plain data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DrawItem {
+    pub vertex_count: u32,
+    pub instance_count: u32,
+    pub first_vertex: u32,
+    pub first_instance: u32,
+}
+
+/// The draw of an entity with a [`Mesh`](crate::mesh::Mesh), described with plain data. The
+/// mesh is identified by its entity, and its GPU buffers are looked up by the backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshDraw {
+    /// The entity owning the mesh.
+    pub mesh: Entity,
+
+    /// The transform of the mesh, from its local space to world space.
+    pub transform: Mat4,
+}
+
+/// Everything that must be rendered during a frame.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DrawList {
+    /// The color used to clear the render target before drawing.
+    pub clear_color: [f32; 4],
+
+    /// The draw calls, in the order they must be executed.
+    pub items: Vec<DrawItem>,
+
+    /// The meshes to draw after the draw calls, in the order they must be drawn.
+    pub meshes: Vec<MeshDraw>,
+
+    /// The view from which the meshes are rendered.
+    pub view: RenderView,
+
+    /// The path of the PNG file to save the rendered frame to, if a screenshot was requested.
+    pub screenshot: Option<PathBuf>,
+}
+
+/// A backend able to execute draw lists. This is implemented by the Vulkan renderer, and by the
+/// [`NullBackend`] for tests.
+pub trait RenderBackend {
+    /// Returns the extent of the surface that will be rendered to during the next frame, or
+    /// `None` if nothing can be rendered right now (for example if the application is suspended
+    /// or the window is occluded).
+    fn frame_extent(&self) -> Option<vk::Extent2D>;
+
+    /// Execute the draw list and present the result.
+    fn execute(&mut self, list: &DrawList);
+}
+
+/// A backend that does not need any GPU and records every draw list it executes. It can be
+/// configured to simulate a suspended application or a surface of any size.
+#[derive(Debug, Clone, Default)]
+pub struct NullBackend {
+    /// The extent returned by [`RenderBackend::frame_extent`].
+    pub extent: Option<vk::Extent2D>,
+
+    /// The draw lists executed so far, one per frame.
+    pub frames: Vec<DrawList>,
+}
+
+impl NullBackend {
+    /// Create a new null backend with a surface of the given size.
+    #[must_use]
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            extent: Some(vk::Extent2D { width, height }),
+            frames: Vec::new(),
+        }
+    }
+
+    /// Returns the draw list of the last executed frame, if any.
+    #[must_use]
+    pub fn last_frame(&self) -> Option<&DrawList> {
+        self.frames.last()
+    }
+}
+
+impl RenderBackend for NullBackend {
+    fn frame_extent(&self) -> Option<vk::Extent2D> {
+        self.extent
+    }
+
+    fn execute(&mut self, list: &DrawList) {
+        self.frames.push(list.clone());
+    }
+}
+
+/// Render a frame with the given backend: build the draw list with the provided function and
+/// execute it if the backend is able to render. Returns whether a frame has been rendered.
+pub fn render_frame(
+    backend: &mut impl RenderBackend,
+    build: impl FnOnce(vk::Extent2D) -> DrawList,
+) -> bool {
+    match backend.frame_extent() {
+        Some(extent) if extent.width > 0 && extent.height > 0 => {
+            let list = build(extent);
+            backend.execute(&list);
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,212 @@
+//! A pass that samples an image and writes it to the current render target, with optional
+//! scaling, gamma conversion and letterboxing. This is mostly used to present offscreen render
+//! targets to the swapchain.
+use crate::fullscreen::{FullscreenPass, FullscreenPassCreateInfo};
+use amethyst_vulkan::{
+    command::{CommandBuffer, Recording},
+    descriptor::{DescriptorBinding, DescriptorPool, DescriptorSet, DescriptorSetLayout},
+    device::VulkanDevice,
+    image::Sampler,
+    shader::{ShaderModule, ShaderType},
+    swapchain::VulkanSwapchain,
+};
+use bevy::prelude::*;
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The size of the push constants used by the blit shader, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 9 * 4;
+
+/// How the source image is scaled to the target.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlitScaling {
+    /// The source image is stretched to cover the whole target, ignoring its aspect ratio.
+    Stretch,
+
+    /// The source image is scaled as much as possible while keeping its aspect ratio, and
+    /// centered in the target. The uncovered area is filled with the border color.
+    #[default]
+    Fit,
+
+    /// Like [`BlitScaling::Fit`], but the scale factor is rounded down to an integer so
+    /// every source pixel covers the same number of target pixels. Useful for pixel art.
+    Integer,
+}
+
+impl BlitScaling {
+    /// Returns the area covered by the source image in the target image, as a scale and an
+    /// offset in texture coordinates of the target image.
+    #[must_use]
+    pub fn area(self, source: vk::Extent2D, target: vk::Extent2D) -> (Vec2, Vec2) {
+        let source = Vec2::new(source.width as f32, source.height as f32);
+        let target = Vec2::new(target.width as f32, target.height as f32);
+        let fit = (target / source).min_element();
+
+        let factor = match self {
+            BlitScaling::Stretch => return (Vec2::ONE, Vec2::ZERO),
+            BlitScaling::Fit => fit,
+            BlitScaling::Integer => fit.floor().max(1.0),
+        };
+
+        let scale = source * factor / target;
+        (scale, (Vec2::ONE - scale) * 0.5)
+    }
+}
+
+/// The gamma conversion applied to the colors of the source image.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GammaConversion {
+    /// The colors are copied as they are. This is also the right choice when the source and
+    /// the target both use a sRGB format, since the conversion is done by the hardware.
+    #[default]
+    None,
+
+    /// The colors are converted from linear to sRGB, for example to present a linear render
+    /// target to a swapchain that does not use a sRGB format.
+    LinearToSrgb,
+
+    /// The colors are converted from sRGB to linear.
+    SrgbToLinear,
+}
+
+/// The options of a blit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlitOptions {
+    /// How the source image is scaled to the target.
+    pub scaling: BlitScaling,
+
+    /// The gamma conversion applied to the colors of the source image.
+    pub gamma: GammaConversion,
+
+    /// The color written in the area of the target not covered by the source image.
+    pub border_color: [f32; 4],
+}
+
+impl Default for BlitOptions {
+    fn default() -> Self {
+        Self {
+            scaling: BlitScaling::default(),
+            gamma: GammaConversion::default(),
+            border_color: [0.0, 0.0, 0.0, 1.0],
+        }
+    }
+}
+
+/// A pass that copies an image to the current render target.
+#[derive(Debug)]
+pub struct BlitPass {
+    /// The descriptor set binding the source image.
+    set: DescriptorSet,
+
+    /// The pool from which the descriptor set is allocated, only kept alive for the set.
+    _pool: DescriptorPool,
+
+    /// The full-screen pass running the blit shader.
+    pass: FullscreenPass,
+
+    /// A sampler with linear filtering, used to scale smooth images.
+    linear: Sampler,
+
+    /// A sampler with nearest filtering, used to scale pixel art without blurring it.
+    nearest: Sampler,
+}
+
+impl BlitPass {
+    /// Create a new blit pass writing to a render target with the given format, or to the
+    /// swapchain if no format is given.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        swapchain: &VulkanSwapchain,
+        format: Option<vk::Format>,
+    ) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[DescriptorBinding {
+                binding: 0,
+                kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                stages: vk::ShaderStageFlags::FRAGMENT,
+            }],
+        ));
+
+        let pass = FullscreenPass::new(
+            device.clone(),
+            swapchain,
+            FullscreenPassCreateInfo {
+                fragment: ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Fragment,
+                    include_str!("../shaders/blit.glsl").to_string(),
+                ),
+                color_formats: format.into_iter().collect(),
+                descriptor_set_layouts: vec![layout.clone()],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                    offset: 0,
+                    size: PUSH_CONSTANTS_SIZE,
+                }],
+            },
+        );
+
+        let pool = DescriptorPool::new(device.clone(), &layout, 1);
+        let set = pool.allocate(&layout);
+        let address = vk::SamplerAddressMode::CLAMP_TO_EDGE;
+
+        Self {
+            linear: Sampler::new(device.clone(), vk::Filter::LINEAR, address),
+            nearest: Sampler::new(device, vk::Filter::NEAREST, address),
+            _pool: pool,
+            pass,
+            set,
+        }
+    }
+
+    /// Set the image copied by the pass, sampled with the given filter. The image must be in
+    /// the `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout when the pass is executed.
+    ///
+    /// # Important
+    /// The source must not be changed while a command buffer recorded with this pass is
+    /// pending execution.
+    pub fn set_source(&self, view: vk::ImageView, filter: vk::Filter) {
+        let sampler = match filter {
+            vk::Filter::NEAREST => &self.nearest,
+            _ => &self.linear,
+        };
+
+        self.set
+            .write_sampled_image(0, view, sampler, vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL);
+    }
+
+    /// Record the pass into the command buffer. This must be called between
+    /// [`CommandBuffer::start_rendering`] and [`CommandBuffer::stop_rendering`], with a render
+    /// area of the `target` extent. The `source` extent is the size of the source image.
+    #[must_use]
+    pub fn record<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        source: vk::Extent2D,
+        target: vk::Extent2D,
+        options: &BlitOptions,
+    ) -> CommandBuffer<'pool, Recording> {
+        let (scale, offset) = options.scaling.area(source, target);
+        let gamma = match options.gamma {
+            GammaConversion::None => 0,
+            GammaConversion::LinearToSrgb => 1,
+            GammaConversion::SrgbToLinear => 2,
+        };
+
+        let [r, g, b, a] = options.border_color;
+        let constants = [scale.x, scale.y, offset.x, offset.y, r, g, b, a]
+            .map(f32::to_bits)
+            .into_iter()
+            .chain([gamma])
+            .flat_map(u32::to_ne_bytes)
+            .collect::<Vec<_>>();
+
+        let layout = self.pass.pipeline().layout();
+        let command = command
+            .bind_descriptor_set(vk::PipelineBindPoint::GRAPHICS, layout, 0, &self.set)
+            .push_constant_bytes(layout, vk::ShaderStageFlags::FRAGMENT, 0, &constants);
+        self.pass.record(command)
+    }
+}
@@ -0,0 +1,317 @@
+use bevy::{
+    math::{Affine3A, Vec3A},
+    prelude::*,
+};
+
+/// An axis-aligned bounding box, defined by its minimum and maximum corners.
+///
+/// When attached to an entity, it is expressed in the local space of the entity. It is computed
+/// automatically for entities with a [`Mesh`](crate::mesh::Mesh).
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Create a new axis-aligned bounding box from its minimum and maximum corners.
+    #[must_use]
+    pub const fn new(min: Vec3, max: Vec3) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the center of the bounding box.
+    #[must_use]
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    /// Returns the half size of the bounding box along each axis.
+    #[must_use]
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// Returns the area of the surface of the bounding box.
+    #[must_use]
+    pub fn surface_area(&self) -> f32 {
+        let size = self.max - self.min;
+        2.0 * (size.x * size.y + size.y * size.z + size.z * size.x)
+    }
+
+    /// Returns the smallest bounding box containing both bounding boxes.
+    #[must_use]
+    pub fn union(&self, other: &Aabb) -> Self {
+        Self::new(self.min.min(other.min), self.max.max(other.max))
+    }
+
+    /// Returns the smallest axis-aligned bounding box containing this bounding box once
+    /// transformed by the given affine transform.
+    #[must_use]
+    pub fn transformed(&self, transform: &Affine3A) -> Self {
+        let center = transform.transform_point3(self.center());
+        let matrix = transform.matrix3;
+        let half = Vec3A::from(self.half_extents());
+        let extents = Vec3::from(
+            matrix.x_axis.abs() * half.x
+                + matrix.y_axis.abs() * half.y
+                + matrix.z_axis.abs() * half.z,
+        );
+        Self::new(center - extents, center + extents)
+    }
+
+    /// Returns the bounding box grown by the given margin along each axis.
+    #[must_use]
+    pub fn inflated(&self, margin: f32) -> Self {
+        Self::new(self.min - margin, self.max + margin)
+    }
+}
+
+/// A bounding sphere, defined by its center and radius.
+///
+/// When attached to an entity, it is expressed in the local space of the entity. It is computed
+/// automatically for entities with a [`Mesh`](crate::mesh::Mesh).
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// Create a new bounding sphere from its center and radius.
+    #[must_use]
+    pub const fn new(center: Vec3, radius: f32) -> Self {
+        Self { center, radius }
+    }
+
+    /// Create the smallest bounding sphere centered on the bounding box and containing it.
+    #[must_use]
+    pub fn from_aabb(aabb: &Aabb) -> Self {
+        Self::new(aabb.center(), aabb.half_extents().length())
+    }
+}
+
+/// An extra margin added to the bounding volumes computed from the mesh of an entity, in local
+/// units. Meshes whose vertices are displaced on the GPU, by skinning or vertex animation,
+/// should use a margin covering the largest displacement, so they are never culled or missed
+/// by picking while visible.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Component)]
+pub struct BoundsMargin(pub f32);
+
+/// A view frustum, defined by six planes pointing inward. A frustum is usually extracted from
+/// the view-projection matrix of a camera, and is used to check whether an object is visible
+/// by the camera or not.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// The planes of the frustum, in the order left, right, bottom, top, near and far. Each
+    /// plane is stored as a normalized `(normal, distance)` vector where the normal points
+    /// inside the frustum.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extract the frustum planes from a view-projection matrix. The projection must follow
+    /// the Vulkan conventions, with a depth range of `[0, 1]`.
+    #[must_use]
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let r0 = view_projection.row(0);
+        let r1 = view_projection.row(1);
+        let r2 = view_projection.row(2);
+        let r3 = view_projection.row(3);
+
+        let planes = [r3 + r0, r3 - r0, r3 + r1, r3 - r1, r2, r3 - r2].map(|plane| {
+            let length = plane.truncate().length();
+            if length > 0.0 {
+                plane / length
+            } else {
+                plane
+            }
+        });
+
+        Self { planes }
+    }
+
+    /// Returns the signed distance between the plane and the point. A positive distance
+    /// means that the point is inside the half-space of the plane.
+    fn distance(plane: Vec4, point: Vec3) -> f32 {
+        plane.truncate().dot(point) + plane.w
+    }
+
+    /// Returns whether the given point is inside the frustum.
+    #[must_use]
+    pub fn contains_point(&self, point: Vec3) -> bool {
+        self.planes
+            .iter()
+            .all(|&plane| Self::distance(plane, point) >= 0.0)
+    }
+
+    /// Returns whether the given sphere intersects or is inside the frustum.
+    #[must_use]
+    pub fn intersects_sphere(&self, sphere: &BoundingSphere) -> bool {
+        self.planes
+            .iter()
+            .all(|&plane| Self::distance(plane, sphere.center) >= -sphere.radius)
+    }
+
+    /// Returns whether the given axis-aligned bounding box intersects or is inside the frustum.
+    /// This test is conservative: it may return `true` for some boxes that are close to the
+    /// frustum corners but outside of it, but never returns `false` for a visible box.
+    #[must_use]
+    pub fn intersects_aabb(&self, aabb: &Aabb) -> bool {
+        self.planes.iter().all(|&plane| {
+            // Test the corner of the box that is the furthest along the plane normal. If
+            // this corner is outside, the whole box is outside.
+            let normal = plane.truncate();
+            let positive = Vec3::select(normal.cmpge(Vec3::ZERO), aabb.max, aabb.min);
+            Self::distance(plane, positive) >= 0.0
+        })
+    }
+
+    /// Returns whether the given axis-aligned bounding box, transformed by the given matrix,
+    /// intersects or is inside the frustum.
+    #[must_use]
+    pub fn intersects_obb(&self, aabb: &Aabb, transform: &Mat4) -> bool {
+        let center = transform.transform_point3(aabb.center());
+        let half = aabb.half_extents();
+        let axes = [
+            transform.x_axis.truncate() * half.x,
+            transform.y_axis.truncate() * half.y,
+            transform.z_axis.truncate() * half.z,
+        ];
+
+        self.planes.iter().all(|&plane| {
+            let normal = plane.truncate();
+            let radius = axes.iter().map(|axis| axis.dot(normal).abs()).sum::<f32>();
+            Self::distance(plane, center) >= -radius
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The frustum of a camera at the origin looking toward -Z, with a 90° vertical field of
+    /// view, a square aspect ratio, and a depth range of `[1, 10]`.
+    fn frustum() -> Frustum {
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 10.0);
+        Frustum::from_view_projection(projection)
+    }
+
+    #[test]
+    fn frustum_planes_are_normalized() {
+        for plane in frustum().planes {
+            assert!((plane.truncate().length() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn frustum_contains_point() {
+        let frustum = frustum();
+
+        assert!(frustum.contains_point(Vec3::new(0.0, 0.0, -5.0)));
+        assert!(frustum.contains_point(Vec3::new(4.9, -4.9, -5.0)));
+        assert!(!frustum.contains_point(Vec3::new(5.1, 0.0, -5.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 5.1, -5.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -0.5)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -10.5)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, 5.0)));
+    }
+
+    #[test]
+    fn frustum_follows_the_view() {
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 10.0);
+        let view = Mat4::look_at_rh(Vec3::new(20.0, 0.0, 0.0), Vec3::ZERO, Vec3::Y);
+        let frustum = Frustum::from_view_projection(projection * view);
+
+        assert!(frustum.contains_point(Vec3::new(15.0, 0.0, 0.0)));
+        assert!(!frustum.contains_point(Vec3::new(0.0, 0.0, -5.0)));
+    }
+
+    #[test]
+    fn frustum_intersects_sphere() {
+        let frustum = frustum();
+
+        assert!(frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, -5.0), 1.0)));
+        assert!(frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, -0.5), 1.0)));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(0.0, 0.0, 3.0), 1.0)));
+        assert!(!frustum.intersects_sphere(&BoundingSphere::new(Vec3::new(8.0, 0.0, -5.0), 1.0)));
+    }
+
+    #[test]
+    fn frustum_intersects_aabb() {
+        let frustum = frustum();
+        let cube = |center: Vec3| Aabb::new(center - 0.5, center + 0.5);
+
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, -5.0))));
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(5.3, 0.0, -5.0))));
+        assert!(frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, -10.3))));
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(6.5, 0.0, -5.0))));
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, 2.0))));
+        assert!(!frustum.intersects_aabb(&cube(Vec3::new(0.0, 0.0, -11.0))));
+
+        // A box containing the whole frustum intersects it.
+        assert!(frustum.intersects_aabb(&Aabb::new(Vec3::splat(-100.0), Vec3::splat(100.0))));
+    }
+
+    #[test]
+    fn frustum_intersects_obb() {
+        let frustum = frustum();
+        let cube = Aabb::new(Vec3::splat(-0.5), Vec3::splat(0.5));
+        assert!(frustum.intersects_obb(&cube, &Mat4::from_translation(Vec3::new(0.0, 0.0, -5.0))));
+        assert!(!frustum.intersects_obb(&cube, &Mat4::from_translation(Vec3::new(6.5, 0.0, -5.0))));
+
+        let scaled = Mat4::from_scale_rotation_translation(
+            Vec3::splat(4.0),
+            Quat::IDENTITY,
+            Vec3::new(6.5, 0.0, -5.0),
+        );
+        assert!(frustum.intersects_obb(&cube, &scaled));
+
+        // A long box along X reaches inside the frustum, unless it is rotated along Y.
+        let long = Aabb::new(Vec3::new(-3.0, -0.1, -0.1), Vec3::new(3.0, 0.1, 0.1));
+        let translation = Mat4::from_translation(Vec3::new(7.0, 0.0, -5.0));
+        let rotation = Mat4::from_rotation_z(std::f32::consts::FRAC_PI_2);
+        assert!(frustum.intersects_obb(&long, &translation));
+        assert!(!frustum.intersects_obb(&long, &(translation * rotation)));
+    }
+
+    #[test]
+    fn transform_aabb() {
+        let aabb = Aabb::new(Vec3::new(-1.0, -2.0, -3.0), Vec3::new(1.0, 2.0, 3.0));
+
+        let translated = aabb.transformed(&Affine3A::from_translation(Vec3::X));
+        assert_eq!(
+            translated,
+            Aabb::new(Vec3::new(0.0, -2.0, -3.0), Vec3::new(2.0, 2.0, 3.0))
+        );
+
+        let scaled = aabb.transformed(&Affine3A::from_scale(Vec3::new(2.0, 1.0, -1.0)));
+        assert_eq!(
+            scaled,
+            Aabb::new(Vec3::new(-2.0, -2.0, -3.0), Vec3::new(2.0, 2.0, 3.0))
+        );
+
+        let rotated = aabb.transformed(&Affine3A::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        assert!(rotated.min.abs_diff_eq(Vec3::new(-2.0, -1.0, -3.0), 1e-5));
+        assert!(rotated.max.abs_diff_eq(Vec3::new(2.0, 1.0, 3.0), 1e-5));
+    }
+
+    #[test]
+    fn transform_aabb_contains_the_transformed_corners() {
+        let aabb = Aabb::new(Vec3::new(0.0, 1.0, 2.0), Vec3::new(1.0, 3.0, 6.0));
+        let transform = Affine3A::from_scale_rotation_translation(
+            Vec3::new(2.0, 0.5, 1.0),
+            Quat::from_euler(EulerRot::XYZ, 0.3, -1.2, 2.0),
+            Vec3::new(5.0, -4.0, 3.0),
+        );
+        let transformed = aabb.transformed(&transform);
+
+        for corner in 0..8 {
+            let select = BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0);
+            let point = transform.transform_point3(Vec3::select(select, aabb.max, aabb.min));
+            assert!(point.cmpge(transformed.min - 1e-4).all());
+            assert!(point.cmple(transformed.max + 1e-4).all());
+        }
+    }
+}
@@ -0,0 +1,572 @@
+//! A dynamic bounding volume hierarchy over the renderable entities, accelerating the queries
+//! that would otherwise test every entity: frustum culling and ray casts. Entities are inserted
+//! and removed incrementally, and their bounding boxes are refitted in place when they move.
+//! Refitting is fast but degrades the quality of the tree over time, which is tracked by a
+//! surface area heuristic and fixed by a full rebuild once it degrades too much. The same
+//! measure can drive the rebuild decisions of ray tracing acceleration structures.
+use crate::{
+    bounds::{Aabb, Frustum},
+    raycast::ray_aabb,
+};
+use bevy::{prelude::*, utils::HashMap};
+
+/// The [`Bvh::degradation`] above which the tree is rebuilt by [`update_bvh`].
+pub const BVH_REBUILD_THRESHOLD: f32 = 1.5;
+
+/// A node of the hierarchy.
+#[derive(Debug, Clone, Copy)]
+struct Node {
+    /// The bounding box of the node, in world space, containing all of its children.
+    aabb: Aabb,
+
+    /// The index of the parent node, or `None` for the root.
+    parent: Option<usize>,
+
+    /// The content of the node.
+    kind: NodeKind,
+}
+
+/// The content of a node of the hierarchy.
+#[derive(Debug, Clone, Copy)]
+enum NodeKind {
+    /// A leaf node, containing a single entity.
+    Leaf(Entity),
+
+    /// An internal node, with the index of its two children.
+    Branch([usize; 2]),
+
+    /// An unused node, that can be reused by the next allocation.
+    Free,
+}
+
+/// A dynamic bounding volume hierarchy of world space bounding boxes. The resource is kept up
+/// to date with the entities having an [`Aabb`] and a [`GlobalTransform`] by the
+/// [`update_bvh`] system, after the transforms are propagated.
+#[derive(Debug, Default, Resource)]
+pub struct Bvh {
+    /// The nodes of the tree. Removed nodes are marked as free and reused.
+    nodes: Vec<Node>,
+
+    /// The index of the free nodes.
+    free: Vec<usize>,
+
+    /// The index of the root node, or `None` if the tree is empty.
+    root: Option<usize>,
+
+    /// The index of the leaf node of each entity.
+    leaves: HashMap<Entity, usize>,
+
+    /// The cost of the tree right after its last rebuild.
+    built_cost: f32,
+}
+
+impl Bvh {
+    /// Create an empty hierarchy.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of entities in the hierarchy.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns whether the hierarchy does not contain any entity.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Returns whether the entity is in the hierarchy.
+    #[must_use]
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.leaves.contains_key(&entity)
+    }
+
+    /// Returns the bounding box of the whole hierarchy, or `None` if it is empty.
+    #[must_use]
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.root.map(|root| self.nodes[root].aabb)
+    }
+
+    /// Insert an entity with the given world space bounding box, or update its bounding box if
+    /// it is already in the hierarchy. An update only refits the ancestors of the entity, and
+    /// does not move it in the tree.
+    pub fn insert(&mut self, entity: Entity, aabb: Aabb) {
+        if let Some(&leaf) = self.leaves.get(&entity) {
+            self.nodes[leaf].aabb = aabb;
+            self.refit(self.nodes[leaf].parent);
+            return;
+        }
+
+        let leaf = self.allocate(Node {
+            aabb,
+            parent: None,
+            kind: NodeKind::Leaf(entity),
+        });
+        self.leaves.insert(entity, leaf);
+
+        let Some(root) = self.root else {
+            self.root = Some(leaf);
+            return;
+        };
+
+        // Find the node that will become the sibling of the new leaf, by descending into the
+        // child whose cost increases the least, until creating a new parent for the current
+        // node is cheaper than descending further.
+        let mut sibling = root;
+        while let NodeKind::Branch(children) = self.nodes[sibling].kind {
+            let area = self.nodes[sibling].aabb.surface_area();
+            let combined = self.nodes[sibling].aabb.union(&aabb).surface_area();
+            let here = 2.0 * combined;
+            let inherited = 2.0 * (combined - area);
+
+            let descend = |child: usize| {
+                let node = &self.nodes[child];
+                let enlarged = node.aabb.union(&aabb).surface_area();
+                match node.kind {
+                    NodeKind::Branch(_) => enlarged - node.aabb.surface_area() + inherited,
+                    _ => enlarged + inherited,
+                }
+            };
+
+            let (left, right) = (descend(children[0]), descend(children[1]));
+            if here < left && here < right {
+                break;
+            }
+            sibling = if left <= right {
+                children[0]
+            } else {
+                children[1]
+            };
+        }
+
+        let grandparent = self.nodes[sibling].parent;
+        let parent = self.allocate(Node {
+            aabb: self.nodes[sibling].aabb.union(&aabb),
+            parent: grandparent,
+            kind: NodeKind::Branch([sibling, leaf]),
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, sibling, parent);
+                self.refit(Some(grandparent));
+            }
+            None => self.root = Some(parent),
+        }
+    }
+
+    /// Remove an entity from the hierarchy. Returns whether the entity was in the hierarchy.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let Some(leaf) = self.leaves.remove(&entity) else {
+            return false;
+        };
+
+        let parent = self.nodes[leaf].parent;
+        self.release(leaf);
+
+        let Some(parent) = parent else {
+            self.root = None;
+            return true;
+        };
+
+        // Replace the parent of the leaf by the sibling of the leaf.
+        let NodeKind::Branch(children) = self.nodes[parent].kind else {
+            unreachable!("The parent of a node is always a branch");
+        };
+        let sibling = if children[0] == leaf {
+            children[1]
+        } else {
+            children[0]
+        };
+
+        let grandparent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grandparent;
+        self.release(parent);
+
+        match grandparent {
+            Some(grandparent) => {
+                self.replace_child(grandparent, parent, sibling);
+                self.refit(Some(grandparent));
+            }
+            None => self.root = Some(sibling),
+        }
+        true
+    }
+
+    /// Rebuild the whole tree from the bounding boxes of its entities, restoring its quality
+    /// after many insertions and refits. The tree is built top-down, splitting the entities in
+    /// two halves along the axis where their centers are the most spread.
+    pub fn rebuild(&mut self) {
+        let mut items = self
+            .leaves
+            .iter()
+            .map(|(&entity, &leaf)| (entity, self.nodes[leaf].aabb))
+            .collect::<Vec<_>>();
+
+        self.nodes.clear();
+        self.free.clear();
+        self.leaves.clear();
+        self.root = (!items.is_empty()).then(|| self.build(&mut items));
+        self.built_cost = self.cost();
+    }
+
+    /// Returns the cost of the tree according to the surface area heuristic: the sum of the
+    /// surface areas of the internal nodes, relative to the surface area of the root. It is
+    /// proportional to the expected number of nodes visited by a random ray.
+    #[must_use]
+    pub fn cost(&self) -> f32 {
+        let Some(root) = self.root else {
+            return 0.0;
+        };
+
+        let area = self.nodes[root].aabb.surface_area();
+        if area <= 0.0 {
+            return 0.0;
+        }
+
+        self.nodes
+            .iter()
+            .filter(|node| matches!(node.kind, NodeKind::Branch(_)))
+            .map(|node| node.aabb.surface_area())
+            .sum::<f32>()
+            / area
+    }
+
+    /// Returns the ratio between the current cost of the tree and its cost right after the
+    /// last rebuild. It starts at 1.0 and grows as entities are inserted and refitted, and is
+    /// infinite if the tree was never rebuilt since its first insertions.
+    #[must_use]
+    pub fn degradation(&self) -> f32 {
+        let cost = self.cost();
+        if self.built_cost > 0.0 {
+            cost / self.built_cost
+        } else if cost > 0.0 {
+            f32::INFINITY
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the entities whose bounding box intersects the frustum.
+    #[must_use]
+    pub fn query_frustum(&self, frustum: &Frustum) -> Vec<Entity> {
+        let mut entities = Vec::new();
+        self.traverse(
+            |aabb| frustum.intersects_aabb(aabb),
+            |entity, _| entities.push(entity),
+        );
+        entities
+    }
+
+    /// Returns the entities whose bounding box is hit by the ray within the given distance,
+    /// with the distance at which the ray enters their box, sorted from the nearest to the
+    /// farthest.
+    #[must_use]
+    pub fn query_ray(&self, ray: Ray3d, max_distance: f32) -> Vec<(Entity, f32)> {
+        let hit = |aabb: &Aabb| {
+            ray_aabb(ray.origin, *ray.direction, aabb)
+                .map(|(near, _)| near)
+                .filter(|&near| near <= max_distance)
+        };
+
+        let mut entities = Vec::new();
+        self.traverse(
+            |aabb| hit(aabb).is_some(),
+            |entity, aabb| entities.extend(hit(aabb).map(|near| (entity, near))),
+        );
+        entities.sort_by(|a, b| a.1.total_cmp(&b.1));
+        entities
+    }
+
+    /// Visit the nodes whose bounding box is accepted by the `visit` function, and call the
+    /// `leaf` function for each accepted leaf.
+    fn traverse(&self, mut visit: impl FnMut(&Aabb) -> bool, mut leaf: impl FnMut(Entity, &Aabb)) {
+        let mut stack = self.root.into_iter().collect::<Vec<_>>();
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !visit(&node.aabb) {
+                continue;
+            }
+
+            match node.kind {
+                NodeKind::Leaf(entity) => leaf(entity, &node.aabb),
+                NodeKind::Branch(children) => stack.extend(children),
+                NodeKind::Free => unreachable!("Free nodes are never referenced"),
+            }
+        }
+    }
+
+    /// Build a subtree containing the given entities, and returns the index of its root.
+    fn build(&mut self, items: &mut [(Entity, Aabb)]) -> usize {
+        if let [(entity, aabb)] = *items {
+            let leaf = self.allocate(Node {
+                aabb,
+                parent: None,
+                kind: NodeKind::Leaf(entity),
+            });
+            self.leaves.insert(entity, leaf);
+            return leaf;
+        }
+
+        let (min, max) = items.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), (_, aabb)| (min.min(aabb.center()), max.max(aabb.center())),
+        );
+        let spread = max - min;
+        let axis = if spread.x >= spread.y && spread.x >= spread.z {
+            0
+        } else if spread.y >= spread.z {
+            1
+        } else {
+            2
+        };
+
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |a, b| {
+            a.1.center()[axis].total_cmp(&b.1.center()[axis])
+        });
+
+        let (left, right) = items.split_at_mut(middle);
+        let children = [self.build(left), self.build(right)];
+        let node = self.allocate(Node {
+            aabb: self.nodes[children[0]]
+                .aabb
+                .union(&self.nodes[children[1]].aabb),
+            parent: None,
+            kind: NodeKind::Branch(children),
+        });
+        for child in children {
+            self.nodes[child].parent = Some(node);
+        }
+        node
+    }
+
+    /// Recompute the bounding box of the given node and of its ancestors, stopping as soon as
+    /// a bounding box does not change.
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(index) = node {
+            let NodeKind::Branch([left, right]) = self.nodes[index].kind else {
+                unreachable!("The parent of a node is always a branch");
+            };
+
+            let aabb = self.nodes[left].aabb.union(&self.nodes[right].aabb);
+            if self.nodes[index].aabb == aabb {
+                break;
+            }
+            self.nodes[index].aabb = aabb;
+            node = self.nodes[index].parent;
+        }
+    }
+
+    /// Replace a child of the given branch by another node.
+    fn replace_child(&mut self, branch: usize, old: usize, new: usize) {
+        if let NodeKind::Branch(children) = &mut self.nodes[branch].kind {
+            for child in children.iter_mut().filter(|child| **child == old) {
+                *child = new;
+            }
+        }
+    }
+
+    /// Store a node, reusing a free node if possible, and returns its index.
+    fn allocate(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Mark a node as free, so it can be reused by the next allocation.
+    fn release(&mut self, index: usize) {
+        self.nodes[index].kind = NodeKind::Free;
+        self.free.push(index);
+    }
+}
+
+/// Keep the [`Bvh`] up to date: entities whose bounding box or transform changed are inserted
+/// or refitted, entities that lost their bounding box are removed, and the tree is rebuilt
+/// once its degradation exceeds [`BVH_REBUILD_THRESHOLD`].
+#[allow(clippy::type_complexity)]
+pub fn update_bvh(
+    mut bvh: ResMut<Bvh>,
+    changed: Query<
+        (Entity, &Aabb, &GlobalTransform),
+        Or<(Changed<Aabb>, Changed<GlobalTransform>)>,
+    >,
+    mut removed: RemovedComponents<Aabb>,
+) {
+    for entity in removed.read() {
+        bvh.remove(entity);
+    }
+
+    for (entity, aabb, transform) in &changed {
+        bvh.insert(entity, aabb.transformed(&transform.affine()));
+    }
+
+    if bvh.degradation() > BVH_REBUILD_THRESHOLD {
+        bvh.rebuild();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a unit box centered on the given position.
+    fn unit_box(center: Vec3) -> Aabb {
+        Aabb::new(center - 0.5, center + 0.5)
+    }
+
+    /// Returns the entities of the frustum query, sorted so they can be compared.
+    fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+        entities.sort();
+        entities
+    }
+
+    /// Build a hierarchy with a row of unit boxes along the X axis, spaced by two units.
+    fn row(count: u32) -> (Bvh, Vec<(Entity, Aabb)>) {
+        let items = (0..count)
+            .map(|index| {
+                let center = Vec3::new(index as f32 * 2.0, 0.0, 0.0);
+                (Entity::from_raw(index), unit_box(center))
+            })
+            .collect::<Vec<_>>();
+
+        let mut bvh = Bvh::new();
+        for &(entity, aabb) in &items {
+            bvh.insert(entity, aabb);
+        }
+        (bvh, items)
+    }
+
+    /// An orthographic frustum covering `[-1, 1]` along X and Y, looking toward -Z from the
+    /// given position.
+    fn frustum_at(position: Vec3) -> Frustum {
+        let view = Mat4::from_translation(-position);
+        let projection = Mat4::orthographic_rh(-1.0, 1.0, -1.0, 1.0, 0.0, 10.0);
+        Frustum::from_view_projection(projection * view)
+    }
+
+    #[test]
+    fn insert_entities() {
+        let (bvh, items) = row(16);
+
+        assert_eq!(bvh.len(), 16);
+        assert!(items.iter().all(|&(entity, _)| bvh.contains(entity)));
+        assert_eq!(
+            bvh.bounds(),
+            Some(Aabb::new(Vec3::splat(-0.5), Vec3::new(30.5, 0.5, 0.5)))
+        );
+    }
+
+    #[test]
+    fn query_frustum_matches_brute_force() {
+        let (bvh, items) = row(16);
+
+        for x in [-5.0, 0.0, 7.0, 13.0, 30.0, 40.0] {
+            let frustum = frustum_at(Vec3::new(x, 0.0, 5.0));
+            let expected = items
+                .iter()
+                .filter(|(_, aabb)| frustum.intersects_aabb(aabb))
+                .map(|&(entity, _)| entity)
+                .collect::<Vec<_>>();
+            assert_eq!(sorted(bvh.query_frustum(&frustum)), sorted(expected));
+        }
+        assert_eq!(
+            bvh.query_frustum(&frustum_at(Vec3::new(14.0, 0.0, 5.0))),
+            [Entity::from_raw(7)]
+        );
+    }
+
+    #[test]
+    fn query_ray_sorted_by_distance() {
+        let (bvh, _) = row(4);
+        let ray = Ray3d::new(Vec3::new(-10.0, 0.0, 0.0), Dir3::X);
+
+        let hits = bvh.query_ray(ray, 14.0);
+        let entities = hits.iter().map(|&(entity, _)| entity).collect::<Vec<_>>();
+        assert_eq!(entities, [0, 1, 2].map(Entity::from_raw));
+        assert_eq!(hits[0].1, 9.5);
+    }
+
+    #[test]
+    fn update_an_entity() {
+        let (mut bvh, _) = row(8);
+        let moved = Entity::from_raw(3);
+        bvh.insert(moved, unit_box(Vec3::new(0.0, 20.0, 0.0)));
+
+        assert_eq!(bvh.len(), 8);
+        assert_eq!(bvh.bounds().map(|aabb| aabb.max.y), Some(20.5));
+        assert_eq!(
+            bvh.query_frustum(&frustum_at(Vec3::new(0.0, 20.0, 5.0))),
+            [moved]
+        );
+        assert!(!bvh
+            .query_frustum(&frustum_at(Vec3::new(6.0, 0.0, 5.0)))
+            .contains(&moved));
+    }
+
+    #[test]
+    fn remove_entities() {
+        let (mut bvh, items) = row(8);
+
+        assert!(bvh.remove(Entity::from_raw(7)));
+        assert!(!bvh.remove(Entity::from_raw(7)));
+        assert_eq!(bvh.len(), 7);
+        assert_eq!(bvh.bounds().map(|aabb| aabb.max.x), Some(12.5));
+
+        for &(entity, _) in &items[..7] {
+            assert!(bvh.remove(entity));
+        }
+        assert!(bvh.is_empty());
+        assert_eq!(bvh.bounds(), None);
+        assert!(bvh.query_frustum(&frustum_at(Vec3::Z)).is_empty());
+    }
+
+    #[test]
+    fn reuse_the_removed_nodes() {
+        let (mut bvh, items) = row(8);
+        let capacity = bvh.nodes.len();
+
+        for &(entity, aabb) in &items {
+            bvh.remove(entity);
+            bvh.insert(entity, aabb);
+        }
+        assert_eq!(bvh.nodes.len(), capacity);
+    }
+
+    #[test]
+    fn rebuild_restores_the_quality() {
+        let (mut bvh, items) = row(64);
+        bvh.rebuild();
+        let built = bvh.cost();
+        assert_eq!(bvh.degradation(), 1.0);
+
+        // Shuffle the boxes in place, which refits the tree without reorganizing it.
+        for (index, &(entity, _)) in items.iter().enumerate() {
+            let center = Vec3::new(((index * 37) % 64) as f32 * 2.0, 0.0, 0.0);
+            bvh.insert(entity, unit_box(center));
+        }
+        assert!(bvh.degradation() > BVH_REBUILD_THRESHOLD);
+
+        bvh.rebuild();
+        assert_eq!(bvh.len(), 64);
+        assert_eq!(bvh.degradation(), 1.0);
+        assert!(bvh.cost() <= built * 1.01);
+        assert_eq!(bvh.query_frustum(&frustum_at(Vec3::new(2.0, 0.0, 5.0))), {
+            let index = (0..64).find(|index| (index * 37) % 64 == 1).unwrap();
+            [Entity::from_raw(index)]
+        });
+    }
+}
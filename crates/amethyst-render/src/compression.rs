@@ -0,0 +1,232 @@
+//! Runtime texture compression. Textures loaded as RGBA8 can be compressed by compute shaders
+//! into BC7 (color) or BC5 (normal maps) when they are uploaded, dividing their memory use and
+//! bandwidth by four (BC7) or two (BC5, compared to a RG8 texture) for projects without an
+//! offline asset pipeline. The encoders favor speed over quality: a texture compressed offline
+//! will look better.
+use amethyst_vulkan::{
+    buffer::Buffer,
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    image::Image,
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The number of descriptor sets used in turn by the encoders.
+const DESCRIPTOR_SETS: usize = 16;
+
+/// The number of blocks encoded by each work group, along each axis.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The size of a compressed 4x4 block, in bytes. It is the same for BC7 and BC5.
+pub const BLOCK_BYTES: usize = 16;
+
+/// The compressed formats supported by the [`TextureCompressor`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompressedFormat {
+    /// BC7 with linear colors, for color textures that are not in the sRGB color space.
+    Bc7,
+
+    /// BC7 with sRGB colors, for most color textures.
+    Bc7Srgb,
+
+    /// BC5, compressing only the red and green channels. This is the format to use for
+    /// normal maps.
+    Bc5,
+}
+
+impl CompressedFormat {
+    /// Returns the format of the compressed images.
+    #[must_use]
+    pub const fn format(self) -> vk::Format {
+        match self {
+            CompressedFormat::Bc7 => vk::Format::BC7_UNORM_BLOCK,
+            CompressedFormat::Bc7Srgb => vk::Format::BC7_SRGB_BLOCK,
+            CompressedFormat::Bc5 => vk::Format::BC5_UNORM_BLOCK,
+        }
+    }
+
+    /// Returns the compressed format of the given image format, if it is supported.
+    #[must_use]
+    pub fn from_format(format: vk::Format) -> Option<Self> {
+        match format {
+            vk::Format::BC7_UNORM_BLOCK => Some(CompressedFormat::Bc7),
+            vk::Format::BC7_SRGB_BLOCK => Some(CompressedFormat::Bc7Srgb),
+            vk::Format::BC5_UNORM_BLOCK => Some(CompressedFormat::Bc5),
+            _ => None,
+        }
+    }
+}
+
+/// Returns the size in bytes of an image of the given size once compressed.
+#[must_use]
+pub const fn compressed_size(width: u32, height: u32) -> usize {
+    width.div_ceil(4) as usize * height.div_ceil(4) as usize * BLOCK_BYTES
+}
+
+/// Compute encoders compressing RGBA8 pixels to BC7 or BC5 blocks.
+#[derive(Debug)]
+pub struct TextureCompressor {
+    /// The descriptor sets used by the encoders, in turn, with the pixels at binding 0 and the
+    /// blocks at binding 1.
+    sets: DescriptorSetRing,
+
+    /// The pipeline encoding BC7 blocks.
+    bc7: ComputePipeline,
+
+    /// The pipeline encoding BC5 blocks.
+    bc5: ComputePipeline,
+}
+
+impl TextureCompressor {
+    /// Create the encoders, or returns `None` if the device cannot sample BC compressed
+    /// textures.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Option<Self> {
+        if device.enabled_features().texture_compression_bc != vk::TRUE {
+            return None;
+        }
+
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[0, 1].map(|binding| DescriptorBinding {
+                binding,
+                kind: vk::DescriptorType::STORAGE_BUFFER,
+                stages: vk::ShaderStageFlags::COMPUTE,
+            }),
+        ));
+
+        let pipeline = |name: &str, code: &str| {
+            ComputePipeline::new(
+                device.clone(),
+                ComputePipelineCreateInfo {
+                    shader: ShaderModule::compile_glsl(
+                        device.clone(),
+                        ShaderType::Compute,
+                        code.to_string(),
+                    ),
+                    descriptor_set_layouts: vec![layout.clone()],
+                    push_constant_ranges: vec![vk::PushConstantRange {
+                        stage_flags: vk::ShaderStageFlags::COMPUTE,
+                        offset: 0,
+                        size: 8,
+                    }],
+                    name: Some(name),
+                },
+            )
+        };
+
+        let bc7 = pipeline("bc7 encode", include_str!("../shaders/bc7_encode.glsl"));
+        let bc5 = pipeline("bc5 encode", include_str!("../shaders/bc5_encode.glsl"));
+
+        Some(Self {
+            sets: DescriptorSetRing::new(device, &layout, DESCRIPTOR_SETS),
+            bc7,
+            bc5,
+        })
+    }
+
+    /// Record the compression of an image of the given size, whose RGBA8 pixels are tightly
+    /// packed in the `pixels` buffer, into the `blocks` buffer. The blocks buffer must be at
+    /// least [`compressed_size`] bytes large, and can then be copied to an image of the same
+    /// size with the given format.
+    #[must_use]
+    pub fn compress<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        pixels: &Buffer,
+        blocks: &Buffer,
+        width: u32,
+        height: u32,
+        format: CompressedFormat,
+    ) -> CommandBuffer<'pool, Recording> {
+        let pipeline = match format {
+            CompressedFormat::Bc7 | CompressedFormat::Bc7Srgb => &self.bc7,
+            CompressedFormat::Bc5 => &self.bc5,
+        };
+
+        let set = self.sets.next();
+        set.write_storage_buffer(0, pixels);
+        set.write_storage_buffer(1, blocks);
+
+        let constants = [width, height]
+            .into_iter()
+            .flat_map(u32::to_ne_bytes)
+            .collect::<Vec<_>>();
+
+        command
+            .bind_compute_pipeline(pipeline)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, pipeline.layout(), 0, set)
+            .push_constant_bytes(
+                pipeline.layout(),
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &constants,
+            )
+            .dispatch(
+                width.div_ceil(4).div_ceil(WORKGROUP_SIZE),
+                height.div_ceil(4).div_ceil(WORKGROUP_SIZE),
+                1,
+            )
+    }
+
+    /// Record the compression of the RGBA8 pixels into the `blocks` buffer, and their upload to
+    /// the given image. The image must have been created with one of the formats returned by
+    /// [`CompressedFormat::format`], a single mip level and the `TRANSFER_DST` usage. It is left
+    /// in the `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout.
+    ///
+    /// # Panics
+    /// Panics if the format of the image is not supported.
+    #[must_use]
+    pub fn upload<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        pixels: &Buffer,
+        blocks: &Buffer,
+        image: &Image,
+    ) -> CommandBuffer<'pool, Recording> {
+        let format = CompressedFormat::from_format(image.format())
+            .expect("Unsupported compressed image format");
+        let extent = image.extent();
+
+        self.compress(command, pixels, blocks, extent.width, extent.height, format)
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                buffers_barriers: vec![vk::BufferMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_READ)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(blocks.inner())
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE)
+                    .build()],
+                images_barriers: vec![vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::empty())
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .subresource_range(image.subresource_range())
+                    .image(image.inner())
+                    .build()],
+            })
+            .copy_buffer_to_image(blocks, image, vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+                images_barriers: vec![vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .old_layout(vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                    .new_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+                    .subresource_range(image.subresource_range())
+                    .image(image.inner())
+                    .build()],
+                ..Default::default()
+            })
+    }
+}
@@ -0,0 +1,247 @@
+//! A physically based depth of field. The circle of confusion of each pixel is computed from
+//! its depth and the aperture and focus of the camera, like a thin lens, and the image is
+//! blurred with a bokeh shape using a scatter-as-gather compute pass.
+//!
+//! The renderer does not run the pass by itself: the application records a [`DepthOfFieldPass`]
+//! once the scene has been rendered into an HDR image with its depth buffer, before the image is
+//! tonemapped.
+use crate::filter::FILTER_FORMAT;
+use amethyst_vulkan::{
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    image::{Image, Sampler},
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The number of descriptor sets used in turn by the pass.
+const DESCRIPTOR_SETS: usize = 8;
+
+/// The size of the work groups of the depth of field shader, along each axis.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The size of the push constants used by the depth of field shader, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 6 * 4;
+
+/// The lens settings of a camera with depth of field. Distances are in world units, which are
+/// assumed to be meters.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DepthOfField {
+    /// The f-number of the lens: the ratio between its focal length and the diameter of its
+    /// aperture. Lower values give a shallower depth of field.
+    pub f_stop: f32,
+
+    /// The distance at which objects are perfectly sharp.
+    pub focus_distance: f32,
+
+    /// The height of the sensor, which determines the focal length of the lens from the field
+    /// of view of the camera. The default is the height of a full-frame sensor.
+    pub sensor_height: f32,
+
+    /// The maximum radius of the circle of confusion, in pixels. Larger values allow more blur
+    /// but cost more samples.
+    pub max_coc: f32,
+
+    /// The distance between two rings of samples of the bokeh, in pixels. Lower values give a
+    /// smoother bokeh but cost more samples.
+    pub sample_spacing: f32,
+}
+
+impl Default for DepthOfField {
+    fn default() -> Self {
+        Self {
+            f_stop: 2.8,
+            focus_distance: 10.0,
+            sensor_height: 0.024,
+            max_coc: 16.0,
+            sample_spacing: 0.5,
+        }
+    }
+}
+
+impl DepthOfField {
+    /// Returns the focal length of the lens for the given vertical field of view, in radians.
+    #[must_use]
+    pub fn focal_length(&self, fov: f32) -> f32 {
+        self.sensor_height / (2.0 * (fov * 0.5).tan())
+    }
+
+    /// Returns the factor converting `|distance - focus| / distance` to the radius of the
+    /// circle of confusion in pixels, for an image with the given height.
+    #[must_use]
+    pub fn coc_scale(&self, fov: f32, image_height: u32) -> f32 {
+        let focal = self.focal_length(fov);
+        let aperture = focal / self.f_stop.max(f32::EPSILON);
+        let focus = self.focus_distance.max(focal + f32::EPSILON);
+        let diameter = aperture * focal / (focus - focal);
+        0.5 * diameter / self.sensor_height * image_height as f32
+    }
+
+    /// Returns the radius of the circle of confusion of a surface at the given distance, in
+    /// pixels, before it is clamped to [`DepthOfField::max_coc`].
+    #[must_use]
+    pub fn circle_of_confusion(&self, fov: f32, image_height: u32, distance: f32) -> f32 {
+        let distance = distance.max(f32::EPSILON);
+        self.coc_scale(fov, image_height) * (distance - self.focus_distance).abs() / distance
+    }
+}
+
+/// The projection of the camera whose image is blurred.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DofProjection {
+    /// The vertical field of view, in radians.
+    pub fov: f32,
+
+    /// The distance of the near clipping plane.
+    pub near: f32,
+
+    /// The distance of the far clipping plane.
+    pub far: f32,
+}
+
+/// The compute pass applying the depth of field.
+#[derive(Debug)]
+pub struct DepthOfFieldPass {
+    /// The descriptor sets used by the pass, in turn, with the color image at binding 0, the depth
+    /// buffer at binding 1 and the target image at binding 2.
+    sets: DescriptorSetRing,
+
+    /// The pipeline applying the depth of field.
+    pipeline: ComputePipeline,
+
+    /// The sampler used to read the color image and the depth buffer.
+    sampler: Sampler,
+}
+
+impl DepthOfFieldPass {
+    /// Compile the depth of field shader and create its pipeline.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[
+                DescriptorBinding {
+                    binding: 0,
+                    kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+                DescriptorBinding {
+                    binding: 1,
+                    kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+                DescriptorBinding {
+                    binding: 2,
+                    kind: vk::DescriptorType::STORAGE_IMAGE,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+            ],
+        ));
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            ComputePipelineCreateInfo {
+                shader: ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Compute,
+                    include_str!("../shaders/depth_of_field.glsl").to_string(),
+                ),
+                descriptor_set_layouts: vec![layout.clone()],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: PUSH_CONSTANTS_SIZE,
+                }],
+                name: Some("depth of field"),
+            },
+        );
+
+        Self {
+            sets: DescriptorSetRing::new(device.clone(), &layout, DESCRIPTOR_SETS),
+            sampler: Sampler::new(
+                device,
+                vk::Filter::LINEAR,
+                vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ),
+            pipeline,
+        }
+    }
+
+    /// Record the depth of field from the source image into the target image, which must have
+    /// the same size. Both must use the [`FILTER_FORMAT`] format and be in the
+    /// `vk::ImageLayout::GENERAL` layout, like the images of the
+    /// [`ImageFilters`](crate::filter::ImageFilters). The depth buffer must be in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout.
+    ///
+    /// # Panics
+    /// Panics if the target image does not use the [`FILTER_FORMAT`] format.
+    #[must_use]
+    pub fn record<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        source: &Image,
+        depth: &Image,
+        target: &Image,
+        settings: &DepthOfField,
+        projection: DofProjection,
+    ) -> CommandBuffer<'pool, Recording> {
+        assert_eq!(
+            target.format(),
+            FILTER_FORMAT,
+            "Depth of field target must use the filter format"
+        );
+
+        let set = self.sets.next();
+        set.write_sampled_image(0, source.view(), &self.sampler, vk::ImageLayout::GENERAL);
+        set.write_sampled_image(
+            1,
+            depth.view(),
+            &self.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+        set.write_storage_image(2, target.view());
+
+        let extent = target.extent();
+        let constants = [
+            projection.near,
+            projection.far,
+            settings.focus_distance,
+            settings.coc_scale(projection.fov, extent.height),
+            settings.max_coc,
+            settings.sample_spacing.max(0.1),
+        ]
+        .into_iter()
+        .flat_map(f32::to_ne_bytes)
+        .collect::<Vec<_>>();
+
+        let layout = self.pipeline.layout();
+        command
+            .bind_compute_pipeline(&self.pipeline)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, layout, 0, set)
+            .push_constant_bytes(layout, vk::ShaderStageFlags::COMPUTE, 0, &constants)
+            .dispatch(
+                extent.width.div_ceil(WORKGROUP_SIZE),
+                extent.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            )
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER,
+                images_barriers: vec![vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ)
+                    .old_layout(vk::ImageLayout::GENERAL)
+                    .new_layout(vk::ImageLayout::GENERAL)
+                    .subresource_range(target.subresource_range())
+                    .image(target.inner())
+                    .build()],
+                ..Default::default()
+            })
+    }
+}
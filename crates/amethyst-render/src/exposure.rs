@@ -0,0 +1,343 @@
+//! Automatic exposure, adapting the brightness of the image to the scene like an eye or a
+//! camera. A compute pass builds a histogram of the luminance of the HDR image, and a second
+//! pass averages it while ignoring the darkest and brightest pixels, then moves the exposure
+//! toward this average over time. The resulting exposure is stored in a GPU buffer read by the
+//! tonemapper, so it never leaves the GPU.
+//!
+//! The renderer draws straight to the swapchain and has no tonemapper yet, so the
+//! [`ExposurePass`] is only useful to applications rendering the scene into their own HDR
+//! target, which bind [`ExposurePass::exposure_buffer`] in their tonemapping shader.
+use amethyst_vulkan::{
+    buffer::{
+        Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo,
+    },
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    image::{Image, Sampler},
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The number of bins of the luminance histogram.
+const HISTOGRAM_BINS: usize = 256;
+
+/// The size of the work groups of the histogram shader, along each axis.
+const WORKGROUP_SIZE: u32 = 16;
+
+/// The number of descriptor sets used in turn by the passes.
+const DESCRIPTOR_SETS: usize = 8;
+
+/// The size of the push constants used by the exposure shaders, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 11 * 4;
+
+/// The exposure of a camera.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Exposure {
+    /// The exposure adapts to the luminance of the scene.
+    Auto(AutoExposure),
+
+    /// A fixed exposure value at ISO 100. Bright daylight is around 15, an overcast day around
+    /// 12, and an indoor scene around 7.
+    Manual { ev100: f32 },
+}
+
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure::Auto(AutoExposure::default())
+    }
+}
+
+impl Exposure {
+    /// Returns the factor applied to the HDR colors before tonemapping for the given exposure
+    /// value, such that the brightest luminance that does not saturate maps to 1.0.
+    #[must_use]
+    pub fn factor(ev100: f32) -> f32 {
+        1.0 / (1.2 * ev100.exp2())
+    }
+}
+
+/// The settings of the automatic exposure.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AutoExposure {
+    /// The lowest exposure value, reached in the darkest scenes. It also bounds the luminance
+    /// range of the histogram.
+    pub min_ev: f32,
+
+    /// The highest exposure value, reached in the brightest scenes. It also bounds the
+    /// luminance range of the histogram.
+    pub max_ev: f32,
+
+    /// How fast the exposure adapts when the scene gets brighter, in inverse seconds.
+    pub speed_up: f32,
+
+    /// How fast the exposure adapts when the scene gets darker, in inverse seconds. It is
+    /// usually slower than brightening, like the human eye.
+    pub speed_down: f32,
+
+    /// The fraction of the darkest pixels ignored by the average.
+    pub low_percent: f32,
+
+    /// The fraction of the pixels below which the average is computed, ignoring the brightest
+    /// pixels above it.
+    pub high_percent: f32,
+
+    /// An exposure compensation, in stops. Positive values brighten the image.
+    pub compensation: f32,
+}
+
+impl Default for AutoExposure {
+    fn default() -> Self {
+        Self {
+            min_ev: -4.0,
+            max_ev: 16.0,
+            speed_up: 3.0,
+            speed_down: 1.0,
+            low_percent: 0.6,
+            high_percent: 0.95,
+            compensation: 0.0,
+        }
+    }
+}
+
+/// The compute passes and buffers of the automatic exposure.
+#[derive(Debug)]
+pub struct ExposurePass {
+    /// The descriptor sets used by the passes, in turn, with the HDR image at binding 0, the
+    /// histogram at binding 1 and the exposure at binding 2.
+    sets: DescriptorSetRing,
+
+    /// The pipeline building the luminance histogram.
+    histogram_pipeline: ComputePipeline,
+
+    /// The pipeline averaging the histogram and adapting the exposure.
+    adapt_pipeline: ComputePipeline,
+
+    /// The sampler used to read the HDR image.
+    sampler: Sampler,
+
+    /// The luminance histogram, cleared after each adaptation.
+    histogram: Buffer,
+
+    /// The current exposure value and exposure factor, as two `f32`.
+    exposure: Buffer,
+
+    /// Whether the next adaptation must jump directly to the target exposure.
+    reset: bool,
+}
+
+impl ExposurePass {
+    /// Compile the exposure shaders and create their pipelines and buffers.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, allocator: &Arc<BufferAllocator>) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[
+                DescriptorBinding {
+                    binding: 0,
+                    kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+                DescriptorBinding {
+                    binding: 1,
+                    kind: vk::DescriptorType::STORAGE_BUFFER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+                DescriptorBinding {
+                    binding: 2,
+                    kind: vk::DescriptorType::STORAGE_BUFFER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+            ],
+        ));
+
+        let pipeline = |name: &str, code: &str| {
+            ComputePipeline::new(
+                device.clone(),
+                ComputePipelineCreateInfo {
+                    shader: ShaderModule::compile_glsl(
+                        device.clone(),
+                        ShaderType::Compute,
+                        code.to_string(),
+                    ),
+                    descriptor_set_layouts: vec![layout.clone()],
+                    push_constant_ranges: vec![vk::PushConstantRange {
+                        stage_flags: vk::ShaderStageFlags::COMPUTE,
+                        offset: 0,
+                        size: PUSH_CONSTANTS_SIZE,
+                    }],
+                    name: Some(name),
+                },
+            )
+        };
+
+        let histogram_pipeline = pipeline(
+            "exposure histogram",
+            include_str!("../shaders/exposure_histogram.glsl"),
+        );
+        let adapt_pipeline = pipeline(
+            "exposure adaptation",
+            include_str!("../shaders/exposure_adapt.glsl"),
+        );
+
+        // Both buffers are small and host visible, so they can be initialized without a staging
+        // copy and the exposure can be read back for debugging.
+        let buffer = |data: &[u32]| {
+            Buffer::new(
+                allocator.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsageInfo {
+                        location: BufferMemoryLocation::PreferHostVisible,
+                        transfer: BufferTransfert::Destination,
+                        access: BufferAccess::Random,
+                        usage: BufferUsage::Storage,
+                        ..Default::default()
+                    },
+                    data: BufferDataInfo::Slice(data),
+                    ..Default::default()
+                },
+            )
+        };
+
+        let histogram = buffer(&[0; HISTOGRAM_BINS]);
+        let exposure = buffer(&[0.0f32.to_bits(), Exposure::factor(0.0).to_bits()]);
+
+        Self {
+            sets: DescriptorSetRing::new(device.clone(), &layout, DESCRIPTOR_SETS),
+            sampler: Sampler::new(
+                device,
+                vk::Filter::NEAREST,
+                vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ),
+            histogram_pipeline,
+            adapt_pipeline,
+            histogram,
+            exposure,
+            reset: true,
+        }
+    }
+
+    /// Returns the buffer holding the current exposure value at ISO 100 followed by the
+    /// exposure factor, as two `f32`. The tonemapper multiplies the HDR colors by the factor.
+    #[must_use]
+    pub const fn exposure_buffer(&self) -> &Buffer {
+        &self.exposure
+    }
+
+    /// Returns the exposure value at ISO 100 computed by the last adaptation.
+    ///
+    /// # Safety
+    /// The command buffers in which the passes were recorded must have completed their
+    /// execution.
+    #[must_use]
+    pub unsafe fn exposure_value(&self) -> f32 {
+        self.exposure.read::<f32>()[0]
+    }
+
+    /// Make the next adaptation jump directly to the exposure of the scene, for example after
+    /// a camera cut.
+    pub fn reset(&mut self) {
+        self.reset = true;
+    }
+
+    /// Record the histogram of the HDR image and the adaptation of the exposure, `delta_time`
+    /// seconds after the previous adaptation. The HDR image must be in the
+    /// `vk::ImageLayout::GENERAL` layout, like the images of the
+    /// [`ImageFilters`](crate::filter::ImageFilters).
+    #[must_use]
+    pub fn record<'pool>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording>,
+        source: &Image,
+        settings: &AutoExposure,
+        delta_time: f32,
+    ) -> CommandBuffer<'pool, Recording> {
+        let min_log = settings.min_ev - 3.0;
+        let range = (settings.max_ev - settings.min_ev).max(1.0);
+        let constants = [
+            min_log,
+            range,
+            delta_time,
+            settings.speed_up,
+            settings.speed_down,
+            settings.low_percent.clamp(0.0, 1.0),
+            settings.high_percent.clamp(0.0, 1.0),
+            settings.compensation,
+            settings.min_ev,
+            settings.max_ev,
+        ]
+        .map(f32::to_bits)
+        .into_iter()
+        .chain([u32::from(std::mem::take(&mut self.reset))])
+        .flat_map(u32::to_ne_bytes)
+        .collect::<Vec<_>>();
+
+        // The histogram shader reads the inverse of the range instead of the range.
+        let mut histogram_constants = constants.clone();
+        histogram_constants[4..8].copy_from_slice(&range.recip().to_ne_bytes());
+
+        let set = self.sets.next();
+        set.write_sampled_image(0, source.view(), &self.sampler, vk::ImageLayout::GENERAL);
+        set.write_storage_buffer(1, &self.histogram);
+        set.write_storage_buffer(2, &self.exposure);
+
+        let barrier = |buffer: &Buffer, dst_access| {
+            vk::BufferMemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                .dst_access_mask(dst_access)
+                .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                .buffer(buffer.inner())
+                .offset(0)
+                .size(vk::WHOLE_SIZE)
+                .build()
+        };
+
+        let extent = source.extent();
+        let histogram = self.histogram_pipeline.layout();
+        let adapt = self.adapt_pipeline.layout();
+        command
+            .bind_compute_pipeline(&self.histogram_pipeline)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, histogram, 0, set)
+            .push_constant_bytes(
+                histogram,
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &histogram_constants,
+            )
+            .dispatch(
+                extent.width.div_ceil(WORKGROUP_SIZE),
+                extent.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            )
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                buffers_barriers: vec![barrier(
+                    &self.histogram,
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE,
+                )],
+                ..Default::default()
+            })
+            .bind_compute_pipeline(&self.adapt_pipeline)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, adapt, 0, set)
+            .push_constant_bytes(adapt, vk::ShaderStageFlags::COMPUTE, 0, &constants)
+            .dispatch(1, 1, 1)
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::HOST,
+                buffers_barriers: vec![
+                    barrier(&self.exposure, vk::AccessFlags::SHADER_READ),
+                    barrier(&self.histogram, vk::AccessFlags::SHADER_WRITE),
+                ],
+                ..Default::default()
+            })
+    }
+}
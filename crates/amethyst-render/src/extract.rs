@@ -0,0 +1,282 @@
+//! The extraction of the entities of the world into the [`DrawList`] of a frame. Extraction
+//! only reads the ECS and produces plain data, so it is tested with the [`NullBackend`] instead
+//! of a GPU.
+//!
+//! [`DrawList`]: crate::backend::DrawList
+//! [`NullBackend`]: crate::backend::NullBackend
+use crate::{
+    backend::MeshDraw, bounds::Frustum, bvh::Bvh, interpolation::InterpolatedTransform, mesh::Mesh,
+    view::RenderView,
+};
+use bevy::{prelude::*, utils::HashSet};
+
+/// The query of the entities extracted by [`extract_meshes`].
+pub type MeshQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Entity,
+        &'static GlobalTransform,
+        Option<(&'static InterpolatedTransform, Option<&'static Parent>)>,
+    ),
+    With<Mesh>,
+>;
+
+/// Returns the draws of the entities with a [`Mesh`] visible from the view, in the order of the
+/// query. The entities of the [`Bvh`] are culled against the frustum of the view, and the
+/// entities that are not in the hierarchy yet are always drawn. Entities with an
+/// [`InterpolatedTransform`] are drawn with it instead of their `Transform`, relative to the
+/// global transform of their parent found in `transforms`.
+#[must_use]
+pub fn extract_meshes(
+    view: &RenderView,
+    bvh: &Bvh,
+    meshes: &MeshQuery,
+    transforms: &Query<&GlobalTransform>,
+) -> Vec<MeshDraw> {
+    let frustum = Frustum::from_view_projection(view.view_projection());
+    let visible = bvh
+        .query_frustum(&frustum)
+        .into_iter()
+        .collect::<HashSet<_>>();
+
+    meshes
+        .iter()
+        .filter(|(entity, ..)| !bvh.contains(*entity) || visible.contains(entity))
+        .map(|(mesh, global, interpolated)| {
+            let interpolated = interpolated.map(|(interpolated, parent)| {
+                let parent = parent.and_then(|parent| transforms.get(parent.get()).ok());
+                (interpolated, parent)
+            });
+            MeshDraw {
+                mesh,
+                transform: mesh_matrix(global, interpolated),
+            }
+        })
+        .collect()
+}
+
+/// Returns the world space matrix of a mesh. An interpolated entity is drawn with its
+/// interpolated transform, relative to the global transform of its parent if it has one.
+fn mesh_matrix(
+    global: &GlobalTransform,
+    interpolated: Option<(&InterpolatedTransform, Option<&GlobalTransform>)>,
+) -> Mat4 {
+    match interpolated {
+        Some((interpolated, Some(parent))) => parent.mul_transform(interpolated.0).compute_matrix(),
+        Some((interpolated, None)) => interpolated.0.compute_matrix(),
+        None => global.compute_matrix(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::{render_frame, DrawList, NullBackend},
+        bvh::update_bvh,
+        mesh::update_bounds,
+    };
+
+    /// The backend the test frames are rendered with.
+    #[derive(Resource)]
+    struct TestBackend(NullBackend);
+
+    /// Render a frame with the meshes of the world visible from the view.
+    fn render_meshes(
+        mut backend: ResMut<TestBackend>,
+        view: Res<RenderView>,
+        bvh: Res<Bvh>,
+        meshes: MeshQuery,
+        transforms: Query<&GlobalTransform>,
+    ) {
+        render_frame(&mut backend.0, |_| DrawList {
+            view: *view,
+            meshes: extract_meshes(&view, &bvh, &meshes, &transforms),
+            ..Default::default()
+        });
+    }
+
+    fn app(backend: NullBackend) -> App {
+        let mut app = App::new();
+        app.insert_resource(TestBackend(backend))
+            .init_resource::<Bvh>()
+            .insert_resource(camera_view())
+            .add_systems(Update, (update_bounds, update_bvh, render_meshes).chain());
+        app
+    }
+
+    /// A camera at `(0, 0, 10)` looking toward the origin.
+    fn camera_view() -> RenderView {
+        let mut projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 0.1, 100.0);
+        projection.y_axis.y = -projection.y_axis.y;
+        RenderView {
+            view: Mat4::look_at_rh(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, Vec3::Y),
+            projection,
+        }
+    }
+
+    /// Spawn a cube at the given position.
+    fn spawn_cube(app: &mut App, position: Vec3) -> Entity {
+        app.world_mut()
+            .spawn((Mesh::cube(0.5), GlobalTransform::from_translation(position)))
+            .id()
+    }
+
+    /// Returns the meshes drawn during the last frame.
+    fn drawn(app: &App) -> Vec<Entity> {
+        let backend = &app.world().resource::<TestBackend>().0;
+        let frame = backend.last_frame().expect("No frame rendered");
+        frame.meshes.iter().map(|draw| draw.mesh).collect()
+    }
+
+    #[test]
+    fn extract_the_meshes() {
+        let mut app = app(NullBackend::new(800, 600));
+        let transform = Transform::from_xyz(1.0, 2.0, 3.0);
+        let mesh = app
+            .world_mut()
+            .spawn((Mesh::cube(0.5), GlobalTransform::from(transform)))
+            .id();
+        app.world_mut().spawn(GlobalTransform::default());
+        app.update();
+
+        let backend = &app.world().resource::<TestBackend>().0;
+        assert_eq!(backend.frames.len(), 1);
+        assert_eq!(
+            backend.last_frame().unwrap().meshes,
+            [MeshDraw {
+                mesh,
+                transform: transform.compute_matrix(),
+            }]
+        );
+    }
+
+    #[test]
+    fn cull_the_meshes_outside_of_the_view() {
+        let mut app = app(NullBackend::new(800, 600));
+        let visible = spawn_cube(&mut app, Vec3::ZERO);
+        let behind = spawn_cube(&mut app, Vec3::new(0.0, 0.0, 20.0));
+        let aside = spawn_cube(&mut app, Vec3::new(50.0, 0.0, 0.0));
+        let far = spawn_cube(&mut app, Vec3::new(0.0, 0.0, -200.0));
+        app.update();
+
+        assert_eq!(drawn(&app), [visible]);
+        assert!([behind, aside, far]
+            .iter()
+            .all(|&entity| app.world().resource::<Bvh>().contains(entity)));
+    }
+
+    #[test]
+    fn draw_the_meshes_entering_the_view() {
+        let mut app = app(NullBackend::new(800, 600));
+        let cube = spawn_cube(&mut app, Vec3::new(50.0, 0.0, 0.0));
+        app.update();
+        assert!(drawn(&app).is_empty());
+
+        *app.world_mut().get_mut::<GlobalTransform>(cube).unwrap() =
+            GlobalTransform::from_translation(Vec3::new(1.0, 0.0, 0.0));
+        app.update();
+        assert_eq!(drawn(&app), [cube]);
+    }
+
+    #[test]
+    fn draw_the_meshes_without_bounds() {
+        let mut app = app(NullBackend::new(800, 600));
+        let empty = app
+            .world_mut()
+            .spawn((Mesh::default(), GlobalTransform::default()))
+            .id();
+        app.update();
+
+        assert_eq!(drawn(&app), [empty]);
+    }
+
+    #[test]
+    fn draw_the_interpolated_transform() {
+        let mut app = app(NullBackend::new(800, 600));
+        let root = app
+            .world_mut()
+            .spawn((
+                Mesh::default(),
+                Transform::from_xyz(1.0, 0.0, 0.0),
+                GlobalTransform::from_xyz(1.0, 0.0, 0.0),
+                InterpolatedTransform(Transform::from_xyz(0.5, 0.0, 0.0)),
+            ))
+            .id();
+        // A child of an entity at `(10, 0, 0)` rotated by 90° around Z.
+        let parent = Transform::from_xyz(10.0, 0.0, 0.0)
+            .with_rotation(Quat::from_rotation_z(std::f32::consts::FRAC_PI_2));
+        let parent_entity = app.world_mut().spawn(GlobalTransform::from(parent)).id();
+        let child = app
+            .world_mut()
+            .spawn((
+                Mesh::default(),
+                Transform::from_xyz(2.0, 0.0, 0.0),
+                GlobalTransform::from(parent * Transform::from_xyz(2.0, 0.0, 0.0)),
+                InterpolatedTransform(Transform::from_xyz(1.0, 0.0, 0.0)),
+            ))
+            .set_parent(parent_entity)
+            .id();
+        app.update();
+
+        let backend = &app.world().resource::<TestBackend>().0;
+        let draws = &backend.last_frame().unwrap().meshes;
+        assert_eq!(draws[0].mesh, root);
+        assert!(draws[0]
+            .transform
+            .abs_diff_eq(Mat4::from_translation(Vec3::new(0.5, 0.0, 0.0)), 1e-5));
+        assert_eq!(draws[1].mesh, child);
+        assert!(draws[1].transform.abs_diff_eq(
+            (parent * Transform::from_xyz(1.0, 0.0, 0.0)).compute_matrix(),
+            1e-5
+        ));
+    }
+
+    #[test]
+    fn draw_the_interpolated_transform_of_a_null_scale() {
+        let mut app = app(NullBackend::new(800, 600));
+        let parent = app
+            .world_mut()
+            .spawn(GlobalTransform::from_xyz(1.0, 0.0, 0.0))
+            .id();
+        let child = Transform::from_xyz(2.0, 0.0, 0.0).with_scale(Vec3::ZERO);
+        app.world_mut()
+            .spawn((
+                Mesh::default(),
+                child,
+                GlobalTransform::from_xyz(3.0, 0.0, 0.0).mul_transform(child),
+                InterpolatedTransform(child),
+            ))
+            .set_parent(parent);
+        app.update();
+
+        let backend = &app.world().resource::<TestBackend>().0;
+        let draws = &backend.last_frame().unwrap().meshes;
+        assert!(!draws[0].transform.is_nan());
+        assert_eq!(
+            draws[0].transform,
+            Transform::from_xyz(3.0, 0.0, 0.0)
+                .with_scale(Vec3::ZERO)
+                .compute_matrix()
+        );
+    }
+
+    #[test]
+    fn skip_the_frame_without_surface() {
+        let mut app = app(NullBackend::default());
+        app.world_mut()
+            .spawn((Mesh::cube(0.5), GlobalTransform::default()));
+        app.update();
+
+        assert!(app.world().resource::<TestBackend>().0.frames.is_empty());
+    }
+
+    #[test]
+    fn skip_the_frame_with_an_empty_surface() {
+        let mut app = app(NullBackend::new(0, 600));
+        app.update();
+
+        assert!(app.world().resource::<TestBackend>().0.frames.is_empty());
+    }
+}
@@ -0,0 +1,237 @@
+//! Reusable compute passes processing images: separable gaussian blur, Kawase blur and sharpen.
+//! They are the building blocks of effects like bloom or depth of field, and can be used by
+//! user effects too.
+//!
+//! Every pass reads a source image with a bilinear sampler and writes a target image of the
+//! same size. All the images must use the [`FILTER_FORMAT`] format, have both the `SAMPLED` and
+//! `STORAGE` usages, and be in the `vk::ImageLayout::GENERAL` layout when the passes execute.
+use amethyst_vulkan::{
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    image::{Image, Sampler},
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The format of the images processed by the filters.
+pub const FILTER_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// The number of descriptor sets owned by the filters. Each pass uses one of them in turn, so
+/// at most this number of passes can be recorded in command buffers pending execution at once.
+pub const FILTER_DESCRIPTOR_SETS: usize = 32;
+
+/// The size of the work groups of the filter shaders, along each axis.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The size of the largest push constants used by the filter shaders, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 16;
+
+/// The compute pipelines and resources used by the image filters.
+#[derive(Debug)]
+pub struct ImageFilters {
+    /// The descriptor sets used by the passes, in turn, with the source image at binding 0 and the
+    /// target image at binding 1.
+    sets: DescriptorSetRing,
+
+    /// The pipeline blurring an image along a single direction.
+    gaussian: ComputePipeline,
+
+    /// The pipeline running a single Kawase blur iteration.
+    kawase: ComputePipeline,
+
+    /// The pipeline sharpening an image.
+    sharpen: ComputePipeline,
+
+    /// The sampler used to read the source images.
+    sampler: Sampler,
+}
+
+impl ImageFilters {
+    /// Compile the filter shaders and create their pipelines.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[
+                DescriptorBinding {
+                    binding: 0,
+                    kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+                DescriptorBinding {
+                    binding: 1,
+                    kind: vk::DescriptorType::STORAGE_IMAGE,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+            ],
+        ));
+
+        let pipeline = |name: &str, code: &str| {
+            ComputePipeline::new(
+                device.clone(),
+                ComputePipelineCreateInfo {
+                    shader: ShaderModule::compile_glsl(
+                        device.clone(),
+                        ShaderType::Compute,
+                        code.to_string(),
+                    ),
+                    descriptor_set_layouts: vec![layout.clone()],
+                    push_constant_ranges: vec![vk::PushConstantRange {
+                        stage_flags: vk::ShaderStageFlags::COMPUTE,
+                        offset: 0,
+                        size: PUSH_CONSTANTS_SIZE,
+                    }],
+                    name: Some(name),
+                },
+            )
+        };
+
+        let gaussian = pipeline(
+            "gaussian blur",
+            include_str!("../shaders/gaussian_blur.glsl"),
+        );
+        let kawase = pipeline("kawase blur", include_str!("../shaders/kawase_blur.glsl"));
+        let sharpen = pipeline("sharpen", include_str!("../shaders/sharpen.glsl"));
+
+        let sets = DescriptorSetRing::new(device.clone(), &layout, FILTER_DESCRIPTOR_SETS);
+
+        Self {
+            sampler: Sampler::new(
+                device,
+                vk::Filter::LINEAR,
+                vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ),
+            gaussian,
+            kawase,
+            sharpen,
+            sets,
+        }
+    }
+
+    /// Blur the source image into the target image with a separable gaussian blur of the
+    /// given standard deviation, in pixels. The image is first blurred horizontally into the
+    /// temporary image, then vertically into the target image.
+    #[must_use]
+    pub fn gaussian_blur<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        source: &Image,
+        temporary: &Image,
+        target: &Image,
+        sigma: f32,
+    ) -> CommandBuffer<'pool, Recording> {
+        let sigma = sigma.max(f32::EPSILON);
+        let radius = (sigma * 3.0).ceil() as i32;
+        let constants = |direction: [f32; 2]| {
+            [direction[0], direction[1], sigma]
+                .map(f32::to_bits)
+                .into_iter()
+                .chain([radius as u32])
+                .flat_map(u32::to_ne_bytes)
+                .collect::<Vec<_>>()
+        };
+
+        let command = self.dispatch(
+            command,
+            &self.gaussian,
+            source,
+            temporary,
+            &constants([1.0, 0.0]),
+        );
+        let command = self.dispatch(
+            barrier(command, temporary),
+            &self.gaussian,
+            temporary,
+            target,
+            &constants([0.0, 1.0]),
+        );
+        barrier(command, target)
+    }
+
+    /// Run a single Kawase blur iteration from the source image into the target image. A
+    /// Kawase blur usually runs several iterations with an increasing distance (for example
+    /// 0, 1, 2, 2, 3), alternating between two images.
+    #[must_use]
+    pub fn kawase_blur<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        source: &Image,
+        target: &Image,
+        distance: f32,
+    ) -> CommandBuffer<'pool, Recording> {
+        let constants = distance.to_ne_bytes();
+        let command = self.dispatch(command, &self.kawase, source, target, &constants);
+        barrier(command, target)
+    }
+
+    /// Sharpen the source image into the target image. A strength of 0.0 copies the image,
+    /// and values between 0.1 and 0.5 give good results in most cases.
+    #[must_use]
+    pub fn sharpen<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        source: &Image,
+        target: &Image,
+        strength: f32,
+    ) -> CommandBuffer<'pool, Recording> {
+        let constants = strength.to_ne_bytes();
+        let command = self.dispatch(command, &self.sharpen, source, target, &constants);
+        barrier(command, target)
+    }
+
+    /// Bind the source and target images to the next descriptor set, and dispatch the given
+    /// pipeline over the whole target image.
+    fn dispatch<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        pipeline: &ComputePipeline,
+        source: &Image,
+        target: &Image,
+        constants: &[u8],
+    ) -> CommandBuffer<'pool, Recording> {
+        let set = self.sets.next();
+        set.write_sampled_image(0, source.view(), &self.sampler, vk::ImageLayout::GENERAL);
+        set.write_storage_image(1, target.view());
+
+        let extent = target.extent();
+        command
+            .bind_compute_pipeline(pipeline)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, pipeline.layout(), 0, set)
+            .push_constant_bytes(
+                pipeline.layout(),
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                constants,
+            )
+            .dispatch(
+                extent.width.div_ceil(WORKGROUP_SIZE),
+                extent.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            )
+    }
+}
+
+/// Insert a barrier making the writes of a compute pass to the image visible to the following
+/// compute and fragment shaders.
+fn barrier<'pool>(
+    command: CommandBuffer<'pool, Recording>,
+    image: &Image,
+) -> CommandBuffer<'pool, Recording> {
+    command.pipeline_barrier(PipelineBarrierInfo {
+        src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+        dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER
+            | vk::PipelineStageFlags::FRAGMENT_SHADER,
+        images_barriers: vec![vk::ImageMemoryBarrier::builder()
+            .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+            .dst_access_mask(vk::AccessFlags::SHADER_READ)
+            .old_layout(vk::ImageLayout::GENERAL)
+            .new_layout(vk::ImageLayout::GENERAL)
+            .subresource_range(image.subresource_range())
+            .image(image.inner())
+            .build()],
+        ..Default::default()
+    })
+}
@@ -0,0 +1,115 @@
+//! A full-screen pass, the standard building block for post-processing effects. The pass draws
+//! a single triangle covering the whole render area, generated by a built-in vertex shader, and
+//! runs a user-provided fragment shader on every pixel. The fragment shader receives the texture
+//! coordinates of the pixel at location 0:
+//!
+//! ```glsl
+//! layout(location = 0) in vec2 uv;
+//! ```
+use amethyst_vulkan::{
+    command::{CommandBuffer, DrawInfo, Recording},
+    descriptor::DescriptorSetLayout,
+    device::VulkanDevice,
+    pipeline::{NoVertex, Pipeline, PipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+    swapchain::VulkanSwapchain,
+};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The information needed to create a full-screen pass.
+#[derive(Debug)]
+pub struct FullscreenPassCreateInfo {
+    /// The fragment shader run on every pixel of the render area.
+    pub fragment: ShaderModule,
+
+    /// The formats of the color attachments written by the fragment shader. If empty, the
+    /// pass renders to a single color attachment with the format of the swapchain.
+    pub color_formats: Vec<vk::Format>,
+
+    /// The layouts of the descriptor sets used by the fragment shader, kept alive by the pass.
+    pub descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
+
+    /// The push constant ranges used by the fragment shader.
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+}
+
+/// A pass that runs a fragment shader on every pixel of the render area.
+#[derive(Debug)]
+pub struct FullscreenPass {
+    pipeline: Pipeline,
+}
+
+impl FullscreenPass {
+    /// The number of vertices of the full-screen triangle.
+    pub const VERTEX_COUNT: u32 = 3;
+
+    /// Create a new full-screen pass with the given fragment shader.
+    ///
+    /// # Panics
+    /// Panics if the given shader is not a fragment shader.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        swapchain: &VulkanSwapchain,
+        info: FullscreenPassCreateInfo,
+    ) -> Self {
+        assert_eq!(
+            info.fragment.kind(),
+            ShaderType::Fragment,
+            "A full-screen pass requires a fragment shader"
+        );
+
+        let vertex = ShaderModule::compile_glsl(
+            device.clone(),
+            ShaderType::Vertex,
+            include_str!("../shaders/fullscreen.glsl").to_string(),
+        );
+
+        let pipeline = Pipeline::new::<NoVertex>(
+            device,
+            swapchain,
+            PipelineCreateInfo {
+                shaders: vec![vertex, info.fragment],
+                color_formats: info.color_formats,
+                descriptor_set_layouts: info.descriptor_set_layouts,
+                push_constant_ranges: info.push_constant_ranges,
+                cull_mode: vk::CullModeFlags::NONE,
+                depth_write: false,
+                depth_test: false,
+                ..Default::default()
+            },
+        );
+
+        Self { pipeline }
+    }
+
+    /// Returns the pipeline used by the pass, for example to bind resources used by the
+    /// fragment shader with its layout.
+    #[must_use]
+    pub const fn pipeline(&self) -> &Pipeline {
+        &self.pipeline
+    }
+
+    /// Record the pass into the command buffer. This must be called between
+    /// [`CommandBuffer::start_rendering`] and [`CommandBuffer::stop_rendering`], and the
+    /// resources used by the fragment shader must be bound before.
+    #[must_use]
+    pub fn record<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+    ) -> CommandBuffer<'pool, Recording> {
+        let command = command.bind_graphic_pipeline(&self.pipeline);
+
+        // SAFETY: The vertices are generated by the vertex shader from their index, so no
+        // vertex buffer is read.
+        unsafe {
+            command.draw(DrawInfo {
+                vertex_count: Self::VERTEX_COUNT,
+                instance_count: 1,
+                first_vertex: 0,
+                first_instance: 0,
+            })
+        }
+    }
+}
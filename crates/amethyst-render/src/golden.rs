@@ -0,0 +1,372 @@
+//! Golden-image testing utilities. A golden-image test renders a scene offscreen (usually with a
+//! software implementation of Vulkan like lavapipe or SwiftShader in CI), reads the image back and
+//! compares it against a reference PNG committed in the repository. Small differences are allowed
+//! with a tolerance, since different drivers may not rasterize exactly the same way.
+//!
+//! The scenes are rendered with a [`HeadlessRenderer`], which does not need a window nor a
+//! display server. When the `AMETHYST_BLESS` environment variable is set, the rendered image is
+//! written as the new reference instead of being compared, which is how references are created
+//! and updated.
+use amethyst_vulkan::{
+    buffer::BufferAllocator,
+    command::{CommandBuffer, CommandPool, Recording, SubmitInfo},
+    context::{ApplicationInfo, VulkanContext, VulkanContextCreateInfo},
+    device::{DeviceFeatureRequest, VulkanDevice, VulkanQueues},
+    image::{Image, ImageCreateInfo},
+};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// The format of the images rendered by a [`HeadlessRenderer`].
+pub const GOLDEN_FORMAT: vk::Format = vk::Format::R8G8B8A8_UNORM;
+
+/// The environment variable that, when set, makes the comparison overwrite the reference images
+/// with the rendered ones instead of comparing them.
+pub const BLESS_ENV: &str = "AMETHYST_BLESS";
+
+/// The tolerance used when comparing a rendered image against its reference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// The maximum absolute difference allowed on each channel of a pixel for the pixel to
+    /// still be considered identical to the reference one.
+    pub channel: u8,
+
+    /// The maximum ratio of pixels (between 0.0 and 1.0) allowed to differ from the reference.
+    pub pixels: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            channel: 2,
+            pixels: 0.001,
+        }
+    }
+}
+
+/// An image rendered by a test, in the RGBA8 format with tightly packed rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedImage {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// The result of a failed golden-image comparison.
+#[derive(Debug, thiserror::Error)]
+pub enum GoldenError {
+    /// The reference image could not be read or the rendered image could not be written.
+    #[error("Image I/O error: {0}")]
+    Image(#[from] image::ImageError),
+
+    /// The reference image does not exist. It can be created by running the test again with the
+    /// [`BLESS_ENV`] environment variable set.
+    #[error("Missing reference image {0:?} (set {BLESS_ENV} to create it)")]
+    MissingReference(PathBuf),
+
+    /// The rendered image does not have the same size as the reference image.
+    #[error("Size mismatch: rendered {rendered:?}, reference {reference:?}")]
+    SizeMismatch {
+        rendered: (u32, u32),
+        reference: (u32, u32),
+    },
+
+    /// Too many pixels differ from the reference image. The rendered image and a difference
+    /// image are written next to the reference to ease debugging.
+    #[error("{different} pixels out of {total} differ from the reference (see {diff:?})")]
+    Mismatch {
+        different: usize,
+        total: usize,
+        diff: PathBuf,
+    },
+}
+
+/// Compare the rendered image against the reference PNG at the given path. If the [`BLESS_ENV`]
+/// environment variable is set, the rendered image is saved as the new reference and the
+/// comparison succeeds.
+///
+/// # Errors
+/// Returns an error if the reference does not exist, if the images differ by more than the given
+/// tolerance, or if the images cannot be read or written.
+pub fn compare(
+    reference: impl AsRef<Path>,
+    rendered: &RenderedImage,
+    tolerance: Tolerance,
+) -> Result<(), GoldenError> {
+    let reference = reference.as_ref();
+    if std::env::var_os(BLESS_ENV).is_some() {
+        save(reference, rendered)?;
+        bevy::log::warn!("Golden image {reference:?} has been (re)generated");
+        return Ok(());
+    }
+    if !reference.exists() {
+        return Err(GoldenError::MissingReference(reference.to_path_buf()));
+    }
+
+    let expected = image::open(reference)?.to_rgba8();
+    if expected.dimensions() != (rendered.width, rendered.height) {
+        return Err(GoldenError::SizeMismatch {
+            rendered: (rendered.width, rendered.height),
+            reference: expected.dimensions(),
+        });
+    }
+
+    // Compare each pixel and build a difference image at the same time, where identical
+    // pixels are black and different pixels are red.
+    let mut different = 0;
+    let mut diff = Vec::with_capacity(rendered.pixels.len());
+    for (a, b) in rendered
+        .pixels
+        .chunks_exact(4)
+        .zip(expected.as_raw().chunks_exact(4))
+    {
+        let max = a
+            .iter()
+            .zip(b)
+            .map(|(&x, &y)| x.abs_diff(y))
+            .max()
+            .unwrap_or(0);
+
+        if max > tolerance.channel {
+            different += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff.extend_from_slice(&[0, 0, 0, 255]);
+        }
+    }
+
+    let total = (rendered.width * rendered.height) as usize;
+    if different as f32 > total as f32 * tolerance.pixels {
+        let actual = sibling(reference, "actual");
+        let diff_path = sibling(reference, "diff");
+        save(&actual, rendered)?;
+        save(
+            &diff_path,
+            &RenderedImage {
+                width: rendered.width,
+                height: rendered.height,
+                pixels: diff,
+            },
+        )?;
+
+        return Err(GoldenError::Mismatch {
+            different,
+            total,
+            diff: diff_path,
+        });
+    }
+
+    Ok(())
+}
+
+/// Renders golden-image scenes offscreen on a device picked without any surface, such as
+/// lavapipe or SwiftShader on machines without a GPU.
+#[derive(Debug)]
+pub struct HeadlessRenderer {
+    /// The allocator of the rendered images.
+    allocator: Arc<BufferAllocator>,
+
+    /// The device rendering the scenes.
+    device: Arc<VulkanDevice>,
+
+    /// The context of the device, which must outlive it.
+    _context: VulkanContext,
+}
+
+impl HeadlessRenderer {
+    /// Create a headless context and pick the best device available.
+    ///
+    /// # Panics
+    /// Panics if the Vulkan loader cannot be found, or if no device is available.
+    #[must_use]
+    pub fn new() -> Self {
+        let context = VulkanContext::headless(
+            &ApplicationInfo::default(),
+            &VulkanContextCreateInfo::default(),
+        );
+        let device = Arc::new(VulkanDevice::pick_best_headless(
+            &context,
+            &DeviceFeatureRequest::default(),
+        ));
+        let allocator = Arc::new(BufferAllocator::new(&context, &device));
+        Self {
+            allocator,
+            device,
+            _context: context,
+        }
+    }
+
+    /// Returns the device rendering the scenes, to create the resources they use.
+    #[must_use]
+    pub const fn device(&self) -> &Arc<VulkanDevice> {
+        &self.device
+    }
+
+    /// Returns the allocator of the device.
+    #[must_use]
+    pub const fn allocator(&self) -> &Arc<BufferAllocator> {
+        &self.allocator
+    }
+
+    /// Render a scene into a new image of the given size in the [`GOLDEN_FORMAT`] format, and
+    /// read it back once the rendering has completed. The scene is recorded by the given closure
+    /// into the image, which is in the `vk::ImageLayout::UNDEFINED` layout and can be used as a
+    /// color attachment or as a transfer destination.
+    #[must_use]
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        record: impl for<'pool> FnOnce(
+            CommandBuffer<'pool, Recording>,
+            &Image,
+        ) -> CommandBuffer<'pool, Recording>,
+    ) -> RenderedImage {
+        let image = Image::new(
+            self.device.clone(),
+            self.allocator.clone(),
+            ImageCreateInfo {
+                extent: vk::Extent2D { width, height },
+                format: GOLDEN_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT
+                    | vk::ImageUsageFlags::TRANSFER_SRC
+                    | vk::ImageUsageFlags::TRANSFER_DST,
+                name: Some("Golden image"),
+                ..Default::default()
+            },
+        );
+
+        self.submit(|command| record(command, &image));
+        RenderedImage {
+            width,
+            height,
+            pixels: image.read_to_vec(),
+        }
+    }
+
+    /// Record commands with the given closure, submit them to the main queue and wait for them
+    /// to complete, for example to run compute kernels whose results are then read back.
+    pub fn submit(
+        &self,
+        record: impl for<'pool> FnOnce(
+            CommandBuffer<'pool, Recording>,
+        ) -> CommandBuffer<'pool, Recording>,
+    ) {
+        let pool = CommandPool::new(
+            self.device.clone(),
+            self.device.queues_info().main_family(),
+            vk::CommandPoolCreateFlags::TRANSIENT,
+        );
+        record(CommandBuffer::new(&pool).start_recording())
+            .stop_recording()
+            .submit_and_wait(SubmitInfo {
+                queue: VulkanQueues::fetch(&self.device).main(),
+                ..Default::default()
+            });
+    }
+}
+
+impl Default for HeadlessRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Save an image to a PNG file, creating the parent directories if needed.
+fn save(path: &Path, rendered: &RenderedImage) -> Result<(), image::ImageError> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(image::ImageError::IoError)?;
+    }
+
+    image::save_buffer(
+        path,
+        &rendered.pixels,
+        rendered.width,
+        rendered.height,
+        image::ExtendedColorType::Rgba8,
+    )
+}
+
+/// Returns a path next to the reference image with a suffix added to its name, for example
+/// `triangle.actual.png` for the `triangle.png` reference.
+fn sibling(reference: &Path, suffix: &str) -> PathBuf {
+    let stem = reference
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    reference.with_file_name(format!("{stem}.{suffix}.png"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the path of a reference image committed in the repository.
+    fn reference(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(name)
+    }
+
+    /// Returns an image of the given size filled with a single color.
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RenderedImage {
+        RenderedImage {
+            width,
+            height,
+            pixels: color.repeat((width * height) as usize),
+        }
+    }
+
+    #[test]
+    fn missing_reference_fails() {
+        if std::env::var_os(BLESS_ENV).is_some() {
+            return;
+        }
+
+        let missing = std::env::temp_dir().join("amethyst-golden/missing.png");
+        let result = compare(&missing, &solid(4, 4, [0; 4]), Tolerance::default());
+        assert!(matches!(result, Err(GoldenError::MissingReference(path)) if path == missing));
+        assert!(!missing.exists());
+    }
+
+    #[test]
+    fn compare_with_tolerance() {
+        let path = std::env::temp_dir().join("amethyst-golden/tolerance.png");
+        save(&path, &solid(4, 4, [100, 100, 100, 255])).unwrap();
+
+        let close = solid(4, 4, [102, 99, 100, 255]);
+        assert!(compare(&path, &close, Tolerance::default()).is_ok());
+
+        let far = solid(4, 4, [110, 100, 100, 255]);
+        let result = compare(&path, &far, Tolerance::default());
+        assert!(matches!(
+            result,
+            Err(GoldenError::Mismatch {
+                different: 16,
+                total: 16,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn render_clear_color() {
+        let renderer = HeadlessRenderer::new();
+        let rendered = renderer.render(64, 64, |recording, image| {
+            recording
+                .transition_image(image, vk::ImageLayout::TRANSFER_DST_OPTIMAL)
+                .clear_color_image(
+                    image,
+                    vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                    vk::ClearColorValue {
+                        float32: [1.0, 0.5, 0.0, 1.0],
+                    },
+                )
+        });
+
+        compare(reference("clear.png"), &rendered, Tolerance::default()).unwrap();
+    }
+}
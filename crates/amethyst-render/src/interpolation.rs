@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+
+/// Runs the gameplay systems registered in the [`FixedUpdate`] schedule at a fixed rate, while
+/// the rendering still happens once per frame. Entities with the [`Interpolate`] component get
+/// an [`InterpolatedTransform`] that is smoothly interpolated between the last two simulation
+/// steps, so their motion stays smooth even if the simulation rate is lower than the frame rate.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedTimestepPlugin {
+    /// The number of simulation steps per second.
+    pub hz: f64,
+}
+
+impl Default for FixedTimestepPlugin {
+    fn default() -> Self {
+        Self { hz: 60.0 }
+    }
+}
+
+impl Plugin for FixedTimestepPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Time::<Fixed>::from_hz(self.hz));
+        app.add_systems(PreUpdate, init_previous_transform);
+        app.add_systems(FixedFirst, store_previous_transform);
+        app.add_systems(PostUpdate, interpolate_transform);
+    }
+}
+
+/// A marker component that enables transform interpolation on an entity. The entity `Transform`
+/// must only be modified by systems running in the [`FixedUpdate`] schedule. The renderer draws
+/// the entity with its [`InterpolatedTransform`] in place of its `Transform`, see
+/// [`extract_meshes`](crate::extract::extract_meshes).
+#[derive(Debug, Default, Clone, Copy, Component)]
+#[require(PreviousTransform, InterpolatedTransform)]
+pub struct Interpolate;
+
+/// The transform of the entity at the beginning of the current simulation step.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct PreviousTransform(pub Transform);
+
+/// The transform of the entity interpolated between the two last simulation steps, depending
+/// on how far the current frame is between them.
+#[derive(Debug, Default, Clone, Copy, Component)]
+pub struct InterpolatedTransform(pub Transform);
+
+/// Initializes the previous transform of newly interpolated entities, so they do not appear
+/// to come from the origin during their first frame
+fn init_previous_transform(
+    mut query: Query<(&Transform, &mut PreviousTransform), Added<Interpolate>>,
+) {
+    for (transform, mut previous) in query.iter_mut() {
+        previous.0 = *transform;
+    }
+}
+
+/// Stores the transform of the interpolated entities before the simulation step runs
+fn store_previous_transform(mut query: Query<(&Transform, &mut PreviousTransform)>) {
+    for (transform, mut previous) in query.iter_mut() {
+        previous.0 = *transform;
+    }
+}
+
+/// Computes the interpolated transform of the entities, based on the fraction of the fixed
+/// timestep that has elapsed since the last simulation step.
+fn interpolate_transform(
+    time: Res<Time<Fixed>>,
+    mut query: Query<(&Transform, &PreviousTransform, &mut InterpolatedTransform)>,
+) {
+    let alpha = time.overstep_fraction();
+    for (current, previous, mut interpolated) in query.iter_mut() {
+        interpolated.0 = Transform {
+            translation: previous.0.translation.lerp(current.translation, alpha),
+            rotation: previous.0.rotation.slerp(current.rotation, alpha),
+            scale: previous.0.scale.lerp(current.scale, alpha),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+    use std::time::Duration;
+
+    /// Moves the interpolated entities by one unit along X every simulation step.
+    fn step(mut query: Query<&mut Transform, With<Interpolate>>) {
+        for mut transform in query.iter_mut() {
+            transform.translation.x += 1.0;
+        }
+    }
+
+    #[test]
+    fn interpolate_between_the_fixed_steps() {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, FixedTimestepPlugin { hz: 10.0 }))
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                150,
+            )))
+            .add_systems(FixedUpdate, step);
+        let entity = app
+            .world_mut()
+            .spawn((Transform::from_scale(Vec3::ZERO), Interpolate))
+            .id();
+
+        // The first update only starts the clock, and no simulation step runs.
+        app.update();
+        let interpolated = |app: &App| app.world().get::<InterpolatedTransform>(entity).unwrap().0;
+        assert_eq!(interpolated(&app), Transform::from_scale(Vec3::ZERO));
+
+        // 150ms: one step from 0 to 1, and half of the next step elapsed.
+        app.update();
+        assert_eq!(
+            interpolated(&app),
+            Transform::from_xyz(0.5, 0.0, 0.0).with_scale(Vec3::ZERO)
+        );
+
+        // 300ms: two more steps from 1 to 3, and nothing of the next step elapsed.
+        app.update();
+        assert_eq!(
+            interpolated(&app),
+            Transform::from_xyz(2.0, 0.0, 0.0).with_scale(Vec3::ZERO)
+        );
+    }
+}
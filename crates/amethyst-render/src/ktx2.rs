@@ -0,0 +1,221 @@
+//! Loading of KTX2 textures. KTX2 is the container of the Khronos Group for GPU textures: it
+//! stores the images of a texture in a Vulkan format, usually block-compressed, along with its
+//! whole mip chain baked offline. The images can therefore be copied to the GPU as they are,
+//! without decoding them on the CPU nor generating the mip levels at runtime.
+//!
+//! Only the textures without supercompression are supported, since decoding Basis Universal or
+//! Zstandard would require additional dependencies. The data format descriptor and the
+//! key/value data are ignored: the Vulkan format of the header is enough to upload the images.
+use amethyst_vulkan::{
+    buffer::BufferAllocator,
+    device::VulkanDevice,
+    image::{format_block, Image, ImageCreateInfo},
+};
+use std::{path::Path, sync::Arc};
+use vulkanalia::prelude::v1_3::*;
+
+/// The identifier at the start of every KTX2 file.
+const IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+
+/// The size of the identifier, the header and the index of a KTX2 file, after which the level
+/// index starts.
+const LEVEL_INDEX_OFFSET: usize = 80;
+
+/// The size of an entry of the level index.
+const LEVEL_INDEX_ENTRY_SIZE: usize = 24;
+
+/// An error that occurred while loading a KTX2 texture.
+#[derive(Debug, thiserror::Error)]
+pub enum Ktx2Error {
+    /// The file could not be read.
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The data does not start with the KTX2 identifier.
+    #[error("Not a KTX2 file")]
+    InvalidIdentifier,
+
+    /// The data ends before the header, the level index or a level.
+    #[error("Truncated KTX2 file")]
+    Truncated,
+
+    /// The texture uses a format that is undefined or unknown.
+    #[error("Unsupported format: {0}")]
+    UnsupportedFormat(i32),
+
+    /// The texture is supercompressed with the given scheme.
+    #[error("Unsupported supercompression scheme: {0}")]
+    Supercompressed(u32),
+
+    /// The texture is a 3D texture.
+    #[error("3D textures are not supported")]
+    Volume,
+
+    /// The size of a level does not match the format and the extent of the texture.
+    #[error("Level {level} has {size} bytes instead of {expected}")]
+    LevelSize {
+        level: usize,
+        size: usize,
+        expected: usize,
+    },
+}
+
+/// A texture loaded from a KTX2 file, whose levels are ready to be copied to an image.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ktx2Texture {
+    /// The format of the images of the texture.
+    pub format: vk::Format,
+
+    /// The extent of the first mip level of the texture.
+    pub extent: vk::Extent2D,
+
+    /// The number of array layers of the texture, at least one.
+    pub layers: u32,
+
+    /// The number of faces of the texture: 6 for a cube map, 1 otherwise.
+    pub faces: u32,
+
+    /// The data of each mip level, starting with the largest one. The data of a level holds
+    /// the images of all the layers and faces, ordered by layer and then by face, each one
+    /// tightly packed.
+    pub levels: Vec<Vec<u8>>,
+}
+
+impl Ktx2Texture {
+    /// Load a KTX2 texture from the file at the given path.
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be read, or if it is not a KTX2 texture supported
+    /// by [`Ktx2Texture::parse`].
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Ktx2Error> {
+        Self::parse(&std::fs::read(path)?)
+    }
+
+    /// Parse a KTX2 texture from the content of a file.
+    ///
+    /// # Errors
+    /// Returns an error if the data is not a valid KTX2 file, if the texture is supercompressed
+    /// or is a 3D texture, or if its format is not supported.
+    pub fn parse(data: &[u8]) -> Result<Self, Ktx2Error> {
+        if !data.starts_with(&IDENTIFIER) {
+            return Err(Ktx2Error::InvalidIdentifier);
+        }
+
+        let header = |index: usize| read_u32(data, IDENTIFIER.len() + index * 4);
+        let raw_format = header(0)? as i32;
+        let width = header(2)?;
+        let height = header(3)?.max(1);
+        let depth = header(4)?;
+        let layers = header(5)?.max(1);
+        let faces = header(6)?.max(1);
+        let level_count = header(7)?.max(1) as usize;
+        let supercompression = header(8)?;
+
+        if supercompression != 0 {
+            return Err(Ktx2Error::Supercompressed(supercompression));
+        }
+        if depth > 1 {
+            return Err(Ktx2Error::Volume);
+        }
+
+        let format = vk::Format::from_raw(raw_format);
+        let block = (format != vk::Format::UNDEFINED)
+            .then(|| format_block(format))
+            .flatten()
+            .ok_or(Ktx2Error::UnsupportedFormat(raw_format))?;
+
+        let levels = (0..level_count)
+            .map(|level| {
+                let entry = LEVEL_INDEX_OFFSET + level * LEVEL_INDEX_ENTRY_SIZE;
+                let offset = read_u64(data, entry)? as usize;
+                let size = read_u64(data, entry + 8)? as usize;
+                let bytes = offset
+                    .checked_add(size)
+                    .and_then(|end| data.get(offset..end))
+                    .ok_or(Ktx2Error::Truncated)?;
+
+                let extent = vk::Extent2D {
+                    width: (width >> level).max(1),
+                    height: (height >> level).max(1),
+                };
+                let expected = block.image_size(extent) * (layers * faces) as usize;
+                if size != expected {
+                    return Err(Ktx2Error::LevelSize {
+                        level,
+                        size,
+                        expected,
+                    });
+                }
+                Ok(bytes.to_vec())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            format,
+            extent: vk::Extent2D { width, height },
+            layers,
+            faces,
+            levels,
+        })
+    }
+
+    /// Create an image holding the whole mip chain of the texture, left in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout. This waits for the upload to
+    /// complete, see [`Image::from_levels`].
+    ///
+    /// # Panics
+    /// Panics if the device cannot sample images in the format of the texture, see
+    /// [`VulkanDevice::supports_sampled_format`].
+    #[must_use]
+    pub fn upload(&self, device: Arc<VulkanDevice>, allocator: Arc<BufferAllocator>) -> Image {
+        assert!(
+            device.supports_sampled_format(self.format),
+            "The device cannot sample images in {:?}",
+            self.format
+        );
+        let levels = self.levels.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        Image::from_levels(
+            device,
+            allocator,
+            ImageCreateInfo {
+                extent: self.extent,
+                format: self.format,
+                mip_levels: self.mip_levels(),
+                array_layers: self.layers * self.faces,
+                cube: self.is_cube(),
+                ..Default::default()
+            },
+            &levels,
+        )
+    }
+
+    /// Returns whether the texture is a cube map.
+    #[must_use]
+    pub const fn is_cube(&self) -> bool {
+        self.faces == 6
+    }
+
+    /// Returns the number of mip levels of the texture.
+    #[must_use]
+    pub fn mip_levels(&self) -> u32 {
+        self.levels.len() as u32
+    }
+}
+
+/// Read a little-endian `u32` at the given offset of the data.
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, Ktx2Error> {
+    data.get(offset..offset + 4)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or(Ktx2Error::Truncated)
+}
+
+/// Read a little-endian `u64` at the given offset of the data.
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, Ktx2Error> {
+    data.get(offset..offset + 8)
+        .and_then(|bytes| bytes.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or(Ktx2Error::Truncated)
+}
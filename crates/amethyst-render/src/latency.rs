@@ -0,0 +1,301 @@
+//! Measurement of the latency between the input of the player and the presentation of the
+//! frame showing its effect. Competitive games are very sensitive to this latency, and it
+//! depends on the whole frame pipeline: when inputs are read, how long the simulation runs,
+//! how long the GPU takes and how long the presentation blocks.
+//!
+//! Each rendered frame publishes a [`FrameLatency`] event breaking down its latency, and the
+//! [`LatencyTracker`] resource keeps the latency of the last frames. Custom markers can be
+//! added with [`LatencyTracker::mark`] to measure when a part of the frame ends.
+use bevy::{
+    input::{
+        gamepad::GamepadEvent,
+        keyboard::KeyboardInput,
+        mouse::{MouseButtonInput, MouseMotion, MouseWheel},
+        touch::TouchInput,
+    },
+    prelude::*,
+};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// The number of frames kept by the [`LatencyTracker`].
+pub const LATENCY_HISTORY: usize = 120;
+
+/// The timestamps of the rendering of a frame, recorded by the renderer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RenderTimestamps {
+    /// When the command buffers of the frame were submitted to the GPU.
+    pub submit: Instant,
+
+    /// When the renderer noticed that the GPU finished executing the command buffers of the
+    /// frame. Since several frames are in flight, the renderer only waits for a frame before
+    /// reusing its resources, so the GPU may have finished earlier.
+    pub gpu_complete: Instant,
+
+    /// When the frame was handed to the presentation engine.
+    pub present: Instant,
+}
+
+/// The latency of a rendered frame, published as an event once the frame is presented.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct FrameLatency {
+    /// The index of the frame, counting only the rendered frames.
+    pub frame: u64,
+
+    /// The time between the first input read by the frame and its presentation, or `None` if
+    /// no input was received since the previous frame. Inputs are timestamped when they reach
+    /// the ECS at the start of the frame, so the time spent in the OS and the event loop is
+    /// not included.
+    pub input_to_present: Option<Duration>,
+
+    /// The time between the start of the frame and the submission of its commands, spent in
+    /// the simulation and the recording of the commands.
+    pub simulation: Duration,
+
+    /// The time spent by the GPU executing the commands of the frame.
+    pub gpu: Duration,
+
+    /// The time spent handing the frame to the presentation engine after the GPU finished
+    /// executing it, which can block when the swapchain has no image available. It is zero
+    /// when the frame is handed to the presentation engine before its execution completes.
+    pub present: Duration,
+
+    /// The custom markers of the frame, with the time elapsed since the start of the frame.
+    pub markers: Vec<(&'static str, Duration)>,
+}
+
+impl FrameLatency {
+    /// Returns the time between the start of the frame and its presentation.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.simulation + self.gpu + self.present
+    }
+}
+
+/// Keeps the timestamps of the current frame and the latency of the last frames.
+#[derive(Debug, Default, Resource)]
+pub struct LatencyTracker {
+    /// The number of frames rendered so far.
+    frame: u64,
+
+    /// When the current frame started.
+    frame_start: Option<Instant>,
+
+    /// When the first input not yet shown by a rendered frame was received.
+    input: Option<Instant>,
+
+    /// The custom markers of the current frame.
+    markers: Vec<(&'static str, Instant)>,
+
+    /// The frames submitted to the GPU whose latency is not known yet, from the oldest to the
+    /// most recent.
+    submitted: VecDeque<SubmittedFrame>,
+
+    /// The latency of the last frames, from the oldest to the most recent.
+    history: VecDeque<FrameLatency>,
+}
+
+impl LatencyTracker {
+    /// Record a custom marker with the given name at the current time, for example at the end
+    /// of the physics step.
+    pub fn mark(&mut self, name: &'static str) {
+        self.markers.push((name, Instant::now()));
+    }
+
+    /// Returns the latency of the last rendered frame.
+    #[must_use]
+    pub fn last(&self) -> Option<&FrameLatency> {
+        self.history.back()
+    }
+
+    /// Returns the latency of the last rendered frames, from the oldest to the most recent.
+    pub fn history(&self) -> impl Iterator<Item = &FrameLatency> {
+        self.history.iter()
+    }
+
+    /// Returns the average input to present latency of the last frames that received an
+    /// input, or `None` if none of them did.
+    #[must_use]
+    pub fn average_input_to_present(&self) -> Option<Duration> {
+        let latencies = self
+            .history
+            .iter()
+            .filter_map(|latency| latency.input_to_present)
+            .collect::<Vec<_>>();
+        let count = u32::try_from(latencies.len())
+            .ok()
+            .filter(|&count| count > 0)?;
+        Some(latencies.iter().sum::<Duration>() / count)
+    }
+
+    /// Returns the highest input to present latency of the last frames.
+    #[must_use]
+    pub fn max_input_to_present(&self) -> Option<Duration> {
+        self.history
+            .iter()
+            .filter_map(|latency| latency.input_to_present)
+            .max()
+    }
+
+    /// Record that the current frame was submitted to the GPU. Its latency is computed once
+    /// the GPU has executed it by [`LatencyTracker::finish_frame`], which may be called during
+    /// a later frame.
+    pub fn submit_frame(&mut self) {
+        self.submitted.push_back(SubmittedFrame {
+            start: self.frame_start,
+            input: self.input.take(),
+            markers: std::mem::take(&mut self.markers),
+        });
+    }
+
+    /// Complete the oldest submitted frame with the timestamps recorded by the renderer, and
+    /// returns its latency. If no frame was submitted with [`LatencyTracker::submit_frame`],
+    /// the current frame is completed.
+    pub fn finish_frame(&mut self, timestamps: RenderTimestamps) -> FrameLatency {
+        if self.submitted.is_empty() {
+            self.submit_frame();
+        }
+        let frame = self.submitted.pop_front().expect("No submitted frame");
+
+        let start = frame.start.unwrap_or(timestamps.submit);
+        let latency = FrameLatency {
+            frame: self.frame,
+            input_to_present: frame
+                .input
+                .map(|input| timestamps.present.saturating_duration_since(input)),
+            simulation: timestamps.submit.saturating_duration_since(start),
+            gpu: timestamps
+                .gpu_complete
+                .saturating_duration_since(timestamps.submit),
+            present: timestamps
+                .present
+                .saturating_duration_since(timestamps.gpu_complete),
+            markers: frame
+                .markers
+                .into_iter()
+                .map(|(name, time)| (name, time.saturating_duration_since(start)))
+                .collect(),
+        };
+
+        if self.history.len() == LATENCY_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(latency.clone());
+        self.frame += 1;
+        latency
+    }
+}
+
+/// The timestamps recorded by the [`LatencyTracker`] for a frame submitted to the GPU.
+#[derive(Debug)]
+struct SubmittedFrame {
+    /// When the frame started.
+    start: Option<Instant>,
+
+    /// When the first input shown by the frame was received.
+    input: Option<Instant>,
+
+    /// The custom markers of the frame.
+    markers: Vec<(&'static str, Instant)>,
+}
+
+/// Record the start of the frame, and the time of the first input received since the last
+/// rendered frame. Inputs received by frames that were not rendered, for example while the
+/// window is minimized, are attributed to the next rendered frame.
+pub fn begin_frame(
+    mut tracker: ResMut<LatencyTracker>,
+    mut keyboard: EventReader<KeyboardInput>,
+    mut buttons: EventReader<MouseButtonInput>,
+    mut motion: EventReader<MouseMotion>,
+    mut wheel: EventReader<MouseWheel>,
+    mut touch: EventReader<TouchInput>,
+    mut gamepad: EventReader<GamepadEvent>,
+) {
+    let now = Instant::now();
+    tracker.frame_start = Some(now);
+    tracker.markers.clear();
+
+    let received = !keyboard.is_empty()
+        || !buttons.is_empty()
+        || !motion.is_empty()
+        || !wheel.is_empty()
+        || !touch.is_empty()
+        || !gamepad.is_empty();
+
+    keyboard.clear();
+    buttons.clear();
+    motion.clear();
+    wheel.clear();
+    touch.clear();
+    gamepad.clear();
+
+    if received && tracker.input.is_none() {
+        tracker.input = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns the timestamps of a frame, in milliseconds since the given instant.
+    fn timestamps(
+        origin: Instant,
+        submit: u64,
+        gpu_complete: u64,
+        present: u64,
+    ) -> RenderTimestamps {
+        let at = |millis| origin + Duration::from_millis(millis);
+        RenderTimestamps {
+            submit: at(submit),
+            gpu_complete: at(gpu_complete),
+            present: at(present),
+        }
+    }
+
+    #[test]
+    fn finish_the_submitted_frames_in_order() {
+        let origin = Instant::now();
+        let mut tracker = LatencyTracker {
+            frame_start: Some(origin),
+            input: Some(origin),
+            ..Default::default()
+        };
+        tracker.submit_frame();
+
+        tracker.frame_start = Some(origin + Duration::from_millis(10));
+        tracker.submit_frame();
+
+        // The first frame completes once the second one is submitted, after being presented.
+        let first = tracker.finish_frame(timestamps(origin, 2, 12, 3));
+        assert_eq!(first.frame, 0);
+        assert_eq!(first.input_to_present, Some(Duration::from_millis(3)));
+        assert_eq!(first.simulation, Duration::from_millis(2));
+        assert_eq!(first.gpu, Duration::from_millis(10));
+        assert_eq!(first.present, Duration::ZERO);
+
+        let second = tracker.finish_frame(timestamps(origin, 11, 22, 13));
+        assert_eq!(second.frame, 1);
+        assert_eq!(second.input_to_present, None);
+        assert_eq!(second.simulation, Duration::from_millis(1));
+        assert_eq!(tracker.history().count(), 2);
+    }
+
+    #[test]
+    fn finish_the_current_frame_without_submission() {
+        let origin = Instant::now();
+        let mut tracker = LatencyTracker {
+            frame_start: Some(origin),
+            input: Some(origin),
+            ..Default::default()
+        };
+
+        let latency = tracker.finish_frame(timestamps(origin, 2, 5, 6));
+        assert_eq!(latency.input_to_present, Some(Duration::from_millis(6)));
+        assert_eq!(latency.gpu, Duration::from_millis(3));
+        assert_eq!(latency.present, Duration::from_millis(1));
+        assert_eq!(tracker.last(), Some(&latency));
+    }
+}
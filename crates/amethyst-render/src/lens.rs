@@ -0,0 +1,187 @@
+//! The final lens effects of the post-processing stack: vignette, film grain and chromatic
+//! aberration. They are cheap and applied in a single full-screen pass, usually when writing
+//! the tonemapped image to the swapchain.
+//!
+//! The pass is not part of the frame rendered by the renderer. An application with its own
+//! post-processing chain records a [`LensEffectsPass`] as the last pass of the chain.
+use crate::fullscreen::{FullscreenPass, FullscreenPassCreateInfo};
+use amethyst_vulkan::{
+    command::{CommandBuffer, Recording},
+    descriptor::{DescriptorBinding, DescriptorPool, DescriptorSet, DescriptorSetLayout},
+    device::VulkanDevice,
+    image::Sampler,
+    shader::{ShaderModule, ShaderType},
+    swapchain::VulkanSwapchain,
+};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The size of the push constants used by the lens effects shader, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 12 * 4;
+
+/// The settings of the lens effects of a camera. Each effect is disabled when its intensity is
+/// zero, which is the default.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LensEffects {
+    /// How far the vignette reaches toward the center of the image. Values around 0.5 give a
+    /// subtle vignette, and 1.0 reaches the corners.
+    pub vignette_intensity: f32,
+
+    /// The width of the transition between the image and the vignette, between 0.0 (hard
+    /// edge) and 1.0.
+    pub vignette_smoothness: f32,
+
+    /// The shape of the vignette: 1.0 gives a circle, and 0.0 follows the aspect ratio of the
+    /// image.
+    pub vignette_roundness: f32,
+
+    /// The color of the vignette, whose alpha is the opacity of the vignette.
+    pub vignette_color: LinearRgba,
+
+    /// The intensity of the film grain. Values around 0.05 give a visible but subtle grain.
+    pub grain_intensity: f32,
+
+    /// The size of a grain, in pixels.
+    pub grain_size: f32,
+
+    /// The strength of the chromatic aberration, as the shift of the red and blue channels at
+    /// the corners of the image, in texture coordinates.
+    pub chromatic_aberration: f32,
+}
+
+impl Default for LensEffects {
+    fn default() -> Self {
+        Self {
+            vignette_intensity: 0.0,
+            vignette_smoothness: 0.5,
+            vignette_roundness: 1.0,
+            vignette_color: LinearRgba::BLACK,
+            grain_intensity: 0.0,
+            grain_size: 1.0,
+            chromatic_aberration: 0.0,
+        }
+    }
+}
+
+/// A full-screen pass applying the [`LensEffects`] to an image.
+#[derive(Debug)]
+pub struct LensEffectsPass {
+    /// The descriptor set binding the source image.
+    set: DescriptorSet,
+
+    /// The pool from which the descriptor set is allocated, only kept alive for the set.
+    _pool: DescriptorPool,
+
+    /// The full-screen pass running the lens effects shader.
+    pass: FullscreenPass,
+
+    /// The sampler used to read the source image.
+    sampler: Sampler,
+}
+
+impl LensEffectsPass {
+    /// Create a new lens effects pass writing to a render target with the given format, or to
+    /// the swapchain if no format is given.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        swapchain: &VulkanSwapchain,
+        format: Option<vk::Format>,
+    ) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[DescriptorBinding {
+                binding: 0,
+                kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                stages: vk::ShaderStageFlags::FRAGMENT,
+            }],
+        ));
+
+        let pass = FullscreenPass::new(
+            device.clone(),
+            swapchain,
+            FullscreenPassCreateInfo {
+                fragment: ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Fragment,
+                    include_str!("../shaders/lens_effects.glsl").to_string(),
+                ),
+                color_formats: format.into_iter().collect(),
+                descriptor_set_layouts: vec![layout.clone()],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::FRAGMENT,
+                    offset: 0,
+                    size: PUSH_CONSTANTS_SIZE,
+                }],
+            },
+        );
+
+        let pool = DescriptorPool::new(device.clone(), &layout, 1);
+        let set = pool.allocate(&layout);
+
+        Self {
+            sampler: Sampler::new(
+                device,
+                vk::Filter::LINEAR,
+                vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ),
+            _pool: pool,
+            pass,
+            set,
+        }
+    }
+
+    /// Set the image the effects are applied to. The image must be in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout when the pass is executed.
+    ///
+    /// # Important
+    /// The source must not be changed while a command buffer recorded with this pass is
+    /// pending execution.
+    pub fn set_source(&self, view: vk::ImageView) {
+        self.set.write_sampled_image(
+            0,
+            view,
+            &self.sampler,
+            vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+        );
+    }
+
+    /// Record the pass into the command buffer. This must be called between
+    /// [`CommandBuffer::start_rendering`] and [`CommandBuffer::stop_rendering`], with a render
+    /// area of the `target` extent. The `time` animates the film grain, in seconds.
+    #[must_use]
+    pub fn record<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        target: vk::Extent2D,
+        effects: &LensEffects,
+        time: f32,
+    ) -> CommandBuffer<'pool, Recording> {
+        let aspect = target.width as f32 / target.height.max(1) as f32;
+        let constants = effects
+            .vignette_color
+            .to_f32_array()
+            .into_iter()
+            .chain([
+                effects.vignette_intensity,
+                effects.vignette_smoothness.clamp(0.0, 1.0),
+                effects.vignette_roundness.clamp(0.0, 1.0),
+                effects.grain_intensity,
+                effects.grain_size,
+                effects.chromatic_aberration,
+                aspect,
+                time,
+            ])
+            .flat_map(f32::to_ne_bytes)
+            .collect::<Vec<_>>();
+
+        let layout = self.pass.pipeline().layout();
+        let command = command
+            .bind_descriptor_set(vk::PipelineBindPoint::GRAPHICS, layout, 0, &self.set)
+            .push_constant_bytes(layout, vk::ShaderStageFlags::FRAGMENT, 0, &constants);
+        self.pass.record(command)
+    }
+}
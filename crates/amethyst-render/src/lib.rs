@@ -1,27 +1,82 @@
 use amethyst_vulkan::{
     buffer::{
         Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
-        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo, MemoryPriority,
+        MemoryReport,
     },
     command::{
-        CommandBuffer, CommandPool, DrawInfo, PipelineBarrierInfo, RenderingInfo, SubmitInfo,
+        CommandBuffer, CommandPool, DrawIndexedInfo, DrawInfo, PipelineBarrierInfo, RenderingInfo,
+        SubmitInfo,
     },
-    context::VulkanContext,
-    device::{VulkanDevice, VulkanQueues},
+    context::{ApplicationInfo, VulkanContext, VulkanContextCreateInfo},
+    descriptor::{DescriptorBinding, DescriptorPool, DescriptorSet, DescriptorSetLayout},
+    device::{DeviceFeatureRequest, VulkanDevice, VulkanQueues},
+    image::{texel_size, Image, ImageCreateInfo, SampleCount},
     pipeline::{Pipeline, PipelineCreateInfo},
-    semaphore::Semaphore,
+    profile::RequiredProfile,
+    query::{GpuProfiler, GpuScopeTiming},
+    ring::RingBuffer,
+    semaphore::{Fence, Semaphore},
     shader::{ShaderModule, ShaderType},
-    swapchain::{Surface, VulkanSwapchain},
+    swapchain::{AcquireResult, PresentResult, Surface, SurfaceFormatPreference, VulkanSwapchain},
+    trace_span, MAX_FRAMES_IN_FLIGHT,
 };
+use backend::{render_frame, DrawItem, DrawList, RenderBackend};
 use bevy::{
     prelude::*,
-    window::{PrimaryWindow, RawHandleWrapperHolder},
+    utils::HashMap,
+    window::{
+        AppLifecycle, PrimaryWindow, RawHandleWrapper, RawHandleWrapperHolder, WindowOccluded,
+    },
+};
+use bvh::Bvh;
+use extract::{extract_meshes, MeshQuery};
+use latency::{FrameLatency, LatencyTracker, RenderTimestamps};
+use lightmap::{BakeLightmaps, LightmapBakeSettings};
+use mesh::GpuMesh;
+use raw_window_handle::RawWindowHandle;
+use screenshot::Screenshot;
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
 };
-use std::sync::Arc;
-use vertex::Vertex2DColor;
+use timings::GpuFrameTimings;
+use vertex::{MeshVertex, Vertex2DColor};
+use view::{RenderView, ViewUniform};
 use vulkanalia::prelude::v1_3::*;
 
+pub mod backend;
+pub mod blit;
+pub mod bounds;
+pub mod bvh;
+pub mod compression;
+pub mod dof;
+pub mod exposure;
+pub mod extract;
+pub mod filter;
+pub mod fullscreen;
+#[cfg(feature = "golden")]
+pub mod golden;
+pub mod interpolation;
+pub mod ktx2;
+pub mod latency;
+pub mod lens;
+pub mod lightmap;
+pub mod material;
+pub mod mesh;
+pub mod picking;
+pub mod probe;
+pub mod raycast;
+pub mod screenshot;
+pub mod sort;
+pub mod timings;
+pub mod upscale;
 pub mod vertex;
+pub mod view;
+
+/// The size of the ring buffer holding the view uniforms of the frames in flight. A frame
+/// only uses one slice of 256 bytes at most, so this leaves plenty of room.
+const VIEWS_SIZE: vk::DeviceSize = 64 * 1024;
 
 /// The vertices of the triangle
 static VERTICES: [Vertex2DColor; 3] = [
@@ -45,9 +100,43 @@ pub struct AmethystRender;
 
 impl Plugin for AmethystRender {
     fn build(&self, app: &mut App) {
+        app.init_resource::<ApplicationInfo>();
+        app.init_resource::<VulkanContextCreateInfo>();
+        app.init_resource::<DeviceFeatureRequest>();
+        app.init_resource::<RequiredProfile>();
+        app.init_resource::<SurfaceFormatPreference>();
+        app.init_resource::<Bvh>();
+        app.init_resource::<LightmapBakeSettings>();
+        app.init_resource::<LatencyTracker>();
+        app.init_resource::<GpuFrameTimings>();
+        app.init_resource::<MemoryReport>();
+        app.init_resource::<RenderView>();
+        app.add_event::<BakeLightmaps>();
+        app.add_event::<FrameLatency>();
+        app.add_event::<Screenshot>();
         app.add_systems(Startup, create_vulkan_context);
-        app.add_systems(Update, render);
-        app.add_systems(PostUpdate, wait_for_device.run_if(is_exiting));
+        app.add_systems(First, latency::begin_frame);
+        // The frame is rendered in the last schedule, once the camera view, the transforms
+        // and the bounding volume hierarchy of the current frame are up to date.
+        app.add_systems(Last, handle_lifecycle.before(render));
+        app.add_systems(Last, mesh::upload_meshes.before(render));
+        app.add_systems(Last, render);
+        app.add_systems(Last, update_memory_report.after(render));
+        app.add_systems(
+            Update,
+            (
+                lightmap::start_lightmap_bake,
+                lightmap::finish_lightmap_bake,
+            ),
+        );
+        app.add_systems(PostUpdate, mesh::update_bounds);
+        app.add_systems(
+            PostUpdate,
+            bvh::update_bvh
+                .after(mesh::update_bounds)
+                .after(TransformSystem::TransformPropagate),
+        );
+        app.add_systems(Last, wait_for_device.after(render).run_if(is_exiting));
     }
 }
 
@@ -63,20 +152,79 @@ pub struct Render {
     /// A vertex buffer that holds the vertices of the triangle
     buffer: Buffer,
 
+    /// The GPU buffers of the meshes, for each entity with a [`Mesh`](mesh::Mesh).
+    meshes: HashMap<Entity, GpuMesh>,
+
+    /// The ring buffer from which the view and projection matrices of each frame are
+    /// allocated, so that a frame does not overwrite the matrices read by the previous ones.
+    views: RingBuffer,
+
+    /// The descriptor set binding the view uniforms, with a dynamic offset selecting the
+    /// matrices of the frame.
+    view_set: DescriptorSet,
+
+    /// The pool from which the view descriptor set is allocated.
+    view_pool: DescriptorPool,
+
     /// A buffer allocator used to allocate buffers
     buffer_allocator: Arc<BufferAllocator>,
 
-    /// A semaphore used to signal when the swapchain image is acquired
-    acquire_semaphore: Semaphore,
+    /// The resources of each frame in flight, used in turn.
+    frames: Vec<FrameResources>,
 
-    /// A semaphore used to signal when the rendering is done
-    render_semaphore: Semaphore,
+    /// The index of the resources used by the next frame.
+    next_frame: usize,
 
     /// A simple pipeline object that renders a triangle1
     pipeline: Pipeline,
 
-    /// The swapchain used for presenting images to the screen
-    swapchain: VulkanSwapchain,
+    /// The pipeline used to render the meshes.
+    mesh_pipeline: Pipeline,
+
+    /// The swapchain used for presenting images to the screen. This is `None` while the
+    /// application is suspended, since the window surface may be destroyed by the system.
+    swapchain: Option<VulkanSwapchain>,
+
+    /// The depth buffer of the meshes, with the extent of the swapchain images. It is created
+    /// and destroyed along with the swapchain.
+    depth: Option<Image>,
+
+    /// The format and color space requested for the swapchain images, kept to create the
+    /// swapchain again with the same format when the application resumes.
+    format_preference: SurfaceFormatPreference,
+
+    /// The window the surface of the swapchain was created for. On Android, the native window
+    /// is replaced when the application comes back from the background, and the surface must
+    /// then be created again for the new window.
+    window: RawWindowHandle,
+
+    /// Whether the surface has been destroyed by the system. The surface and the swapchain are
+    /// recreated from the current window handle before the next frame.
+    surface_lost: bool,
+
+    /// Whether the primary window is fully hidden by other windows. Nothing is rendered while
+    /// the window is occluded to save power.
+    occluded: bool,
+
+    /// Whether the application is suspended. The swapchain is not recreated until the
+    /// application is resumed.
+    suspended: bool,
+
+    /// Whether the swapchain does not match the window anymore, usually because it has been
+    /// resized. The swapchain is recreated before the next frame.
+    outdated: bool,
+
+    /// Whether a frame was submitted by the last call to the render backend, taken by the
+    /// render system to start measuring the latency of the frame.
+    submitted: bool,
+
+    /// The timestamps of the last completed frame, taken by the render system to measure the
+    /// latency of the frame.
+    timestamps: Option<RenderTimestamps>,
+
+    /// The GPU timings of the last completed frame, taken by the render system to update the
+    /// [`GpuFrameTimings`] resource.
+    gpu_timings: Option<Vec<GpuScopeTiming>>,
 
     /// The queues used for rendering
     queues: VulkanQueues,
@@ -88,38 +236,268 @@ pub struct Render {
     context: Arc<VulkanContext>,
 }
 
-fn create_vulkan_context(
-    mut command: Commands,
-    window: Query<&RawHandleWrapperHolder, With<PrimaryWindow>>,
-) {
-    let handle = window
-        .get_single()
-        .expect("No primary window found")
+/// The resources used to record and execute a frame. Each frame in flight has its own
+/// resources, so the next frames are recorded while the GPU still executes the previous ones.
+#[derive(Debug)]
+struct FrameResources {
+    /// The command pool used to record the commands of the frame. It is reset once the frame
+    /// previously recorded with it is complete, so its command buffers are reused.
+    command_pool: Mutex<CommandPool>,
+
+    /// The profiler measuring the time spent by the GPU on each pass of the frame.
+    profiler: GpuProfiler,
+
+    /// A semaphore used to signal when the swapchain image is acquired
+    acquire_semaphore: Semaphore,
+
+    /// A semaphore used to signal when the rendering is done
+    render_semaphore: Semaphore,
+
+    /// The fence signaled once the GPU has executed the frame.
+    fence: Fence,
+
+    /// The index of the frame executing with these resources, as returned by
+    /// [`VulkanDevice::begin_frame`], or `None` if no frame is executing.
+    frame: Option<u64>,
+
+    /// When the frame was submitted to the GPU.
+    submit: Instant,
+
+    /// When the frame was handed to the presentation engine.
+    present: Instant,
+}
+
+impl FrameResources {
+    /// Create the resources of a frame.
+    fn new(device: &Arc<VulkanDevice>) -> Self {
+        Self {
+            command_pool: Mutex::new(CommandPool::new(
+                device.clone(),
+                device.queues_info().main_family(),
+                vk::CommandPoolCreateFlags::TRANSIENT,
+            )),
+            profiler: GpuProfiler::new(device.clone()),
+            acquire_semaphore: Semaphore::new(device.clone()),
+            render_semaphore: Semaphore::new(device.clone()),
+            fence: Fence::new(device.clone(), vk::FenceCreateFlags::empty()),
+            frame: None,
+            submit: Instant::now(),
+            present: Instant::now(),
+        }
+    }
+
+    /// Wait for the frame executing with these resources, if any, and mark it as complete so
+    /// the resources dropped during the frame are destroyed. Returns the timestamps and the
+    /// GPU timings of the frame.
+    fn wait(
+        &mut self,
+        device: &VulkanDevice,
+    ) -> Option<(RenderTimestamps, Option<Vec<GpuScopeTiming>>)> {
+        let frame = self.frame.take()?;
+        self.fence.wait();
+        let gpu_complete = Instant::now();
+        device.end_frame(frame);
+
+        let timestamps = RenderTimestamps {
+            submit: self.submit,
+            gpu_complete,
+            present: self.present,
+        };
+        Some((timestamps, self.profiler.resolve()))
+    }
+}
+
+impl Drop for FrameResources {
+    fn drop(&mut self) {
+        // The command pool must not be destroyed while the GPU still executes the frame.
+        if self.frame.is_some() {
+            self.fence.wait();
+        }
+    }
+}
+
+impl Render {
+    /// Destroy the swapchain and its surface, since the system may destroy the window surface
+    /// while the application is suspended. Nothing is rendered until [`Render::resume`] is called.
+    fn suspend(&mut self) {
+        if self.swapchain.is_none() {
+            return;
+        }
+
+        // Wait for the device to finish using the swapchain images before destroying it
+        unsafe {
+            self.device
+                .logical()
+                .device_wait_idle()
+                .expect("Failed to wait for device idle")
+        };
+        self.swapchain = None;
+        self.recreate_depth_buffer();
+    }
+
+    /// Recreate the surface and the swapchain from the current window handle, if they were
+    /// destroyed when the application was suspended.
+    fn resume(&mut self, handle: RawHandleWrapper) {
+        if self.swapchain.is_some() {
+            return;
+        }
+
+        // SAFETY: Lifecycle events are handled in the main thread, so we can safely
+        // get the handle in any platform.
+        self.window = handle.get_window_handle();
+        let handle = unsafe { handle.get_handle() };
+        let surface = Surface::new(self.context.clone(), handle);
+        self.swapchain = Some(VulkanSwapchain::with_format_preference(
+            self.context.clone(),
+            self.device.clone(),
+            surface,
+            self.format_preference,
+        ));
+        self.recreate_depth_buffer();
+        self.outdated = false;
+        self.surface_lost = false;
+    }
+
+    /// Recreate the swapchain so it matches the current size and properties of the window
+    /// surface again.
+    fn recreate_swapchain(&mut self) {
+        let Some(swapchain) = &mut self.swapchain else {
+            return;
+        };
+
+        // Wait for the device to finish using the swapchain images before replacing them
+        unsafe {
+            self.device
+                .logical()
+                .device_wait_idle()
+                .expect("Failed to wait for device idle")
+        };
+        swapchain.recreate(&self.context);
+        self.recreate_depth_buffer();
+        self.outdated = false;
+    }
+
+    /// Create the depth buffer again so it matches the extent of the swapchain, or destroy it
+    /// if there is no swapchain.
+    fn recreate_depth_buffer(&mut self) {
+        self.depth = self.swapchain.as_ref().map(|swapchain| {
+            create_depth_buffer(&self.device, &self.buffer_allocator, swapchain.extent())
+        });
+    }
+}
+
+/// Returns the format of the depth buffer: 32-bit floats if the device can render to them,
+/// which almost every device does, or 24-bit integers otherwise. Vulkan requires the support
+/// of at least one of these formats.
+fn depth_buffer_format(device: &VulkanDevice) -> vk::Format {
+    [vk::Format::D32_SFLOAT, vk::Format::X8_D24_UNORM_PACK32]
+        .into_iter()
+        .find(|&format| {
+            device
+                .format_properties(format)
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+        })
+        .expect("The device does not support any depth buffer format")
+}
+
+/// Create a depth buffer with the given extent. Its content is cleared at the start of each
+/// frame and never stored, so it is a transient attachment.
+fn create_depth_buffer(
+    device: &Arc<VulkanDevice>,
+    allocator: &Arc<BufferAllocator>,
+    extent: vk::Extent2D,
+) -> Image {
+    Image::new(
+        device.clone(),
+        allocator.clone(),
+        ImageCreateInfo {
+            extent,
+            format: depth_buffer_format(device),
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            priority: MemoryPriority::High,
+            transient: true,
+            name: Some("Depth buffer"),
+            ..Default::default()
+        },
+    )
+}
+
+/// Returns the raw window handle of a window, or `None` if the window does not have a handle
+/// yet (or anymore, when the application is suspended).
+fn window_handle(holder: &RawHandleWrapperHolder) -> Option<RawHandleWrapper> {
+    holder
         .0
         .lock()
         .expect("Could not lock primary window handle")
         .as_ref()
-        .expect("Vulkan plugin requires a window to work correctly")
-        .clone();
+        .cloned()
+}
+
+/// Returns whether the window is minimized, or more generally whether it has a null size. A
+/// swapchain cannot be created nor used with a null extent, so nothing must be rendered.
+fn is_minimized(window: &Window) -> bool {
+    window.physical_width() == 0 || window.physical_height() == 0
+}
+
+fn create_vulkan_context(
+    mut command: Commands,
+    application: Res<ApplicationInfo>,
+    context_info: Res<VulkanContextCreateInfo>,
+    device_request: Res<DeviceFeatureRequest>,
+    required: Res<RequiredProfile>,
+    format_preference: Res<SurfaceFormatPreference>,
+    window: Query<&RawHandleWrapperHolder, With<PrimaryWindow>>,
+) {
+    trace_span!("create_vulkan_context");
+    let holder = window.get_single().expect("No primary window found");
+    let handle = window_handle(holder).expect("Vulkan plugin requires a window to work correctly");
+    let window = handle.get_window_handle();
 
     // SAFETY: Adding plugin to the app should be done in the main thread,
     // so we can safely get the handle in any platform.
     let handle = unsafe { handle.get_handle() };
 
     // Create the Vulkan context and surface objects
-    let context = Arc::new(VulkanContext::new(&handle));
+    let context = Arc::new(VulkanContext::new(&handle, &application, &context_info));
     let surface = Surface::new(context.clone(), handle);
 
     // Create the device, swapchain, and queues objects
-    let device = Arc::new(VulkanDevice::pick_best(&context, &surface));
-    let swapchain = VulkanSwapchain::new(context.clone(), device.clone(), surface);
+    let device = Arc::new(VulkanDevice::pick_best(&context, &surface, &device_request));
+
+    // Check the device before anything is rendered, since a device missing a required feature
+    // may render garbage instead of failing cleanly.
+    let report = required.profile.check_device(&context, device.physical());
+    if report.is_conformant() {
+        info!("{report}");
+    } else if required.abort {
+        panic!("{report}");
+    } else {
+        error!("{report}");
+    }
+    let swapchain = VulkanSwapchain::with_format_preference(
+        context.clone(),
+        device.clone(),
+        surface,
+        *format_preference,
+    );
+    if swapchain.format_preference() != SurfaceFormatPreference::Srgb {
+        info!(
+            "Presenting {:?} images in the {:?} color space",
+            swapchain.format(),
+            swapchain.color_space()
+        );
+    }
     let queues = VulkanQueues::fetch(&device);
+    let depth_format = depth_buffer_format(&device);
 
     // Create a pipeline object that does not require vertex data and
     // use a simple vertex and fragment shader. Since we are trying to
     // render a simple triangle, we don't need to pass any vertex data
     // to the vertex shader (hence the `NoVertex` type) and we also don't
-    // need to write to the depth buffer.
+    // need to write to the depth buffer. The pipeline is still created for
+    // the depth buffer format, since it is used in the same render pass as
+    // the meshes.
     let pipeline = Pipeline::new::<Vertex2DColor>(
         device.clone(),
         &swapchain,
@@ -136,6 +514,7 @@ fn create_vulkan_context(
                     include_str!("../shaders/fragment.glsl").to_string(),
                 ),
             ],
+            depth_format,
             depth_write: false,
             depth_test: false,
             front_face: vk::FrontFace::CLOCKWISE,
@@ -144,6 +523,49 @@ fn create_vulkan_context(
         },
     );
 
+    // The meshes are rendered through the view uniform buffer, bound to the first set.
+    let view_layout = Arc::new(DescriptorSetLayout::new(
+        device.clone(),
+        &[DescriptorBinding {
+            binding: 0,
+            kind: vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+            stages: vk::ShaderStageFlags::VERTEX,
+        }],
+    ));
+
+    // The projection flips the Y axis of the clip space, so the triangles keep their counter
+    // clockwise winding on the screen.
+    let mesh_pipeline = Pipeline::new::<MeshVertex>(
+        device.clone(),
+        &swapchain,
+        PipelineCreateInfo {
+            shaders: vec![
+                ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Vertex,
+                    include_str!("../shaders/mesh_vertex.glsl").to_string(),
+                ),
+                ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Fragment,
+                    include_str!("../shaders/mesh_fragment.glsl").to_string(),
+                ),
+            ],
+            descriptor_set_layouts: vec![view_layout.clone()],
+            push_constant_ranges: vec![vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::VERTEX,
+                offset: 0,
+                size: std::mem::size_of::<Mat4>() as u32,
+            }],
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
+            cull_mode: vk::CullModeFlags::BACK,
+            depth_format,
+            depth_write: true,
+            depth_test: true,
+            ..Default::default()
+        },
+    );
+
     let buffer_allocator = Arc::new(BufferAllocator::new(&context, &device));
     let buffer = Buffer::new(
         buffer_allocator.clone(),
@@ -160,42 +582,237 @@ fn create_vulkan_context(
         },
     );
 
+    let depth = create_depth_buffer(&device, &buffer_allocator, swapchain.extent());
+    let views = RingBuffer::new(buffer_allocator.clone(), BufferUsage::Uniforms, VIEWS_SIZE);
+    let view_pool = DescriptorPool::new(device.clone(), &view_layout, 1);
+    let view_set = view_pool.allocate(&view_layout);
+    view_set.write_buffer_range(
+        0,
+        vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+        views.buffer(),
+        0,
+        std::mem::size_of::<ViewUniform>() as vk::DeviceSize,
+    );
+
     command.insert_resource(Render {
-        acquire_semaphore: Semaphore::new(device.clone()),
-        render_semaphore: Semaphore::new(device.clone()),
+        frames: (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| FrameResources::new(&device))
+            .collect(),
+        next_frame: 0,
         buffer_allocator,
         buffer,
+        meshes: HashMap::default(),
+        views,
+        view_set,
+        view_pool,
         context,
         device,
-        swapchain,
+        swapchain: Some(swapchain),
+        depth: Some(depth),
+        format_preference: *format_preference,
+        window,
+        surface_lost: false,
+        occluded: false,
+        suspended: false,
+        outdated: false,
+        submitted: false,
+        timestamps: None,
+        gpu_timings: None,
         queues,
         pipeline,
+        mesh_pipeline,
     });
 }
 
+/// Handles the suspend and resume events of the application, as well as the occlusion of the
+/// primary window. On mobile platforms, the window surface is destroyed when the application is
+/// suspended, so the swapchain must be destroyed too and recreated when the application resumes.
+fn handle_lifecycle(
+    mut render: ResMut<Render>,
+    mut lifecycle: EventReader<AppLifecycle>,
+    mut occluded: EventReader<WindowOccluded>,
+    window: Query<(&Window, &RawHandleWrapperHolder), With<PrimaryWindow>>,
+) {
+    for event in occluded.read() {
+        render.occluded = event.occluded;
+    }
+
+    for event in lifecycle.read() {
+        match event {
+            AppLifecycle::WillSuspend | AppLifecycle::Suspended => {
+                render.suspended = true;
+                render.suspend();
+            }
+            AppLifecycle::WillResume | AppLifecycle::Running => render.suspended = false,
+            AppLifecycle::Idle => (),
+        }
+    }
+
+    let Ok((window, holder)) = window.get_single() else {
+        return;
+    };
+
+    // The surface is unusable once the system has destroyed it, or once the window it was
+    // created for has been replaced, which may happen on Android without a suspend event
+    // reaching the renderer. The swapchain and the surface are then destroyed, and recreated
+    // below from the current window handle.
+    let current = window_handle(holder).map(|handle| handle.get_window_handle());
+    if render.surface_lost || current.is_some_and(|current| current != render.window) {
+        render.suspend();
+    }
+
+    // Recreate the swapchain once the application is resumed. If the window is minimized,
+    // wait until it is restored since a swapchain cannot be created with a null extent.
+    if render.suspended || render.swapchain.is_some() {
+        return;
+    }
+
+    if let Some(handle) = window_handle(holder).filter(|_| !is_minimized(window)) {
+        render.resume(handle);
+    }
+}
+
+/// Build the draw list of the frame: a single triangle for now.
+#[must_use]
+pub fn build_draw_list(_extent: vk::Extent2D) -> DrawList {
+    DrawList {
+        clear_color: [0.0, 0.0, 0.0, 1.0],
+        items: vec![DrawItem {
+            vertex_count: VERTICES.len() as u32,
+            instance_count: 1,
+            first_vertex: 0,
+            first_instance: 0,
+        }],
+        meshes: Vec::new(),
+        view: RenderView::default(),
+        screenshot: None,
+    }
+}
+
 // Render the triangle
-fn render(render: Res<Render>) {
-    let command_pool = CommandPool::new(
-        render.device.clone(),
-        render.device.queues_info().main_family(),
-        vk::CommandPoolCreateFlags::empty(),
-    );
+#[allow(clippy::too_many_arguments)]
+fn render(
+    mut render: ResMut<Render>,
+    mut latency: ResMut<LatencyTracker>,
+    mut latencies: EventWriter<FrameLatency>,
+    mut gpu_timings: ResMut<GpuFrameTimings>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    view: Res<RenderView>,
+    bvh: Res<Bvh>,
+    meshes: MeshQuery,
+    transforms: Query<&GlobalTransform>,
+    mut screenshots: EventReader<Screenshot>,
+) {
+    trace_span!("render");
 
-    let command = CommandBuffer::new(&command_pool);
+    // Skip the frame if the window is minimized. Acquiring an image from a swapchain whose
+    // surface has a null extent triggers validation errors or even errors on some drivers.
+    // The ECS keeps running normally, only the rendering is skipped.
+    let Some(window) = window
+        .get_single()
+        .ok()
+        .filter(|window| !is_minimized(window))
+    else {
+        return;
+    };
 
-    // Acquire the next image from the swapchain. If no image is available,
-    // this function wait until an image is available.
-    let (image_index, image, iview) = render
+    // Resizing the window does not make the swapchain out of date on every platform, so the
+    // size of the swapchain is also compared with the size of the window.
+    let size = vk::Extent2D {
+        width: window.physical_width(),
+        height: window.physical_height(),
+    };
+    if render
         .swapchain
-        .acquire_next_image(&render.acquire_semaphore);
+        .as_ref()
+        .is_some_and(|s| s.window_extent() != size)
+    {
+        render.outdated = true;
+    }
+    if render.outdated {
+        render.recreate_swapchain();
+    }
 
-    // SAFETY: Most of the following code is safe thank to our encapsulation
-    // of the Vulkan API. The only unsafe function call is the `draw` method
-    // call because the caller must ensure that the draw call parameters will
-    // not cause any out-of-bounds access of any buffer using behind the scenes.
-    unsafe {
-        command
-            .start_recording()
+    render_frame(&mut *render, |extent| {
+        let mut list = build_draw_list(extent);
+        list.view = *view;
+        list.meshes = extract_meshes(&view, &bvh, &meshes, &transforms);
+        list.screenshot = screenshots.read().last().map(|s| s.path.clone());
+        list
+    });
+    if std::mem::take(&mut render.submitted) {
+        latency.submit_frame();
+    }
+    if let Some(timestamps) = render.timestamps.take() {
+        let latency = latency.finish_frame(timestamps);
+        if let Some(scopes) = render.gpu_timings.take() {
+            gpu_timings.frame = latency.frame;
+            gpu_timings.scopes = scopes;
+        }
+        latencies.send(latency);
+    }
+}
+
+/// Update the [`MemoryReport`] resource with the memory used by the buffer allocator, and warn
+/// when a memory heap goes over its budget.
+fn update_memory_report(render: Res<Render>, mut memory: ResMut<MemoryReport>) {
+    let report = render.buffer_allocator.memory_report();
+    if report.is_over_budget() && !memory.is_over_budget() {
+        warn!("GPU memory is over budget: {report}");
+    }
+    *memory = report;
+}
+
+impl RenderBackend for Render {
+    fn frame_extent(&self) -> Option<vk::Extent2D> {
+        // Do not render anything while the application is suspended or the window is
+        // not visible: the swapchain may not exist or presenting may block forever.
+        let swapchain = self.swapchain.as_ref()?;
+        if self.occluded || swapchain.is_zero_sized() {
+            return None;
+        }
+        Some(swapchain.window_extent())
+    }
+
+    fn execute(&mut self, list: &DrawList) {
+        let (Some(swapchain), Some(depth)) = (&self.swapchain, &self.depth) else {
+            return;
+        };
+
+        // Wait for the frame previously executed with the resources of this frame, so they can
+        // be reused. The more recent frames keep executing while this frame is recorded.
+        let resources = &mut self.frames[self.next_frame];
+        if let Some((timestamps, gpu_timings)) = resources.wait(&self.device) {
+            self.timestamps = Some(timestamps);
+            self.gpu_timings = gpu_timings;
+        }
+        let command_pool = resources
+            .command_pool
+            .get_mut()
+            .expect("Command pool poisoned");
+        command_pool.reset(vk::CommandPoolResetFlags::empty());
+        let command = CommandBuffer::new(command_pool);
+
+        // Acquire the next image from the swapchain. If no image is available,
+        // this function wait until an image is available. If the swapchain is out of
+        // date, the frame is skipped and the swapchain is recreated before the next one.
+        let acquired = swapchain.acquire_next_image(&resources.acquire_semaphore);
+        self.outdated |= acquired.needs_recreation();
+        self.surface_lost |= acquired == AcquireResult::SurfaceLost;
+        let Some(acquired) = acquired.image() else {
+            return;
+        };
+        let (image_index, image, iview) = (acquired.index, acquired.image, acquired.view);
+
+        // The resources dropped while the frame is recorded and executed may still be used
+        // by its commands, so they are only destroyed once the frame is complete.
+        let frame = self.device.begin_frame();
+        self.views.begin_frame(frame);
+
+        let profiler = &mut resources.profiler;
+        let recording = profiler.begin_frame(command.start_recording());
+        let mut recording = profiler
+            .begin_scope(recording, "scene")
             .pipeline_barrier(PipelineBarrierInfo {
                 src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
                 dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
@@ -213,9 +830,11 @@ fn render(render: Res<Render>) {
                     })
                     .image(image)
                     .build()],
+                ..Default::default()
             })
-            .bind_graphic_pipeline(&render.pipeline)
-            .bind_vertex_buffer(&render.buffer)
+            .transition_image(depth, vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+            .bind_graphic_pipeline(&self.pipeline)
+            .bind_vertex_buffer(&self.buffer)
             .start_rendering(RenderingInfo {
                 colors_attachements: vec![vk::RenderingAttachmentInfo::builder()
                     .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
@@ -223,53 +842,219 @@ fn render(render: Res<Render>) {
                     .load_op(vk::AttachmentLoadOp::CLEAR)
                     .clear_value(vk::ClearValue {
                         color: vk::ClearColorValue {
-                            float32: [0.0, 0.0, 0.0, 1.0],
+                            float32: list.clear_color,
                         },
                     })
                     .image_view(iview)
                     .build()],
-                render_area: render.swapchain.extent(),
-            })
-            .draw(DrawInfo {
-                vertex_count: 3,
-                instance_count: 1,
-                first_vertex: 0,
-                first_instance: 0,
-            })
-            .stop_rendering()
+                depth_attachment: Some(
+                    vk::RenderingAttachmentInfo::builder()
+                        .image_layout(vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL)
+                        .store_op(vk::AttachmentStoreOp::DONT_CARE)
+                        .load_op(vk::AttachmentLoadOp::CLEAR)
+                        .clear_value(vk::ClearValue {
+                            depth_stencil: vk::ClearDepthStencilValue {
+                                depth: 1.0,
+                                stencil: 0,
+                            },
+                        })
+                        .image_view(depth.view())
+                        .build(),
+                ),
+                render_area: swapchain.extent(),
+                layer_count: 1,
+                view_mask: 0,
+                shading_rate_attachment: None,
+                flags: vk::RenderingFlags::empty(),
+                color_formats: vec![swapchain.format()],
+                depth_format: depth.format(),
+                samples: SampleCount::One,
+            });
+
+        // The draw list is built outside of the backend, so the vertices of each draw call
+        // are checked against the vertex buffer before being drawn.
+        let vertex_count = self.buffer.size() / std::mem::size_of::<Vertex2DColor>() as u64;
+        for item in &list.items {
+            let last_vertex = u64::from(item.first_vertex) + u64::from(item.vertex_count);
+            if last_vertex > vertex_count {
+                warn!("Skipping a draw call reading vertices out of bounds: {item:?}");
+                continue;
+            }
+
+            // SAFETY: The draw call only reads the vertices of the vertex buffer bound above,
+            // since its range of vertices has been checked to fit in the buffer.
+            recording = unsafe {
+                recording.draw(DrawInfo {
+                    vertex_count: item.vertex_count,
+                    instance_count: item.instance_count,
+                    first_vertex: item.first_vertex,
+                    first_instance: item.first_instance,
+                })
+            };
+        }
+
+        if !list.meshes.is_empty() {
+            // The projection is rotated like the swapchain images, so that the meshes are
+            // displayed upright on rotated displays.
+            let pre_rotated = RenderView {
+                projection: swapchain.pre_rotation() * list.view.projection,
+                ..list.view
+            };
+            let view = self
+                .views
+                .push::<ViewUniform>(&[pre_rotated.to_uniform()])
+                .expect("View ring buffer is full");
+
+            recording = recording
+                .bind_graphic_pipeline(&self.mesh_pipeline)
+                .bind_descriptor_set_with_offsets(
+                    vk::PipelineBindPoint::GRAPHICS,
+                    self.mesh_pipeline.layout(),
+                    0,
+                    &self.view_set,
+                    &[view.offset() as u32],
+                );
+        }
+        for draw in &list.meshes {
+            let Some(mesh) = self.meshes.get(&draw.mesh) else {
+                continue;
+            };
+
+            // SAFETY: The index buffer of a GPU mesh only references the vertices of its
+            // vertex buffer, since the mesh is validated when it is uploaded.
+            recording = unsafe {
+                recording
+                    .bind_vertex_buffer(mesh.vertices())
+                    .bind_index_buffer(mesh.indices())
+                    .push_constants(
+                        vk::ShaderStageFlags::VERTEX,
+                        0,
+                        &draw.transform.to_cols_array(),
+                    )
+                    .draw_indexed(DrawIndexedInfo {
+                        index_count: mesh.index_count(),
+                        instance_count: 1,
+                        first_index: 0,
+                        vertex_offset: 0,
+                        first_instance: 0,
+                    })
+            };
+        }
+
+        let barrier = |old, new, src_access, dst_access| {
+            vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old)
+                .new_layout(new)
+                .subresource_range(vk::ImageSubresourceRange {
+                    aspect_mask: vk::ImageAspectFlags::COLOR,
+                    base_mip_level: 0,
+                    level_count: 1,
+                    base_array_layer: 0,
+                    layer_count: 1,
+                })
+                .image(image)
+                .build()
+        };
+
+        // Copy the image to the host before presenting it if a screenshot was requested.
+        let mut recording = profiler.end_scope(recording.stop_rendering());
+        let readback = list.screenshot.as_ref().and_then(|path| {
+            if !swapchain.supports_readback() {
+                error!("The swapchain does not support screenshots");
+                return None;
+            }
+            let size = texel_size(swapchain.format())? as usize
+                * swapchain.extent().width as usize
+                * swapchain.extent().height as usize;
+            let buffer = screenshot::readback_buffer(&self.buffer_allocator, size);
+            Some((path.clone(), buffer))
+        });
+        let (present_from, src_stage_mask, src_access) = match &readback {
+            Some((_, buffer)) => {
+                recording = profiler
+                    .begin_scope(recording, "screenshot")
+                    .pipeline_barrier(PipelineBarrierInfo {
+                        src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                        dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                        images_barriers: vec![barrier(
+                            vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                            vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                            vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                            vk::AccessFlags::TRANSFER_READ,
+                        )],
+                        ..Default::default()
+                    })
+                    .copy_swapchain_image_to_buffer(image, swapchain.extent(), buffer);
+                recording = profiler.end_scope(recording);
+                (
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::AccessFlags::TRANSFER_READ,
+                )
+            }
+            None => (
+                vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            ),
+        };
+
+        let executable = recording
             .pipeline_barrier(PipelineBarrierInfo {
-                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                src_stage_mask,
                 dst_stage_mask: vk::PipelineStageFlags::BOTTOM_OF_PIPE,
-                images_barriers: vec![vk::ImageMemoryBarrier::builder()
-                    .src_access_mask(vk::AccessFlags::COLOR_ATTACHMENT_WRITE)
-                    .dst_access_mask(vk::AccessFlags::empty())
-                    .old_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
-                    .new_layout(vk::ImageLayout::PRESENT_SRC_KHR)
-                    .subresource_range(vk::ImageSubresourceRange {
-                        aspect_mask: vk::ImageAspectFlags::COLOR,
-                        base_mip_level: 0,
-                        level_count: 1,
-                        base_array_layer: 0,
-                        layer_count: 1,
-                    })
-                    .image(image)
-                    .build()],
+                images_barriers: vec![barrier(
+                    present_from,
+                    vk::ImageLayout::PRESENT_SRC_KHR,
+                    src_access,
+                    vk::AccessFlags::empty(),
+                )],
+                ..Default::default()
             })
-            .stop_recording()
-            .submit_and_wait(SubmitInfo {
+            .stop_recording();
+
+        // The frame is marked as complete once its fence is waited for, the next time its
+        // resources are used.
+        resources.fence.reset();
+        resources.submit = Instant::now();
+        executable.submit(
+            SubmitInfo {
                 wait_dst_stage_mask: vec![vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT],
-                signal_semaphores: vec![render.render_semaphore.inner()],
-                wait_semaphores: vec![render.acquire_semaphore.inner()],
-                queue: render.queues.main(),
-            });
-    };
+                signal_semaphores: vec![resources.render_semaphore.inner()],
+                wait_semaphores: vec![resources.acquire_semaphore.inner()],
+                queue: self.queues.main(),
+                ..Default::default()
+            },
+            Some(&resources.fence),
+        );
+        resources.frame = Some(frame);
+        self.submitted = true;
 
-    // Present the image to the screen
-    render.swapchain.present_image(
-        render.queues.present(),
-        image_index,
-        &render.render_semaphore,
-    );
+        if let Some((path, buffer)) = readback {
+            resources.fence.wait();
+
+            // SAFETY: The readback buffer is host visible, and the submission that copied the
+            // image into it has completed since its fence was waited for.
+            let pixels = unsafe { buffer.read::<u8>() };
+            screenshot::save(path, swapchain.extent(), swapchain.format(), pixels);
+        }
+
+        // Present the image to the screen
+        let presented = {
+            trace_span!("present");
+            swapchain.present_image(
+                self.queues.present(),
+                image_index,
+                &resources.render_semaphore,
+            )
+        };
+        resources.present = Instant::now();
+        self.outdated |= presented.needs_recreation();
+        self.surface_lost |= presented == PresentResult::SurfaceLost;
+        self.next_frame = (self.next_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+    }
 }
 
 /// A system that verifies if the application is about to exit. This system returns
@@ -0,0 +1,465 @@
+//! Lightmap baking for static global illumination. The meshes marked with [`LightmapStatic`]
+//! are packed into a lightmap atlas, and the light reaching each texel is computed by tracing
+//! rays against the scene: direct light from the sun with shadows, and light from the sky with
+//! ambient occlusion. The atlas is sampled by the lit shader with the lightmap texture
+//! coordinates of the meshes (see [`LIGHTMAP_SAMPLING_GLSL`]).
+//!
+//! Baking runs on the CPU in a background task, so it can be triggered at runtime with the
+//! [`BakeLightmaps`] event without stalling the application, or offline with [`bake`].
+use crate::{bounds::Aabb, bvh::Bvh, mesh::Mesh, raycast::ray_triangle};
+use bevy::{
+    prelude::*,
+    tasks::{block_on, futures_lite::future, AsyncComputeTaskPool, Task},
+};
+use std::f32::consts::TAU;
+
+/// The GLSL function sampling the lightmap atlas, to be included in lit fragment shaders.
+pub const LIGHTMAP_SAMPLING_GLSL: &str = include_str!("../shaders/lightmap.glsl");
+
+/// The distance by which rays start away from the surfaces, to avoid hitting the surface they
+/// start from.
+const RAY_BIAS: f32 = 1e-3;
+
+/// Marks a mesh as static, so it is included in the lightmap baking. The mesh must have
+/// lightmap texture coordinates (see [`Mesh::with_lightmap_uvs`]).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Component)]
+pub struct LightmapStatic;
+
+/// The area of the lightmap atlas covered by the chart of a mesh, written by the baking. The
+/// atlas coordinates are `lightmap_uv * scale + offset`.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+pub struct Lightmap {
+    /// The size of the chart in the atlas, in texture coordinates.
+    pub scale: Vec2,
+
+    /// The position of the chart in the atlas, in texture coordinates.
+    pub offset: Vec2,
+}
+
+/// The settings of the lightmap baking.
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct LightmapBakeSettings {
+    /// The size of the lightmap atlas, in texels.
+    pub atlas_size: u32,
+
+    /// The number of texels per world unit along each axis. It is lowered automatically if
+    /// the meshes do not fit in the atlas.
+    pub texels_per_unit: f32,
+
+    /// The direction in which the sunlight travels.
+    pub sun_direction: Vec3,
+
+    /// The irradiance of the sun on a surface facing it, in linear RGB.
+    pub sun_color: Vec3,
+
+    /// The irradiance of the sky on an unoccluded surface facing up, in linear RGB.
+    pub sky_color: Vec3,
+
+    /// The number of rays traced toward the sky for each texel.
+    pub sky_samples: u32,
+
+    /// The distance beyond which the geometry does not occlude the sky anymore.
+    pub occlusion_distance: f32,
+}
+
+impl Default for LightmapBakeSettings {
+    fn default() -> Self {
+        Self {
+            atlas_size: 1024,
+            texels_per_unit: 8.0,
+            sun_direction: Vec3::new(-0.3, -1.0, -0.4).normalize(),
+            sun_color: Vec3::splat(3.0),
+            sky_color: Vec3::new(0.4, 0.5, 0.7),
+            sky_samples: 64,
+            occlusion_distance: 50.0,
+        }
+    }
+}
+
+/// Request the baking of the lightmaps of the static meshes with the current
+/// [`LightmapBakeSettings`]. Requests sent while a baking is running are ignored.
+#[derive(Debug, Default, Clone, Copy, Event)]
+pub struct BakeLightmaps;
+
+/// The baked lightmap atlas. The renderer uploads it to a `R16G16B16A16_SFLOAT` texture.
+#[derive(Debug, Clone, PartialEq, Resource)]
+pub struct BakedLightmap {
+    /// The size of the atlas, in texels.
+    pub size: UVec2,
+
+    /// The irradiance of each texel in linear RGB, row by row. The alpha channel is 1.0 for
+    /// texels covered by a mesh, and 0.0 for unused texels.
+    pub texels: Vec<Vec4>,
+}
+
+/// A static mesh to bake, with its geometry in world space.
+#[derive(Debug, Clone)]
+pub struct BakeMesh {
+    /// The entity of the mesh.
+    pub entity: Entity,
+
+    /// The vertex positions, in world space.
+    pub positions: Vec<Vec3>,
+
+    /// The lightmap texture coordinates of each vertex.
+    pub lightmap_uvs: Vec<Vec2>,
+
+    /// The triangle indices.
+    pub indices: Vec<u32>,
+}
+
+impl BakeMesh {
+    /// Capture the geometry of a mesh placed with the given transform. Returns `None` if the
+    /// mesh does not have lightmap texture coordinates.
+    #[must_use]
+    pub fn new(entity: Entity, mesh: &Mesh, transform: &GlobalTransform) -> Option<Self> {
+        if mesh.lightmap_uvs.len() != mesh.positions.len() || mesh.positions.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            entity,
+            positions: mesh
+                .positions
+                .iter()
+                .map(|&position| transform.transform_point(position))
+                .collect(),
+            lightmap_uvs: mesh.lightmap_uvs.clone(),
+            indices: mesh.indices.clone(),
+        })
+    }
+
+    /// Returns an iterator over the world positions and lightmap texture coordinates of each
+    /// triangle.
+    fn triangles(&self) -> impl Iterator<Item = ([Vec3; 3], [Vec2; 3])> + '_ {
+        self.indices.chunks_exact(3).filter_map(|triangle| {
+            let vertex = |i: usize| {
+                let index = triangle[i] as usize;
+                Some((*self.positions.get(index)?, *self.lightmap_uvs.get(index)?))
+            };
+            let (a, b, c) = (vertex(0)?, vertex(1)?, vertex(2)?);
+            Some(([a.0, b.0, c.0], [a.1, b.1, c.1]))
+        })
+    }
+
+    /// Returns the total area of the triangles, in square world units.
+    fn area(&self) -> f32 {
+        self.triangles()
+            .map(|([a, b, c], _)| (b - a).cross(c - a).length() * 0.5)
+            .sum()
+    }
+
+    /// Returns the bounding box of the mesh.
+    fn aabb(&self) -> Aabb {
+        let (min, max) = self.positions.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), &p| (min.min(p), max.max(p)),
+        );
+        Aabb::new(min, max)
+    }
+}
+
+/// The result of a lightmap baking.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightmapBake {
+    /// The baked atlas.
+    pub atlas: BakedLightmap,
+
+    /// The area of the atlas covered by each mesh. Meshes that did not fit in the atlas are
+    /// missing.
+    pub lightmaps: Vec<(Entity, Lightmap)>,
+}
+
+/// Bake the lightmaps of the given meshes. This can take a long time for large scenes, and is
+/// usually run in a background task or offline.
+#[must_use]
+pub fn bake(meshes: &[BakeMesh], settings: &LightmapBakeSettings) -> LightmapBake {
+    let size = settings.atlas_size.max(1);
+    let mut atlas = BakedLightmap {
+        size: UVec2::splat(size),
+        texels: vec![Vec4::ZERO; (size * size) as usize],
+    };
+
+    let placements = pack(meshes, settings);
+    let scene = Scene::new(meshes);
+    for (mesh, &rect) in meshes.iter().zip(&placements) {
+        let Some(rect) = rect else {
+            warn!(
+                "Lightmap of entity {} does not fit in the atlas",
+                mesh.entity
+            );
+            continue;
+        };
+
+        for (positions, uvs) in mesh.triangles() {
+            bake_triangle(&scene, settings, &mut atlas, rect, positions, uvs);
+        }
+    }
+
+    dilate(&mut atlas);
+
+    let lightmaps = meshes
+        .iter()
+        .zip(&placements)
+        .filter_map(|(mesh, rect)| {
+            let rect = (*rect)?;
+            let lightmap = Lightmap {
+                scale: rect.size().as_vec2() / size as f32,
+                offset: rect.min.as_vec2() / size as f32,
+            };
+            Some((mesh.entity, lightmap))
+        })
+        .collect();
+
+    LightmapBake { atlas, lightmaps }
+}
+
+/// Pack the charts of the meshes into the atlas with a shelf packer, sized according to their
+/// surface area. The texel density is halved until every chart fits.
+fn pack(meshes: &[BakeMesh], settings: &LightmapBakeSettings) -> Vec<Option<URect>> {
+    let size = settings.atlas_size.max(1);
+    let areas = meshes.iter().map(BakeMesh::area).collect::<Vec<_>>();
+    let mut density = settings.texels_per_unit;
+
+    loop {
+        let sides = areas
+            .iter()
+            .map(|area| ((area.sqrt() * density).ceil() as u32).clamp(4, size))
+            .collect::<Vec<_>>();
+
+        let mut order = (0..meshes.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| std::cmp::Reverse(sides[index]));
+
+        let mut rects = vec![None; meshes.len()];
+        let mut cursor = UVec2::ZERO;
+        let mut shelf = 0;
+        let mut fits = true;
+        for index in order {
+            let side = sides[index];
+            if cursor.x + side > size {
+                cursor = UVec2::new(0, cursor.y + shelf);
+                shelf = 0;
+            }
+            if cursor.y + side > size {
+                fits = false;
+                break;
+            }
+
+            rects[index] = Some(URect::from_corners(cursor, cursor + side));
+            cursor.x += side;
+            shelf = shelf.max(side);
+        }
+
+        if fits || density < f32::EPSILON {
+            return rects;
+        }
+        density *= 0.5;
+    }
+}
+
+/// Compute the irradiance of the texels of the atlas covered by a triangle.
+fn bake_triangle(
+    scene: &Scene,
+    settings: &LightmapBakeSettings,
+    atlas: &mut BakedLightmap,
+    rect: URect,
+    positions: [Vec3; 3],
+    uvs: [Vec2; 3],
+) {
+    let normal = (positions[1] - positions[0])
+        .cross(positions[2] - positions[0])
+        .normalize_or_zero();
+    if normal == Vec3::ZERO {
+        return;
+    }
+
+    // Position of the vertices in the atlas, in texels.
+    let texels = uvs.map(|uv| rect.min.as_vec2() + uv * rect.size().as_vec2());
+    let min = texels[0].min(texels[1]).min(texels[2]).floor().as_uvec2();
+    let max = texels[0].max(texels[1]).max(texels[2]).ceil().as_uvec2();
+    let min = min.max(rect.min);
+    let max = max.min(rect.max);
+
+    let area = edge(texels[0], texels[1], texels[2]);
+    if area.abs() < f32::EPSILON {
+        return;
+    }
+
+    // Texels whose center is slightly outside the triangle are still baked, so the texels
+    // on the edges are covered for bilinear filtering.
+    let tolerance = -0.5 / area.abs().sqrt();
+    for y in min.y..max.y {
+        for x in min.x..max.x {
+            let center = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+            let weights = Vec3::new(
+                edge(texels[1], texels[2], center),
+                edge(texels[2], texels[0], center),
+                edge(texels[0], texels[1], center),
+            ) / area;
+            if weights.min_element() < tolerance {
+                continue;
+            }
+
+            let weights = weights.max(Vec3::ZERO) / weights.max(Vec3::ZERO).element_sum();
+            let position =
+                positions[0] * weights.x + positions[1] * weights.y + positions[2] * weights.z;
+            let irradiance = scene.irradiance(settings, position + normal * RAY_BIAS, normal);
+            atlas.texels[(y * atlas.size.x + x) as usize] = irradiance.extend(1.0);
+        }
+    }
+}
+
+/// Returns twice the signed area of the triangle `(a, b, c)`.
+fn edge(a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    (b - a).perp_dot(c - a)
+}
+
+/// Fill the unused texels next to covered texels with the average of their covered neighbours,
+/// so bilinear filtering at the edges of the charts does not blend with black.
+fn dilate(atlas: &mut BakedLightmap) {
+    let size = atlas.size.as_ivec2();
+    let source = atlas.texels.clone();
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let index = (y * size.x + x) as usize;
+            if source[index].w > 0.0 {
+                continue;
+            }
+
+            let mut sum = Vec3::ZERO;
+            let mut count = 0.0;
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= size.x || ny >= size.y {
+                    continue;
+                }
+                let neighbour = source[(ny * size.x + nx) as usize];
+                if neighbour.w > 0.0 {
+                    sum += neighbour.truncate();
+                    count += 1.0;
+                }
+            }
+
+            if count > 0.0 {
+                atlas.texels[index] = (sum / count).extend(0.0);
+            }
+        }
+    }
+}
+
+/// The static geometry rays are traced against while baking.
+struct Scene<'a> {
+    meshes: &'a [BakeMesh],
+    bvh: Bvh,
+}
+
+impl<'a> Scene<'a> {
+    /// Build the hierarchy of the meshes, indexed by their position in the slice.
+    fn new(meshes: &'a [BakeMesh]) -> Self {
+        let mut bvh = Bvh::new();
+        for (index, mesh) in meshes.iter().enumerate() {
+            bvh.insert(Entity::from_raw(index as u32), mesh.aabb());
+        }
+        bvh.rebuild();
+        Self { meshes, bvh }
+    }
+
+    /// Returns whether a ray hits the geometry within the given distance.
+    fn occluded(&self, origin: Vec3, direction: Vec3, max_distance: f32) -> bool {
+        let Ok(direction) = Dir3::new(direction) else {
+            return false;
+        };
+
+        let ray = Ray3d::new(origin, direction);
+        self.bvh
+            .query_ray(ray, max_distance)
+            .into_iter()
+            .any(|(index, _)| {
+                self.meshes[index.index() as usize]
+                    .triangles()
+                    .filter_map(|(triangle, _)| ray_triangle(origin, *direction, triangle))
+                    .any(|distance| distance <= max_distance)
+            })
+    }
+
+    /// Returns the irradiance received by a point with the given normal.
+    fn irradiance(&self, settings: &LightmapBakeSettings, position: Vec3, normal: Vec3) -> Vec3 {
+        let to_sun = -settings.sun_direction.normalize_or_zero();
+        let facing = normal.dot(to_sun);
+        let direct = if facing > 0.0 && !self.occluded(position, to_sun, f32::MAX) {
+            settings.sun_color * facing
+        } else {
+            Vec3::ZERO
+        };
+
+        // Cosine-weighted samples of the hemisphere, using a Hammersley sequence so the
+        // result is deterministic.
+        let (tangent, bitangent) = normal.any_orthonormal_pair();
+        let samples = settings.sky_samples.max(1);
+        let visible = (0..samples)
+            .filter(|&i| {
+                let u = i as f32 / samples as f32;
+                let v = i.reverse_bits() as f32 * 2.328_306_4e-10;
+                let (radius, angle) = (u.sqrt(), v * TAU);
+                let direction = tangent * radius * angle.cos()
+                    + bitangent * radius * angle.sin()
+                    + normal * (1.0 - u).sqrt();
+                !self.occluded(position, direction, settings.occlusion_distance)
+            })
+            .count();
+
+        direct + settings.sky_color * (visible as f32 / samples as f32)
+    }
+}
+
+/// The lightmap baking running in the background.
+#[derive(Resource)]
+pub struct LightmapBakeTask(Task<LightmapBake>);
+
+/// Start baking the lightmaps in a background task when [`BakeLightmaps`] is sent.
+pub fn start_lightmap_bake(
+    mut commands: Commands,
+    mut requests: EventReader<BakeLightmaps>,
+    settings: Res<LightmapBakeSettings>,
+    running: Option<Res<LightmapBakeTask>>,
+    meshes: Query<(Entity, &Mesh, &GlobalTransform), With<LightmapStatic>>,
+) {
+    if requests.read().count() == 0 || running.is_some() {
+        return;
+    }
+
+    let meshes = meshes
+        .iter()
+        .filter_map(|(entity, mesh, transform)| {
+            let captured = BakeMesh::new(entity, mesh, transform);
+            if captured.is_none() {
+                warn!("Static mesh {entity} has no lightmap texture coordinates");
+            }
+            captured
+        })
+        .collect::<Vec<_>>();
+
+    let settings = settings.clone();
+    let task = AsyncComputeTaskPool::get().spawn(async move { bake(&meshes, &settings) });
+    commands.insert_resource(LightmapBakeTask(task));
+}
+
+/// Store the result of the background baking once it is done: the atlas in the
+/// [`BakedLightmap`] resource, and the [`Lightmap`] component of each baked mesh.
+pub fn finish_lightmap_bake(mut commands: Commands, task: Option<ResMut<LightmapBakeTask>>) {
+    let Some(mut task) = task else {
+        return;
+    };
+
+    let Some(result) = block_on(future::poll_once(&mut task.0)) else {
+        return;
+    };
+
+    for (entity, lightmap) in result.lightmaps {
+        if let Some(mut entity) = commands.get_entity(entity) {
+            entity.insert(lightmap);
+        }
+    }
+    commands.insert_resource(result.atlas);
+    commands.remove_resource::<LightmapBakeTask>();
+}
@@ -0,0 +1,31 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The surface parameters of a mesh, following the metallic-roughness PBR model.
+#[derive(Debug, Clone, Copy, PartialEq, Component, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Material {
+    /// The albedo of dielectric surfaces, or the specular color of metallic surfaces. The alpha
+    /// channel is the opacity of the surface.
+    pub base_color: LinearRgba,
+
+    /// How metallic the surface is, between 0.0 (dielectric) and 1.0 (metal).
+    pub metallic: f32,
+
+    /// The perceptual roughness of the surface, between 0.0 (mirror) and 1.0 (fully rough).
+    pub roughness: f32,
+
+    /// The light emitted by the surface, added after lighting.
+    pub emissive: LinearRgba,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            base_color: LinearRgba::WHITE,
+            metallic: 0.0,
+            roughness: 0.5,
+            emissive: LinearRgba::BLACK,
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use crate::{
+    bounds::{Aabb, BoundingSphere, BoundsMargin},
+    vertex::MeshVertex,
+    Render,
+};
+use amethyst_vulkan::buffer::{
+    Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo, BufferMemoryLocation,
+    BufferTransfert, BufferUsage, BufferUsageInfo,
+};
+use bevy::prelude::*;
+use std::sync::Arc;
+
+/// A triangle mesh. The vertex data is kept on the CPU so it can be used for ray casting or to
+/// compute bounding volumes, in addition to being uploaded to the GPU for rendering.
+#[derive(Debug, Clone, Default, PartialEq, Component)]
+pub struct Mesh {
+    /// The position of each vertex.
+    pub positions: Vec<Vec3>,
+
+    /// The normal of each vertex. It is either empty or has the same length as `positions`.
+    pub normals: Vec<Vec3>,
+
+    /// The texture coordinates of each vertex. It is either empty or has the same length as
+    /// `positions`.
+    pub uvs: Vec<Vec2>,
+
+    /// The lightmap texture coordinates of each vertex, in the chart of the mesh. It is either
+    /// empty or has the same length as `positions`. Each triangle must cover a distinct area
+    /// of the chart, see [`Mesh::with_lightmap_uvs`].
+    pub lightmap_uvs: Vec<Vec2>,
+
+    /// The indices of the vertices of each triangle, three per triangle, in counter-clockwise
+    /// order when looking at the front face.
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    /// Create a new mesh from its vertex positions and triangle indices.
+    #[must_use]
+    pub fn new(positions: Vec<Vec3>, indices: Vec<u32>) -> Self {
+        Self {
+            positions,
+            indices,
+            ..Default::default()
+        }
+    }
+
+    /// Create an axis-aligned cube centered on the origin, with the given half size. Each face
+    /// has its own vertices, so the normals are flat.
+    #[must_use]
+    pub fn cube(half_size: f32) -> Self {
+        let faces = [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ];
+        let mut mesh = Mesh::default();
+
+        for normal in faces {
+            // Two axes spanning the face, such that `u x v = normal`.
+            let u = normal.any_orthonormal_vector();
+            let v = normal.cross(u);
+            let base = mesh.positions.len() as u32;
+
+            for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+                mesh.positions.push((normal + u * x + v * y) * half_size);
+                mesh.normals.push(normal);
+                mesh.uvs.push(Vec2::new((x + 1.0) * 0.5, (1.0 - y) * 0.5));
+            }
+            mesh.indices
+                .extend([base, base + 1, base + 2, base, base + 2, base + 3]);
+        }
+
+        mesh
+    }
+
+    /// Generate lightmap texture coordinates, placing each triangle in its own cell of a square
+    /// grid covering the chart of the mesh. Cells are separated by the given padding, as a
+    /// fraction of the cell size, so that bilinear filtering does not bleed between triangles.
+    ///
+    /// Triangles cannot share vertices in such a layout, so the mesh is converted to a
+    /// non-indexed mesh whose indices are sequential. This unwrap is simple but wastes some
+    /// space: meshes authored with lightmap coordinates should keep them instead.
+    #[must_use]
+    pub fn with_lightmap_uvs(self, padding: f32) -> Self {
+        let count = self.triangle_count();
+        let grid = (count as f32).sqrt().ceil().max(1.0) as usize;
+        let cell = 1.0 / grid as f32;
+        let margin = cell * padding.clamp(0.0, 0.45);
+
+        let mut mesh = Mesh::default();
+        for (index, triangle) in self.indices.chunks_exact(3).enumerate() {
+            let origin = Vec2::new((index % grid) as f32, (index / grid) as f32) * cell;
+            let size = cell - 2.0 * margin;
+            let corners = [Vec2::ZERO, Vec2::new(size, 0.0), Vec2::new(0.0, size)];
+
+            for (&vertex, corner) in triangle.iter().zip(corners) {
+                let vertex = vertex as usize;
+                mesh.indices.push(mesh.positions.len() as u32);
+                mesh.positions.push(self.positions[vertex]);
+                mesh.lightmap_uvs.push(origin + margin + corner);
+                if let Some(&normal) = self.normals.get(vertex) {
+                    mesh.normals.push(normal);
+                }
+                if let Some(&uv) = self.uvs.get(vertex) {
+                    mesh.uvs.push(uv);
+                }
+            }
+        }
+
+        mesh
+    }
+
+    /// Returns the number of triangles of the mesh.
+    #[must_use]
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Returns an iterator over the vertex positions of each triangle. Triangles referencing
+    /// vertices out of bounds are skipped.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        self.indices.chunks_exact(3).filter_map(|triangle| {
+            Some([
+                *self.positions.get(triangle[0] as usize)?,
+                *self.positions.get(triangle[1] as usize)?,
+                *self.positions.get(triangle[2] as usize)?,
+            ])
+        })
+    }
+
+    /// Compute the axis-aligned bounding box of the mesh vertices, or `None` if the mesh does
+    /// not have any vertex.
+    #[must_use]
+    pub fn compute_aabb(&self) -> Option<Aabb> {
+        let first = *self.positions.first()?;
+        let (min, max) = self
+            .positions
+            .iter()
+            .fold((first, first), |(min, max), &p| (min.min(p), max.max(p)));
+        Some(Aabb::new(min, max))
+    }
+}
+
+/// Compute the [`Aabb`] and [`BoundingSphere`] of the entities whose mesh or [`BoundsMargin`]
+/// was added or modified, so they stay in sync with meshes animated on the CPU. The components
+/// are removed if the mesh does not have any vertex.
+#[allow(clippy::type_complexity)]
+pub fn update_bounds(
+    mut commands: Commands,
+    meshes: Query<
+        (Entity, &Mesh, Option<&BoundsMargin>),
+        Or<(Changed<Mesh>, Changed<BoundsMargin>)>,
+    >,
+) {
+    for (entity, mesh, margin) in &meshes {
+        let margin = margin.map_or(0.0, |margin| margin.0);
+        match mesh.compute_aabb() {
+            Some(aabb) => {
+                let aabb = aabb.inflated(margin);
+                commands
+                    .entity(entity)
+                    .insert((aabb, BoundingSphere::from_aabb(&aabb)));
+            }
+            None => {
+                commands.entity(entity).remove::<(Aabb, BoundingSphere)>();
+            }
+        }
+    }
+}
+
+/// The vertex and index buffers of a [`Mesh`], uploaded in device local memory.
+#[derive(Debug)]
+pub struct GpuMesh {
+    /// The interleaved vertices of the mesh.
+    vertices: Buffer,
+
+    /// The indices of the mesh.
+    indices: Buffer,
+
+    /// The number of indices of the mesh.
+    index_count: u32,
+}
+
+impl GpuMesh {
+    /// Upload the given mesh to the GPU, or returns `None` if it does not have any triangle or
+    /// if one of its indices is out of bounds. Missing normals and texture coordinates are
+    /// filled with zeros.
+    #[must_use]
+    pub fn new(allocator: &Arc<BufferAllocator>, mesh: &Mesh) -> Option<Self> {
+        // Trailing indices that do not form a whole triangle are ignored.
+        let indices = &mesh.indices[..mesh.triangle_count() * 3];
+        let vertex_count = mesh.positions.len();
+        if indices.is_empty() || indices.iter().any(|&i| i as usize >= vertex_count) {
+            return None;
+        }
+
+        let vertices = mesh
+            .positions
+            .iter()
+            .enumerate()
+            .map(|(index, position)| {
+                let normal = mesh.normals.get(index).copied().unwrap_or_default();
+                let uv = mesh.uvs.get(index).copied().unwrap_or_default();
+                MeshVertex {
+                    position: position.to_array(),
+                    normal: normal.to_array(),
+                    uv: uv.to_array(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        Some(Self {
+            vertices: upload(allocator, BufferUsage::Vertices, &vertices),
+            indices: upload(allocator, BufferUsage::Indices, indices),
+            index_count: indices.len() as u32,
+        })
+    }
+
+    /// Returns the vertex buffer of the mesh.
+    #[must_use]
+    pub const fn vertices(&self) -> &Buffer {
+        &self.vertices
+    }
+
+    /// Returns the index buffer of the mesh, containing 32-bit indices.
+    #[must_use]
+    pub const fn indices(&self) -> &Buffer {
+        &self.indices
+    }
+
+    /// Returns the number of indices of the mesh.
+    #[must_use]
+    pub const fn index_count(&self) -> u32 {
+        self.index_count
+    }
+}
+
+/// Upload the given data into a new buffer in device local memory.
+fn upload<T>(allocator: &Arc<BufferAllocator>, usage: BufferUsage, data: &[T]) -> Buffer {
+    Buffer::new(
+        allocator.clone(),
+        BufferCreateInfo {
+            usage: BufferUsageInfo {
+                location: BufferMemoryLocation::PreferDeviceLocal,
+                transfer: BufferTransfert::Destination,
+                access: BufferAccess::None,
+                usage,
+                ..Default::default()
+            },
+            data: BufferDataInfo::Slice(data),
+            ..Default::default()
+        },
+    )
+}
+
+/// Upload the meshes that were added or modified since the last frame, and release the GPU
+/// meshes of the entities whose [`Mesh`] was removed. The buffers of a released mesh are only
+/// destroyed once the frames in flight that may still draw it are complete.
+pub fn upload_meshes(
+    mut render: ResMut<Render>,
+    meshes: Query<(Entity, &Mesh), Changed<Mesh>>,
+    mut removed: RemovedComponents<Mesh>,
+) {
+    for entity in removed.read() {
+        render.meshes.remove(&entity);
+    }
+
+    for (entity, mesh) in &meshes {
+        match GpuMesh::new(&render.buffer_allocator, mesh) {
+            Some(gpu) => render.meshes.insert(entity, gpu),
+            None => render.meshes.remove(&entity),
+        };
+    }
+}
@@ -0,0 +1,385 @@
+//! Picking utilities, finding what is under the cursor.
+use amethyst_vulkan::{
+    buffer::{
+        Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo, MemoryPriority,
+    },
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    device::VulkanDevice,
+    image::{Image, ImageCreateInfo},
+    MAX_FRAMES_IN_FLIGHT,
+};
+use bevy::prelude::*;
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// Reads back the depth of a single pixel of a depth buffer after rendering, to reconstruct the
+/// world position under the cursor. This allows picking objects or the terrain without any
+/// physics or CPU ray casting, at the cost of a small copy after each frame.
+///
+/// The depth buffer must use the `vk::Format::D32_SFLOAT` format and have the `TRANSFER_SRC`
+/// usage.
+#[derive(Debug)]
+pub struct DepthReadback {
+    /// A host visible buffer receiving the depth of the pixel.
+    buffer: Buffer,
+
+    /// The pixel whose depth was copied by the last recorded readback, if any.
+    pixel: Option<UVec2>,
+}
+
+impl DepthReadback {
+    /// Create a new depth readback.
+    #[must_use]
+    pub fn new(allocator: Arc<BufferAllocator>) -> Self {
+        let buffer = Buffer::new(
+            allocator,
+            BufferCreateInfo::<f32> {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Destination,
+                    access: BufferAccess::Random,
+                    usage: BufferUsage::None,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Uninitialized(std::mem::size_of::<f32>()),
+                ..Default::default()
+            },
+        );
+
+        Self {
+            buffer,
+            pixel: None,
+        }
+    }
+
+    /// Record the copy of the depth of the given pixel to the host, after the rendering is
+    /// done. The depth buffer must be in the given layout, and is left in the same layout.
+    /// Pixels outside of the depth buffer are ignored.
+    ///
+    /// # Panics
+    /// Panics if the depth buffer does not use the `vk::Format::D32_SFLOAT` format.
+    #[must_use]
+    pub fn record<'pool>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording>,
+        depth: &Image,
+        layout: vk::ImageLayout,
+        pixel: UVec2,
+    ) -> CommandBuffer<'pool, Recording> {
+        assert_eq!(
+            depth.format(),
+            vk::Format::D32_SFLOAT,
+            "Depth readback requires a D32_SFLOAT depth buffer"
+        );
+
+        let extent = depth.extent();
+        if pixel.x >= extent.width || pixel.y >= extent.height {
+            self.pixel = None;
+            return command;
+        }
+
+        let barrier = |old, new, src_access, dst_access| {
+            vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old)
+                .new_layout(new)
+                .subresource_range(depth.subresource_range())
+                .image(depth.inner())
+                .build()
+        };
+
+        self.pixel = Some(pixel);
+        command
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+                dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                images_barriers: vec![barrier(
+                    layout,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                    vk::AccessFlags::TRANSFER_READ,
+                )],
+                ..Default::default()
+            })
+            .copy_image_to_buffer(
+                depth,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::Rect2D {
+                    offset: vk::Offset2D {
+                        x: pixel.x as i32,
+                        y: pixel.y as i32,
+                    },
+                    extent: vk::Extent2D {
+                        width: 1,
+                        height: 1,
+                    },
+                },
+                &self.buffer,
+            )
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                    | vk::PipelineStageFlags::HOST,
+                images_barriers: vec![barrier(
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    layout,
+                    vk::AccessFlags::TRANSFER_READ,
+                    vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+                )],
+                ..Default::default()
+            })
+    }
+
+    /// Returns the pixel and its depth copied by the last recorded readback, or `None` if no
+    /// readback was recorded.
+    ///
+    /// # Safety
+    /// The command buffer in which the readback was recorded must have completed its execution.
+    #[must_use]
+    pub unsafe fn read(&self) -> Option<(UVec2, f32)> {
+        let pixel = self.pixel?;
+        let depth = self.buffer.read::<f32>().first().copied()?;
+        Some((pixel, depth))
+    }
+}
+
+/// Reconstruct the world position of a pixel from its depth, using the inverse of the
+/// view-projection matrix used to render it. The projection must follow the Vulkan conventions
+/// used by the cameras, with the Y axis pointing down in clip space and a depth range of
+/// `[0, 1]`. Returns `None` if the pixel is on the far plane, where nothing was drawn.
+#[must_use]
+pub fn unproject(
+    inverse_view_projection: Mat4,
+    viewport: UVec2,
+    pixel: UVec2,
+    depth: f32,
+) -> Option<Vec3> {
+    if depth >= 1.0 {
+        return None;
+    }
+
+    let ndc = (pixel.as_vec2() + 0.5) / viewport.as_vec2() * 2.0 - 1.0;
+    let world = inverse_view_projection * Vec4::new(ndc.x, ndc.y, depth, 1.0);
+    (world.w != 0.0).then(|| world.truncate() / world.w)
+}
+
+/// The format of the entity ID render target. Each pixel stores the bits of the entity drawn
+/// on it (see [`Entity::to_bits`]), split into its low and high 32 bits. A value of zero means
+/// that no entity was drawn, since the generation of an entity is never zero.
+pub const ENTITY_ID_FORMAT: vk::Format = vk::Format::R32G32_UINT;
+
+/// An optional render target storing the entity drawn on each pixel, for pixel-accurate picking
+/// in editors. The pipelines drawing entities must write to it as an additional color
+/// attachment with the [`ENTITY_ID_FORMAT`] format:
+///
+/// ```glsl
+/// layout(location = 1) out uvec2 entity;
+/// ```
+///
+/// The readback is asynchronous: each frame in flight copies the requested pixel to its own
+/// host buffer, which is read once the frame has completed.
+#[derive(Debug)]
+pub struct EntityIdTarget {
+    /// The render target.
+    image: Image,
+
+    /// A host visible buffer per frame in flight, receiving the entity of the requested pixel,
+    /// and the pixel copied into it.
+    readbacks: Vec<(Buffer, Option<UVec2>)>,
+}
+
+impl EntityIdTarget {
+    /// Create a new entity ID render target with the given size.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        allocator: Arc<BufferAllocator>,
+        extent: vk::Extent2D,
+    ) -> Self {
+        let image = Image::new(
+            device,
+            allocator.clone(),
+            ImageCreateInfo {
+                extent,
+                format: ENTITY_ID_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::TRANSFER_SRC,
+                priority: MemoryPriority::High,
+                ..Default::default()
+            },
+        );
+
+        let readbacks = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                let buffer = Buffer::new(
+                    allocator.clone(),
+                    BufferCreateInfo::<u32> {
+                        usage: BufferUsageInfo {
+                            location: BufferMemoryLocation::PreferHostVisible,
+                            transfer: BufferTransfert::Destination,
+                            access: BufferAccess::Random,
+                            usage: BufferUsage::None,
+                            ..Default::default()
+                        },
+                        data: BufferDataInfo::Uninitialized(2 * std::mem::size_of::<u32>()),
+                        ..Default::default()
+                    },
+                );
+                (buffer, None)
+            })
+            .collect();
+
+        Self { image, readbacks }
+    }
+
+    /// Returns the render target.
+    #[must_use]
+    pub const fn image(&self) -> &Image {
+        &self.image
+    }
+
+    /// Returns the attachment info used to render into the target, cleared to zero (no entity)
+    /// at the beginning of the rendering.
+    #[must_use]
+    pub fn attachment(&self) -> vk::RenderingAttachmentInfo {
+        vk::RenderingAttachmentInfo::builder()
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .clear_value(vk::ClearValue {
+                color: vk::ClearColorValue { uint32: [0; 4] },
+            })
+            .image_view(self.image.view())
+            .build()
+    }
+
+    /// Record the copy of the entity drawn on the given pixel to the host buffer of the given
+    /// frame in flight, after the rendering is done. The target must be in the
+    /// `vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL` layout, and is left in this layout.
+    #[must_use]
+    pub fn record_readback<'pool>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording>,
+        frame: usize,
+        pixel: UVec2,
+    ) -> CommandBuffer<'pool, Recording> {
+        let extent = self.image.extent();
+        let (buffer, requested) = &mut self.readbacks[frame % MAX_FRAMES_IN_FLIGHT];
+        if pixel.x >= extent.width || pixel.y >= extent.height {
+            *requested = None;
+            return command;
+        }
+
+        let image = &self.image;
+        let barrier = |old, new, src_access, dst_access| {
+            vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old)
+                .new_layout(new)
+                .subresource_range(image.subresource_range())
+                .image(image.inner())
+                .build()
+        };
+
+        *requested = Some(pixel);
+        command
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+                dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                images_barriers: vec![barrier(
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::TRANSFER_READ,
+                )],
+                ..Default::default()
+            })
+            .copy_image_to_buffer(
+                image,
+                vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                vk::Rect2D {
+                    offset: vk::Offset2D {
+                        x: pixel.x as i32,
+                        y: pixel.y as i32,
+                    },
+                    extent: vk::Extent2D {
+                        width: 1,
+                        height: 1,
+                    },
+                },
+                buffer,
+            )
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                    | vk::PipelineStageFlags::HOST,
+                images_barriers: vec![barrier(
+                    vk::ImageLayout::TRANSFER_SRC_OPTIMAL,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    vk::AccessFlags::TRANSFER_READ,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                )],
+                ..Default::default()
+            })
+    }
+
+    /// Returns the pixel copied by the readback of the given frame in flight and the entity
+    /// drawn on it, or `None` if no readback was recorded for this frame.
+    ///
+    /// # Safety
+    /// The command buffer in which the readback of this frame was recorded must have completed
+    /// its execution.
+    #[must_use]
+    pub unsafe fn resolve(&self, frame: usize) -> Option<(UVec2, Option<Entity>)> {
+        let (buffer, requested) = &self.readbacks[frame % MAX_FRAMES_IN_FLIGHT];
+        let pixel = (*requested)?;
+        let bits = buffer.read::<u32>();
+        let bits = u64::from(bits[0]) | (u64::from(bits[1]) << 32);
+        Some((pixel, Entity::try_from_bits(bits).ok()))
+    }
+}
+
+/// Asynchronous entity picking with the [`EntityIdTarget`]. A pick requests a readback of the
+/// given pixel, whose result is available one or more frames later: systems usually call
+/// [`EntityPicking::pick`] every frame while the cursor moves, and get the entity under the
+/// cursor as soon as the readback of its pixel has completed.
+///
+/// This is a building block: the built-in pipelines do not draw entity IDs, so the resource is
+/// not added by the render plugin. A custom render path drawing into an [`EntityIdTarget`]
+/// must record the readback of [`EntityPicking::requested`] with
+/// [`EntityIdTarget::record_readback`], and give the result of [`EntityIdTarget::resolve`] to
+/// [`EntityPicking::complete`] once the frame has completed.
+#[derive(Debug, Default, Resource)]
+pub struct EntityPicking {
+    /// The pixel whose entity must be read back by the next frame.
+    requested: Option<UVec2>,
+
+    /// The last pixel read back and the entity drawn on it.
+    last: Option<(UVec2, Option<Entity>)>,
+}
+
+impl EntityPicking {
+    /// Request the entity drawn on the given pixel, and returns it if a readback of this pixel
+    /// has already completed.
+    pub fn pick(&mut self, x: u32, y: u32) -> Option<Entity> {
+        let pixel = UVec2::new(x, y);
+        self.requested = Some(pixel);
+        match self.last {
+            Some((last, entity)) if last == pixel => entity,
+            _ => None,
+        }
+    }
+
+    /// Returns the pixel that must be read back by the next frame, if any.
+    #[must_use]
+    pub const fn requested(&self) -> Option<UVec2> {
+        self.requested
+    }
+
+    /// Store the result of a completed readback, as returned by [`EntityIdTarget::resolve`].
+    pub fn complete(&mut self, pixel: UVec2, entity: Option<Entity>) {
+        self.last = Some((pixel, entity));
+    }
+}
@@ -0,0 +1,501 @@
+//! Reflection probes, capturing the scene around a point into a cube map used for local
+//! specular reflections. Each probe is rendered on demand or incrementally, then prefiltered
+//! for increasing roughness into its mip levels. Objects blend the two nearest probes, weighted
+//! by their distance to the probes, and fall back to the environment outside of their radius.
+//!
+//! The lit shaders sample the probes with the functions of [`PROBE_SAMPLING_GLSL`].
+//!
+//! The built-in render path does not render the probes yet, so the render plugin does not add
+//! the [`ProbeRenderQueue`] resource nor the [`schedule_probes`] and [`blend_probes`] systems.
+//! A render path consuming the queue must add them after the transform propagation.
+use crate::mesh::Mesh;
+use amethyst_vulkan::{
+    buffer::{BufferAllocator, MemoryPriority},
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    image::{Image, ImageCreateInfo, ImageView, Sampler},
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use bevy::{prelude::*, utils::HashMap};
+use std::{f32::consts::FRAC_PI_2, sync::Arc};
+use vulkanalia::prelude::v1_3::*;
+
+/// The GLSL functions sampling the reflection probes, to be included in lit fragment shaders.
+pub const PROBE_SAMPLING_GLSL: &str = include_str!("../shaders/reflection_probes.glsl");
+
+/// The format of the probe cube maps.
+pub const PROBE_FORMAT: vk::Format = vk::Format::R16G16B16A16_SFLOAT;
+
+/// The number of mip levels of the prefiltered cube maps. The first level is a copy of the
+/// radiance, and the last one is prefiltered for a roughness of 1.0.
+pub const PROBE_MIP_LEVELS: u32 = 6;
+
+/// The number of GGX samples taken for each texel of the prefiltered cube maps.
+const PREFILTER_SAMPLES: u32 = 64;
+
+/// The size of the work groups of the prefilter shader, along each axis.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The number of descriptor sets used in turn by the prefilter passes.
+const DESCRIPTOR_SETS: usize = 6 * PROBE_MIP_LEVELS as usize;
+
+/// The look direction and up vector of each face of a cube map, in the layer order.
+const CUBE_FACES: [(Vec3, Vec3); 6] = [
+    (Vec3::X, Vec3::NEG_Y),
+    (Vec3::NEG_X, Vec3::NEG_Y),
+    (Vec3::Y, Vec3::Z),
+    (Vec3::NEG_Y, Vec3::NEG_Z),
+    (Vec3::Z, Vec3::NEG_Y),
+    (Vec3::NEG_Z, Vec3::NEG_Y),
+];
+
+/// When a reflection probe is rendered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ProbeUpdate {
+    /// The probe is rendered when it is added, moved or modified. Use
+    /// [`DetectChangesMut::set_changed`] on the component to render it again.
+    #[default]
+    OnDemand,
+
+    /// The six faces of the probe are rendered every frame.
+    EveryFrame,
+
+    /// One face of the probe is rendered each frame, and the probe is prefiltered once every
+    /// six frames. This spreads the cost of dynamic probes over several frames.
+    Incremental,
+}
+
+/// A reflection probe, capturing the scene around the position of the entity.
+#[derive(Debug, Clone, Copy, PartialEq, Component)]
+#[require(Transform)]
+pub struct ReflectionProbe {
+    /// The size of each face of the cube map, in pixels.
+    pub resolution: u32,
+
+    /// The radius of the sphere influenced by the probe. The weight of the probe decreases
+    /// linearly from its center to this radius.
+    pub radius: f32,
+
+    /// When the probe is rendered.
+    pub update: ProbeUpdate,
+
+    /// The distance of the near clipping plane used to render the faces.
+    pub near: f32,
+
+    /// The distance of the far clipping plane used to render the faces.
+    pub far: f32,
+}
+
+impl Default for ReflectionProbe {
+    fn default() -> Self {
+        Self {
+            resolution: 128,
+            radius: 10.0,
+            update: ProbeUpdate::default(),
+            near: 0.1,
+            far: 1000.0,
+        }
+    }
+}
+
+impl ReflectionProbe {
+    /// Returns the view-projection matrix used to render the given face of the probe placed at
+    /// the given position.
+    ///
+    /// Unlike the cameras, the Y axis is not flipped in clip space so the faces follow the
+    /// cube map conventions. The winding order of the triangles is therefore reversed, and the
+    /// pipelines rendering the faces must use the opposite front face.
+    #[must_use]
+    pub fn face_view_projection(&self, position: Vec3, face: usize) -> Mat4 {
+        let (direction, up) = CUBE_FACES[face];
+        let projection = Mat4::perspective_rh(FRAC_PI_2, 1.0, self.near, self.far);
+        projection * Mat4::look_to_rh(position, direction, up)
+    }
+}
+
+/// A face of a reflection probe to render this frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbeFace {
+    /// The entity of the probe.
+    pub probe: Entity,
+
+    /// The index of the face, which is also the array layer of the cube map.
+    pub face: usize,
+
+    /// The view-projection matrix used to render the face.
+    pub view_projection: Mat4,
+
+    /// Whether the probe must be prefiltered once this face is rendered, because all of its
+    /// faces are up to date.
+    pub prefilter: bool,
+}
+
+/// The faces of the reflection probes to render this frame, filled by [`schedule_probes`].
+#[derive(Debug, Default, Resource)]
+pub struct ProbeRenderQueue {
+    /// The faces to render, in order.
+    pub faces: Vec<ProbeFace>,
+
+    /// The next face to render of each incremental probe.
+    next_face: HashMap<Entity, usize>,
+}
+
+/// Fill the [`ProbeRenderQueue`] with the faces of the probes that must be rendered this frame.
+pub fn schedule_probes(
+    mut queue: ResMut<ProbeRenderQueue>,
+    probes: Query<(Entity, Ref<ReflectionProbe>, Ref<GlobalTransform>)>,
+    mut removed: RemovedComponents<ReflectionProbe>,
+) {
+    for entity in removed.read() {
+        queue.next_face.remove(&entity);
+    }
+
+    queue.faces.clear();
+    for (entity, probe, transform) in &probes {
+        let position = transform.translation();
+        let changed = probe.is_changed() || transform.is_changed();
+        let face = |face: usize, prefilter: bool| ProbeFace {
+            probe: entity,
+            face,
+            view_projection: probe.face_view_projection(position, face),
+            prefilter,
+        };
+
+        match probe.update {
+            ProbeUpdate::OnDemand if changed => {
+                queue
+                    .faces
+                    .extend((0..6).map(|index| face(index, index == 5)));
+            }
+            ProbeUpdate::OnDemand => (),
+            ProbeUpdate::EveryFrame => {
+                queue
+                    .faces
+                    .extend((0..6).map(|index| face(index, index == 5)));
+            }
+            ProbeUpdate::Incremental => {
+                let next = queue.next_face.entry(entity).or_default();
+                let index = *next;
+                *next = (index + 1) % 6;
+                queue.faces.push(face(index, index == 5));
+            }
+        }
+    }
+}
+
+/// The probes blended into the specular lighting of an object, with their weight. The sum of
+/// the weights is at most 1.0, and the remaining weight is given to the environment.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Component)]
+pub struct ReflectionProbeBlend {
+    /// The two nearest probes influencing the object, the most influent first.
+    pub probes: [Option<(Entity, f32)>; 2],
+}
+
+/// Select the two nearest probes influencing each mesh, and store them with their weight in
+/// the [`ReflectionProbeBlend`] component of the mesh.
+pub fn blend_probes(
+    mut commands: Commands,
+    probes: Query<(Entity, &ReflectionProbe, &GlobalTransform)>,
+    meshes: Query<(Entity, &GlobalTransform, Option<&ReflectionProbeBlend>), With<Mesh>>,
+) {
+    for (entity, transform, current) in &meshes {
+        let position = transform.translation();
+        let mut nearest = [None::<(Entity, f32)>; 2];
+        for (probe, settings, probe_transform) in &probes {
+            let distance = probe_transform.translation().distance(position);
+            let weight = 1.0 - distance / settings.radius.max(f32::EPSILON);
+            if weight <= 0.0 {
+                continue;
+            }
+
+            if nearest[0].map_or(true, |(_, best)| weight > best) {
+                nearest = [Some((probe, weight)), nearest[0]];
+            } else if nearest[1].map_or(true, |(_, second)| weight > second) {
+                nearest[1] = Some((probe, weight));
+            }
+        }
+
+        // Normalize the weights when the probes overlap, so they never add more light than a
+        // single probe.
+        let total = nearest
+            .iter()
+            .flatten()
+            .map(|(_, weight)| weight)
+            .sum::<f32>();
+        if total > 1.0 {
+            for (_, weight) in nearest.iter_mut().flatten() {
+                *weight /= total;
+            }
+        }
+
+        let blend = ReflectionProbeBlend { probes: nearest };
+        if current != Some(&blend) {
+            commands.entity(entity).insert(blend);
+        }
+    }
+}
+
+/// The GPU images of a reflection probe: the radiance cube map the scene is rendered into, and
+/// the prefiltered cube map sampled by the lit shaders.
+///
+/// # Important
+/// The order of fields in the struct is important: the views must be destroyed before the
+/// images.
+#[derive(Debug)]
+pub struct ProbeTargets {
+    /// A view of each face of the radiance cube map.
+    face_views: Vec<ImageView>,
+
+    /// A view of each mip level of the prefiltered cube map, as an array of six images.
+    mip_views: Vec<ImageView>,
+
+    /// The radiance cube map, into which the faces are rendered.
+    radiance: Image,
+
+    /// The prefiltered cube map.
+    prefiltered: Image,
+}
+
+impl ProbeTargets {
+    /// Create the images of a probe whose faces have the given resolution.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        allocator: Arc<BufferAllocator>,
+        resolution: u32,
+    ) -> Self {
+        let extent = vk::Extent2D {
+            width: resolution,
+            height: resolution,
+        };
+
+        let radiance = Image::new(
+            device.clone(),
+            allocator.clone(),
+            ImageCreateInfo {
+                extent,
+                format: PROBE_FORMAT,
+                usage: vk::ImageUsageFlags::COLOR_ATTACHMENT | vk::ImageUsageFlags::SAMPLED,
+                array_layers: 6,
+                cube: true,
+                priority: MemoryPriority::High,
+                ..Default::default()
+            },
+        );
+
+        let prefiltered = Image::new(
+            device,
+            allocator,
+            ImageCreateInfo {
+                extent,
+                format: PROBE_FORMAT,
+                usage: vk::ImageUsageFlags::STORAGE | vk::ImageUsageFlags::SAMPLED,
+                mip_levels: PROBE_MIP_LEVELS.min(resolution.ilog2() + 1),
+                array_layers: 6,
+                cube: true,
+                ..Default::default()
+            },
+        );
+
+        let face_views = (0..6)
+            .map(|face| radiance.create_view(vk::ImageViewType::_2D, 0..1, face..face + 1))
+            .collect();
+        let mip_views = (0..prefiltered.mip_levels())
+            .map(|mip| prefiltered.create_view(vk::ImageViewType::_2D_ARRAY, mip..mip + 1, 0..6))
+            .collect();
+
+        Self {
+            face_views,
+            mip_views,
+            radiance,
+            prefiltered,
+        }
+    }
+
+    /// Returns the attachment info used to render the given face of the probe. The radiance
+    /// cube map must be in the `vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL` layout.
+    #[must_use]
+    pub fn face_attachment(&self, face: usize) -> vk::RenderingAttachmentInfo {
+        vk::RenderingAttachmentInfo::builder()
+            .image_layout(vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL)
+            .store_op(vk::AttachmentStoreOp::STORE)
+            .load_op(vk::AttachmentLoadOp::CLEAR)
+            .clear_value(vk::ClearValue {
+                color: vk::ClearColorValue {
+                    float32: [0.0, 0.0, 0.0, 1.0],
+                },
+            })
+            .image_view(self.face_views[face].inner())
+            .build()
+    }
+
+    /// Returns the radiance cube map.
+    #[must_use]
+    pub const fn radiance(&self) -> &Image {
+        &self.radiance
+    }
+
+    /// Returns the prefiltered cube map, sampled by the lit shaders.
+    #[must_use]
+    pub const fn prefiltered(&self) -> &Image {
+        &self.prefiltered
+    }
+}
+
+/// The compute pass prefiltering the reflection probes.
+#[derive(Debug)]
+pub struct ProbeFilter {
+    /// The descriptor sets used by the passes, in turn, with the radiance cube map at binding 0 and
+    /// the prefiltered mip level at binding 1.
+    sets: DescriptorSetRing,
+
+    /// The pipeline prefiltering a mip level.
+    pipeline: ComputePipeline,
+
+    /// The sampler used to read the radiance cube map.
+    sampler: Sampler,
+}
+
+impl ProbeFilter {
+    /// Compile the prefilter shader and create its pipeline.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[
+                DescriptorBinding {
+                    binding: 0,
+                    kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+                DescriptorBinding {
+                    binding: 1,
+                    kind: vk::DescriptorType::STORAGE_IMAGE,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                },
+            ],
+        ));
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            ComputePipelineCreateInfo {
+                shader: ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Compute,
+                    include_str!("../shaders/probe_prefilter.glsl").to_string(),
+                ),
+                descriptor_set_layouts: vec![layout.clone()],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: 8,
+                }],
+                name: Some("probe prefilter"),
+            },
+        );
+
+        Self {
+            sets: DescriptorSetRing::new(device.clone(), &layout, DESCRIPTOR_SETS),
+            sampler: Sampler::new(
+                device,
+                vk::Filter::LINEAR,
+                vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ),
+            pipeline,
+        }
+    }
+
+    /// Record the prefiltering of a probe whose six faces were rendered. The radiance cube map
+    /// must be in the `vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL` layout, and is left in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout like the prefiltered cube map.
+    #[must_use]
+    pub fn prefilter<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        targets: &ProbeTargets,
+    ) -> CommandBuffer<'pool, Recording> {
+        let barrier = |image: &Image, old, new, src_access, dst_access| {
+            vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old)
+                .new_layout(new)
+                .subresource_range(image.subresource_range())
+                .image(image.inner())
+                .build()
+        };
+
+        let mut command = command.pipeline_barrier(PipelineBarrierInfo {
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+            images_barriers: vec![
+                barrier(
+                    &targets.radiance,
+                    vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
+                    vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                    vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+                    vk::AccessFlags::SHADER_READ,
+                ),
+                barrier(
+                    &targets.prefiltered,
+                    vk::ImageLayout::UNDEFINED,
+                    vk::ImageLayout::GENERAL,
+                    vk::AccessFlags::empty(),
+                    vk::AccessFlags::SHADER_WRITE,
+                ),
+            ],
+            ..Default::default()
+        });
+
+        let levels = targets.mip_views.len() as u32;
+        let resolution = targets.prefiltered.extent().width;
+        for (mip, view) in targets.mip_views.iter().enumerate() {
+            let roughness = if levels > 1 {
+                mip as f32 / (levels - 1) as f32
+            } else {
+                0.0
+            };
+
+            let set = self.sets.next();
+            set.write_sampled_image(
+                0,
+                targets.radiance.view(),
+                &self.sampler,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            );
+            set.write_storage_image(1, view.inner());
+
+            let constants = [roughness.to_bits(), PREFILTER_SAMPLES]
+                .into_iter()
+                .flat_map(u32::to_ne_bytes)
+                .collect::<Vec<_>>();
+
+            let size = (resolution >> mip).max(1);
+            let layout = self.pipeline.layout();
+            command = command
+                .bind_compute_pipeline(&self.pipeline)
+                .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, layout, 0, set)
+                .push_constant_bytes(layout, vk::ShaderStageFlags::COMPUTE, 0, &constants)
+                .dispatch(
+                    size.div_ceil(WORKGROUP_SIZE),
+                    size.div_ceil(WORKGROUP_SIZE),
+                    6,
+                );
+        }
+
+        command.pipeline_barrier(PipelineBarrierInfo {
+            src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags::FRAGMENT_SHADER,
+            images_barriers: vec![barrier(
+                &targets.prefiltered,
+                vk::ImageLayout::GENERAL,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+                vk::AccessFlags::SHADER_WRITE,
+                vk::AccessFlags::SHADER_READ,
+            )],
+            ..Default::default()
+        })
+    }
+}
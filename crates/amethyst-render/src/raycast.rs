@@ -0,0 +1,296 @@
+//! CPU ray casting against the triangles of the meshes, for gameplay code that needs to know
+//! what a ray hits (shooting, placing objects, clicking on the world...) without a physics
+//! engine. Unlike the GPU picking, the result is available immediately.
+use crate::{bounds::Aabb, bvh::Bvh, mesh::Mesh};
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+/// The result of a ray cast.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// The entity owning the mesh that was hit.
+    pub entity: Entity,
+
+    /// The distance between the origin of the ray and the hit point, in world units.
+    pub distance: f32,
+
+    /// The hit point, in world space.
+    pub point: Vec3,
+
+    /// The normal of the triangle that was hit, in world space. It always faces the origin of
+    /// the ray, even if the back face of the triangle was hit.
+    pub normal: Vec3,
+}
+
+/// A system parameter casting rays against every entity with a [`Mesh`] and a
+/// [`GlobalTransform`]. Each mesh is first tested against its bounding box, and then against
+/// each of its triangles, so ray casts against large meshes can be expensive. The [`Aabb`]
+/// component of the entity is used when present, instead of computing the box each time.
+///
+/// When the [`Bvh`] resource exists, only the meshes whose bounding box is hit according to
+/// the hierarchy are tested. The hierarchy is updated at the end of each frame, so meshes
+/// spawned or moved since the last update may be missed or tested at their previous position.
+#[derive(SystemParam)]
+pub struct Raycast<'w, 's> {
+    bvh: Option<Res<'w, Bvh>>,
+    meshes: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Mesh,
+            &'static GlobalTransform,
+            Option<&'static Aabb>,
+        ),
+    >,
+}
+
+impl Raycast<'_, '_> {
+    /// Cast a ray and returns the nearest hit within the given distance, if any.
+    #[must_use]
+    pub fn cast(&self, ray: Ray3d, max_distance: f32) -> Option<RayHit> {
+        self.hits(ray, max_distance)
+            .into_iter()
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Cast a ray and returns the nearest hit of each mesh within the given distance, sorted
+    /// from the nearest to the farthest.
+    #[must_use]
+    pub fn cast_all(&self, ray: Ray3d, max_distance: f32) -> Vec<RayHit> {
+        let mut hits = self.hits(ray, max_distance);
+        hits.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+        hits
+    }
+
+    /// Returns the nearest hit of each mesh within the given distance, in no particular order.
+    fn hits(&self, ray: Ray3d, max_distance: f32) -> Vec<RayHit> {
+        let hit =
+            |(entity, mesh, transform, aabb): (Entity, &Mesh, &GlobalTransform, Option<&Aabb>)| {
+                let aabb = aabb.copied().or_else(|| mesh.compute_aabb())?;
+                cast_mesh(ray, max_distance, mesh, &aabb, transform)
+                    .map(|hit| RayHit { entity, ..hit })
+            };
+
+        match &self.bvh {
+            Some(bvh) => bvh
+                .query_ray(ray, max_distance)
+                .into_iter()
+                .filter_map(|(entity, _)| self.meshes.get(entity).ok())
+                .filter_map(hit)
+                .collect(),
+            None => self.meshes.iter().filter_map(hit).collect(),
+        }
+    }
+}
+
+/// Returns the nearest hit between the ray and the triangles of the mesh, whose bounding box
+/// is given, with a placeholder entity. The ray is transformed into the local space of the
+/// mesh without being normalized, so distances along it are still world distances.
+fn cast_mesh(
+    ray: Ray3d,
+    max_distance: f32,
+    mesh: &Mesh,
+    aabb: &Aabb,
+    transform: &GlobalTransform,
+) -> Option<RayHit> {
+    let inverse = transform.affine().inverse();
+    let origin = inverse.transform_point3(ray.origin);
+    let direction = inverse.transform_vector3(*ray.direction);
+
+    let (near, _) = ray_aabb(origin, direction, aabb)?;
+    if near > max_distance {
+        return None;
+    }
+
+    let (distance, normal) = mesh
+        .triangles()
+        .filter_map(|triangle| {
+            let distance = ray_triangle(origin, direction, triangle)?;
+            let normal = (triangle[1] - triangle[0]).cross(triangle[2] - triangle[0]);
+            Some((distance, normal))
+        })
+        .filter(|&(distance, _)| distance <= max_distance)
+        .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+    // Normals are transformed by the inverse transpose of the transform, to stay perpendicular
+    // to the surface with non-uniform scales.
+    let normal = (inverse.matrix3.transpose() * normal).normalize_or_zero();
+    let normal = if normal.dot(*ray.direction) > 0.0 {
+        -normal
+    } else {
+        normal
+    };
+
+    Some(RayHit {
+        entity: Entity::PLACEHOLDER,
+        distance,
+        point: ray.get_point(distance),
+        normal,
+    })
+}
+
+/// Intersect a ray with an axis-aligned bounding box using the slab method. Returns the
+/// distances along the ray where it enters and leaves the box, or `None` if the ray misses the
+/// box or if the box is behind the ray. The entry distance is zero if the origin of the ray is
+/// inside the box.
+#[must_use]
+pub fn ray_aabb(origin: Vec3, direction: Vec3, aabb: &Aabb) -> Option<(f32, f32)> {
+    let inverse = direction.recip();
+    let t0 = (aabb.min - origin) * inverse;
+    let t1 = (aabb.max - origin) * inverse;
+
+    let near = t0.min(t1).max_element().max(0.0);
+    let far = t0.max(t1).min_element();
+    (near <= far).then_some((near, far))
+}
+
+/// Intersect a ray with a triangle using the Möller-Trumbore algorithm. Both faces of the
+/// triangle can be hit. Returns the distance along the ray to the hit point, in units of the
+/// direction length, or `None` if the ray misses the triangle.
+#[must_use]
+pub fn ray_triangle(origin: Vec3, direction: Vec3, triangle: [Vec3; 3]) -> Option<f32> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+    let p = direction.cross(edge2);
+    let determinant = edge1.dot(p);
+    if determinant.abs() < f32::EPSILON {
+        return None;
+    }
+
+    let inverse = determinant.recip();
+    let s = origin - triangle[0];
+    let u = s.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(edge1);
+    let v = direction.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(q) * inverse;
+    (t >= 0.0).then_some(t)
+}
+
+/// Build the world space ray going through the given cursor position, in pixels from the
+/// top-left corner of the viewport, using the inverse of the view-projection matrix of the
+/// camera. The projection must follow the Vulkan conventions used by the cameras, with the Y
+/// axis pointing down in clip space and a depth range of `[0, 1]`.
+#[must_use]
+pub fn screen_ray(inverse_view_projection: Mat4, viewport: Vec2, cursor: Vec2) -> Option<Ray3d> {
+    let ndc = cursor / viewport * 2.0 - 1.0;
+    let near = inverse_view_projection.project_point3(ndc.extend(0.0));
+    let far = inverse_view_projection.project_point3(ndc.extend(1.0));
+    let direction = Dir3::new(far - near).ok()?;
+    Some(Ray3d::new(near, direction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIANGLE: [Vec3; 3] = [
+        Vec3::new(-1.0, -1.0, 0.0),
+        Vec3::new(1.0, -1.0, 0.0),
+        Vec3::new(0.0, 1.0, 0.0),
+    ];
+
+    fn unit_cube() -> Aabb {
+        Aabb::new(Vec3::splat(-1.0), Vec3::splat(1.0))
+    }
+
+    #[test]
+    fn ray_aabb_hit() {
+        let hit = ray_aabb(Vec3::new(-5.0, 0.5, 0.5), Vec3::X, &unit_cube());
+        assert_eq!(hit, Some((4.0, 6.0)));
+
+        let diagonal = ray_aabb(Vec3::splat(-3.0), Vec3::ONE, &unit_cube());
+        assert_eq!(diagonal, Some((2.0, 4.0)));
+    }
+
+    #[test]
+    fn ray_aabb_from_inside() {
+        let hit = ray_aabb(Vec3::ZERO, Vec3::NEG_Y, &unit_cube());
+        assert_eq!(hit, Some((0.0, 1.0)));
+    }
+
+    #[test]
+    fn ray_aabb_miss() {
+        assert_eq!(
+            ray_aabb(Vec3::new(-5.0, 2.0, 0.0), Vec3::X, &unit_cube()),
+            None
+        );
+        assert_eq!(
+            ray_aabb(Vec3::new(5.0, 0.0, 0.0), Vec3::X, &unit_cube()),
+            None
+        );
+        assert_eq!(
+            ray_aabb(
+                Vec3::new(-5.0, 0.0, 0.0),
+                Vec3::new(1.0, 1.0, 0.0),
+                &unit_cube()
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn ray_triangle_hit() {
+        let front = ray_triangle(Vec3::new(0.0, 0.0, 3.0), Vec3::NEG_Z, TRIANGLE);
+        assert_eq!(front, Some(3.0));
+
+        let back = ray_triangle(Vec3::new(0.0, 0.0, -2.0), Vec3::Z, TRIANGLE);
+        assert_eq!(back, Some(2.0));
+    }
+
+    #[test]
+    fn ray_triangle_distance_in_direction_units() {
+        let hit = ray_triangle(
+            Vec3::new(0.0, 0.0, 4.0),
+            Vec3::new(0.0, 0.0, -2.0),
+            TRIANGLE,
+        );
+        assert_eq!(hit, Some(2.0));
+    }
+
+    #[test]
+    fn ray_triangle_miss() {
+        // Outside of the triangle, next to each edge.
+        for origin in [
+            Vec3::new(0.0, -1.5, 1.0),
+            Vec3::new(0.9, 0.5, 1.0),
+            Vec3::new(-0.9, 0.5, 1.0),
+        ] {
+            assert_eq!(ray_triangle(origin, Vec3::NEG_Z, TRIANGLE), None);
+        }
+
+        // Behind the ray, and parallel to the triangle.
+        assert_eq!(
+            ray_triangle(Vec3::new(0.0, 0.0, 1.0), Vec3::Z, TRIANGLE),
+            None
+        );
+        assert_eq!(
+            ray_triangle(Vec3::new(0.0, 0.0, 1.0), Vec3::X, TRIANGLE),
+            None
+        );
+    }
+
+    #[test]
+    fn screen_ray_through_the_center() {
+        let mut projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 2.0, 0.1, 100.0);
+        projection.y_axis.y = -projection.y_axis.y;
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 10.0), Vec3::ZERO, Vec3::Y);
+        let inverse = (projection * view).inverse();
+        let viewport = Vec2::new(800.0, 400.0);
+
+        let ray = screen_ray(inverse, viewport, viewport / 2.0).unwrap();
+        assert!(ray.origin.abs_diff_eq(Vec3::new(0.0, 0.0, 9.9), 1e-4));
+        assert!(ray.direction.abs_diff_eq(Vec3::NEG_Z, 1e-4));
+
+        // The top-left corner of the viewport is up and to the left of the camera.
+        let corner = screen_ray(inverse, viewport, Vec2::ZERO).unwrap();
+        assert!(corner.direction.x < 0.0 && corner.direction.y > 0.0);
+    }
+}
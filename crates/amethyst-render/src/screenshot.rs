@@ -0,0 +1,84 @@
+//! Screenshots of the frames presented to the window. Sending a [`Screenshot`] event copies the
+//! next presented frame back to the host, and the copy is encoded and written as a PNG file on
+//! the IO task pool so that the frame loop is not blocked by the encoding.
+use amethyst_vulkan::buffer::{
+    Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo, BufferMemoryLocation,
+    BufferTransfert, BufferUsage, BufferUsageInfo,
+};
+use bevy::{prelude::*, tasks::IoTaskPool};
+use std::{path::PathBuf, sync::Arc};
+use vulkanalia::prelude::v1_3::*;
+
+/// Request a screenshot of the next presented frame. If several screenshots are requested
+/// during the same frame, only the last one is taken.
+#[derive(Debug, Clone, PartialEq, Eq, Event)]
+pub struct Screenshot {
+    /// The path of the PNG file to write.
+    pub path: PathBuf,
+}
+
+impl Screenshot {
+    /// Request a screenshot written to the given path.
+    #[must_use]
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+}
+
+/// Create a host visible buffer of the given size, receiving the copy of a swapchain image.
+pub(crate) fn readback_buffer(allocator: &Arc<BufferAllocator>, size: usize) -> Buffer {
+    Buffer::new(
+        allocator.clone(),
+        BufferCreateInfo::<u8> {
+            usage: BufferUsageInfo {
+                location: BufferMemoryLocation::PreferHostVisible,
+                transfer: BufferTransfert::Destination,
+                access: BufferAccess::Random,
+                usage: BufferUsage::None,
+                ..Default::default()
+            },
+            data: BufferDataInfo::Uninitialized(size),
+            ..Default::default()
+        },
+    )
+}
+
+/// Convert the pixels read back from a swapchain image of the given format to RGBA, and write
+/// them as a PNG file in the background.
+pub(crate) fn save(path: PathBuf, extent: vk::Extent2D, format: vk::Format, mut pixels: Vec<u8>) {
+    match format {
+        vk::Format::R8G8B8A8_UNORM | vk::Format::R8G8B8A8_SRGB => (),
+        vk::Format::B8G8R8A8_UNORM | vk::Format::B8G8R8A8_SRGB => {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+        _ => {
+            error!("Screenshots of a {format:?} swapchain are not supported");
+            return;
+        }
+    }
+
+    // The buffer may be larger than the image, and the swapchain is presented opaque, so its
+    // alpha channel is meaningless.
+    pixels.truncate((extent.width * extent.height * 4) as usize);
+    for pixel in pixels.chunks_exact_mut(4) {
+        pixel[3] = u8::MAX;
+    }
+
+    IoTaskPool::get()
+        .spawn(async move {
+            let saved = image::save_buffer(
+                &path,
+                &pixels,
+                extent.width,
+                extent.height,
+                image::ExtendedColorType::Rgba8,
+            );
+            match saved {
+                Ok(()) => info!("Screenshot saved to {}", path.display()),
+                Err(error) => error!("Failed to save the screenshot {}: {error}", path.display()),
+            }
+        })
+        .detach();
+}
@@ -0,0 +1,585 @@
+//! GPU compute kernels for exclusive prefix sums and key-value radix sorts over storage buffers
+//! of `u32`. They are the building blocks of particle sorting, clustered lighting and the
+//! compaction of GPU culling results.
+//!
+//! All the buffers must be created with the [`BufferUsage::Storage`] usage. The kernels insert
+//! the barriers needed between their own dispatches, and a final barrier making their results
+//! visible to the following compute shaders.
+use amethyst_vulkan::{
+    buffer::{
+        Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo,
+    },
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The number of elements processed by each work group of the kernels.
+pub const BLOCK_SIZE: u32 = 256;
+
+/// The number of bits sorted by each pass of the radix sort.
+const RADIX_BITS: u32 = 4;
+
+/// The number of different digits in a radix sort pass.
+const RADIX_DIGITS: u32 = 1 << RADIX_BITS;
+
+/// The number of descriptor sets used in turn by the kernels. A scan uses two descriptor sets
+/// per level, and a sort uses two descriptor sets plus one scan per pass.
+const DESCRIPTOR_SETS: usize = 128;
+
+/// The size of the largest push constants used by the kernels, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 12;
+
+/// Create the descriptor set layout shared by all the kernels: five storage buffers, each
+/// kernel using only some of them.
+fn storage_layout(device: Arc<VulkanDevice>) -> Arc<DescriptorSetLayout> {
+    let bindings = (0..5)
+        .map(|binding| DescriptorBinding {
+            binding,
+            kind: vk::DescriptorType::STORAGE_BUFFER,
+            stages: vk::ShaderStageFlags::COMPUTE,
+        })
+        .collect::<Vec<_>>();
+    Arc::new(DescriptorSetLayout::new(device, &bindings))
+}
+
+/// Create a compute pipeline running the given shader with the shared layout, named after the
+/// kernel for the validation messages and graphics debuggers.
+fn kernel(
+    device: &Arc<VulkanDevice>,
+    layout: &Arc<DescriptorSetLayout>,
+    name: &str,
+    code: &str,
+) -> ComputePipeline {
+    ComputePipeline::new(
+        device.clone(),
+        ComputePipelineCreateInfo {
+            shader: ShaderModule::compile_glsl(
+                device.clone(),
+                ShaderType::Compute,
+                code.to_string(),
+            ),
+            descriptor_set_layouts: vec![layout.clone()],
+            push_constant_ranges: vec![vk::PushConstantRange {
+                stage_flags: vk::ShaderStageFlags::COMPUTE,
+                offset: 0,
+                size: PUSH_CONSTANTS_SIZE,
+            }],
+            name: Some(name),
+        },
+    )
+}
+
+/// Create a device local storage buffer able to hold `count` elements.
+fn storage_buffer(allocator: &Arc<BufferAllocator>, count: u32) -> Buffer {
+    Buffer::new(
+        allocator.clone(),
+        BufferCreateInfo::<u32> {
+            usage: BufferUsageInfo {
+                location: BufferMemoryLocation::PreferDeviceLocal,
+                transfer: BufferTransfert::All,
+                access: BufferAccess::None,
+                usage: BufferUsage::Storage,
+                ..Default::default()
+            },
+            data: BufferDataInfo::Uninitialized(count.max(1) as usize * std::mem::size_of::<u32>()),
+            ..Default::default()
+        },
+    )
+}
+
+/// Insert a barrier making the writes of a kernel to the given buffers visible to the following
+/// compute shaders.
+fn barrier<'pool>(
+    command: CommandBuffer<'pool, Recording>,
+    buffers: &[&Buffer],
+) -> CommandBuffer<'pool, Recording> {
+    command.pipeline_barrier(PipelineBarrierInfo {
+        src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+        dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+        buffers_barriers: buffers
+            .iter()
+            .map(|buffer| {
+                vk::BufferMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::SHADER_READ | vk::AccessFlags::SHADER_WRITE)
+                    .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                    .buffer(buffer.inner())
+                    .offset(0)
+                    .size(vk::WHOLE_SIZE)
+                    .build()
+            })
+            .collect(),
+        ..Default::default()
+    })
+}
+
+/// Encode the push constants of a kernel.
+fn constants(values: &[u32]) -> Vec<u8> {
+    values.iter().copied().flat_map(u32::to_ne_bytes).collect()
+}
+
+/// An exclusive prefix sum (scan) over a buffer of `u32`: each output element is the sum of
+/// all the input elements before it. Each block of [`BLOCK_SIZE`] elements is scanned by a work
+/// group, then the sums of the blocks are scanned recursively and added back to the blocks.
+#[derive(Debug)]
+pub struct PrefixSum {
+    /// The descriptor sets used by the dispatches, in turn.
+    sets: DescriptorSetRing,
+
+    /// The pipeline scanning each block and computing the block sums.
+    blocks: ComputePipeline,
+
+    /// The pipeline adding the scanned block sums to each block.
+    add: ComputePipeline,
+
+    /// The block sums of each level of the recursion.
+    levels: Vec<Buffer>,
+
+    /// The maximum number of elements that can be scanned.
+    capacity: u32,
+}
+
+impl PrefixSum {
+    /// Create the kernels and the intermediate buffers needed to scan up to `capacity`
+    /// elements.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, allocator: &Arc<BufferAllocator>, capacity: u32) -> Self {
+        let layout = storage_layout(device.clone());
+        let blocks = kernel(
+            &device,
+            &layout,
+            "scan blocks",
+            include_str!("../shaders/scan_blocks.glsl"),
+        );
+        let add = kernel(
+            &device,
+            &layout,
+            "scan add",
+            include_str!("../shaders/scan_add.glsl"),
+        );
+
+        // Each level holds one sum per block of the previous level, until a single block
+        // remains.
+        let mut levels = Vec::new();
+        let mut count = capacity.max(1);
+        loop {
+            count = count.div_ceil(BLOCK_SIZE);
+            levels.push(storage_buffer(allocator, count));
+            if count == 1 {
+                break;
+            }
+        }
+
+        Self {
+            sets: DescriptorSetRing::new(device, &layout, DESCRIPTOR_SETS),
+            blocks,
+            add,
+            levels,
+            capacity,
+        }
+    }
+
+    /// Returns the maximum number of elements that can be scanned.
+    #[must_use]
+    pub const fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Record an exclusive prefix sum of the first `count` elements of the input buffer into
+    /// the output buffer. The input and the output can be the same buffer.
+    ///
+    /// # Panics
+    /// Panics if `count` is greater than the capacity of the prefix sum.
+    #[must_use]
+    pub fn exclusive_scan<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        input: &Buffer,
+        output: &Buffer,
+        count: u32,
+    ) -> CommandBuffer<'pool, Recording> {
+        assert!(count <= self.capacity, "Too many elements to scan");
+        if count == 0 {
+            return command;
+        }
+        self.scan_level(command, input, output, count, 0)
+    }
+
+    /// Scan a level of the recursion: scan each block, then scan the block sums and add them
+    /// back to the blocks if there is more than one block.
+    fn scan_level<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        input: &Buffer,
+        output: &Buffer,
+        count: u32,
+        level: usize,
+    ) -> CommandBuffer<'pool, Recording> {
+        let groups = count.div_ceil(BLOCK_SIZE);
+        let sums = &self.levels[level];
+
+        let set = self.sets.next();
+        set.write_storage_buffer(0, input);
+        set.write_storage_buffer(1, output);
+        set.write_storage_buffer(2, sums);
+
+        let command = command
+            .bind_compute_pipeline(&self.blocks)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, self.blocks.layout(), 0, set)
+            .push_constant_bytes(
+                self.blocks.layout(),
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &constants(&[count]),
+            )
+            .dispatch(groups, 1, 1);
+        let command = barrier(command, &[output, sums]);
+
+        if groups == 1 {
+            return command;
+        }
+
+        let command = self.scan_level(command, sums, sums, groups, level + 1);
+
+        let set = self.sets.next();
+        set.write_storage_buffer(0, output);
+        set.write_storage_buffer(2, sums);
+
+        let command = command
+            .bind_compute_pipeline(&self.add)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, self.add.layout(), 0, set)
+            .push_constant_bytes(
+                self.add.layout(),
+                vk::ShaderStageFlags::COMPUTE,
+                0,
+                &constants(&[count]),
+            )
+            .dispatch(groups, 1, 1);
+        barrier(command, &[output])
+    }
+}
+
+/// A stable key-value radix sort over two buffers of `u32`, sorting the keys in ascending order
+/// and moving the values along with them. The sort runs 8 passes of 4 bits, each made of a
+/// histogram of the digits of each block, a prefix sum of the histogram, and a scatter of the
+/// keys and values to their sorted position.
+#[derive(Debug)]
+pub struct RadixSort {
+    /// The descriptor sets used by the dispatches, in turn.
+    sets: DescriptorSetRing,
+
+    /// The pipeline counting the digits of each block.
+    histogram: ComputePipeline,
+
+    /// The pipeline writing the keys and values to their sorted position.
+    scatter: ComputePipeline,
+
+    /// The prefix sum used to scan the histograms.
+    scan: PrefixSum,
+
+    /// The digit counts of each block, stored digit-major.
+    counts: Buffer,
+
+    /// The keys between two passes.
+    keys: Buffer,
+
+    /// The values between two passes.
+    values: Buffer,
+
+    /// The maximum number of elements that can be sorted.
+    capacity: u32,
+}
+
+impl RadixSort {
+    /// Create the kernels and the intermediate buffers needed to sort up to `capacity`
+    /// key-value pairs.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, allocator: &Arc<BufferAllocator>, capacity: u32) -> Self {
+        let layout = storage_layout(device.clone());
+        let histogram = kernel(
+            &device,
+            &layout,
+            "radix histogram",
+            include_str!("../shaders/radix_histogram.glsl"),
+        );
+        let scatter = kernel(
+            &device,
+            &layout,
+            "radix scatter",
+            include_str!("../shaders/radix_scatter.glsl"),
+        );
+
+        let blocks = capacity.max(1).div_ceil(BLOCK_SIZE);
+        Self {
+            scan: PrefixSum::new(device.clone(), allocator, blocks * RADIX_DIGITS),
+            sets: DescriptorSetRing::new(device, &layout, DESCRIPTOR_SETS),
+            counts: storage_buffer(allocator, blocks * RADIX_DIGITS),
+            keys: storage_buffer(allocator, capacity),
+            values: storage_buffer(allocator, capacity),
+            histogram,
+            scatter,
+            capacity,
+        }
+    }
+
+    /// Returns the maximum number of elements that can be sorted.
+    #[must_use]
+    pub const fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Record a sort of the first `count` keys and values, in place. Keys that compare equal
+    /// keep their relative order.
+    ///
+    /// # Panics
+    /// Panics if `count` is greater than the capacity of the sort.
+    #[must_use]
+    pub fn sort<'pool>(
+        &self,
+        command: CommandBuffer<'pool, Recording>,
+        keys: &Buffer,
+        values: &Buffer,
+        count: u32,
+    ) -> CommandBuffer<'pool, Recording> {
+        assert!(count <= self.capacity, "Too many elements to sort");
+        if count == 0 {
+            return command;
+        }
+
+        // Each pass reads from one pair of buffers and writes into the other one. The number
+        // of passes is even, so the sorted keys and values end up in the original buffers.
+        let blocks = count.div_ceil(BLOCK_SIZE);
+        let mut command = command;
+        for pass in 0..u32::BITS / RADIX_BITS {
+            let (src_keys, src_values, dst_keys, dst_values) = if pass % 2 == 0 {
+                (keys, values, &self.keys, &self.values)
+            } else {
+                (&self.keys, &self.values, keys, values)
+            };
+
+            let push = constants(&[count, pass * RADIX_BITS, blocks]);
+
+            let set = self.sets.next();
+            set.write_storage_buffer(0, src_keys);
+            set.write_storage_buffer(4, &self.counts);
+            command = command
+                .bind_compute_pipeline(&self.histogram)
+                .bind_descriptor_set(
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.histogram.layout(),
+                    0,
+                    set,
+                )
+                .push_constant_bytes(
+                    self.histogram.layout(),
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &push,
+                )
+                .dispatch(blocks, 1, 1);
+            command = barrier(command, &[&self.counts]);
+
+            let digits = blocks * RADIX_DIGITS;
+            command = self
+                .scan
+                .exclusive_scan(command, &self.counts, &self.counts, digits);
+
+            let set = self.sets.next();
+            set.write_storage_buffer(0, src_keys);
+            set.write_storage_buffer(1, src_values);
+            set.write_storage_buffer(2, dst_keys);
+            set.write_storage_buffer(3, dst_values);
+            set.write_storage_buffer(4, &self.counts);
+            command = command
+                .bind_compute_pipeline(&self.scatter)
+                .bind_descriptor_set(
+                    vk::PipelineBindPoint::COMPUTE,
+                    self.scatter.layout(),
+                    0,
+                    set,
+                )
+                .push_constant_bytes(
+                    self.scatter.layout(),
+                    vk::ShaderStageFlags::COMPUTE,
+                    0,
+                    &push,
+                )
+                .dispatch(blocks, 1, 1);
+            command = barrier(command, &[dst_keys, dst_values]);
+        }
+
+        command
+    }
+}
+
+#[cfg(all(test, feature = "golden"))]
+mod tests {
+    //! The kernels run on the headless device of the golden-image tests, and their results are
+    //! checked against a CPU reference. They only run with the `golden` feature on a machine
+    //! with a Vulkan device, so a plain `cargo test` does not run them.
+    use super::*;
+    use crate::golden::HeadlessRenderer;
+
+    /// Create a host visible storage buffer holding the given values.
+    fn host_buffer(renderer: &HeadlessRenderer, values: &[u32]) -> Buffer {
+        Buffer::new(
+            renderer.allocator().clone(),
+            BufferCreateInfo {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::All,
+                    access: BufferAccess::Random,
+                    usage: BufferUsage::Storage,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Slice(values),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Insert a barrier making the writes of the kernels to the given buffers visible to the
+    /// host, since waiting for the submission alone does not.
+    fn host_barrier<'pool>(
+        command: CommandBuffer<'pool, Recording>,
+        buffers: &[&Buffer],
+    ) -> CommandBuffer<'pool, Recording> {
+        command.pipeline_barrier(PipelineBarrierInfo {
+            src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+            dst_stage_mask: vk::PipelineStageFlags::HOST,
+            buffers_barriers: buffers
+                .iter()
+                .map(|buffer| {
+                    vk::BufferMemoryBarrier::builder()
+                        .src_access_mask(vk::AccessFlags::SHADER_WRITE)
+                        .dst_access_mask(vk::AccessFlags::HOST_READ)
+                        .src_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .dst_queue_family_index(vk::QUEUE_FAMILY_IGNORED)
+                        .buffer(buffer.inner())
+                        .offset(0)
+                        .size(vk::WHOLE_SIZE)
+                        .build()
+                })
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Returns the first `count` values of the buffer.
+    fn read(buffer: &Buffer, count: usize) -> Vec<u32> {
+        // SAFETY: The buffer is host visible, and the submission writing into it has completed
+        // and made its writes visible to the host.
+        let mut values = unsafe { buffer.read::<u32>() };
+        values.truncate(count);
+        values
+    }
+
+    /// Returns `count` pseudo-random values below `max`, generated with a xorshift.
+    fn pseudo_random(count: usize, max: u32, mut seed: u32) -> Vec<u32> {
+        (0..count)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed % max
+            })
+            .collect()
+    }
+
+    /// The CPU reference of the exclusive prefix sum.
+    fn exclusive_scan(values: &[u32]) -> Vec<u32> {
+        values
+            .iter()
+            .scan(0u32, |sum, &value| {
+                let before = *sum;
+                *sum = sum.wrapping_add(value);
+                Some(before)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn prefix_sum_matches_the_cpu_reference() {
+        let renderer = HeadlessRenderer::new();
+
+        // One block, a partial block, and enough blocks to recurse twice.
+        for count in [1, 200, 256, 1000, 70_000] {
+            let values = pseudo_random(count, 100, 0x1234_5678);
+            let input = host_buffer(&renderer, &values);
+            let output = host_buffer(&renderer, &vec![0; count]);
+            let scan = PrefixSum::new(
+                renderer.device().clone(),
+                renderer.allocator(),
+                count as u32,
+            );
+
+            renderer.submit(|command| {
+                let command = scan.exclusive_scan(command, &input, &output, count as u32);
+                host_barrier(command, &[&output])
+            });
+            assert_eq!(
+                read(&output, count),
+                exclusive_scan(&values),
+                "{count} elements"
+            );
+        }
+    }
+
+    #[test]
+    fn prefix_sum_in_place() {
+        let renderer = HeadlessRenderer::new();
+        let values = pseudo_random(1000, 100, 42);
+        let buffer = host_buffer(&renderer, &values);
+        let scan = PrefixSum::new(renderer.device().clone(), renderer.allocator(), 1000);
+
+        renderer.submit(|command| {
+            let command = scan.exclusive_scan(command, &buffer, &buffer, 1000);
+            host_barrier(command, &[&buffer])
+        });
+        assert_eq!(read(&buffer, 1000), exclusive_scan(&values));
+    }
+
+    #[test]
+    fn radix_sort_matches_the_cpu_reference() {
+        let renderer = HeadlessRenderer::new();
+        let count = 5000;
+
+        // Few distinct keys spread over all the digits, so the stability of the sort matters.
+        let keys = pseudo_random(count, 64, 0xdead_beef)
+            .into_iter()
+            .map(|key| key.wrapping_mul(0x0410_0401))
+            .collect::<Vec<_>>();
+        let values = (0..count as u32).collect::<Vec<_>>();
+        let mut expected = keys
+            .iter()
+            .copied()
+            .zip(values.iter().copied())
+            .collect::<Vec<_>>();
+        expected.sort_by_key(|&(key, _)| key);
+
+        let key_buffer = host_buffer(&renderer, &keys);
+        let value_buffer = host_buffer(&renderer, &values);
+        let sort = RadixSort::new(
+            renderer.device().clone(),
+            renderer.allocator(),
+            count as u32,
+        );
+
+        renderer.submit(|command| {
+            let command = sort.sort(command, &key_buffer, &value_buffer, count as u32);
+            host_barrier(command, &[&key_buffer, &value_buffer])
+        });
+        let sorted = read(&key_buffer, count)
+            .into_iter()
+            .zip(read(&value_buffer, count))
+            .collect::<Vec<_>>();
+        assert_eq!(sorted, expected);
+    }
+}
@@ -0,0 +1,39 @@
+//! The time spent by the GPU on each pass of the last rendered frame, measured with timestamp
+//! queries by a [`GpuProfiler`](amethyst_vulkan::query::GpuProfiler) and published in the
+//! [`GpuFrameTimings`] resource.
+use amethyst_vulkan::query::GpuScopeTiming;
+use bevy::prelude::*;
+use std::time::Duration;
+
+/// The GPU timings of the last rendered frame. The scopes are empty until a frame has been
+/// rendered, or if the device does not support timestamps.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct GpuFrameTimings {
+    /// The index of the frame the timings were measured on, counting only the rendered frames.
+    pub frame: u64,
+
+    /// The measured scopes of the frame, in the order they were opened. A nested scope
+    /// follows the scope enclosing it.
+    pub scopes: Vec<GpuScopeTiming>,
+}
+
+impl GpuFrameTimings {
+    /// Returns the time spent by the GPU in the first scope with the given name.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<Duration> {
+        self.scopes
+            .iter()
+            .find(|scope| scope.name == name)
+            .map(|scope| scope.duration)
+    }
+
+    /// Returns the time spent by the GPU in all the outermost scopes of the frame.
+    #[must_use]
+    pub fn total(&self) -> Duration {
+        self.scopes
+            .iter()
+            .filter(|scope| scope.depth == 0)
+            .map(|scope| scope.duration)
+            .sum()
+    }
+}
@@ -0,0 +1,362 @@
+//! Temporal upscaling, rendering the scene at a reduced internal resolution and reconstructing
+//! a full resolution image from several frames. The projection is jittered by a sub-pixel
+//! offset each frame, so successive frames sample different positions inside each pixel, and
+//! the motion vectors of the scene are used to reproject the samples of the previous frames.
+//!
+//! The scene must be rendered into a color image of [`UpscaleQuality::render_extent`] with
+//! the projection returned by [`jitter_projection`], along with its depth buffer and a motion
+//! vector image using the [`MOTION_FORMAT`] format. Since the renderer does not produce motion
+//! vectors, the [`TemporalUpscalePass`] is not part of its frame, and the quality preset is
+//! chosen by the application recording the pass.
+use crate::filter::FILTER_FORMAT;
+use amethyst_vulkan::{
+    buffer::{BufferAllocator, MemoryPriority},
+    command::{CommandBuffer, PipelineBarrierInfo, Recording},
+    descriptor::{DescriptorBinding, DescriptorSetLayout, DescriptorSetRing},
+    device::VulkanDevice,
+    image::{Image, ImageCreateInfo, Sampler},
+    pipeline::{ComputePipeline, ComputePipelineCreateInfo},
+    shader::{ShaderModule, ShaderType},
+};
+use bevy::math::{Mat4, Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The format of the motion vectors. Each texel stores the difference between the current
+/// and the previous texture coordinates of the surface it shows, so that the previous
+/// coordinates are `uv - motion`.
+pub const MOTION_FORMAT: vk::Format = vk::Format::R16G16_SFLOAT;
+
+/// The number of descriptor sets used in turn by the pass.
+const DESCRIPTOR_SETS: usize = 8;
+
+/// The size of the work groups of the upscaling shader, along each axis.
+const WORKGROUP_SIZE: u32 = 8;
+
+/// The size of the push constants used by the upscaling shader, in bytes.
+const PUSH_CONSTANTS_SIZE: u32 = 4 * 4;
+
+/// The maximum number of frames accumulated in the history. Higher values give a more stable
+/// image but react slower to changes of lighting.
+const MAX_ACCUMULATION: f32 = 16.0;
+
+/// The resolution at which the scene is rendered before being upscaled, trading image quality
+/// for performance. The ratios match the usual presets of temporal upscalers.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UpscaleQuality {
+    /// The scene is rendered at the display resolution. The temporal pass still runs and
+    /// acts as an anti-aliasing pass.
+    #[default]
+    Native,
+
+    /// The scene is rendered at 77% of the display resolution on each axis.
+    UltraQuality,
+
+    /// The scene is rendered at 67% of the display resolution on each axis.
+    Quality,
+
+    /// The scene is rendered at 59% of the display resolution on each axis.
+    Balanced,
+
+    /// The scene is rendered at half the display resolution on each axis.
+    Performance,
+
+    /// The scene is rendered at a third of the display resolution on each axis.
+    UltraPerformance,
+}
+
+impl UpscaleQuality {
+    /// Returns the ratio between the display resolution and the render resolution, on each
+    /// axis.
+    #[must_use]
+    pub const fn ratio(self) -> f32 {
+        match self {
+            UpscaleQuality::Native => 1.0,
+            UpscaleQuality::UltraQuality => 1.3,
+            UpscaleQuality::Quality => 1.5,
+            UpscaleQuality::Balanced => 1.7,
+            UpscaleQuality::Performance => 2.0,
+            UpscaleQuality::UltraPerformance => 3.0,
+        }
+    }
+
+    /// Returns the resolution at which the scene is rendered for the given display resolution.
+    #[must_use]
+    pub fn render_extent(self, display: vk::Extent2D) -> vk::Extent2D {
+        let ratio = self.ratio();
+        vk::Extent2D {
+            width: ((display.width as f32 / ratio).round() as u32).max(1),
+            height: ((display.height as f32 / ratio).round() as u32).max(1),
+        }
+    }
+
+    /// Returns the bias to apply to the mip level of the textures sampled by the scene, so
+    /// they keep the sharpness of the display resolution once upscaled.
+    #[must_use]
+    pub fn mip_bias(self) -> f32 {
+        -self.ratio().log2()
+    }
+
+    /// Returns the number of jitter offsets cycled through by the projection. Lower render
+    /// resolutions need more offsets to cover each display pixel.
+    #[must_use]
+    pub fn jitter_phases(self) -> u32 {
+        (8.0 * self.ratio() * self.ratio()).ceil() as u32
+    }
+}
+
+/// Returns the element of the Halton sequence of the given base at the given index, which is
+/// a low discrepancy sequence in `[0, 1)`.
+#[must_use]
+pub fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.0;
+    let mut result = 0.0;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+/// Returns the sub-pixel jitter offset of the given frame, in render pixels, between -0.5 and
+/// 0.5 on each axis.
+#[must_use]
+pub fn jitter_offset(frame: u64, phases: u32) -> Vec2 {
+    // The sequence starts at index 1, since the first element of any base is zero.
+    let index = (frame % u64::from(phases.max(1))) as u32 + 1;
+    Vec2::new(halton(index, 2), halton(index, 3)) - 0.5
+}
+
+/// Offset a projection matrix by the given jitter, in render pixels, for an image of the
+/// given extent. This works for both perspective and orthographic projections.
+#[must_use]
+pub fn jitter_projection(projection: Mat4, jitter: Vec2, render: vk::Extent2D) -> Mat4 {
+    let offset = jitter * 2.0 / Vec2::new(render.width as f32, render.height as f32);
+    Mat4::from_translation(Vec3::new(offset.x, offset.y, 0.0)) * projection
+}
+
+/// The compute pass reconstructing the full resolution image, and its history.
+#[derive(Debug)]
+pub struct TemporalUpscalePass {
+    /// The descriptor sets used by the pass, in turn, with the color, depth, motion and history
+    /// images at bindings 0 to 3 and the target image at binding 4.
+    sets: DescriptorSetRing,
+
+    /// The pipeline reconstructing the image.
+    pipeline: ComputePipeline,
+
+    /// The sampler used to read the history.
+    sampler: Sampler,
+
+    /// The reconstructed images of the current and previous frames, used in turn. The alpha
+    /// channel stores the number of frames accumulated.
+    history: [Image; 2],
+
+    /// The index of the history image written by the next frame.
+    current: usize,
+
+    /// The number of frames reconstructed, used to select the jitter offset.
+    frame: u64,
+
+    /// Whether the history must be ignored by the next frame.
+    reset: bool,
+}
+
+impl TemporalUpscalePass {
+    /// Compile the upscaling shader and create the history images for the given display
+    /// resolution. The pass must be created again when the display is resized.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        allocator: &Arc<BufferAllocator>,
+        display: vk::Extent2D,
+    ) -> Self {
+        let layout = Arc::new(DescriptorSetLayout::new(
+            device.clone(),
+            &[0, 1, 2, 3]
+                .map(|binding| DescriptorBinding {
+                    binding,
+                    kind: vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                })
+                .into_iter()
+                .chain([DescriptorBinding {
+                    binding: 4,
+                    kind: vk::DescriptorType::STORAGE_IMAGE,
+                    stages: vk::ShaderStageFlags::COMPUTE,
+                }])
+                .collect::<Vec<_>>(),
+        ));
+
+        let pipeline = ComputePipeline::new(
+            device.clone(),
+            ComputePipelineCreateInfo {
+                shader: ShaderModule::compile_glsl(
+                    device.clone(),
+                    ShaderType::Compute,
+                    include_str!("../shaders/temporal_upscale.glsl").to_string(),
+                ),
+                descriptor_set_layouts: vec![layout.clone()],
+                push_constant_ranges: vec![vk::PushConstantRange {
+                    stage_flags: vk::ShaderStageFlags::COMPUTE,
+                    offset: 0,
+                    size: PUSH_CONSTANTS_SIZE,
+                }],
+                name: Some("temporal upscale"),
+            },
+        );
+
+        let history = || {
+            Image::new(
+                device.clone(),
+                allocator.clone(),
+                ImageCreateInfo {
+                    extent: display,
+                    format: FILTER_FORMAT,
+                    usage: vk::ImageUsageFlags::STORAGE
+                        | vk::ImageUsageFlags::SAMPLED
+                        | vk::ImageUsageFlags::TRANSFER_SRC,
+                    priority: MemoryPriority::High,
+                    ..Default::default()
+                },
+            )
+        };
+
+        Self {
+            sets: DescriptorSetRing::new(device.clone(), &layout, DESCRIPTOR_SETS),
+            history: [history(), history()],
+            sampler: Sampler::new(
+                device,
+                vk::Filter::LINEAR,
+                vk::SamplerAddressMode::CLAMP_TO_EDGE,
+            ),
+            pipeline,
+            current: 0,
+            frame: 0,
+            reset: true,
+        }
+    }
+
+    /// Returns the jitter offset to apply to the projection of the next frame, in render
+    /// pixels. See [`jitter_projection`].
+    #[must_use]
+    pub fn jitter(&self, quality: UpscaleQuality) -> Vec2 {
+        jitter_offset(self.frame, quality.jitter_phases())
+    }
+
+    /// Returns the image reconstructed by the last frame, at the display resolution. It uses
+    /// the [`FILTER_FORMAT`] format and is in the `vk::ImageLayout::GENERAL` layout, so it can
+    /// be processed by the following passes but must not be written to.
+    #[must_use]
+    pub fn output(&self) -> &Image {
+        &self.history[1 - self.current]
+    }
+
+    /// Discard the history, for example after a camera cut, so the next frame does not blend
+    /// with unrelated images.
+    pub fn reset(&mut self) {
+        self.reset = true;
+    }
+
+    /// Record the reconstruction of the next frame from the color, depth and motion images
+    /// rendered with the given jitter offset. They must have the same extent and be in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout.
+    #[must_use]
+    pub fn record<'pool>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording>,
+        color: &Image,
+        depth: &Image,
+        motion: &Image,
+        jitter: Vec2,
+    ) -> CommandBuffer<'pool, Recording> {
+        let reset = std::mem::take(&mut self.reset);
+        let constants = [jitter.x, jitter.y, MAX_ACCUMULATION]
+            .map(f32::to_bits)
+            .into_iter()
+            .chain([u32::from(reset)])
+            .flat_map(u32::to_ne_bytes)
+            .collect::<Vec<_>>();
+
+        let target = &self.history[self.current];
+        let previous = &self.history[1 - self.current];
+        let read_only = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+
+        let set = self.sets.next();
+        set.write_sampled_image(0, color.view(), &self.sampler, read_only);
+        set.write_sampled_image(1, depth.view(), &self.sampler, read_only);
+        set.write_sampled_image(2, motion.view(), &self.sampler, read_only);
+        set.write_sampled_image(3, previous.view(), &self.sampler, vk::ImageLayout::GENERAL);
+        set.write_storage_image(4, target.view());
+
+        // The history images start in an undefined layout, and are moved to the general layout
+        // the first time they are used. The previous content is discarded anyway on reset.
+        let old_layout = if self.frame == 0 {
+            vk::ImageLayout::UNDEFINED
+        } else {
+            vk::ImageLayout::GENERAL
+        };
+        let barrier = |image: &Image, src_access, dst_access, old_layout| {
+            vk::ImageMemoryBarrier::builder()
+                .src_access_mask(src_access)
+                .dst_access_mask(dst_access)
+                .old_layout(old_layout)
+                .new_layout(vk::ImageLayout::GENERAL)
+                .subresource_range(image.subresource_range())
+                .image(image.inner())
+                .build()
+        };
+
+        let extent = target.extent();
+        let layout = self.pipeline.layout();
+        let command = command
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                images_barriers: vec![
+                    barrier(
+                        target,
+                        vk::AccessFlags::SHADER_READ | vk::AccessFlags::TRANSFER_READ,
+                        vk::AccessFlags::SHADER_WRITE,
+                        old_layout,
+                    ),
+                    barrier(
+                        previous,
+                        vk::AccessFlags::empty(),
+                        vk::AccessFlags::SHADER_READ,
+                        old_layout,
+                    ),
+                ],
+                ..Default::default()
+            })
+            .bind_compute_pipeline(&self.pipeline)
+            .bind_descriptor_set(vk::PipelineBindPoint::COMPUTE, layout, 0, set)
+            .push_constant_bytes(layout, vk::ShaderStageFlags::COMPUTE, 0, &constants)
+            .dispatch(
+                extent.width.div_ceil(WORKGROUP_SIZE),
+                extent.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            )
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER,
+                dst_stage_mask: vk::PipelineStageFlags::COMPUTE_SHADER
+                    | vk::PipelineStageFlags::FRAGMENT_SHADER
+                    | vk::PipelineStageFlags::TRANSFER,
+                images_barriers: vec![barrier(
+                    target,
+                    vk::AccessFlags::SHADER_WRITE,
+                    vk::AccessFlags::SHADER_READ | vk::AccessFlags::TRANSFER_READ,
+                    vk::ImageLayout::GENERAL,
+                )],
+                ..Default::default()
+            });
+
+        self.current = 1 - self.current;
+        self.frame += 1;
+        command
+    }
+}
@@ -39,3 +39,86 @@ unsafe impl VertexAttributeDescription for Vertex2DColor {
         ]
     }
 }
+
+/// The vertex of a [`Mesh`](crate::mesh::Mesh), as uploaded to the GPU.
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct MeshVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+unsafe impl VertexBindingDescription for MeshVertex {
+    fn binding_description() -> Vec<vk::VertexInputBindingDescription> {
+        vec![vk::VertexInputBindingDescription {
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+            binding: 0,
+        }]
+    }
+}
+
+unsafe impl VertexAttributeDescription for MeshVertex {
+    fn attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        vec![
+            vk::VertexInputAttributeDescription {
+                offset: core::mem::offset_of!(Self, position) as u32,
+                format: vk::Format::R32G32B32_SFLOAT,
+                location: 0,
+                binding: 0,
+            },
+            vk::VertexInputAttributeDescription {
+                offset: core::mem::offset_of!(Self, normal) as u32,
+                format: vk::Format::R32G32B32_SFLOAT,
+                location: 1,
+                binding: 0,
+            },
+            vk::VertexInputAttributeDescription {
+                offset: core::mem::offset_of!(Self, uv) as u32,
+                format: vk::Format::R32G32_SFLOAT,
+                location: 2,
+                binding: 0,
+            },
+        ]
+    }
+}
+
+/// The per-instance data of an instanced [`MeshVertex`] draw, read from a second vertex buffer
+/// with [`Instanced<MeshVertex, MeshInstance>`](amethyst_vulkan::pipeline::Instanced). The
+/// model matrix takes the locations 3 to 6, one for each column, and the color the location 7.
+#[derive(Default, Debug, Clone, Copy)]
+#[repr(C)]
+pub struct MeshInstance {
+    pub model: [[f32; 4]; 4],
+    pub color: [f32; 4],
+}
+
+unsafe impl VertexBindingDescription for MeshInstance {
+    fn binding_description() -> Vec<vk::VertexInputBindingDescription> {
+        vec![vk::VertexInputBindingDescription {
+            stride: std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::INSTANCE,
+            binding: 0,
+        }]
+    }
+}
+
+unsafe impl VertexAttributeDescription for MeshInstance {
+    fn attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        let columns = (0..4).map(|column| vk::VertexInputAttributeDescription {
+            offset: (core::mem::offset_of!(Self, model) + column * 16) as u32,
+            format: vk::Format::R32G32B32A32_SFLOAT,
+            location: 3 + column as u32,
+            binding: 0,
+        });
+        columns
+            .chain([vk::VertexInputAttributeDescription {
+                offset: core::mem::offset_of!(Self, color) as u32,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                location: 7,
+                binding: 0,
+            }])
+            .collect()
+    }
+}
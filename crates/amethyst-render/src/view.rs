@@ -0,0 +1,42 @@
+//! The point of view from which the scene is rendered. The renderer does not know about the
+//! cameras of the application: a camera controller writes its matrices into the [`RenderView`]
+//! resource each frame, and they are uploaded to a uniform buffer read by the shaders.
+use bevy::prelude::*;
+
+/// The view and projection matrices, as laid out in the uniform buffer read by the shaders.
+pub type ViewUniform = [[f32; 16]; 2];
+
+/// The view and projection matrices used to render the scene. The projection must follow the
+/// Vulkan conventions: the Y axis points down in clip space and the depth range is `[0, 1]`.
+#[derive(Debug, Clone, Copy, PartialEq, Resource)]
+pub struct RenderView {
+    /// The matrix transforming world space coordinates into the camera space.
+    pub view: Mat4,
+
+    /// The matrix transforming camera space coordinates into clip space.
+    pub projection: Mat4,
+}
+
+impl RenderView {
+    /// Returns the combined view and projection matrix.
+    #[must_use]
+    pub fn view_projection(&self) -> Mat4 {
+        self.projection * self.view
+    }
+
+    /// Returns the matrices as laid out in the uniform buffer read by the shaders.
+    #[must_use]
+    pub fn to_uniform(&self) -> ViewUniform {
+        [self.view.to_cols_array(), self.projection.to_cols_array()]
+    }
+}
+
+impl Default for RenderView {
+    /// An identity view, rendering the world coordinates directly as clip coordinates.
+    fn default() -> Self {
+        Self {
+            view: Mat4::IDENTITY,
+            projection: Mat4::IDENTITY,
+        }
+    }
+}
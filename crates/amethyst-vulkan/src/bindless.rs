@@ -0,0 +1,241 @@
+//! Bindless textures. Instead of binding a descriptor set for each material, all the textures
+//! are written once in a large array of combined image samplers, and the shaders index the
+//! array with an index stored in the material data. This relies on the descriptor indexing
+//! features of Vulkan 1.2: the array is only partially bound, its descriptors are written
+//! while the command buffers using it are pending, and its size is chosen when the descriptor
+//! set is allocated.
+//!
+//! The shaders declare the array as an unsized array, and index it with `nonuniformEXT` when
+//! the index is not uniform across the invocations:
+//!
+//! ```glsl
+//! layout(set = 0, binding = 0) uniform sampler2D textures[];
+//! vec4 color = texture(textures[nonuniformEXT(index)], uv);
+//! ```
+use crate::{deletion::Deletion, descriptor::DescriptorSet, device::VulkanDevice, image::Sampler};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// A registry of textures assigned to stable indices of a bindless array of combined image
+/// samplers. A texture keeps its index until it is removed, and the index of a removed texture
+/// is only reused once the frames that may still sample it are complete.
+///
+/// The textures of the array are not recorded for the hazard tracking: their images must be
+/// in the `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout whenever the array is used.
+#[derive(Debug)]
+pub struct BindlessTextures {
+    device: Arc<VulkanDevice>,
+
+    /// The layout of the descriptor set, with a single variable-count array at binding 0.
+    layout: vk::DescriptorSetLayout,
+
+    /// The pool from which the descriptor set is allocated.
+    pool: vk::DescriptorPool,
+
+    /// The descriptor set holding the array.
+    set: DescriptorSet,
+
+    /// The number of textures that the array can hold.
+    capacity: u32,
+
+    /// The index assigned to each texture, identified by its view and its sampler.
+    indices: HashMap<(vk::ImageView, vk::Sampler), u32>,
+
+    /// The indices of the removed textures that can be reused, filled by the deletion queue
+    /// of the device once the frames that may use them are complete.
+    free: Arc<Mutex<Vec<u32>>>,
+
+    /// The number of indices that have been assigned at least once.
+    next: u32,
+}
+
+impl BindlessTextures {
+    /// Create a bindless array holding up to `capacity` textures, readable by the given shader
+    /// stages.
+    ///
+    /// # Panics
+    /// Panics if the device does not support the descriptor indexing features, see
+    /// [`VulkanDevice::supports_descriptor_indexing`], or if the capacity is zero.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, capacity: u32, stages: vk::ShaderStageFlags) -> Self {
+        assert!(
+            device.supports_descriptor_indexing(),
+            "Bindless textures require the descriptor indexing features"
+        );
+        assert!(
+            capacity > 0,
+            "A bindless array must hold at least one texture"
+        );
+
+        let bindings = [vk::DescriptorSetLayoutBinding::builder()
+            .binding(0)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)
+            .stage_flags(stages)
+            .build()];
+        let binding_flags = [vk::DescriptorBindingFlags::PARTIALLY_BOUND
+            | vk::DescriptorBindingFlags::VARIABLE_DESCRIPTOR_COUNT
+            | vk::DescriptorBindingFlags::UPDATE_AFTER_BIND
+            | vk::DescriptorBindingFlags::UPDATE_UNUSED_WHILE_PENDING];
+        let mut flags_info =
+            vk::DescriptorSetLayoutBindingFlagsCreateInfo::builder().binding_flags(&binding_flags);
+        let layout_info = vk::DescriptorSetLayoutCreateInfo::builder()
+            .flags(vk::DescriptorSetLayoutCreateFlags::UPDATE_AFTER_BIND_POOL)
+            .bindings(&bindings)
+            .push_next(&mut flags_info);
+
+        let sizes = [vk::DescriptorPoolSize::builder()
+            .type_(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .descriptor_count(capacity)
+            .build()];
+        let pool_info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(vk::DescriptorPoolCreateFlags::UPDATE_AFTER_BIND)
+            .pool_sizes(&sizes)
+            .max_sets(1);
+
+        let (layout, pool) = unsafe {
+            let layout = device
+                .logical()
+                .create_descriptor_set_layout(&layout_info, None)
+                .expect("Failed to create bindless descriptor set layout");
+            let pool = device
+                .logical()
+                .create_descriptor_pool(&pool_info, None)
+                .expect("Failed to create bindless descriptor pool");
+            (layout, pool)
+        };
+
+        let counts = [capacity];
+        let mut count_info = vk::DescriptorSetVariableDescriptorCountAllocateInfo::builder()
+            .descriptor_counts(&counts);
+        let layouts = [layout];
+        let allocate_info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts)
+            .push_next(&mut count_info);
+
+        let set = unsafe {
+            device
+                .logical()
+                .allocate_descriptor_sets(&allocate_info)
+                .expect("Failed to allocate bindless descriptor set")[0]
+        };
+
+        Self {
+            set: DescriptorSet::from_raw(device.clone(), pool, set),
+            device,
+            layout,
+            pool,
+            capacity,
+            indices: HashMap::new(),
+            free: Arc::default(),
+            next: 0,
+        }
+    }
+
+    /// Write a texture sampled with the given sampler to the array, and returns its index.
+    /// If the texture was already written with the same sampler, its index is returned
+    /// without writing it again. The image of the view must be in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout when the array is used.
+    ///
+    /// # Panics
+    /// Panics if the array is full.
+    #[must_use]
+    pub fn insert(&mut self, view: vk::ImageView, sampler: &Sampler) -> u32 {
+        if let Some(&index) = self.indices.get(&(view, sampler.inner())) {
+            return index;
+        }
+
+        let index = match self.free.lock().expect("Free indices poisoned").pop() {
+            Some(index) => index,
+            None => {
+                assert!(self.next < self.capacity, "The bindless array is full");
+                self.next += 1;
+                self.next - 1
+            }
+        };
+
+        let image_info = [vk::DescriptorImageInfo::builder()
+            .image_view(view)
+            .sampler(sampler.inner())
+            .image_layout(vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL)
+            .build()];
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.set.inner())
+            .dst_binding(0)
+            .dst_array_element(index)
+            .descriptor_type(vk::DescriptorType::COMBINED_IMAGE_SAMPLER)
+            .image_info(&image_info);
+
+        // SAFETY: The descriptor is not used by the pending command buffers, since the index
+        // was either never assigned or released once the frames using it were complete.
+        unsafe {
+            self.device
+                .logical()
+                .update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+        }
+
+        self.indices.insert((view, sampler.inner()), index);
+        index
+    }
+
+    /// Remove a texture from the array. Its index is reused once the frames that may still
+    /// sample it are complete, so the shaders must not use the index anymore.
+    pub fn remove(&mut self, view: vk::ImageView, sampler: &Sampler) {
+        if let Some(index) = self.indices.remove(&(view, sampler.inner())) {
+            self.device
+                .destroy_later(Deletion::BindlessIndex(self.free.clone(), index));
+        }
+    }
+
+    /// Returns the index of a texture if it was written to the array.
+    #[must_use]
+    pub fn get(&self, view: vk::ImageView, sampler: &Sampler) -> Option<u32> {
+        self.indices.get(&(view, sampler.inner())).copied()
+    }
+
+    /// Returns the number of textures in the array.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Returns whether the array does not contain any texture.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Returns the number of textures that the array can hold.
+    #[must_use]
+    pub const fn capacity(&self) -> u32 {
+        self.capacity
+    }
+
+    /// Returns the descriptor set holding the array, to bind to the pipelines.
+    #[must_use]
+    pub const fn set(&self) -> &DescriptorSet {
+        &self.set
+    }
+
+    /// Returns the layout of the descriptor set, to create the pipelines using the array.
+    #[must_use]
+    pub const fn layout(&self) -> vk::DescriptorSetLayout {
+        self.layout
+    }
+}
+
+impl Drop for BindlessTextures {
+    fn drop(&mut self) {
+        self.device
+            .destroy_later(Deletion::DescriptorPool(self.pool));
+        unsafe {
+            self.device
+                .logical()
+                .destroy_descriptor_set_layout(self.layout, None);
+        }
+    }
+}
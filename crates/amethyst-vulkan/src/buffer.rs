@@ -1,38 +1,294 @@
-use crate::{context::VulkanContext, device::VulkanDevice};
-use std::sync::Arc;
+use crate::{
+    command::{CommandBuffer, CommandPool, SubmitInfo, Transfer},
+    context::VulkanContext,
+    deletion::Deletion,
+    device::{VulkanDevice, VulkanQueues},
+    pool::Suballocation,
+};
+use bevy::prelude::Resource;
+use std::{
+    fmt,
+    marker::PhantomData,
+    ops::Range,
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+};
 use vma::Alloc;
 use vulkanalia::prelude::v1_3::*;
 
 /// A buffer allocator that uses the Vulkan Memory Allocator library.
 /// This is a simple and thin wrapper around the VMA allocator.
+///
+/// The allocator also uploads the initial data of the buffers allocated in device local
+/// memory through a staging buffer, on the async transfer queue if the device has one, or on
+/// the main queue otherwise. The uploads wait for the queue to be idle, and the queue must
+/// not be used by another thread at the same time.
+///
+/// # Important
+/// The order of fields in the struct is important to ensure that the allocator is destroyed
+/// before the device, which is kept alive by the upload command pool. The inner allocator is
+/// shared with the deletion queue of the device while allocations wait to be freed, and the
+/// device frees them before being destroyed.
 #[derive(Debug)]
 pub struct BufferAllocator {
-    inner: vma::Allocator,
+    inner: Arc<vma::Allocator>,
+
+    /// The command pool used to record the uploads of the initial data of the buffers.
+    upload_pool: Mutex<CommandPool<Transfer>>,
+
+    /// The queue on which the uploads are submitted.
+    upload_queue: vk::Queue,
+
+    /// The queue families that share the buffers uploaded through a staging buffer, or `None`
+    /// if the uploads are done on the main queue family.
+    upload_families: Option<[u32; 2]>,
+
+    /// The device on which the buffers are allocated, whose deletion queue destroys them.
+    device: Arc<VulkanDevice>,
 }
 
 impl BufferAllocator {
     /// Create a new buffer allocator with default settings.
     #[must_use]
-    pub fn new(context: &VulkanContext, device: &VulkanDevice) -> Self {
+    pub fn new(context: &VulkanContext, device: &Arc<VulkanDevice>) -> Self {
         // Create the buffer allocator. It use the Vulkan Memory Allocator library
         // with rust bindings.
+        let mut options =
+            vma::AllocatorOptions::new(&context.instance(), device.logical(), device.physical());
+
+        // If the memory priority extension is enabled, let VMA use it so the priority of each
+        // allocation is given to the driver. This allows the driver to keep the most important
+        // allocations in device local memory when the VRAM is oversubscribed.
+        if device.is_extension_enabled(vk::EXT_MEMORY_PRIORITY_EXTENSION.name) {
+            options.flags |= vma::AllocatorCreateFlags::EXT_MEMORY_PRIORITY;
+        }
+
+        // With the memory budget extension, VMA asks the driver how much memory the process
+        // uses and can still use in each heap, including the memory allocated by the driver
+        // itself and by other allocators, instead of estimating it from its own allocations.
+        if device.is_extension_enabled(vk::EXT_MEMORY_BUDGET_EXTENSION.name) {
+            options.flags |= vma::AllocatorCreateFlags::EXT_MEMORY_BUDGET;
+        }
+
         let inner = unsafe {
-            vma::Allocator::new(&vma::AllocatorOptions::new(
-                &context.instance(),
-                device.logical(),
-                device.physical(),
-            ))
-            .expect("Failed to create buffer allocator")
+            Arc::new(vma::Allocator::new(&options).expect("Failed to create buffer allocator"))
         };
 
-        Self { inner }
+        let queues = VulkanQueues::fetch(device);
+        let main_family = device.queues_info().main_family();
+        let upload_families = device
+            .queues_info()
+            .async_transfer_family()
+            .map(|family| [family, main_family]);
+
+        Self {
+            inner,
+            upload_pool: Mutex::new(CommandPool::transfer(
+                device.clone(),
+                vk::CommandPoolCreateFlags::TRANSIENT,
+            )),
+            upload_queue: queues.async_transfer().unwrap_or(queues.main()),
+            upload_families,
+            device: device.clone(),
+        }
     }
 
     /// Get a reference to the inner allocator.
     #[must_use]
-    pub const fn inner(&self) -> &vma::Allocator {
+    pub fn inner(&self) -> &vma::Allocator {
         &self.inner
     }
+
+    /// Returns the inner allocator, to free an allocation later in the deletion queue of the
+    /// device. The queue cannot keep the whole allocator alive, since the allocator keeps the
+    /// device alive, and therefore the queue itself.
+    pub(crate) fn shared_inner(&self) -> Arc<vma::Allocator> {
+        self.inner.clone()
+    }
+
+    /// Returns the device on which the buffers are allocated.
+    #[must_use]
+    pub const fn device(&self) -> &Arc<VulkanDevice> {
+        &self.device
+    }
+
+    /// Returns the memory used by the allocations of this allocator and the budget of each
+    /// memory heap of the device. The budget is only an estimation when the memory budget
+    /// extension is not supported by the device.
+    ///
+    /// # Panics
+    /// Panics if the budgets of the memory heaps cannot be retrieved.
+    #[must_use]
+    pub fn memory_report(&self) -> MemoryReport {
+        let properties = unsafe {
+            self.device
+                .instance()
+                .get_physical_device_memory_properties(self.device.physical())
+        };
+        let budgets = self
+            .inner
+            .get_heap_budgets()
+            .expect("Failed to get memory heap budgets");
+
+        let heaps = properties.memory_heaps[..properties.memory_heap_count as usize]
+            .iter()
+            .zip(budgets)
+            .map(|(heap, budget)| HeapReport {
+                size: heap.size,
+                device_local: heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL),
+                block_count: budget.statistics.blockCount,
+                block_bytes: budget.statistics.blockBytes,
+                allocation_count: budget.statistics.allocationCount,
+                allocation_bytes: budget.statistics.allocationBytes,
+                usage: budget.usage,
+                budget: budget.budget,
+            })
+            .collect();
+
+        MemoryReport {
+            budget_extension: self
+                .device
+                .is_extension_enabled(vk::EXT_MEMORY_BUDGET_EXTENSION.name),
+            heaps,
+        }
+    }
+}
+
+/// A snapshot of the memory allocated by a [`BufferAllocator`] and of the budget of each memory
+/// heap of the device, returned by [`BufferAllocator::memory_report`]. The report can be logged
+/// with its [`Display`](fmt::Display) implementation, or read field by field to be displayed in
+/// an overlay.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Resource)]
+pub struct MemoryReport {
+    /// Whether the usage and the budget of the heaps were given by the driver through the
+    /// memory budget extension. Otherwise, the usage only counts the memory blocks of the
+    /// allocator and the budget is estimated from the size of the heaps.
+    pub budget_extension: bool,
+
+    /// The report of each memory heap of the device, in the order of the heap indices.
+    pub heaps: Vec<HeapReport>,
+}
+
+impl MemoryReport {
+    /// Returns the number of allocations made by the allocator in all the heaps.
+    #[must_use]
+    pub fn allocation_count(&self) -> u32 {
+        self.heaps.iter().map(|heap| heap.allocation_count).sum()
+    }
+
+    /// Returns the size in bytes of all the allocations made by the allocator.
+    #[must_use]
+    pub fn allocation_bytes(&self) -> u64 {
+        self.heaps.iter().map(|heap| heap.allocation_bytes).sum()
+    }
+
+    /// Returns the size in bytes of all the memory blocks allocated from the device by the
+    /// allocator. This is the memory actually allocated, including the unused space of the
+    /// blocks between the allocations.
+    #[must_use]
+    pub fn block_bytes(&self) -> u64 {
+        self.heaps.iter().map(|heap| heap.block_bytes).sum()
+    }
+
+    /// Returns whether the usage of at least one heap exceeds its budget. Allocating more
+    /// memory in such a heap may fail, or degrade the performance as the driver moves memory
+    /// out of the device local heaps.
+    #[must_use]
+    pub fn is_over_budget(&self) -> bool {
+        self.heaps.iter().any(HeapReport::is_over_budget)
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} allocation(s) using {} in {} of memory blocks",
+            self.allocation_count(),
+            Bytes(self.allocation_bytes()),
+            Bytes(self.block_bytes())
+        )?;
+        for (index, heap) in self.heaps.iter().enumerate() {
+            let local = if heap.device_local {
+                " (device local)"
+            } else {
+                ""
+            };
+            write!(
+                f,
+                "\n  - heap {index}{local}: {} allocation(s) using {}, {} of {} used, {} total",
+                heap.allocation_count,
+                Bytes(heap.allocation_bytes),
+                Bytes(heap.usage),
+                Bytes(heap.budget),
+                Bytes(heap.size)
+            )?;
+        }
+        if !self.budget_extension {
+            f.write_str("\n  (budgets estimated without the memory budget extension)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The memory allocated by a [`BufferAllocator`] in a memory heap, and the usage and budget of
+/// the whole process in that heap.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapReport {
+    /// The size of the heap in bytes.
+    pub size: u64,
+
+    /// Whether the heap is device local, which is usually the VRAM of a discrete GPU.
+    pub device_local: bool,
+
+    /// The number of memory blocks allocated by the allocator in the heap.
+    pub block_count: u32,
+
+    /// The size in bytes of the memory blocks allocated by the allocator in the heap.
+    pub block_bytes: u64,
+
+    /// The number of allocations made by the allocator in the heap.
+    pub allocation_count: u32,
+
+    /// The size in bytes of the allocations made by the allocator in the heap.
+    pub allocation_bytes: u64,
+
+    /// The memory in bytes used in the heap by the whole process, including the other
+    /// allocators and the driver.
+    pub usage: u64,
+
+    /// The memory in bytes that the process can use in the heap before allocations fail or
+    /// degrade the performance. This is shared with the other applications, and may change
+    /// over time.
+    pub budget: u64,
+}
+
+impl HeapReport {
+    /// Returns whether the usage of the heap exceeds its budget.
+    #[must_use]
+    pub const fn is_over_budget(&self) -> bool {
+        self.usage > self.budget
+    }
+}
+
+/// A size in bytes, displayed with a binary unit.
+struct Bytes(u64);
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} B", self.0);
+        }
+
+        let mut size = self.0 as f64 / 1024.0;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < UNITS.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        write!(f, "{size:.1} {}", UNITS[unit])
+    }
 }
 
 /// A buffer object that can be used to store data on the GPU.
@@ -45,6 +301,10 @@ pub struct Buffer {
     /// size, and memory type.
     allocation: vma::Allocation,
 
+    /// The size of the buffer requested at its creation, in bytes. The allocation backing it
+    /// may be larger.
+    size: vk::DeviceSize,
+
     /// The buffer in which this buffer belongs to. This object is not owned by
     /// this buffer, and other buffer can share the same buffer, but with a
     /// different allocation (start offset and size).
@@ -55,18 +315,35 @@ impl Buffer {
     /// Create a new buffer with the given device, allocator, and buffer creation
     /// information.
     #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
     pub fn new<T>(allocator: Arc<BufferAllocator>, create_info: BufferCreateInfo<T>) -> Self {
         // Create the allocation information for the buffer from our splitted
         // buffer information that allow a better API design.
         let mut allocation_info = vma::AllocationOptions::from(create_info.usage.location);
         allocation_info.flags |= vma::AllocationCreateFlags::from(create_info.usage.access);
         allocation_info.memory_type_bits = create_info.usage.memory_type;
+        allocation_info.priority = create_info.usage.priority.value();
 
-        let usage = vk::BufferUsageFlags::from(create_info.usage.transfer)
+        // The initial data of a buffer in device local memory is copied from a staging buffer,
+        // so the buffer must be a transfer destination. When the copy is done on the async
+        // transfer queue, the buffer is shared with the main queue family instead of having
+        // its ownership transferred.
+        let staged = matches!(create_info.data, BufferDataInfo::Slice(_))
+            && create_info.usage.location == BufferMemoryLocation::PreferDeviceLocal;
+        let mut usage = vk::BufferUsageFlags::from(create_info.usage.transfer)
             | vk::BufferUsageFlags::from(create_info.usage.usage);
-        let buffer_info = vk::BufferCreateInfo::builder()
-            .size(create_info.data.size() as vk::DeviceSize)
-            .usage(usage);
+        if staged {
+            usage |= vk::BufferUsageFlags::TRANSFER_DST;
+        }
+
+        let families = allocator.upload_families.filter(|_| staged);
+        let size = create_info.data.size() as vk::DeviceSize;
+        let mut buffer_info = vk::BufferCreateInfo::builder().size(size).usage(usage);
+        if let Some(families) = &families {
+            buffer_info = buffer_info
+                .sharing_mode(vk::SharingMode::CONCURRENT)
+                .queue_family_indices(families);
+        }
 
         // Create the buffer with the allocator.
         let (buffer, allocation) = unsafe {
@@ -80,50 +357,107 @@ impl Buffer {
                 .expect("Failed to create buffer")
         };
 
+        if let Some(name) = create_info.name {
+            allocator
+                .device()
+                .set_object_name(vk::ObjectType::BUFFER, buffer.as_raw(), name);
+        }
+
+        let buffer = Self {
+            allocator,
+            allocation,
+            buffer,
+            size,
+        };
+
         // Copy the data to the buffer if it is provided.
         if let BufferDataInfo::Slice(data) = create_info.data {
             match create_info.usage.location {
-                BufferMemoryLocation::PreferDeviceLocal => {
-                    // Create a staging buffer
-                    // Copy the data to the staging buffer
-                    // Copy the data from the staging buffer to the device local buffer
-                    // using a command buffer.
-                    todo!()
-                }
+                BufferMemoryLocation::PreferDeviceLocal => buffer.upload(data),
                 BufferMemoryLocation::PreferHostVisible => {
-                    let allocation_info = allocator.inner().get_allocation_info(allocation);
-                    let ptr = allocation_info.pMappedData as *mut T;
+                    let ptr = buffer.mapped_ptr().expect("Buffer is not mapped");
                     unsafe {
-                        assert!(!ptr.is_null());
-                        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr, data.len());
+                        std::ptr::copy_nonoverlapping(
+                            data.as_ptr(),
+                            ptr.as_ptr().cast::<T>(),
+                            data.len(),
+                        );
                     }
                 }
             }
         }
 
-        Self {
-            allocator,
-            allocation,
-            buffer,
-        }
+        buffer
     }
 
-    /// Get the start offset of this buffer inside the `vk::Buffer` object.
+    /// Copy the given data to the start of this buffer through a host visible staging buffer,
+    /// and wait for the copy to complete.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    fn upload<T>(&self, data: &[T]) {
+        let staging = Buffer::new(
+            self.allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Source,
+                    access: BufferAccess::Sequential,
+                    usage: BufferUsage::None,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Slice(data),
+                ..Default::default()
+            },
+        );
+
+        let mut pool = self
+            .allocator
+            .upload_pool
+            .lock()
+            .expect("Upload command pool poisoned");
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        CommandBuffer::new(&*pool)
+            .start_recording()
+            .label("buffer upload")
+            .copy_buffer(&staging, self, size)
+            .stop_recording()
+            .submit_and_wait(SubmitInfo {
+                queue: self.allocator.upload_queue,
+                ..Default::default()
+            });
+
+        // The upload is complete, so its command buffer can be reused by the next one.
+        pool.reset(vk::CommandPoolResetFlags::empty());
+    }
+
+    /// Get the size of this buffer, as requested at its creation. This is the size of the
+    /// `vk::Buffer` object, which may be smaller than the allocation backing it.
     #[must_use]
-    pub fn start_offset(&self) -> vk::DeviceSize {
-        self.allocator
-            .inner
-            .get_allocation_info(self.allocation)
-            .offset
+    pub const fn size(&self) -> vk::DeviceSize {
+        self.size
     }
 
-    /// Get the size of this buffer.
+    /// Returns a pointer to the memory of this buffer if it is mapped, which is the case for
+    /// buffers allocated in host visible memory.
     #[must_use]
-    pub fn size(&self) -> vk::DeviceSize {
-        self.allocator
-            .inner
-            .get_allocation_info(self.allocation)
-            .size
+    pub fn mapped_ptr(&self) -> Option<NonNull<u8>> {
+        let info = self.allocator.inner.get_allocation_info(self.allocation);
+        NonNull::new(info.pMappedData.cast::<u8>())
+    }
+
+    /// Read the content of this buffer as a vector of `T`.
+    ///
+    /// # Safety
+    /// The buffer must be mapped, its memory must contain valid `T` values, and the GPU must
+    /// not be writing to it while it is read, for example by waiting for the submission that
+    /// writes into it to complete.
+    ///
+    /// # Panics
+    /// Panics if the buffer is not mapped.
+    #[must_use]
+    pub unsafe fn read<T: Copy>(&self) -> Vec<T> {
+        let ptr = self.mapped_ptr().expect("Buffer is not mapped");
+        let count = self.size() as usize / std::mem::size_of::<T>();
+        std::slice::from_raw_parts(ptr.as_ptr().cast::<T>(), count).to_vec()
     }
 
     /// Return the buffer allocator that allocated this buffer.
@@ -144,14 +478,258 @@ impl Buffer {
 
 impl Drop for Buffer {
     fn drop(&mut self) {
-        unsafe {
-            self.allocator
-                .inner
-                .destroy_buffer(self.buffer, self.allocation);
+        self.allocator.device.destroy_later(Deletion::Buffer(
+            self.allocator.shared_inner(),
+            self.buffer,
+            self.allocation,
+        ));
+    }
+}
+
+/// A typed view of a range of `T` elements of a [`Buffer`]. The view shares the ownership of
+/// the buffer, so several views can be created over the same buffer and the buffer is only
+/// destroyed once all of them are dropped.
+///
+/// The commands take the buffer of the view, and the offset of the view relative to the
+/// start of the buffer, for example with
+/// [`CommandBuffer::bind_vertex_buffers`](crate::command::CommandBuffer::bind_vertex_buffers).
+/// When the buffer is bound as a whole, the view can also be addressed with the index of its
+/// first element, for example as the vertex offset or the first index of an indexed draw.
+#[derive(Debug)]
+pub struct SubBuffer<T> {
+    /// The buffer this view belongs to.
+    buffer: Arc<Buffer>,
+
+    /// The offset of the first element of the view, in bytes, relative to the start of the
+    /// buffer.
+    offset: vk::DeviceSize,
+
+    /// The number of elements of the view.
+    len: usize,
+
+    /// The range of the buffer reserved for the view when it was allocated from a
+    /// [`BufferPool`], returned to the pool once the view and all its clones are dropped.
+    ///
+    /// [`BufferPool`]: crate::pool::BufferPool
+    suballocation: Option<Arc<Suballocation>>,
+
+    /// The view does not own any `T`, so it is `Send` and `Sync` regardless of `T`.
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> SubBuffer<T> {
+    /// Create a view of `len` elements of the given buffer, starting at the given offset in
+    /// bytes relative to the start of the buffer.
+    ///
+    /// # Panics
+    /// Panics if the offset is not a multiple of the size of `T`, or if the view does not fit
+    /// in the buffer.
+    #[must_use]
+    pub fn new(buffer: Arc<Buffer>, offset: vk::DeviceSize, len: usize) -> Self {
+        check_bounds::<T>(offset, len, buffer.size());
+        Self {
+            buffer,
+            offset,
+            len,
+            suballocation: None,
+            marker: PhantomData,
+        }
+    }
+
+    /// Create a view of the given range of a buffer reserved by a pool, which returns the
+    /// range to the pool once the view and all its clones are dropped.
+    pub(crate) fn suballocated(
+        buffer: Arc<Buffer>,
+        suballocation: Suballocation,
+        len: usize,
+    ) -> Self {
+        let offset = suballocation.range().start;
+        Self {
+            suballocation: Some(Arc::new(suballocation)),
+            ..Self::new(buffer, offset, len)
+        }
+    }
+
+    /// Create a view of all the elements of the given buffer.
+    #[must_use]
+    pub fn whole(buffer: Arc<Buffer>) -> Self {
+        let len = buffer.size() as usize / std::mem::size_of::<T>().max(1);
+        Self::new(buffer, 0, len)
+    }
+
+    /// Create a view of the given range of elements of this view, for example the vertices of
+    /// a single mesh stored in a larger allocation. The new view keeps the range reserved in
+    /// the pool alive, if any, like a clone of this view.
+    ///
+    /// # Panics
+    /// Panics if the range exceeds the elements of the view.
+    #[must_use]
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        let (offset, len) = slice_range::<T>(self.offset, self.len, range);
+        Self {
+            offset,
+            len,
+            ..self.clone()
+        }
+    }
+
+    /// Reinterpret the view as a view of `U` elements covering the same bytes, for example to
+    /// split an allocation of raw bytes into vertices and indices.
+    ///
+    /// # Panics
+    /// Panics if the offset or the size of the view is not a multiple of the size of `U`. The
+    /// size of a type is a multiple of its alignment, so the offset is then aligned for `U`.
+    #[must_use]
+    pub fn cast<U>(&self) -> SubBuffer<U> {
+        let len = cast_len::<T, U>(self.offset, self.len);
+        SubBuffer {
+            suballocation: self.suballocation.clone(),
+            ..SubBuffer::new(self.buffer.clone(), self.offset, len)
+        }
+    }
+
+    /// Returns the buffer this view belongs to.
+    #[must_use]
+    pub const fn buffer(&self) -> &Arc<Buffer> {
+        &self.buffer
+    }
+
+    /// Returns the offset of the view, in bytes, relative to the start of its buffer.
+    #[must_use]
+    pub const fn offset(&self) -> vk::DeviceSize {
+        self.offset
+    }
+
+    /// Returns the index of the first element of the view when its buffer is viewed as an
+    /// array of `T`.
+    #[must_use]
+    pub const fn first_element(&self) -> u32 {
+        (self.offset / std::mem::size_of::<T>() as vk::DeviceSize) as u32
+    }
+
+    /// Returns the number of elements of the view.
+    #[must_use]
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the view does not contain any element.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the size of the view, in bytes.
+    #[must_use]
+    pub const fn size(&self) -> vk::DeviceSize {
+        (self.len * std::mem::size_of::<T>()) as vk::DeviceSize
+    }
+
+    /// Returns a pointer to the first element of the view if its buffer is mapped.
+    #[must_use]
+    pub fn mapped_ptr(&self) -> Option<NonNull<T>> {
+        self.buffer
+            .mapped_ptr()
+            .map(|ptr| unsafe { ptr.add(self.offset as usize).cast::<T>() })
+    }
+
+    /// Copy the given elements to the start of the view.
+    ///
+    /// # Safety
+    /// The GPU must not be accessing the view while it is written, for example by waiting for
+    /// the submissions that use it to complete.
+    ///
+    /// # Panics
+    /// Panics if the buffer is not mapped, or if there are more elements than the view can
+    /// hold.
+    pub unsafe fn write(&self, data: &[T])
+    where
+        T: Copy,
+    {
+        assert!(
+            data.len() <= self.len,
+            "The data does not fit in the sub-buffer"
+        );
+        let ptr = self.mapped_ptr().expect("Buffer is not mapped");
+        std::ptr::copy_nonoverlapping(data.as_ptr(), ptr.as_ptr(), data.len());
+    }
+
+    /// Read the elements of the view.
+    ///
+    /// # Safety
+    /// The memory of the view must contain valid `T` values, and the GPU must not be writing
+    /// to it while it is read, for example by waiting for the submission that writes into it
+    /// to complete.
+    ///
+    /// # Panics
+    /// Panics if the buffer is not mapped.
+    #[must_use]
+    pub unsafe fn read(&self) -> Vec<T>
+    where
+        T: Copy,
+    {
+        let ptr = self.mapped_ptr().expect("Buffer is not mapped");
+        std::slice::from_raw_parts(ptr.as_ptr(), self.len).to_vec()
+    }
+}
+
+impl<T> Clone for SubBuffer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            buffer: self.buffer.clone(),
+            offset: self.offset,
+            len: self.len,
+            suballocation: self.suballocation.clone(),
+            marker: PhantomData,
         }
     }
 }
 
+/// Check that a view of `len` elements of `T` starting at the given offset fits in a buffer of
+/// the given size, and that its offset is a multiple of the size of `T`.
+fn check_bounds<T>(offset: vk::DeviceSize, len: usize, size: vk::DeviceSize) {
+    let stride = std::mem::size_of::<T>().max(1) as vk::DeviceSize;
+    assert!(
+        offset % stride == 0,
+        "The offset of a sub-buffer must be a multiple of the size of its elements"
+    );
+    assert!(
+        offset + (len * std::mem::size_of::<T>()) as vk::DeviceSize <= size,
+        "The sub-buffer does not fit in the buffer"
+    );
+}
+
+/// Returns the offset and the length of the given range of elements of a view of `len`
+/// elements of `T` starting at the given offset.
+fn slice_range<T>(
+    offset: vk::DeviceSize,
+    len: usize,
+    range: Range<usize>,
+) -> (vk::DeviceSize, usize) {
+    assert!(
+        range.start <= range.end && range.end <= len,
+        "The range {range:?} exceeds the {len} elements of the sub-buffer"
+    );
+    let start = (range.start * std::mem::size_of::<T>()) as vk::DeviceSize;
+    (offset + start, range.len())
+}
+
+/// Returns the number of `U` elements covering the bytes of a view of `len` elements of `T`
+/// starting at the given offset.
+fn cast_len<T, U>(offset: vk::DeviceSize, len: usize) -> usize {
+    let stride = std::mem::size_of::<U>().max(1) as vk::DeviceSize;
+    let size = (len * std::mem::size_of::<T>()) as vk::DeviceSize;
+    assert!(
+        offset % stride == 0,
+        "The offset of the sub-buffer is not a multiple of the size of the new element type"
+    );
+    assert!(
+        size % stride == 0,
+        "The size of the sub-buffer is not a multiple of the size of the new element type"
+    );
+    (size / stride) as usize
+}
+
 /// The usage of the buffer.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum BufferUsage {
@@ -171,6 +749,10 @@ pub enum BufferUsage {
     /// The buffer will be used for storing data.
     Storage,
 
+    /// The buffer will be used for storing indirect draw commands or draw counts. Those are
+    /// usually written by a compute shader, so the buffer can also be used as storage.
+    Indirect,
+
     /// The buffer can be used for any purpose. This is useful for buffers that
     /// are used for multiple purposes, or when the buffer usage is not known
     /// at the time of creation, but can restrict the buffer allocator to use
@@ -185,6 +767,9 @@ impl From<BufferUsage> for vk::BufferUsageFlags {
             BufferUsage::Vertices => vk::BufferUsageFlags::VERTEX_BUFFER,
             BufferUsage::Storage => vk::BufferUsageFlags::STORAGE_BUFFER,
             BufferUsage::Indices => vk::BufferUsageFlags::INDEX_BUFFER,
+            BufferUsage::Indirect => {
+                vk::BufferUsageFlags::INDIRECT_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER
+            }
             BufferUsage::Unbounded => vk::BufferUsageFlags::all(),
             BufferUsage::None => vk::BufferUsageFlags::empty(),
         }
@@ -283,7 +868,7 @@ impl From<BufferAccess> for vma::AllocationCreateFlags {
 }
 
 /// Information about the buffer usage.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct BufferUsageInfo {
     /// The location where the buffer should be allocated, either in device local
     /// memory or host visible memory. See the [`BufferMemoryLocation`] enum for
@@ -309,6 +894,10 @@ pub struct BufferUsageInfo {
     /// every memory type acceptable for this allocation. If the value is 0, it is ignored
     /// and all memory types are considered acceptable if all other conditions are met.
     pub memory_type: u32,
+
+    /// The priority of the buffer memory. See the [`MemoryPriority`] enum for more
+    /// information.
+    pub priority: MemoryPriority,
 }
 
 impl Default for BufferUsageInfo {
@@ -319,6 +908,41 @@ impl Default for BufferUsageInfo {
             access: BufferAccess::Random,
             usage: BufferUsage::Unbounded,
             memory_type: 0,
+            priority: MemoryPriority::default(),
+        }
+    }
+}
+
+/// The priority of a memory allocation, used by the driver to decide which allocations should
+/// stay in device local memory when the VRAM is oversubscribed and some allocations must be
+/// moved to system memory. This is only a hint, and it is ignored if the device does not
+/// support the `VK_EXT_memory_priority` extension.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum MemoryPriority {
+    /// The allocation can be evicted first, for example rarely used or streamed resources.
+    Low,
+
+    /// The default priority, for most resources.
+    #[default]
+    Normal,
+
+    /// The allocation should be evicted last. This should be used for critical resources
+    /// like render targets or frequently used textures.
+    High,
+
+    /// A custom priority, between 0.0 (lowest) and 1.0 (highest).
+    Custom(f32),
+}
+
+impl MemoryPriority {
+    /// Returns the priority as a value between 0.0 and 1.0, as expected by Vulkan.
+    #[must_use]
+    pub fn value(self) -> f32 {
+        match self {
+            MemoryPriority::Low => 0.0,
+            MemoryPriority::Normal => 0.5,
+            MemoryPriority::High => 1.0,
+            MemoryPriority::Custom(priority) => priority.clamp(0.0, 1.0),
         }
     }
 }
@@ -334,6 +958,9 @@ pub struct BufferCreateInfo<'a, T> {
 
     /// The data of the buffer.
     pub data: BufferDataInfo<'a, T>,
+
+    /// The name of the buffer, displayed in the validation messages and by graphics debuggers.
+    pub name: Option<&'a str>,
 }
 
 impl<T> Default for BufferCreateInfo<'_, T> {
@@ -342,6 +969,7 @@ impl<T> Default for BufferCreateInfo<'_, T> {
             usage: BufferUsageInfo::default(),
             alignment: core::mem::align_of::<T>(),
             data: BufferDataInfo::Uninitialized(0),
+            name: None,
         }
     }
 }
@@ -375,3 +1003,67 @@ impl<T> BufferDataInfo<'_, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slice_a_sub_buffer() {
+        assert_eq!(slice_range::<u32>(0, 16, 4..10), (16, 6));
+        assert_eq!(slice_range::<u32>(16, 6, 1..3), (20, 2));
+        assert_eq!(slice_range::<u32>(16, 6, 6..6), (40, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the 6 elements")]
+    fn slice_out_of_bounds() {
+        let _ = slice_range::<u32>(16, 6, 2..7);
+    }
+
+    #[test]
+    fn check_the_bounds_of_a_sub_buffer() {
+        check_bounds::<u32>(0, 16, 64);
+        check_bounds::<u32>(60, 1, 64);
+        check_bounds::<[u32; 3]>(24, 3, 64);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not fit in the buffer")]
+    fn sub_buffer_past_the_end() {
+        check_bounds::<u32>(48, 5, 64);
+    }
+
+    #[test]
+    #[should_panic(expected = "multiple of the size of its elements")]
+    fn sub_buffer_between_elements() {
+        check_bounds::<u32>(6, 1, 64);
+    }
+
+    #[test]
+    fn cast_a_sub_buffer() {
+        assert_eq!(cast_len::<u32, u16>(8, 4), 8);
+        assert_eq!(cast_len::<u32, [u32; 2]>(8, 4), 2);
+        assert_eq!(cast_len::<u32, [u32; 3]>(12, 6), 2);
+        assert_eq!(cast_len::<u32, u8>(4, 0), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "offset of the sub-buffer is not a multiple")]
+    fn cast_a_misaligned_sub_buffer() {
+        let _ = cast_len::<u32, u64>(4, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "offset of the sub-buffer is not a multiple")]
+    fn cast_to_an_element_larger_than_its_alignment() {
+        // The offset is aligned for `[u32; 3]`, but not a multiple of its size.
+        let _ = cast_len::<u32, [u32; 3]>(8, 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "size of the sub-buffer is not a multiple")]
+    fn cast_a_sub_buffer_of_partial_elements() {
+        let _ = cast_len::<u32, u64>(0, 3);
+    }
+}
@@ -1,28 +1,112 @@
-use crate::{buffer::Buffer, device::VulkanDevice, pipeline::Pipeline};
-use std::{marker::PhantomData, sync::Arc};
+use crate::{
+    buffer::{Buffer, SubBuffer},
+    descriptor::DescriptorSet,
+    device::VulkanDevice,
+    hazard::{Access, HazardTracker, ResourceUse},
+    image::{
+        depth_stencil_formats, format_block, has_stencil_component, is_depth_format, layout_access,
+        Image, SampleCount,
+    },
+    pipeline::{ComputePipeline, DepthBias, Pipeline},
+    query::QueryPool,
+    recorder::SecondaryCommands,
+    renderpass::{self, Framebuffer, RenderPassLayout},
+    semaphore::{Event, Fence},
+};
+use bytemuck::Pod;
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    ffi::CString,
+    marker::PhantomData,
+    ops::Range,
+    sync::Arc,
+};
+use vk::ExtDebugUtilsExtension;
 use vulkanalia::prelude::v1_3::*;
 
+/// The maximum number of bytes written by a single [`CommandBuffer::update_buffer`], as
+/// required by the Vulkan specification.
+pub const MAX_UPDATE_BUFFER_SIZE: vk::DeviceSize = 65536;
+
 /// A command pool. Command pools are used to allocate command buffers. Commands
 /// bufferdevices from the same pool must be accessed from the same thread. If you need
 /// to use command buffers from multiple threads, you need to create multiple
 /// command pools.
+///
+/// Command buffers are not freed when dropped, but given back to the pool. They are reused
+/// by the next command buffers allocated from the pool once it has been [reset], so a pool
+/// that lives across frames must be reset regularly.
+///
+/// [reset]: CommandPool::reset
+///
+/// The pool is typed by the [`Capability`] of its queue family, which is inherited by the
+/// command buffers allocated from it and restricts the commands they can record.
 #[derive(Debug)]
-pub struct CommandPool {
+pub struct CommandPool<Q: Capability = Graphics> {
     /// The device that owns the command pool.
     device: Arc<VulkanDevice>,
 
     /// The vulkan command pool object.
     inner: vk::CommandPool,
 
-    /// A marker to make `CommandPool` non-send, since command buffers from the
-    /// same pool must be accessed from the same thread.
-    _non_send: PhantomData<*const ()>,
+    /// The command buffers given back to the pool since it was last reset, with their level.
+    released: RefCell<Vec<(vk::CommandBufferLevel, vk::CommandBuffer)>>,
+
+    /// The command buffers that have been reset and can be reused, with their level.
+    available: RefCell<Vec<(vk::CommandBufferLevel, vk::CommandBuffer)>>,
+
+    /// The fence waited for by [`CommandBuffer::submit_and_wait`], created on its first use
+    /// and reset after each wait so it can be reused by the next submission.
+    fence: OnceCell<Fence>,
+
+    /// A marker to make `CommandPool` non-sync, since command buffers from the
+    /// same pool must be accessed from the same thread. The pool itself can be
+    /// moved to another thread when no command buffer borrows it.
+    _non_sync: PhantomData<Cell<()>>,
+
+    /// A marker to register the capability of the queue family during compile time.
+    capability: PhantomData<Q>,
 }
 
 impl CommandPool {
-    /// Create a new command pool for the given queue family.
+    /// Create a new command pool for the given queue family, which must support graphics,
+    /// compute and transfer operations, like the main queue family.
     #[must_use]
     pub fn new(device: Arc<VulkanDevice>, queue: u32, flags: vk::CommandPoolCreateFlags) -> Self {
+        Self::create(device, queue, flags)
+    }
+}
+
+impl CommandPool<Compute> {
+    /// Create a new command pool for the async compute queue family, or for the main queue
+    /// family if the device does not have an async compute queue.
+    #[must_use]
+    pub fn compute(device: Arc<VulkanDevice>, flags: vk::CommandPoolCreateFlags) -> Self {
+        let queues = device.queues_info();
+        let family = queues
+            .async_compute_family()
+            .unwrap_or(queues.main_family());
+        Self::create(device, family, flags)
+    }
+}
+
+impl CommandPool<Transfer> {
+    /// Create a new command pool for the async transfer queue family, or for the main queue
+    /// family if the device does not have an async transfer queue.
+    #[must_use]
+    pub fn transfer(device: Arc<VulkanDevice>, flags: vk::CommandPoolCreateFlags) -> Self {
+        let queues = device.queues_info();
+        let family = queues
+            .async_transfer_family()
+            .unwrap_or(queues.main_family());
+        Self::create(device, family, flags)
+    }
+}
+
+impl<Q: Capability> CommandPool<Q> {
+    /// Create a new command pool for the given queue family, which must support the
+    /// operations of the capability `Q`.
+    fn create(device: Arc<VulkanDevice>, queue: u32, flags: vk::CommandPoolCreateFlags) -> Self {
         let info = vk::CommandPoolCreateInfo::builder()
             .queue_family_index(queue)
             .flags(flags.into());
@@ -37,7 +121,11 @@ impl CommandPool {
         Self {
             device,
             inner,
-            _non_send: PhantomData,
+            released: RefCell::default(),
+            available: RefCell::default(),
+            fence: OnceCell::new(),
+            _non_sync: PhantomData,
+            capability: PhantomData,
         }
     }
 
@@ -46,9 +134,25 @@ impl CommandPool {
     pub const fn inner(&self) -> vk::CommandPool {
         self.inner
     }
+
+    /// Reset all the command buffers allocated from the pool, so the command buffers given
+    /// back to the pool can be reused. The pool cannot be reset while one of its command
+    /// buffers is alive, but the caller must ensure that the GPU has finished executing them.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn reset(&mut self, flags: vk::CommandPoolResetFlags) {
+        unsafe {
+            self.device
+                .logical()
+                .reset_command_pool(self.inner, flags)
+                .expect("Failed to reset command pool");
+        }
+
+        let released = std::mem::take(self.released.get_mut());
+        self.available.get_mut().extend(released);
+    }
 }
 
-impl Drop for CommandPool {
+impl<Q: Capability> Drop for CommandPool<Q> {
     fn drop(&mut self) {
         unsafe {
             self.device.logical().destroy_command_pool(self.inner, None);
@@ -75,22 +179,66 @@ impl State for Recording {}
 pub struct Executable;
 impl State for Executable {}
 
+/// The operations supported by the queue family of a command pool. Every queue family
+/// supports transfer operations, so commands like copies and barriers can be recorded in any
+/// command buffer.
+pub trait Capability {}
+
+/// A capability that allows recording compute commands, like dispatches.
+pub trait ComputeCapability: Capability {}
+
+/// A capability that allows recording graphics commands, like draws.
+pub trait GraphicsCapability: ComputeCapability {}
+
+/// The queue family supports graphics, compute and transfer operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Graphics;
+impl Capability for Graphics {}
+impl ComputeCapability for Graphics {}
+impl GraphicsCapability for Graphics {}
+
+/// The queue family supports compute and transfer operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Compute;
+impl Capability for Compute {}
+impl ComputeCapability for Compute {}
+
+/// The queue family only supports transfer operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transfer;
+impl Capability for Transfer {}
+
 /// A command buffer. Command buffers are used to record commands that will be
-/// submitted to the GPU.
+/// submitted to the GPU. Command buffers allocated from a pool with a [`Transfer`] or
+/// [`Compute`] capability cannot record commands unsupported by their queue family.
 #[derive(Debug)]
-pub struct CommandBuffer<'pool, T: State = Idle> {
+pub struct CommandBuffer<'pool, T: State = Idle, Q: Capability = Graphics> {
     /// The vulkan command buffer object.
     inner: vk::CommandBuffer,
 
     /// The command pool that allocated the command buffer.
-    pool: &'pool CommandPool,
+    pool: &'pool CommandPool<Q>,
 
     /// A marker to register the state of the command buffer
     /// during compile time.
     state: PhantomData<T>,
+
+    /// The uses of the resources recorded so far, if the hazard tracking was enabled when
+    /// the command buffer was allocated.
+    hazards: Option<Box<HazardTracker>>,
+
+    /// The layout of the last bound pipeline, used to update its push constants.
+    layout: Option<vk::PipelineLayout>,
+
+    /// Whether the command buffer is a primary or a secondary command buffer.
+    level: vk::CommandBufferLevel,
+
+    /// The framebuffers of the render passes recorded when dynamic rendering is not supported,
+    /// destroyed once the command buffer is dropped and the frame using it is complete.
+    framebuffers: Vec<Framebuffer>,
 }
 
-impl<T: State> CommandBuffer<'_, T> {
+impl<T: State, Q: Capability> CommandBuffer<'_, T, Q> {
     /// Returns the vulkan command buffer object.
     #[must_use]
     pub const fn inner(&self) -> vk::CommandBuffer {
@@ -102,28 +250,63 @@ impl<T: State> CommandBuffer<'_, T> {
     pub fn device(&self) -> &Arc<VulkanDevice> {
         &self.pool.device
     }
+
+    /// Record the uses of resources with the hazard tracker, if the tracking is enabled.
+    fn track(&mut self, record: impl FnOnce(&mut HazardTracker)) {
+        if let Some(hazards) = &mut self.hazards {
+            record(hazards);
+        }
+    }
 }
 
-impl<'pool> CommandBuffer<'pool, Idle> {
-    /// Allocate a new primary command buffer from the given pool.
+impl<'pool, Q: Capability> CommandBuffer<'pool, Idle, Q> {
+    /// Allocate a new primary command buffer from the given pool, reusing a command buffer
+    /// given back to the pool before its last reset if there is one.
+    #[must_use]
+    pub fn new(pool: &'pool CommandPool<Q>) -> Self {
+        Self::allocate(pool, vk::CommandBufferLevel::PRIMARY)
+    }
+
+    /// Allocate a new secondary command buffer from the given pool. Secondary command buffers
+    /// are recorded with [`CommandBuffer::start_secondary_recording`] and executed by a
+    /// primary command buffer, which allows recording a render pass from several threads.
     #[must_use]
-    pub fn new(pool: &'pool CommandPool) -> Self {
-        let info = vk::CommandBufferAllocateInfo::builder()
-            .level(vk::CommandBufferLevel::PRIMARY)
-            .command_pool(pool.inner())
-            .command_buffer_count(1);
+    pub fn secondary(pool: &'pool CommandPool<Q>) -> Self {
+        Self::allocate(pool, vk::CommandBufferLevel::SECONDARY)
+    }
 
-        let inner = unsafe {
-            pool.device
-                .logical()
-                .allocate_command_buffers(&info)
-                .expect("Failed to allocate command buffer")[0]
+    /// Allocate a new command buffer of the given level, reusing a command buffer of the same
+    /// level given back to the pool before its last reset if there is one.
+    fn allocate(pool: &'pool CommandPool<Q>, level: vk::CommandBufferLevel) -> Self {
+        let reused = {
+            let mut available = pool.available.borrow_mut();
+            available
+                .iter()
+                .position(|&(reusable, _)| reusable == level)
+                .map(|index| available.swap_remove(index).1)
         };
+        let inner = reused.unwrap_or_else(|| {
+            let info = vk::CommandBufferAllocateInfo::builder()
+                .level(level)
+                .command_pool(pool.inner())
+                .command_buffer_count(1);
+
+            unsafe {
+                pool.device
+                    .logical()
+                    .allocate_command_buffers(&info)
+                    .expect("Failed to allocate command buffer")[0]
+            }
+        });
 
         Self {
             state: PhantomData,
+            hazards: HazardTracker::new(),
+            layout: None,
+            level,
             inner,
             pool,
+            framebuffers: Vec::new(),
         }
     }
 
@@ -131,7 +314,8 @@ impl<'pool> CommandBuffer<'pool, Idle> {
     /// command buffer will be in the recording state after this method is called,
     /// allowing you to record commands.
     #[must_use]
-    pub fn start_recording(self) -> CommandBuffer<'pool, Recording> {
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn start_recording(self) -> CommandBuffer<'pool, Recording, Q> {
         let begin_info = vk::CommandBufferBeginInfo::builder()
             .flags(vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT);
 
@@ -142,23 +326,145 @@ impl<'pool> CommandBuffer<'pool, Idle> {
                 .begin_command_buffer(self.inner, &begin_info)
                 .expect("Failed to begin command buffer");
         }
+        self.into_recording()
+    }
+
+    /// Consumes the idle secondary command buffer and starts recording commands executed
+    /// inside the dynamic render pass instance described by `info`. The viewport and the
+    /// scissor are set to the render area, since secondary command buffers do not inherit
+    /// the dynamic state of the primary command buffer.
+    ///
+    /// # Panics
+    /// Panics if the command buffer is not a secondary command buffer.
+    #[must_use]
+    pub fn start_secondary_recording(
+        self,
+        info: &InheritanceInfo,
+    ) -> CommandBuffer<'pool, Recording, Q> {
+        assert_eq!(
+            self.level,
+            vk::CommandBufferLevel::SECONDARY,
+            "Only secondary command buffers inherit a render pass instance"
+        );
+
+        // Without dynamic rendering, the secondary command buffer inherits the first subpass
+        // of a render pass compatible with the one started by the primary command buffer.
+        let device = self.device().clone();
+        let (depth_format, stencil_format) = depth_stencil_formats(info.depth_format);
+        let mut rendering = vk::CommandBufferInheritanceRenderingInfo::builder()
+            .view_mask(info.view_mask)
+            .color_attachment_formats(&info.color_formats)
+            .depth_attachment_format(depth_format)
+            .stencil_attachment_format(stencil_format)
+            .rasterization_samples(info.samples.into());
+        let mut inheritance = vk::CommandBufferInheritanceInfo::builder();
+        if device.supports_dynamic_rendering() {
+            inheritance = inheritance.push_next(&mut rendering);
+        } else {
+            let layout =
+                RenderPassLayout::compatible(&info.color_formats, info.depth_format, info.samples)
+                    .with_view_mask(info.view_mask);
+            inheritance = inheritance
+                .render_pass(device.render_pass(&layout))
+                .subpass(0);
+        }
+        let begin_info = vk::CommandBufferBeginInfo::builder()
+            .flags(
+                vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT
+                    | vk::CommandBufferUsageFlags::RENDER_PASS_CONTINUE,
+            )
+            .inheritance_info(&inheritance);
+
+        let render_area = vk::Rect2D::builder().extent(info.render_area).build();
+        let viewport = vk::Viewport::builder()
+            .width(info.render_area.width as f32)
+            .height(info.render_area.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build();
+
+        unsafe {
+            let device = device.logical();
+            device
+                .begin_command_buffer(self.inner, &begin_info)
+                .expect("Failed to begin command buffer");
+            device.cmd_set_viewport(self.inner, 0, &[viewport]);
+            device.cmd_set_scissor(self.inner, 0, &[render_area]);
+        }
+        self.into_recording()
+    }
 
+    /// Change the state of the command buffer to recording, once it has begun.
+    fn into_recording(self) -> CommandBuffer<'pool, Recording, Q> {
         // Encapsulate the command buffer in a ManuallyDrop to change the state
         // without running the destructor.
-        let command = std::mem::ManuallyDrop::new(self);
+        let mut command = std::mem::ManuallyDrop::new(self);
         CommandBuffer {
             state: PhantomData,
+            hazards: command.hazards.take(),
+            layout: command.layout,
+            level: command.level,
             inner: command.inner,
             pool: command.pool,
+            framebuffers: std::mem::take(&mut command.framebuffers),
         }
     }
 }
 
-impl<'pool> CommandBuffer<'pool, Recording> {
+impl<'pool, Q: Capability> CommandBuffer<'pool, Recording, Q> {
+    /// Set the label of the next commands, reported by the hazard tracking when one of them
+    /// causes a hazard. See the [`hazard`](crate::hazard) module.
+    #[must_use]
+    pub fn label(mut self, label: &str) -> Self {
+        self.track(|hazards| hazards.set_label(label));
+        self
+    }
+
+    /// Open a debug label region, grouping the next commands under the given name in graphics
+    /// debuggers like RenderDoc or Nsight, until the matching [`CommandBuffer::end_label`].
+    /// Regions can be nested. The name is also used as the label of the next commands for the
+    /// hazard tracking, like [`CommandBuffer::label`]. The region is only recorded if the
+    /// debug utils extension is enabled.
+    ///
+    /// # Panics
+    /// Panics if the name contains a null byte.
+    #[must_use]
+    pub fn begin_label(mut self, name: &str) -> Self {
+        self.track(|hazards| hazards.set_label(name));
+        if !self.device().debug_utils() {
+            return self;
+        }
+
+        let name = CString::new(name).expect("Label names must not contain null bytes");
+        let label = vk::DebugUtilsLabelEXT::builder().label_name(name.as_bytes_with_nul());
+        unsafe {
+            self.device()
+                .instance()
+                .cmd_begin_debug_utils_label_ext(self.inner, &label);
+        }
+        self
+    }
+
+    /// Close the last debug label region opened with [`CommandBuffer::begin_label`].
+    #[must_use]
+    pub fn end_label(self) -> Self {
+        if self.device().debug_utils() {
+            unsafe {
+                self.device()
+                    .instance()
+                    .cmd_end_debug_utils_label_ext(self.inner);
+            }
+        }
+        self
+    }
+
+    /// Insert a pipeline barrier, synchronizing the commands before and after it and
+    /// transitioning the layout of images.
     #[must_use]
-    pub fn pipeline_barrier(self, info: PipelineBarrierInfo) -> Self {
-        let buffers_barriers: [vk::BufferMemoryBarrier; 0] = [];
+    pub fn pipeline_barrier(mut self, info: PipelineBarrierInfo) -> Self {
+        self.track(|hazards| hazards.barrier(&info));
         let memories_barriers: [vk::MemoryBarrier; 0] = [];
+        let buffers_barriers = info.buffers_barriers.as_slice();
         let images_barriers = info.images_barriers.as_slice();
 
         unsafe {
@@ -168,80 +474,648 @@ impl<'pool> CommandBuffer<'pool, Recording> {
                 info.dst_stage_mask.into(),
                 vk::DependencyFlags::empty(),
                 &memories_barriers,
-                &buffers_barriers,
-                &images_barriers,
+                buffers_barriers,
+                images_barriers,
             );
         }
         self
     }
 
-    /// Bind a graphic pipeline to the command buffer.
+    /// Signal the event once all the previous commands have completed the given stages. This
+    /// is the first half of a split barrier, completed by [`wait_events`](Self::wait_events).
     #[must_use]
-    pub fn bind_graphic_pipeline(self, pipeline: &Pipeline) -> Self {
+    pub fn set_event(self, event: &Event, stage_mask: vk::PipelineStageFlags) -> Self {
         unsafe {
-            self.device().logical().cmd_bind_pipeline(
+            self.device()
+                .logical()
+                .cmd_set_event(self.inner, event.inner(), stage_mask);
+        }
+        self
+    }
+
+    /// Reset the event to the unsignaled state once all the previous commands have completed
+    /// the given stages.
+    #[must_use]
+    pub fn reset_event(self, event: &Event, stage_mask: vk::PipelineStageFlags) -> Self {
+        unsafe {
+            self.device()
+                .logical()
+                .cmd_reset_event(self.inner, event.inner(), stage_mask);
+        }
+        self
+    }
+
+    /// Wait for the events to be signaled before executing the given stages of the next
+    /// commands, and apply the memory barriers and layout transitions of the info like
+    /// [`pipeline_barrier`](Self::pipeline_barrier) does. The source stage mask must contain
+    /// all the stages given when the events were set, and the events must have been set by
+    /// previous commands of the same queue or by the host.
+    #[must_use]
+    pub fn wait_events(mut self, events: &[&Event], info: PipelineBarrierInfo) -> Self {
+        self.track(|hazards| hazards.barrier(&info));
+        let events = events.iter().map(|event| event.inner()).collect::<Vec<_>>();
+        let memories_barriers: [vk::MemoryBarrier; 0] = [];
+
+        unsafe {
+            self.device().logical().cmd_wait_events(
                 self.inner,
-                vk::PipelineBindPoint::GRAPHICS,
-                pipeline.inner(),
+                &events,
+                info.src_stage_mask,
+                info.dst_stage_mask,
+                &memories_barriers,
+                &info.buffers_barriers,
+                &info.images_barriers,
             );
         }
         self
     }
 
-    /// Bind a vertex buffer to the command buffer.
+    /// Transition the whole image to the given layout. The previous layout of each mip level
+    /// and array layer is tracked by the image, and the stages and accesses to synchronize are
+    /// deduced from the layouts. See [`layout_access`].
     #[must_use]
-    pub fn bind_vertex_buffer(self, buffer: &Buffer) -> Self {
-        let offset = buffer.start_offset();
-        let buffers = [buffer.inner()];
+    pub fn transition_image(self, image: &Image, layout: vk::ImageLayout) -> Self {
+        let mip_levels = 0..image.mip_levels();
+        let array_layers = 0..image.array_layers();
+        self.transition_subresources(image, mip_levels, array_layers, layout)
+    }
+
+    /// Transition the given mip levels and array layers of an image to the given layout, like
+    /// [`CommandBuffer::transition_image`].
+    ///
+    /// # Panics
+    /// Panics if the ranges are empty or exceed the mip levels or array layers of the image.
+    #[must_use]
+    pub fn transition_subresources(
+        self,
+        image: &Image,
+        mip_levels: Range<u32>,
+        array_layers: Range<u32>,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        let transitions = image.record_transition(mip_levels, array_layers, layout);
+        if transitions.is_empty() {
+            return self;
+        }
+
+        let (dst_stage_mask, dst_access) = layout_access(layout);
+        let mut src_stage_mask = vk::PipelineStageFlags::empty();
+        let images_barriers = transitions
+            .into_iter()
+            .map(|(old, range)| {
+                let (stage, src_access) = layout_access(old);
+                src_stage_mask |= stage;
+                vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(src_access)
+                    .dst_access_mask(dst_access)
+                    .old_layout(old)
+                    .new_layout(layout)
+                    .subresource_range(range)
+                    .image(image.inner())
+                    .build()
+            })
+            .collect();
+
+        self.pipeline_barrier(PipelineBarrierInfo {
+            src_stage_mask,
+            dst_stage_mask,
+            images_barriers,
+            ..Default::default()
+        })
+    }
+
+    /// Copy the given number of bytes from the start of a buffer to the start of another
+    /// buffer.
+    #[must_use]
+    pub fn copy_buffer(
+        mut self,
+        source: &Buffer,
+        destination: &Buffer,
+        size: vk::DeviceSize,
+    ) -> Self {
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(source.inner(), Access::Read));
+            hazards.use_resource(ResourceUse::buffer(destination.inner(), Access::Write));
+        });
+
+        let region = vk::BufferCopy::builder()
+            .src_offset(0)
+            .dst_offset(0)
+            .size(size)
+            .build();
+
+        unsafe {
+            self.device().logical().cmd_copy_buffer(
+                self.inner,
+                source.inner(),
+                destination.inner(),
+                &[region],
+            );
+        }
+        self
+    }
+
+    /// Fill a range of a buffer with the given 32-bit word, repeated. The range is relative
+    /// to the start of the buffer, for example `0..4` to reset a counter written by a compute
+    /// shader at the beginning of each frame.
+    ///
+    /// # Panics
+    /// Panics if the range is empty, exceeds the size of the buffer, or if its bounds are not
+    /// multiples of 4.
+    #[must_use]
+    pub fn fill_buffer(mut self, buffer: &Buffer, range: Range<vk::DeviceSize>, data: u32) -> Self {
+        assert!(
+            !range.is_empty() && range.end <= buffer.size(),
+            "Invalid buffer range"
+        );
+        assert!(
+            range.start % 4 == 0 && range.end % 4 == 0,
+            "The filled range must be aligned to 4 bytes"
+        );
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Write));
+        });
+
+        unsafe {
+            self.device().logical().cmd_fill_buffer(
+                self.inner,
+                buffer.inner(),
+                range.start,
+                range.end - range.start,
+                data,
+            );
+        }
+        self
+    }
+
+    /// Write the given data into a buffer, starting at the given offset in bytes relative to
+    /// the start of the buffer. The data is copied into the command buffer when the command is
+    /// recorded, which makes it convenient for small updates of a device local buffer without
+    /// going through a staging buffer.
+    ///
+    /// # Panics
+    /// Panics if the data is empty or larger than 65536 bytes, if it does not fit in the
+    /// remaining range of the buffer after the offset, or if the offset or the size of the data
+    /// is not a multiple of 4.
+    #[must_use]
+    pub fn update_buffer(mut self, buffer: &Buffer, offset: vk::DeviceSize, data: &[u8]) -> Self {
+        let size = data.len() as vk::DeviceSize;
+        assert!(
+            !data.is_empty() && size <= MAX_UPDATE_BUFFER_SIZE,
+            "Buffer updates must be between 1 and {MAX_UPDATE_BUFFER_SIZE} bytes, got {size}"
+        );
+        assert!(
+            offset <= buffer.size() && size <= buffer.size() - offset,
+            "The data does not fit in the buffer after the offset {offset}"
+        );
+        assert!(
+            offset % 4 == 0 && size % 4 == 0,
+            "The updated range must be aligned to 4 bytes"
+        );
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Write));
+        });
 
         unsafe {
             self.device()
                 .logical()
-                .cmd_bind_vertex_buffers(self.inner, 0, &buffers, &[offset]);
+                .cmd_update_buffer(self.inner, buffer.inner(), offset, data);
         }
         self
     }
 
-    /// Start a dynamic render pass instance
+    /// Write a single value into a buffer, starting at the given offset in bytes relative to
+    /// the start of the buffer, for example a uniform block of a device local buffer. See
+    /// [`CommandBuffer::update_buffer`] for the restrictions on the offset and the size.
     #[must_use]
-    pub fn start_rendering(self, info: RenderingInfo) -> Self {
-        let render_area = vk::Rect2D::builder()
-            .extent(vk::Extent2D::from(info.render_area))
+    pub fn update_buffer_with<T: Pod>(
+        self,
+        buffer: &Buffer,
+        offset: vk::DeviceSize,
+        value: &T,
+    ) -> Self {
+        self.update_buffer(buffer, offset, bytemuck::bytes_of(value))
+    }
+
+    /// Clear all the mip levels and array layers of a color image outside of a render pass.
+    /// The image must be in the given layout, either `vk::ImageLayout::TRANSFER_DST_OPTIMAL`
+    /// or `vk::ImageLayout::GENERAL`.
+    #[must_use]
+    pub fn clear_color_image(
+        mut self,
+        image: &Image,
+        layout: vk::ImageLayout,
+        color: vk::ClearColorValue,
+    ) -> Self {
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::image(image.inner(), Access::Write, layout));
+        });
+
+        unsafe {
+            self.device().logical().cmd_clear_color_image(
+                self.inner,
+                image.inner(),
+                layout,
+                &color,
+                &[image.subresource_range()],
+            );
+        }
+        self
+    }
+
+    /// Clear all the mip levels, array layers and aspects of a depth, stencil or depth-stencil
+    /// image outside of a render pass. The image must be in the given layout, either
+    /// `vk::ImageLayout::TRANSFER_DST_OPTIMAL` or `vk::ImageLayout::GENERAL`.
+    ///
+    /// # Panics
+    /// Panics if the image does not have a depth or a stencil format.
+    #[must_use]
+    pub fn clear_depth_stencil_image(
+        mut self,
+        image: &Image,
+        layout: vk::ImageLayout,
+        value: vk::ClearDepthStencilValue,
+    ) -> Self {
+        assert!(
+            is_depth_format(image.format()) || has_stencil_component(image.format()),
+            "Cannot clear the depth or the stencil of an image in {:?}",
+            image.format()
+        );
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::image(image.inner(), Access::Write, layout));
+        });
+
+        unsafe {
+            self.device().logical().cmd_clear_depth_stencil_image(
+                self.inner,
+                image.inner(),
+                layout,
+                &value,
+                &[image.subresource_range()],
+            );
+        }
+        self
+    }
+
+    /// Copy the start of a buffer into a sub-buffer, filling the whole sub-buffer.
+    #[must_use]
+    pub fn copy_to_sub_buffer<T>(mut self, source: &Buffer, destination: &SubBuffer<T>) -> Self {
+        let buffer = destination.buffer();
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(source.inner(), Access::Read));
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Write));
+        });
+
+        let region = vk::BufferCopy::builder()
+            .src_offset(0)
+            .dst_offset(destination.offset())
+            .size(destination.size())
             .build();
 
-        let rendering_info = vk::RenderingInfo::builder()
-            .color_attachments(&info.colors_attachements)
-            .render_area(render_area)
-            .layer_count(1);
+        unsafe {
+            self.device().logical().cmd_copy_buffer(
+                self.inner,
+                source.inner(),
+                buffer.inner(),
+                &[region],
+            );
+        }
+        self
+    }
+
+    /// Copy the content of a buffer to the first mip level of an image. The data in the buffer
+    /// must be tightly packed, and the image must be in the given layout, usually
+    /// `vk::ImageLayout::TRANSFER_DST_OPTIMAL`.
+    #[must_use]
+    pub fn copy_buffer_to_image(
+        self,
+        buffer: &Buffer,
+        image: &Image,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        self.copy_buffer_to_image_layers(buffer, image, 0..1, layout)
+    }
+
+    /// Copy the content of a buffer to the given array layers of the first mip level of an
+    /// image, for example the six faces of a cube map. The layers must be tightly packed one
+    /// after the other in the buffer, and the image must be in the given layout, usually
+    /// `vk::ImageLayout::TRANSFER_DST_OPTIMAL`.
+    ///
+    /// # Panics
+    /// Panics if the range of layers is empty or exceeds the array layers of the image.
+    #[must_use]
+    pub fn copy_buffer_to_image_layers(
+        mut self,
+        buffer: &Buffer,
+        image: &Image,
+        array_layers: Range<u32>,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        assert!(
+            !array_layers.is_empty() && array_layers.end <= image.array_layers(),
+            "Invalid array layer range"
+        );
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Read));
+            hazards.use_resource(ResourceUse::image(image.inner(), Access::Write, layout));
+        });
+
+        let region = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: image.copy_aspect(),
+                mip_level: 0,
+                base_array_layer: array_layers.start,
+                layer_count: array_layers.len() as u32,
+            })
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width: image.extent().width,
+                height: image.extent().height,
+                depth: 1,
+            })
+            .build();
 
         unsafe {
-            self.device()
-                .logical()
-                .cmd_begin_rendering(self.inner, &rendering_info);
+            self.device().logical().cmd_copy_buffer_to_image(
+                self.inner,
+                buffer.inner(),
+                image.inner(),
+                layout,
+                &[region],
+            );
         }
         self
     }
 
-    /// Draw primitives.
+    /// Copy the content of a buffer to the given mip levels of an image, for example a mip
+    /// chain generated offline and loaded from a KTX2 file. Each level holds all the array
+    /// layers of the image, and the levels must be tightly packed one after the other in the
+    /// buffer, starting with the first level of the range. The image must be in the given
+    /// layout, usually `vk::ImageLayout::TRANSFER_DST_OPTIMAL`.
     ///
-    /// # Safety
-    /// TODO
+    /// # Panics
+    /// Panics if the range of levels is empty or exceeds the mip levels of the image, or if
+    /// the size of the blocks of the image format is not known.
     #[must_use]
-    pub unsafe fn draw(self, info: DrawInfo) -> Self {
-        self.device().logical().cmd_draw(
-            self.inner,
-            info.vertex_count,
-            info.instance_count,
-            info.first_vertex,
-            info.first_instance,
+    pub fn copy_buffer_to_image_mips(
+        mut self,
+        buffer: &Buffer,
+        image: &Image,
+        mip_levels: Range<u32>,
+        layout: vk::ImageLayout,
+    ) -> Self {
+        assert!(
+            !mip_levels.is_empty() && mip_levels.end <= image.mip_levels(),
+            "Invalid mip level range"
         );
+        let block = format_block(image.format())
+            .unwrap_or_else(|| panic!("Cannot copy mip levels in {:?}", image.format()));
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Read));
+            hazards.use_resource(ResourceUse::image(image.inner(), Access::Write, layout));
+        });
+
+        let mut offset = 0;
+        let regions = mip_levels
+            .map(|level| {
+                let extent = image.mip_extent(level);
+                let region = vk::BufferImageCopy::builder()
+                    .buffer_offset(offset)
+                    .buffer_row_length(0)
+                    .buffer_image_height(0)
+                    .image_subresource(vk::ImageSubresourceLayers {
+                        aspect_mask: image.copy_aspect(),
+                        mip_level: level,
+                        base_array_layer: 0,
+                        layer_count: image.array_layers(),
+                    })
+                    .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+                    .image_extent(vk::Extent3D {
+                        width: extent.width,
+                        height: extent.height,
+                        depth: 1,
+                    })
+                    .build();
+                offset +=
+                    (block.image_size(extent) * image.array_layers() as usize) as vk::DeviceSize;
+                region
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.device().logical().cmd_copy_buffer_to_image(
+                self.inner,
+                buffer.inner(),
+                image.inner(),
+                layout,
+                &regions,
+            );
+        }
         self
     }
 
-    /// End a dynamic render pass instance
+    /// Copy regions of an image to another image, for example to copy a render target before
+    /// drawing over it, or to gather the levels of several images in an atlas. The formats of
+    /// the images must be size-compatible, and they must have the same number of samples.
+    /// The texels are copied as they are, without scaling nor conversion. The images must be
+    /// in the `TRANSFER_SRC_OPTIMAL` and `TRANSFER_DST_OPTIMAL` layouts respectively.
+    ///
+    /// # Panics
+    /// Panics if the images do not have the same number of samples, or if no region is given.
     #[must_use]
-    pub fn stop_rendering(self) -> Self {
-        unsafe { self.device().logical().cmd_end_rendering(self.inner) }
+    pub fn copy_image(
+        mut self,
+        source: &Image,
+        destination: &Image,
+        regions: &[vk::ImageCopy],
+    ) -> Self {
+        assert_eq!(
+            source.samples(),
+            destination.samples(),
+            "Copied images must have the same number of samples"
+        );
+        assert!(!regions.is_empty(), "At least one region must be copied");
+
+        let source_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        let destination_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::image(
+                source.inner(),
+                Access::Read,
+                source_layout,
+            ));
+            hazards.use_resource(ResourceUse::image(
+                destination.inner(),
+                Access::Write,
+                destination_layout,
+            ));
+        });
+
+        unsafe {
+            self.device().logical().cmd_copy_image(
+                self.inner,
+                source.inner(),
+                source_layout,
+                destination.inner(),
+                destination_layout,
+                regions,
+            );
+        }
+        self
+    }
+
+    /// Copy a region of the first mip level of an image to a buffer, tightly packed. The image
+    /// must be in the given layout, usually `vk::ImageLayout::TRANSFER_SRC_OPTIMAL`.
+    #[must_use]
+    pub fn copy_image_to_buffer(
+        self,
+        image: &Image,
+        layout: vk::ImageLayout,
+        region: vk::Rect2D,
+        buffer: &Buffer,
+    ) -> Self {
+        self.copy_image_subresource_to_buffer(image, layout, 0, 0, region, buffer)
+    }
+
+    /// Copy a region of the given mip level and array layer of an image to a buffer, tightly
+    /// packed, for example to read back a face of a cube map or a downsampled level. The image
+    /// must be in the given layout, usually `vk::ImageLayout::TRANSFER_SRC_OPTIMAL`.
+    ///
+    /// # Panics
+    /// Panics if the mip level or the array layer does not exist in the image.
+    #[must_use]
+    pub fn copy_image_subresource_to_buffer(
+        mut self,
+        image: &Image,
+        layout: vk::ImageLayout,
+        mip_level: u32,
+        array_layer: u32,
+        region: vk::Rect2D,
+        buffer: &Buffer,
+    ) -> Self {
+        assert!(
+            mip_level < image.mip_levels() && array_layer < image.array_layers(),
+            "Invalid image subresource"
+        );
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::image(image.inner(), Access::Read, layout));
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Write));
+        });
+
+        let copy = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: image.copy_aspect(),
+                mip_level,
+                base_array_layer: array_layer,
+                layer_count: 1,
+            })
+            .image_offset(vk::Offset3D {
+                x: region.offset.x,
+                y: region.offset.y,
+                z: 0,
+            })
+            .image_extent(vk::Extent3D {
+                width: region.extent.width,
+                height: region.extent.height,
+                depth: 1,
+            })
+            .build();
+
+        unsafe {
+            self.device().logical().cmd_copy_image_to_buffer(
+                self.inner,
+                image.inner(),
+                layout,
+                buffer.inner(),
+                &[copy],
+            );
+        }
+        self
+    }
+
+    /// Copy a whole swapchain image to a buffer, tightly packed. Swapchain images are not
+    /// wrapped in an [`Image`], so the image is given by its handle and is not tracked. The
+    /// image must be in the `vk::ImageLayout::TRANSFER_SRC_OPTIMAL` layout, and the swapchain
+    /// must [support readback](crate::swapchain::VulkanSwapchain::supports_readback).
+    #[must_use]
+    pub fn copy_swapchain_image_to_buffer(
+        mut self,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        buffer: &Buffer,
+    ) -> Self {
+        let layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::image(image, Access::Read, layout));
+            hazards.use_resource(ResourceUse::buffer(buffer.inner(), Access::Write));
+        });
+
+        let copy = vk::BufferImageCopy::builder()
+            .buffer_offset(0)
+            .buffer_row_length(0)
+            .buffer_image_height(0)
+            .image_subresource(vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count: 1,
+            })
+            .image_offset(vk::Offset3D { x: 0, y: 0, z: 0 })
+            .image_extent(vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            })
+            .build();
+
+        unsafe {
+            self.device().logical().cmd_copy_image_to_buffer(
+                self.inner,
+                image,
+                layout,
+                buffer.inner(),
+                &[copy],
+            );
+        }
+        self
+    }
+
+    /// Reset the given queries of a pool, so they can be written again. This must be recorded
+    /// outside of a render pass, before the queries are written.
+    #[must_use]
+    pub fn reset_query_pool(self, pool: &QueryPool, queries: Range<u32>) -> Self {
+        unsafe {
+            self.device().logical().cmd_reset_query_pool(
+                self.inner,
+                pool.inner(),
+                queries.start,
+                queries.end - queries.start,
+            );
+        }
+        self
+    }
+
+    /// Write the time at which all the previous commands have completed the given stage into
+    /// a query of a timestamp pool. The query must have been reset since it was last written.
+    #[must_use]
+    pub fn write_timestamp(
+        self,
+        stage: vk::PipelineStageFlags,
+        pool: &QueryPool,
+        query: u32,
+    ) -> Self {
+        debug_assert_eq!(pool.kind(), vk::QueryType::TIMESTAMP);
+        unsafe {
+            self.device()
+                .logical()
+                .cmd_write_timestamp(self.inner, stage, pool.inner(), query);
+        }
         self
     }
 
@@ -250,7 +1124,8 @@ impl<'pool> CommandBuffer<'pool, Recording> {
     /// this method is called, allowing you to submit the command buffer to the
     /// GPU.
     #[must_use]
-    pub fn stop_recording(self) -> CommandBuffer<'pool, Executable> {
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn stop_recording(self) -> CommandBuffer<'pool, Executable, Q> {
         unsafe {
             self.device()
                 .logical()
@@ -260,61 +1135,836 @@ impl<'pool> CommandBuffer<'pool, Recording> {
 
         // Encapsulate the command buffer in a ManuallyDrop to change the state
         // without running the destructor.
-        let command = std::mem::ManuallyDrop::new(self);
+        let mut command = std::mem::ManuallyDrop::new(self);
         CommandBuffer {
             state: PhantomData,
+            hazards: command.hazards.take(),
+            layout: command.layout,
+            level: command.level,
             inner: command.inner,
             pool: command.pool,
+            framebuffers: std::mem::take(&mut command.framebuffers),
         }
     }
 }
 
-impl<'pool> CommandBuffer<'pool, Executable> {
-    /// Submit the command buffer to a queue and wait for it to finish executing.
-    pub fn submit_and_wait(self, info: SubmitInfo) {
-        let commands = [self.inner];
-        let submit_info = vk::SubmitInfo::builder()
-            .wait_dst_stage_mask(&info.wait_dst_stage_mask)
-            .signal_semaphores(&info.signal_semaphores)
-            .wait_semaphores(&info.wait_semaphores)
-            .command_buffers(&commands);
+impl<Q: ComputeCapability> CommandBuffer<'_, Recording, Q> {
+    /// Bind a compute pipeline to the command buffer.
+    #[must_use]
+    pub fn bind_compute_pipeline(mut self, pipeline: &ComputePipeline) -> Self {
+        self.layout = Some(pipeline.layout());
+        unsafe {
+            self.device().logical().cmd_bind_pipeline(
+                self.inner,
+                vk::PipelineBindPoint::COMPUTE,
+                pipeline.inner(),
+            );
+        }
+        self
+    }
+
+    /// Dispatch compute work groups with the bound compute pipeline.
+    #[must_use]
+    pub fn dispatch(mut self, x: u32, y: u32, z: u32) -> Self {
+        self.track(|hazards| hazards.execute(vk::PipelineBindPoint::COMPUTE));
+        unsafe {
+            self.device().logical().cmd_dispatch(self.inner, x, y, z);
+        }
+        self
+    }
+
+    /// Bind a descriptor set to the given set number of a pipeline.
+    #[must_use]
+    pub fn bind_descriptor_set(
+        self,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        set: u32,
+        descriptor: &DescriptorSet,
+    ) -> Self {
+        self.bind_descriptor_set_with_offsets(bind_point, layout, set, descriptor, &[])
+    }
+
+    /// Bind a descriptor set to the given set number of a pipeline, with one offset for each
+    /// dynamic uniform or storage buffer of the set, in the order of their bindings.
+    #[must_use]
+    pub fn bind_descriptor_set_with_offsets(
+        mut self,
+        bind_point: vk::PipelineBindPoint,
+        layout: vk::PipelineLayout,
+        set: u32,
+        descriptor: &DescriptorSet,
+        dynamic_offsets: &[u32],
+    ) -> Self {
+        self.track(|hazards| hazards.bind(bind_point, set, descriptor.uses()));
+        unsafe {
+            self.device().logical().cmd_bind_descriptor_sets(
+                self.inner,
+                bind_point,
+                layout,
+                set,
+                &[descriptor.inner()],
+                dynamic_offsets,
+            );
+        }
+        self
+    }
+
+    /// Update the push constants of the last bound pipeline with the given value, starting at
+    /// the given offset. The value must match the layout of the push constant block declared
+    /// by the shaders, and the range must be declared when creating the pipeline.
+    ///
+    /// # Panics
+    /// Panics if no pipeline was bound to the command buffer.
+    #[must_use]
+    pub fn push_constants<T: Pod>(
+        self,
+        stages: vk::ShaderStageFlags,
+        offset: u32,
+        value: &T,
+    ) -> Self {
+        let layout = self
+            .layout
+            .expect("No pipeline bound to update the push constants of");
+        self.push_constant_bytes(layout, stages, offset, bytemuck::bytes_of(value))
+    }
 
+    /// Update the push constants of a pipeline layout with the given data, starting at
+    /// the given offset.
+    #[must_use]
+    pub fn push_constant_bytes(
+        self,
+        layout: vk::PipelineLayout,
+        stages: vk::ShaderStageFlags,
+        offset: u32,
+        data: &[u8],
+    ) -> Self {
         unsafe {
             self.device()
                 .logical()
-                .queue_submit(info.queue, &[submit_info], vk::Fence::null())
-                .expect("Failed to submit command buffer to graphics queue");
+                .cmd_push_constants(self.inner, layout, stages, offset, data);
+        }
+        self
+    }
+}
+
+impl<Q: GraphicsCapability> CommandBuffer<'_, Recording, Q> {
+    /// Bind a graphic pipeline to the command buffer.
+    #[must_use]
+    pub fn bind_graphic_pipeline(mut self, pipeline: &Pipeline) -> Self {
+        self.layout = Some(pipeline.layout());
+        unsafe {
+            self.device().logical().cmd_bind_pipeline(
+                self.inner,
+                vk::PipelineBindPoint::GRAPHICS,
+                pipeline.inner(),
+            );
         }
+        self
+    }
+
+    /// Bind a vertex buffer to the command buffer.
+    #[must_use]
+    pub fn bind_vertex_buffer(self, buffer: &Buffer) -> Self {
+        let buffers = [buffer.inner()];
 
         unsafe {
             self.device()
                 .logical()
-                .queue_wait_idle(info.queue)
-                .expect("Failed to wait for graphic queue to finish rendering");
+                .cmd_bind_vertex_buffers(self.inner, 0, &buffers, &[0]);
         }
+        self
     }
-}
 
-impl<T: State> Drop for CommandBuffer<'_, T> {
-    fn drop(&mut self) {
+    /// Bind several vertex buffers to consecutive bindings starting at `first_binding`, for
+    /// example the vertices and the instances of an
+    /// [`Instanced`](crate::pipeline::Instanced) pipeline. Each buffer is read from the given
+    /// offset, relative to its start.
+    ///
+    /// # Panics
+    /// Panics if the number of offsets does not match the number of buffers.
+    #[must_use]
+    pub fn bind_vertex_buffers(
+        self,
+        first_binding: u32,
+        buffers: &[&Buffer],
+        offsets: &[vk::DeviceSize],
+    ) -> Self {
+        assert_eq!(
+            buffers.len(),
+            offsets.len(),
+            "Each vertex buffer must have an offset"
+        );
+
+        let buffers = buffers
+            .iter()
+            .map(|buffer| buffer.inner())
+            .collect::<Vec<_>>();
+
+        unsafe {
+            self.device().logical().cmd_bind_vertex_buffers(
+                self.inner,
+                first_binding,
+                &buffers,
+                &offsets,
+            );
+        }
+        self
+    }
+
+    /// Bind an index buffer of 32-bit indices to the command buffer.
+    #[must_use]
+    pub fn bind_index_buffer(self, buffer: &Buffer) -> Self {
+        unsafe {
+            self.device().logical().cmd_bind_index_buffer(
+                self.inner,
+                buffer.inner(),
+                0,
+                vk::IndexType::UINT32,
+            );
+        }
+        self
+    }
+
+    /// Start a dynamic render pass instance. This also sets the viewport and the scissor
+    /// to cover the whole render area.
+    ///
+    /// When the device does not support dynamic rendering, a classic render pass equivalent to
+    /// the dynamic render pass is begun instead, with a framebuffer of the attachment views
+    /// created for this render pass. See the [`renderpass`] module.
+    ///
+    /// # Panics
+    /// Panics if dynamic rendering is not supported and the formats of the attachments are not
+    /// given, or if the render pass is suspended or resumed, or if a shading rate attachment
+    /// is used without support for the attachment fragment shading rate.
+    #[must_use]
+    pub fn start_rendering(mut self, info: RenderingInfo) -> Self {
+        self.track(|hazards| {
+            // The resolve views are null unless the attachment is resolved, and null views
+            // are never registered.
+            let attachments = info
+                .colors_attachements
+                .iter()
+                .chain(&info.depth_attachment);
+            for attachment in attachments {
+                let resolve = (
+                    attachment.resolve_image_view,
+                    attachment.resolve_image_layout,
+                );
+                for (view, layout) in [(attachment.image_view, attachment.image_layout), resolve] {
+                    if let Some(usage) = ResourceUse::view(view, Access::Write, layout) {
+                        hazards.use_resource(usage);
+                    }
+                }
+            }
+            if let Some(attachment) = &info.shading_rate_attachment {
+                let (view, layout) = (attachment.image_view, attachment.image_layout);
+                if let Some(usage) = ResourceUse::view(view, Access::Read, layout) {
+                    hazards.use_resource(usage);
+                }
+            }
+        });
+        assert!(
+            info.shading_rate_attachment.is_none()
+                || self.device().supports_attachment_shading_rate(),
+            "The device does not support the attachment fragment shading rate"
+        );
+
+        let render_area = vk::Rect2D::builder()
+            .extent(vk::Extent2D::from(info.render_area))
+            .build();
+
+        let viewport = vk::Viewport::builder()
+            .width(info.render_area.width as f32)
+            .height(info.render_area.height as f32)
+            .min_depth(0.0)
+            .max_depth(1.0)
+            .build();
+
+        if self.device().supports_dynamic_rendering() {
+            let mut rendering_info = vk::RenderingInfo::builder()
+                .flags(info.flags)
+                .color_attachments(&info.colors_attachements)
+                .render_area(render_area)
+                .layer_count(info.layer_count)
+                .view_mask(info.view_mask);
+            // A depth-stencil attachment is both the depth and the stencil attachment, and a
+            // stencil-only attachment is only the stencil attachment. Without a format, it is
+            // assumed to be a depth attachment.
+            if let Some(depth) = &info.depth_attachment {
+                let (depth_format, stencil_format) = depth_stencil_formats(info.depth_format);
+                if depth_format != vk::Format::UNDEFINED
+                    || info.depth_format == vk::Format::UNDEFINED
+                {
+                    rendering_info = rendering_info.depth_attachment(depth);
+                }
+                if stencil_format != vk::Format::UNDEFINED {
+                    rendering_info = rendering_info.stencil_attachment(depth);
+                }
+            }
+            let mut shading_rate = info.shading_rate_attachment;
+            if let Some(shading_rate) = &mut shading_rate {
+                rendering_info = rendering_info.push_next(shading_rate);
+            }
+            unsafe {
+                self.device()
+                    .logical()
+                    .cmd_begin_rendering(self.inner, &rendering_info);
+            }
+        } else {
+            self.begin_render_pass(&info, render_area);
+        }
+
+        unsafe {
+            let device = self.device().logical();
+            device.cmd_set_viewport(self.inner, 0, &[viewport]);
+            device.cmd_set_scissor(self.inner, 0, &[render_area]);
+        }
+        self
+    }
+
+    /// Set the viewports and the scissors starting at the given index, for the pipelines with
+    /// several viewports. The shaders select the viewport of each primitive by writing
+    /// `gl_ViewportIndex`. Starting a render pass only sets the first viewport and scissor.
+    ///
+    /// # Panics
+    /// Panics if there is not one scissor per viewport.
+    #[must_use]
+    pub fn set_viewports(
+        self,
+        first: u32,
+        viewports: &[vk::Viewport],
+        scissors: &[vk::Rect2D],
+    ) -> Self {
+        assert_eq!(
+            viewports.len(),
+            scissors.len(),
+            "There must be one scissor per viewport"
+        );
+        unsafe {
+            let device = self.device().logical();
+            device.cmd_set_viewport(self.inner, first, viewports);
+            device.cmd_set_scissor(self.inner, first, scissors);
+        }
+        self
+    }
+
+    /// Set the depth bias of the next draws, for the pipelines created with
+    /// [`PipelineCreateInfo::dynamic_depth_bias`], for example to use a different bias for each
+    /// cascade of a shadow map.
+    ///
+    /// # Panics
+    /// Panics if the depth bias is clamped and the device does not support it.
+    ///
+    /// [`PipelineCreateInfo::dynamic_depth_bias`]:
+    ///     crate::pipeline::PipelineCreateInfo::dynamic_depth_bias
+    #[must_use]
+    pub fn set_depth_bias(self, bias: DepthBias) -> Self {
+        assert!(
+            bias.clamp == 0.0 || self.device().supports_depth_bias_clamp(),
+            "The device does not support clamping the depth bias"
+        );
+        unsafe {
+            self.device().logical().cmd_set_depth_bias(
+                self.inner,
+                bias.constant_factor,
+                bias.clamp,
+                bias.slope_factor,
+            );
+        }
+        self
+    }
+
+    /// Set the width of the lines of the next draws, for the pipelines created with
+    /// [`PipelineCreateInfo::dynamic_line_width`].
+    ///
+    /// # Panics
+    /// Panics if the width is not 1.0 and the device does not support wide lines or a line
+    /// of this width.
+    ///
+    /// [`PipelineCreateInfo::dynamic_line_width`]:
+    ///     crate::pipeline::PipelineCreateInfo::dynamic_line_width
+    #[must_use]
+    pub fn set_line_width(self, width: f32) -> Self {
+        let [min_width, max_width] = self.device().limits().line_width_range;
+        assert!(
+            width == 1.0
+                || (self.device().supports_wide_lines()
+                    && (min_width..=max_width).contains(&width)),
+            "Unsupported line width"
+        );
         unsafe {
             self.device()
                 .logical()
-                .free_command_buffers(self.pool.inner(), &[self.inner]);
+                .cmd_set_line_width(self.inner, width);
         }
+        self
+    }
+
+    /// Begin a classic render pass equivalent to the given dynamic render pass instance, on
+    /// devices that do not support dynamic rendering.
+    fn begin_render_pass(&mut self, info: &RenderingInfo, render_area: vk::Rect2D) {
+        assert!(
+            !info
+                .flags
+                .intersects(vk::RenderingFlags::SUSPENDING | vk::RenderingFlags::RESUMING),
+            "Suspending and resuming render passes requires dynamic rendering"
+        );
+
+        let render_pass = self
+            .device()
+            .render_pass(&RenderPassLayout::from_rendering(info));
+        let (views, clear_values): (Vec<_>, Vec<_>) =
+            renderpass::attachments(info).into_iter().unzip();
+        let framebuffer = Framebuffer::new(
+            self.device().clone(),
+            render_pass,
+            &views,
+            info.render_area,
+            // With multiview, the layers rendered are given by the view mask of the subpass
+            // and the framebuffer must have a single layer.
+            if info.view_mask == 0 {
+                info.layer_count
+            } else {
+                1
+            },
+        );
+
+        let contents = if info
+            .flags
+            .contains(vk::RenderingFlags::CONTENTS_SECONDARY_COMMAND_BUFFERS)
+        {
+            vk::SubpassContents::SECONDARY_COMMAND_BUFFERS
+        } else {
+            vk::SubpassContents::INLINE
+        };
+        let begin_info = vk::RenderPassBeginInfo::builder()
+            .render_pass(render_pass)
+            .framebuffer(framebuffer.inner())
+            .render_area(render_area)
+            .clear_values(&clear_values);
+
+        unsafe {
+            self.device()
+                .logical()
+                .cmd_begin_render_pass(self.inner, &begin_info, contents);
+        }
+        self.framebuffers.push(framebuffer);
+    }
+
+    /// Draw primitives.
+    ///
+    /// # Safety
+    /// TODO
+    #[must_use]
+    pub unsafe fn draw(mut self, info: DrawInfo) -> Self {
+        self.track(|hazards| hazards.execute(vk::PipelineBindPoint::GRAPHICS));
+        self.device().logical().cmd_draw(
+            self.inner,
+            info.vertex_count,
+            info.instance_count,
+            info.first_vertex,
+            info.first_instance,
+        );
+        self
+    }
+
+    /// Draw indexed primitives, with the indices of the bound index buffer.
+    ///
+    /// # Safety
+    /// The indices must not reference vertices out of the bound vertex buffer.
+    #[must_use]
+    pub unsafe fn draw_indexed(mut self, info: DrawIndexedInfo) -> Self {
+        self.track(|hazards| hazards.execute(vk::PipelineBindPoint::GRAPHICS));
+        self.device().logical().cmd_draw_indexed(
+            self.inner,
+            info.index_count,
+            info.instance_count,
+            info.first_index,
+            info.vertex_offset,
+            info.first_instance,
+        );
+        self
+    }
+
+    /// Draw indexed primitives with the parameters of `draw_count` consecutive
+    /// `vk::DrawIndexedIndirectCommand` read from the start of the `commands` buffer.
+    ///
+    /// # Safety
+    /// The commands must not reference indices out of the bound index buffer, nor vertices out
+    /// of the bound vertex buffer.
+    ///
+    /// # Panics
+    /// Panics if more than one draw is requested and the multi draw indirect feature is not
+    /// enabled.
+    #[must_use]
+    pub unsafe fn draw_indexed_indirect(mut self, commands: &Buffer, draw_count: u32) -> Self {
+        assert!(
+            draw_count <= 1 || self.device().enabled_features().multi_draw_indirect == vk::TRUE,
+            "Multiple indirect draws require the multi draw indirect feature"
+        );
+
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(commands.inner(), Access::Read));
+            hazards.execute(vk::PipelineBindPoint::GRAPHICS);
+        });
+        self.device().logical().cmd_draw_indexed_indirect(
+            self.inner,
+            commands.inner(),
+            0,
+            draw_count,
+            std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+        );
+        self
+    }
+
+    /// Draw indexed primitives like [`CommandBuffer::draw_indexed_indirect`], with the number
+    /// of draws read as an `u32` from the start of the `count` buffer and clamped to
+    /// `max_draw_count`. This lets a compute shader emit a variable number of draws without
+    /// any readback.
+    ///
+    /// # Safety
+    /// The commands must not reference indices out of the bound index buffer, nor vertices out
+    /// of the bound vertex buffer, and the commands buffer must hold at least `max_draw_count`
+    /// commands.
+    ///
+    /// # Panics
+    /// Panics if the draw indirect count feature is not enabled, see
+    /// [`VulkanDevice::supports_draw_indirect_count`].
+    #[must_use]
+    pub unsafe fn draw_indexed_indirect_count(
+        mut self,
+        commands: &Buffer,
+        count: &Buffer,
+        max_draw_count: u32,
+    ) -> Self {
+        assert!(
+            self.device().supports_draw_indirect_count(),
+            "The draw indirect count feature is not supported by the device"
+        );
+
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::buffer(commands.inner(), Access::Read));
+            hazards.use_resource(ResourceUse::buffer(count.inner(), Access::Read));
+            hazards.execute(vk::PipelineBindPoint::GRAPHICS);
+        });
+        self.device().logical().cmd_draw_indexed_indirect_count(
+            self.inner,
+            commands.inner(),
+            0,
+            count.inner(),
+            0,
+            max_draw_count,
+            std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32,
+        );
+        self
+    }
+
+    /// Resolve the first mip level of a multisampled image into an image with a single sample,
+    /// for all the array layers of the source. Prefer resolving at the end of the render pass
+    /// with the resolve attachments of [`RenderingInfo`], which avoids reading back the samples
+    /// on tiled GPUs. The images must be in the `TRANSFER_SRC_OPTIMAL` and
+    /// `TRANSFER_DST_OPTIMAL` layouts respectively.
+    ///
+    /// # Panics
+    /// Panics if the source is not multisampled, if the destination is, or if their extent
+    /// differ.
+    #[must_use]
+    pub fn resolve_image(mut self, source: &Image, destination: &Image) -> Self {
+        assert!(
+            source.samples() != SampleCount::One && destination.samples() == SampleCount::One,
+            "Can only resolve a multisampled image into a single sampled image"
+        );
+        assert_eq!(
+            source.extent(),
+            destination.extent(),
+            "Resolved images must have the same extent"
+        );
+
+        let source_layout = vk::ImageLayout::TRANSFER_SRC_OPTIMAL;
+        let destination_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        self.track(|hazards| {
+            hazards.use_resource(ResourceUse::image(
+                source.inner(),
+                Access::Read,
+                source_layout,
+            ));
+            hazards.use_resource(ResourceUse::image(
+                destination.inner(),
+                Access::Write,
+                destination_layout,
+            ));
+        });
+
+        let subresource = vk::ImageSubresourceLayers {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            mip_level: 0,
+            base_array_layer: 0,
+            layer_count: source.array_layers(),
+        };
+        let region = vk::ImageResolve::builder()
+            .src_subresource(subresource)
+            .dst_subresource(subresource)
+            .extent(vk::Extent3D {
+                width: source.extent().width,
+                height: source.extent().height,
+                depth: 1,
+            })
+            .build();
+
+        unsafe {
+            self.device().logical().cmd_resolve_image(
+                self.inner,
+                source.inner(),
+                source_layout,
+                destination.inner(),
+                destination_layout,
+                &[region],
+            );
+        }
+        self
+    }
+
+    /// End a dynamic render pass instance, or the classic render pass begun instead when
+    /// dynamic rendering is not supported.
+    #[must_use]
+    pub fn stop_rendering(self) -> Self {
+        unsafe {
+            let device = self.device().logical();
+            if self.device().supports_dynamic_rendering() {
+                device.cmd_end_rendering(self.inner);
+            } else {
+                device.cmd_end_render_pass(self.inner);
+            }
+        }
+        self
+    }
+
+    /// Execute secondary command buffers recorded by a
+    /// [`ThreadedCommandRecorder`](crate::recorder::ThreadedCommandRecorder), in the order
+    /// they were recorded. The render pass they inherit must have been started with the
+    /// `CONTENTS_SECONDARY_COMMAND_BUFFERS` flag.
+    #[must_use]
+    pub fn execute_commands(self, commands: &SecondaryCommands<'_>) -> Self {
+        if !commands.handles().is_empty() {
+            unsafe {
+                self.device()
+                    .logical()
+                    .cmd_execute_commands(self.inner, commands.handles());
+            }
+        }
+        self
+    }
+}
+
+impl<Q: Capability> CommandBuffer<'_, Executable, Q> {
+    /// Submit the command buffer to a queue and wait for it to finish executing. Only this
+    /// submission is waited for, so the other work of the queue keeps executing. The wait uses
+    /// a fence owned by the command pool, so no fence is created for each submission.
+    ///
+    /// # Panics
+    /// Panics if timeline values are given for the wait or the signal semaphores, but not one
+    /// value per semaphore.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn submit_and_wait(mut self, info: SubmitInfo) {
+        // The framebuffers would be destroyed immediately when the command buffer is dropped
+        // outside of a frame, so they are kept until the GPU is done with them.
+        let framebuffers = std::mem::take(&mut self.framebuffers);
+        let pool = self.pool;
+        let fence = pool
+            .fence
+            .get_or_init(|| Fence::new(pool.device.clone(), vk::FenceCreateFlags::empty()));
+        self.submit(info, Some(fence));
+        fence.wait();
+        fence.reset();
+        drop(framebuffers);
+    }
+
+    /// Submit the command buffer to a queue without waiting for it to finish executing. The
+    /// given fence, if any, is signaled once the command buffer has finished executing. The
+    /// command buffer is given back to its pool, which must not be reset before the GPU has
+    /// finished executing it, for example by waiting for the fence.
+    ///
+    /// # Panics
+    /// Panics if timeline values are given for the wait or the signal semaphores, but not one
+    /// value per semaphore.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn submit(self, info: SubmitInfo, fence: Option<&Fence>) {
+        Self::submit_batch(vec![(self, info)], fence);
+    }
+
+    /// Submit several command buffers to the same queue with a single call to the driver,
+    /// which is cheaper than submitting them one by one. Each command buffer waits for and
+    /// signals the semaphores of its own submit info, and the given fence, if any, is signaled
+    /// once all of them have finished executing.
+    ///
+    /// # Panics
+    /// Panics if the batch is empty, if the submit infos do not all target the same queue, or
+    /// if timeline values are given for the wait or the signal semaphores of a submission,
+    /// but not one value per semaphore.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn submit_batch(submissions: Vec<(Self, SubmitInfo)>, fence: Option<&Fence>) {
+        let (first, first_info) = submissions.first().expect("Cannot submit an empty batch");
+        let device = first.device().clone();
+        let queue = first_info.queue;
+        for (_, info) in &submissions {
+            assert_eq!(
+                info.queue, queue,
+                "All the submissions of a batch must target the same queue"
+            );
+            assert!(
+                info.wait_values.is_empty() || info.wait_values.len() == info.wait_semaphores.len(),
+                "There must be one wait value per wait semaphore"
+            );
+            assert!(
+                info.signal_values.is_empty()
+                    || info.signal_values.len() == info.signal_semaphores.len(),
+                "There must be one signal value per signal semaphore"
+            );
+        }
+
+        let commands = submissions
+            .iter()
+            .map(|(command, _)| [command.inner])
+            .collect::<Vec<_>>();
+        let mut timelines = submissions
+            .iter()
+            .map(|(_, info)| {
+                vk::TimelineSemaphoreSubmitInfo::builder()
+                    .wait_semaphore_values(&info.wait_values)
+                    .signal_semaphore_values(&info.signal_values)
+            })
+            .collect::<Vec<_>>();
+        let submit_infos = submissions
+            .iter()
+            .zip(&commands)
+            .zip(&mut timelines)
+            .map(|(((_, info), commands), timeline)| {
+                let submit_info = vk::SubmitInfo::builder()
+                    .wait_dst_stage_mask(&info.wait_dst_stage_mask)
+                    .signal_semaphores(&info.signal_semaphores)
+                    .wait_semaphores(&info.wait_semaphores)
+                    .command_buffers(commands);
+                if info.wait_values.is_empty() && info.signal_values.is_empty() {
+                    submit_info
+                } else {
+                    submit_info.push_next(timeline)
+                }
+            })
+            .collect::<Vec<_>>();
+
+        unsafe {
+            device
+                .logical()
+                .queue_submit(
+                    queue,
+                    &submit_infos,
+                    fence.map_or(vk::Fence::null(), Fence::inner),
+                )
+                .expect("Failed to submit command buffers");
+        }
+    }
+}
+
+impl<T: State, Q: Capability> Drop for CommandBuffer<'_, T, Q> {
+    fn drop(&mut self) {
+        // The command buffer may still be executed by the GPU, so it is only reused after
+        // the pool is reset. It is freed with the pool otherwise.
+        self.pool
+            .released
+            .borrow_mut()
+            .push((self.level, self.inner));
     }
 }
 
 /// A pipeline barrier info.
+#[derive(Default)]
 pub struct PipelineBarrierInfo {
     pub src_stage_mask: vk::PipelineStageFlags,
     pub dst_stage_mask: vk::PipelineStageFlags,
+    pub buffers_barriers: Vec<vk::BufferMemoryBarrier>,
     pub images_barriers: Vec<vk::ImageMemoryBarrier>,
 }
 
 /// A rendering info.
 pub struct RenderingInfo {
+    /// The color attachments rendered to, in the order of the color formats of the pipelines
+    /// used in the render pass. Multiple attachments are written at once by the fragment
+    /// shader outputs with the matching location.
+    ///
+    /// With MSAA, the attachments are multisampled images and each of them can be resolved
+    /// at the end of the render pass by setting its `resolve_mode`, `resolve_image_view` and
+    /// `resolve_image_layout` to a single sampled image.
     pub colors_attachements: Vec<vk::RenderingAttachmentInfo>,
+
+    /// The depth attachment, if the pipelines used in the render pass have a depth format.
+    /// It must have the same number of samples as the color attachments. With a stencil or a
+    /// depth-stencil format, which must then be given in the depth format, it is also the
+    /// stencil attachment, and its load and store operations apply to both aspects.
+    pub depth_attachment: Option<vk::RenderingAttachmentInfo>,
+
+    /// The extent of the area rendered, which must fit in all the attachments.
+    pub render_area: vk::Extent2D,
+
+    /// The number of array layers rendered, starting at the first layer of the attachment
+    /// views. With several layers, the views must be array views covering them, and the
+    /// shaders select the layer of each primitive by writing `gl_Layer`, for example to
+    /// render all the cascades of a shadow map or all the faces of a cube map in one pass.
+    pub layer_count: u32,
+
+    /// The views rendered with multiview, as a bit mask of the array layers of the attachment
+    /// views, or 0 without multiview. Each draw is broadcast to all the views, and the shaders
+    /// read the index of the current view from `gl_ViewIndex`, for example to use the matrices
+    /// of the left or the right eye of a VR headset. The layer count is ignored when the view
+    /// mask is not 0, and the pipelines used must be created with the same view mask.
+    pub view_mask: u32,
+
+    /// The shading rate attachment, whose texels give the fragment shading rate of the regions
+    /// of the framebuffer of the texel size, for example to lower the rate at the edge of the
+    /// field of view. The image must be in the `FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR`
+    /// layout, and its texels encoded with [`shading_rate_texel`]. Only the pipelines whose
+    /// [`FragmentShadingRate`] uses the attachment read it.
+    ///
+    /// [`shading_rate_texel`]: crate::image::shading_rate_texel
+    /// [`FragmentShadingRate`]: crate::pipeline::FragmentShadingRate
+    pub shading_rate_attachment: Option<vk::RenderingFragmentShadingRateAttachmentInfoKHR>,
+
+    /// The flags of the render pass instance. With `CONTENTS_SECONDARY_COMMAND_BUFFERS`, the
+    /// render pass is only recorded by the secondary command buffers executed with
+    /// [`CommandBuffer::execute_commands`], and no command can be recorded directly in it.
+    pub flags: vk::RenderingFlags,
+
+    /// The formats of the color attachments, in the same order. Dynamic rendering does not need
+    /// them, but they are required to create the render pass used instead on devices that do
+    /// not support it, see [`VulkanDevice::supports_dynamic_rendering`].
+    pub color_formats: Vec<vk::Format>,
+
+    /// The format of the depth attachment, or `vk::Format::UNDEFINED` if there is none. It is
+    /// required by dynamic rendering too when the attachment has a stencil aspect.
+    pub depth_format: vk::Format,
+
+    /// The number of samples of the attachments.
+    pub samples: SampleCount,
+}
+
+/// The dynamic render pass instance in which a secondary command buffer is executed. It must
+/// match the attachments of the render pass started by the primary command buffer.
+#[derive(Debug, Clone, Default)]
+pub struct InheritanceInfo {
+    /// The formats of the color attachments.
+    pub color_formats: Vec<vk::Format>,
+
+    /// The format of the depth or depth-stencil attachment, or `vk::Format::UNDEFINED` if there
+    /// is none.
+    pub depth_format: vk::Format,
+
+    /// The number of samples of the attachments.
+    pub samples: SampleCount,
+
+    /// The views rendered with multiview, or 0 without multiview.
+    pub view_mask: u32,
+
+    /// The extent of the area rendered, used to set the viewport and the scissor.
     pub render_area: vk::Extent2D,
 }
 
@@ -325,9 +1975,29 @@ pub struct DrawInfo {
     pub first_instance: u32,
 }
 
+pub struct DrawIndexedInfo {
+    pub index_count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub vertex_offset: i32,
+    pub first_instance: u32,
+}
+
+/// The queue and the semaphores of a submission. Binary and timeline semaphores can be mixed:
+/// the values of the timeline semaphores are given at the same index as the semaphore in the
+/// value lists, and the values of the binary semaphores are ignored.
+#[derive(Debug, Clone, Default)]
 pub struct SubmitInfo {
     pub queue: vk::Queue,
     pub signal_semaphores: Vec<vk::Semaphore>,
     pub wait_semaphores: Vec<vk::Semaphore>,
     pub wait_dst_stage_mask: Vec<vk::PipelineStageFlags>,
+
+    /// The value each signal semaphore is set to. Empty if all the signal semaphores are binary,
+    /// otherwise there must be one value per signal semaphore.
+    pub signal_values: Vec<u64>,
+
+    /// The value each wait semaphore must reach. Empty if all the wait semaphores are binary,
+    /// otherwise there must be one value per wait semaphore.
+    pub wait_values: Vec<u64>,
 }
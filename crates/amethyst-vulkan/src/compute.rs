@@ -0,0 +1,147 @@
+//! Compute work on the async compute queue. Devices with a dedicated compute queue family can
+//! execute compute shaders alongside the graphics work of the main queue, for example to
+//! simulate particles or post-process the previous frame while the shadows and the geometry of
+//! the current frame are rendered.
+//!
+//! The [`AsyncComputePass`] submits compute work on that queue and synchronizes it with the
+//! main queue through timeline semaphores, in both directions: a dispatch can wait for values
+//! of semaphores signaled by the main queue, and signals its own semaphore once it completes.
+use crate::{
+    command::{CommandBuffer, CommandPool, Compute, Recording, SubmitInfo},
+    device::{VulkanDevice, VulkanQueues},
+    semaphore::TimelineSemaphore,
+    MAX_FRAMES_IN_FLIGHT,
+};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// Submits compute work on the async compute queue, or on the main queue if the device does
+/// not have one. The queue must not be used by another thread at the same time.
+///
+/// Resources written by a dispatch and read by the main queue, or the other way around, must
+/// be usable by both queue families: their ownership must be transferred between the families
+/// with barriers, unless their content does not need to be preserved.
+#[derive(Debug)]
+pub struct AsyncComputePass {
+    /// The command pools used to record the dispatches, with the value of the semaphore
+    /// signaled once the last dispatch recorded from each of them is complete. The pools are
+    /// used in turn, so that a pool is only reset once its dispatches are complete.
+    pools: Vec<(CommandPool<Compute>, u64)>,
+
+    /// The index of the pool used by the next dispatch.
+    next_pool: usize,
+
+    /// The semaphore signaled with the value of each dispatch once it is complete.
+    semaphore: TimelineSemaphore,
+
+    /// The value signaled by the last submitted dispatch.
+    value: u64,
+
+    /// The queue on which the dispatches are submitted.
+    queue: vk::Queue,
+
+    /// Whether the queue is a dedicated compute queue.
+    asynchronous: bool,
+}
+
+impl AsyncComputePass {
+    /// Create a new async compute pass.
+    #[must_use]
+    pub fn new(device: &Arc<VulkanDevice>) -> Self {
+        let queues = VulkanQueues::fetch(device);
+        let pools = (0..MAX_FRAMES_IN_FLIGHT)
+            .map(|_| {
+                let pool =
+                    CommandPool::compute(device.clone(), vk::CommandPoolCreateFlags::TRANSIENT);
+                (pool, 0)
+            })
+            .collect();
+
+        Self {
+            pools,
+            next_pool: 0,
+            semaphore: TimelineSemaphore::new(device.clone(), 0),
+            value: 0,
+            queue: queues.async_compute().unwrap_or(queues.main()),
+            asynchronous: queues.async_compute().is_some(),
+        }
+    }
+
+    /// Returns whether the dispatches are executed on a dedicated compute queue, and can
+    /// therefore overlap with the work of the main queue.
+    #[must_use]
+    pub const fn is_async(&self) -> bool {
+        self.asynchronous
+    }
+
+    /// Record compute work with the given closure and submit it, once each of the given
+    /// semaphores has reached its value. Returns the value of the [semaphore] signaled once
+    /// the work is complete, which a submission of the main queue using its results must
+    /// wait for.
+    ///
+    /// If the pool used by this dispatch is still executing the work recorded
+    /// [`MAX_FRAMES_IN_FLIGHT`] dispatches ago, this waits for it to complete.
+    ///
+    /// [semaphore]: AsyncComputePass::semaphore
+    #[must_use]
+    pub fn dispatch<F>(&mut self, wait: &[(&TimelineSemaphore, u64)], record: F) -> u64
+    where
+        F: for<'pool> FnOnce(
+            CommandBuffer<'pool, Recording, Compute>,
+        ) -> CommandBuffer<'pool, Recording, Compute>,
+    {
+        let (pool, last_value) = &mut self.pools[self.next_pool];
+        self.next_pool = (self.next_pool + 1) % self.pools.len();
+        self.semaphore.wait(*last_value);
+        pool.reset(vk::CommandPoolResetFlags::empty());
+
+        self.value += 1;
+        *last_value = self.value;
+
+        let command = CommandBuffer::new(&*pool)
+            .start_recording()
+            .label("async compute");
+        record(command).stop_recording().submit(
+            SubmitInfo {
+                queue: self.queue,
+                wait_semaphores: wait
+                    .iter()
+                    .map(|(semaphore, _)| semaphore.inner())
+                    .collect(),
+                wait_values: wait.iter().map(|&(_, value)| value).collect(),
+                wait_dst_stage_mask: vec![vk::PipelineStageFlags::COMPUTE_SHADER; wait.len()],
+                signal_semaphores: vec![self.semaphore.inner()],
+                signal_values: vec![self.value],
+            },
+            None,
+        );
+        self.value
+    }
+
+    /// Returns whether the dispatch that returned the given value is complete.
+    #[must_use]
+    pub fn is_complete(&self, value: u64) -> bool {
+        self.semaphore.value() >= value
+    }
+
+    /// Returns the value of the semaphore signaled once all the dispatches submitted so far
+    /// are complete.
+    #[must_use]
+    pub const fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns the timeline semaphore signaled by the dispatches.
+    #[must_use]
+    pub const fn semaphore(&self) -> &TimelineSemaphore {
+        &self.semaphore
+    }
+}
+
+impl Drop for AsyncComputePass {
+    fn drop(&mut self) {
+        // The command pools must not be destroyed while the GPU is still executing the
+        // dispatches.
+        self.semaphore.wait(self.value);
+    }
+}
@@ -1,6 +1,10 @@
 use bevy::prelude::*;
 use raw_window_handle::HasWindowHandle;
-use std::{borrow::Cow, collections::HashSet, ffi::CStr};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    ffi::{c_char, CStr, CString},
+};
 use vk::ExtDebugUtilsExtension;
 use vulkanalia::{
     loader::{LibloadingLoader, LIBRARY},
@@ -13,19 +17,93 @@ use vulkanalia::{
 /// specific optimizations built into drivers, but we never know :)
 pub static ENGINE_NAME: &[u8] = b"Amethyst\0";
 
-/// The name of the application. This is used to identify the application when creating the Vulkan
-/// instance, for the same reasons as the engine name ([`ENGINE_NAME`]).
-pub static APPLICATION_NAME: &[u8] = b"Amethyst application\0";
+/// The default name of the application, used when the application does not provide its own name
+/// with an [`ApplicationInfo`] resource.
+pub const DEFAULT_APPLICATION_NAME: &str = "Amethyst application";
+
+/// Information about the application. This is used to identify the application when creating the
+/// Vulkan instance, for the same reasons as the engine name ([`ENGINE_NAME`]), and is also displayed
+/// by tools like RenderDoc. Insert this resource before the render plugin starts to use your own
+/// application name and version.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct ApplicationInfo {
+    /// The name of the application. It must not contain any null byte.
+    pub name: String,
+
+    /// The version of the application, as a `(major, minor, patch)` tuple.
+    pub version: (u32, u32, u32),
+}
+
+impl Default for ApplicationInfo {
+    fn default() -> Self {
+        Self {
+            name: DEFAULT_APPLICATION_NAME.to_string(),
+            version: (0, 1, 0),
+        }
+    }
+}
 
 /// The name of the validation layer. This is used to enable the validation layer when creating the
 /// Vulkan instance. This is only used in debug builds, and is not used in release builds.
 pub static VALIDATION_LAYER: vk::ExtensionName =
     vk::ExtensionName::from_bytes(b"VK_LAYER_KHRONOS_validation");
 
-/// Whether to enable validation layers. This is only enabled in debug builds, and is disabled in
-/// release builds.
+/// Whether to enable validation layers by default. This is only enabled in debug builds, and is
+/// disabled in release builds. Insert a [`VulkanContextCreateInfo`] resource to choose the
+/// validation at runtime instead.
 pub const ENABLE_VALIDATION: bool = cfg!(debug_assertions);
 
+/// The validation performed by the validation layer. Every mode except
+/// [`ValidationMode::Disabled`] includes the standard validation of the API usage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValidationMode {
+    /// The validation layer is not enabled.
+    Disabled,
+
+    /// The standard validation of the API usage.
+    Standard,
+
+    /// The standard validation, and the GPU-assisted validation which instruments the shaders
+    /// to detect out of bounds accesses to the descriptor arrays and the buffers, for example
+    /// with bindless textures. This slows down the shaders considerably.
+    GpuAssisted,
+
+    /// The standard validation, and the synchronization validation which detects the missing
+    /// barriers and the hazards between the commands.
+    Synchronization,
+}
+
+/// Information required to create the Vulkan context. Insert this resource before the render
+/// plugin starts to change the validation enabled by default.
+#[derive(Debug, Clone, PartialEq, Eq, Resource)]
+pub struct VulkanContextCreateInfo {
+    /// The validation performed by the validation layer. If the layer is not installed, the
+    /// validation is disabled whatever the mode.
+    pub validation: ValidationMode,
+
+    /// Whether the validation layer also warns about the API usages that are valid but not
+    /// recommended, for example because they perform poorly on some GPUs. This has no effect
+    /// when the validation is disabled.
+    pub best_practices: bool,
+
+    /// The severities of the validation messages reported to the logger.
+    pub message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
+}
+
+impl Default for VulkanContextCreateInfo {
+    fn default() -> Self {
+        Self {
+            validation: if ENABLE_VALIDATION {
+                ValidationMode::Standard
+            } else {
+                ValidationMode::Disabled
+            },
+            best_practices: false,
+            message_severity: vk::DebugUtilsMessageSeverityFlagsEXT::all(),
+        }
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Resource)]
 pub struct VulkanContext {
@@ -37,11 +115,60 @@ pub struct VulkanContext {
 
     // The debug messenger. This is only created if validation layers are enabled.
     messenger: Option<vk::DebugUtilsMessengerEXT>,
+
+    /// The validation enabled on the instance, which is disabled if the validation layer is
+    /// not available.
+    validation: ValidationMode,
+
+    /// Whether the debug utils extension is enabled, to name the Vulkan objects and label the
+    /// commands for the validation messages and the graphics debuggers.
+    debug_utils: bool,
+
+    /// Whether the portability enumeration extension is enabled, to list the physical devices
+    /// of portability implementations like MoltenVK.
+    portability_enumeration: bool,
+
+    /// Whether the swapchain color space extension is enabled, to present to HDR and wide
+    /// gamut displays.
+    swapchain_colorspace: bool,
 }
 
 impl VulkanContext {
+    /// Create a context able to present to the given window.
     #[must_use]
-    pub fn new(handle: impl HasWindowHandle) -> Self {
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn new(
+        handle: impl HasWindowHandle,
+        application: &ApplicationInfo,
+        info: &VulkanContextCreateInfo,
+    ) -> Self {
+        let extensions = vulkanalia::window::get_required_instance_extensions(&handle)
+            .iter()
+            .map(|name| name.as_ptr())
+            .collect::<Vec<_>>();
+        Self::create(extensions, true, application, info)
+    }
+
+    /// Create a context without any window, which does not enable the surface extensions. This
+    /// is meant to be used with [`VulkanDevice::pick_best_headless`] to render to images only,
+    /// and works on machines without a display server.
+    ///
+    /// [`VulkanDevice::pick_best_headless`]: crate::device::VulkanDevice::pick_best_headless
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn headless(application: &ApplicationInfo, info: &VulkanContextCreateInfo) -> Self {
+        Self::create(Vec::new(), false, application, info)
+    }
+
+    /// Create a context with the given instance extensions enabled, in addition to the debug
+    /// utils extension when it is available. When the context presents to a window, the
+    /// swapchain color space extension is enabled as well when it is available.
+    fn create(
+        mut required_instance_extensions: Vec<*const c_char>,
+        windowed: bool,
+        application: &ApplicationInfo,
+        info: &VulkanContextCreateInfo,
+    ) -> Self {
         let entry = unsafe {
             let loader = LibloadingLoader::new(LIBRARY).expect("Failed to load Vulkan loader");
             Entry::new(loader).expect("Failed to load Vulkan entry point")
@@ -61,47 +188,100 @@ impl VulkanContext {
         // If the validation layer is available and validation is enabled, add the validation
         // layer to the list of layers to enable. If at least one condition is not met, disable
         // validation by not adding any layers.
-        let layers = if !available_layers.is_empty() && ENABLE_VALIDATION {
-            if available_layers.contains(&VALIDATION_LAYER) {
-                vec![VALIDATION_LAYER.as_ptr()]
-            } else {
-                debug!("Validation layer not available, disabling validation");
-                vec![]
-            }
-        } else {
+        let mut validation = info.validation;
+        if validation != ValidationMode::Disabled && !available_layers.contains(&VALIDATION_LAYER) {
+            debug!("Validation layer not available, disabling validation");
+            validation = ValidationMode::Disabled;
+        }
+        let layers = if validation == ValidationMode::Disabled {
             vec![]
+        } else {
+            vec![VALIDATION_LAYER.as_ptr()]
+        };
+
+        // The validation beyond the standard one is enabled with the validation features
+        // extension, provided by the validation layer itself.
+        let mut validation_features = match validation {
+            ValidationMode::Disabled | ValidationMode::Standard => vec![],
+            ValidationMode::GpuAssisted => vec![
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED,
+                vk::ValidationFeatureEnableEXT::GPU_ASSISTED_RESERVE_BINDING_SLOT,
+            ],
+            ValidationMode::Synchronization => {
+                vec![vk::ValidationFeatureEnableEXT::SYNCHRONIZATION_VALIDATION]
+            }
         };
+        if info.best_practices && validation != ValidationMode::Disabled {
+            validation_features.push(vk::ValidationFeatureEnableEXT::BEST_PRACTICES);
+        }
+        if !validation_features.is_empty() {
+            required_instance_extensions.push(vk::EXT_VALIDATION_FEATURES_EXTENSION.name.as_ptr());
+        }
 
         // Create the application info with the application and engine names,
         // versions, and the Vulkan API version. This does not really matter
         // except for the Vulkan API version, which should be set to the version
         // of Vulkan that the application is targeting.
+        let (major, minor, patch) = application.version;
+        let application_name = CString::new(application.name.as_str())
+            .expect("Application name must not contain null bytes");
         let application_info = vk::ApplicationInfo::builder()
-            .application_version(vk::make_version(0, 1, 0))
+            .application_version(vk::make_version(major, minor, patch))
             .engine_version(vk::make_version(0, 1, 0))
             .api_version(vk::make_version(1, 3, 0))
-            .application_name(APPLICATION_NAME)
+            .application_name(application_name.as_bytes_with_nul())
             .engine_name(ENGINE_NAME);
 
-        let mut required_instance_extensions =
-            vulkanalia::window::get_required_instance_extensions(&handle)
+        let available_extensions = unsafe {
+            entry
+                .enumerate_instance_extension_properties(None)
+                .expect("Failed to enumerate instance extensions")
                 .iter()
-                .map(|name| name.as_ptr())
-                .collect::<Vec<_>>();
+                .map(|e| e.extension_name)
+                .collect::<HashSet<_>>()
+        };
 
-        // If validation is enabled, add the validation layer to the list of required instance
-        // extensions to enable the validation layer.
-        if !layers.is_empty() {
+        // Enable the debug utils extension whenever it is available, and not only with the
+        // validation layer: graphics debuggers like RenderDoc provide it as well, and display
+        // the object names and command labels set through it.
+        let debug_utils = available_extensions.contains(&vk::EXT_DEBUG_UTILS_EXTENSION.name);
+        if debug_utils {
             required_instance_extensions.push(vk::EXT_DEBUG_UTILS_EXTENSION.name.as_ptr());
         }
 
+        // Portability implementations layered over another API, like MoltenVK over Metal on
+        // macOS and iOS, are not fully conformant. The loader hides their physical devices
+        // unless the application opts in with the portability enumeration extension.
+        let portability_enumeration =
+            available_extensions.contains(&vk::KHR_PORTABILITY_ENUMERATION_EXTENSION.name);
+        let flags = if portability_enumeration {
+            required_instance_extensions
+                .push(vk::KHR_PORTABILITY_ENUMERATION_EXTENSION.name.as_ptr());
+            vk::InstanceCreateFlags::ENUMERATE_PORTABILITY_KHR
+        } else {
+            vk::InstanceCreateFlags::empty()
+        };
+
+        // The surfaces only expose the color spaces of HDR and wide gamut displays, like scRGB,
+        // HDR10 or Display P3, with the swapchain color space extension.
+        let swapchain_colorspace =
+            windowed && available_extensions.contains(&vk::EXT_SWAPCHAIN_COLORSPACE_EXTENSION.name);
+        if swapchain_colorspace {
+            required_instance_extensions.push(vk::EXT_SWAPCHAIN_COLORSPACE_EXTENSION.name.as_ptr());
+        }
+
         // Create the Vulkan instance with the required extensions, layers, and application
         // info previously created.
-        let instance_create_info = vk::InstanceCreateInfo::builder()
+        let mut features_info =
+            vk::ValidationFeaturesEXT::builder().enabled_validation_features(&validation_features);
+        let mut instance_create_info = vk::InstanceCreateInfo::builder()
+            .flags(flags)
             .application_info(&application_info)
             .enabled_extension_names(&required_instance_extensions)
-            .enabled_layer_names(&layers)
-            .build();
+            .enabled_layer_names(&layers);
+        if !validation_features.is_empty() {
+            instance_create_info = instance_create_info.push_next(&mut features_info);
+        }
 
         let instance = unsafe {
             entry
@@ -111,9 +291,9 @@ impl VulkanContext {
 
         // Create the debug messenger if validation is enabled.
         let mut messenger = None;
-        if ENABLE_VALIDATION && !layers.is_empty() {
+        if debug_utils && validation != ValidationMode::Disabled {
             let debug_info = vk::DebugUtilsMessengerCreateInfoEXT::builder()
-                .message_severity(vk::DebugUtilsMessageSeverityFlagsEXT::all())
+                .message_severity(info.message_severity)
                 .message_type(vk::DebugUtilsMessageTypeFlagsEXT::all())
                 .user_callback(Some(vulkan_debug_callback));
 
@@ -128,6 +308,10 @@ impl VulkanContext {
             entry,
             instance,
             messenger,
+            validation,
+            debug_utils,
+            portability_enumeration,
+            swapchain_colorspace,
         }
     }
 
@@ -136,6 +320,36 @@ impl VulkanContext {
     pub const fn instance(&self) -> &Instance {
         &self.instance
     }
+
+    /// Returns the validation enabled on the instance. This is [`ValidationMode::Disabled`] if
+    /// the validation layer is not available, even if another mode was requested.
+    #[must_use]
+    pub const fn validation(&self) -> ValidationMode {
+        self.validation
+    }
+
+    /// Returns whether the debug utils extension is enabled. Without it, the names given to
+    /// the Vulkan objects and the labels of the commands are ignored.
+    #[must_use]
+    pub const fn debug_utils(&self) -> bool {
+        self.debug_utils
+    }
+
+    /// Returns whether the portability enumeration extension is enabled, so that the physical
+    /// devices of portability implementations like MoltenVK can be chosen. See
+    /// [`VulkanDevice::portability_subset`](crate::device::VulkanDevice::portability_subset).
+    #[must_use]
+    pub const fn portability_enumeration(&self) -> bool {
+        self.portability_enumeration
+    }
+
+    /// Returns whether the swapchain color space extension is enabled. Without it, the
+    /// surfaces only support the sRGB color space, and the swapchains always fall back to
+    /// [`SurfaceFormatPreference::Srgb`](crate::swapchain::SurfaceFormatPreference::Srgb).
+    #[must_use]
+    pub const fn swapchain_colorspace(&self) -> bool {
+        self.swapchain_colorspace
+    }
 }
 
 impl Drop for VulkanContext {
@@ -0,0 +1,160 @@
+//! Deferred destruction of the GPU resources. A resource cannot be destroyed while the GPU may
+//! still use it, but the wrappers are usually dropped while the commands of the last frames
+//! that use them are still executing. Instead of waiting for the device to be idle, the
+//! wrappers hand their Vulkan objects to the [`DeletionQueue`] of their device, which destroys
+//! them once the frames that may use them are complete.
+//!
+//! The frames are delimited with [`VulkanDevice::begin_frame`] and [`VulkanDevice::end_frame`].
+//! While no frame is executing, for example before the first frame or in tools that wait for
+//! each submission, the resources are destroyed immediately.
+//!
+//! [`VulkanDevice::begin_frame`]: crate::device::VulkanDevice::begin_frame
+//! [`VulkanDevice::end_frame`]: crate::device::VulkanDevice::end_frame
+use crate::pool::FreeList;
+use std::{
+    collections::VecDeque,
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// A Vulkan object whose destruction is deferred.
+#[derive(Debug)]
+pub(crate) enum Deletion {
+    /// A buffer and its memory, allocated by the given allocator. Only the inner allocator of
+    /// the [`BufferAllocator`] is kept, since the [`BufferAllocator`] keeps the device alive,
+    /// which owns this queue.
+    ///
+    /// [`BufferAllocator`]: crate::buffer::BufferAllocator
+    Buffer(Arc<vma::Allocator>, vk::Buffer, vma::Allocation),
+
+    /// An image and its memory, allocated by the given allocator.
+    Image(Arc<vma::Allocator>, vk::Image, vma::Allocation),
+    ImageView(vk::ImageView),
+    Sampler(vk::Sampler),
+    Pipeline(vk::Pipeline, vk::PipelineLayout),
+    DescriptorPool(vk::DescriptorPool),
+
+    /// A descriptor set, freed back to its pool.
+    DescriptorSet(vk::DescriptorPool, vk::DescriptorSet),
+    QueryPool(vk::QueryPool),
+    Event(vk::Event),
+    RenderPass(vk::RenderPass),
+    Framebuffer(vk::Framebuffer),
+
+    /// An index of a bindless texture array, returned to the free indices of the array.
+    BindlessIndex(Arc<Mutex<Vec<u32>>>, u32),
+
+    /// A range of a pooled buffer, returned to the free list of the buffer.
+    Suballocation(Arc<Mutex<FreeList>>, Range<vk::DeviceSize>),
+}
+
+impl Deletion {
+    /// Destroy the object.
+    ///
+    /// # Safety
+    /// The GPU must not use the object anymore.
+    unsafe fn destroy(self, device: &Device) {
+        match self {
+            Self::Buffer(allocator, buffer, allocation) => {
+                allocator.destroy_buffer(buffer, allocation);
+            }
+            Self::Image(allocator, image, allocation) => {
+                allocator.destroy_image(image, allocation);
+            }
+            Self::ImageView(view) => device.destroy_image_view(view, None),
+            Self::Sampler(sampler) => device.destroy_sampler(sampler, None),
+            Self::Pipeline(pipeline, layout) => {
+                device.destroy_pipeline_layout(layout, None);
+                device.destroy_pipeline(pipeline, None);
+            }
+            Self::DescriptorPool(pool) => device.destroy_descriptor_pool(pool, None),
+            Self::DescriptorSet(pool, set) => {
+                device
+                    .free_descriptor_sets(pool, &[set])
+                    .expect("Failed to free descriptor set");
+            }
+            Self::QueryPool(pool) => device.destroy_query_pool(pool, None),
+            Self::Event(event) => device.destroy_event(event, None),
+            Self::RenderPass(render_pass) => device.destroy_render_pass(render_pass, None),
+            Self::Framebuffer(framebuffer) => device.destroy_framebuffer(framebuffer, None),
+            Self::BindlessIndex(free, index) => {
+                free.lock().expect("Free indices poisoned").push(index);
+            }
+            Self::Suballocation(free, range) => {
+                free.lock().expect("Free list poisoned").free(range);
+            }
+        }
+    }
+}
+
+/// The objects waiting for the frames that may use them to complete.
+#[derive(Debug, Default)]
+pub(crate) struct DeletionQueue {
+    /// The index of the last frame that has begun.
+    frame: u64,
+
+    /// The index of the last frame that has completed.
+    completed: u64,
+
+    /// The objects to destroy, with the index of the frame during which they were dropped.
+    pending: VecDeque<(u64, Deletion)>,
+}
+
+impl DeletionQueue {
+    /// Destroy the given object once the frames that may use it are complete, or immediately
+    /// if no frame is executing.
+    pub(crate) fn push(&mut self, device: &Device, deletion: Deletion) {
+        if self.completed == self.frame {
+            // SAFETY: All the frames are complete, so the GPU does not use the object anymore.
+            unsafe { deletion.destroy(device) };
+        } else {
+            self.pending.push_back((self.frame, deletion));
+        }
+    }
+
+    /// Begin a new frame, and returns its index.
+    pub(crate) fn begin_frame(&mut self) -> u64 {
+        self.frame += 1;
+        self.frame
+    }
+
+    /// Mark the given frame and all the previous ones as complete, and destroy the objects
+    /// dropped during them.
+    pub(crate) fn end_frame(&mut self, device: &Device, frame: u64) {
+        self.completed = self.completed.max(frame);
+        while self
+            .pending
+            .front()
+            .is_some_and(|&(dropped, _)| dropped <= self.completed)
+        {
+            let (_, deletion) = self.pending.pop_front().expect("Pending deletion");
+
+            // SAFETY: The frames during which the object may have been used are complete.
+            unsafe { deletion.destroy(device) };
+        }
+    }
+
+    /// Destroy all the pending objects.
+    ///
+    /// # Safety
+    /// The GPU must not use the objects anymore, for example because the device is idle.
+    pub(crate) unsafe fn flush(&mut self, device: &Device) {
+        for (_, deletion) in self.pending.drain(..) {
+            deletion.destroy(device);
+        }
+        self.completed = self.frame;
+    }
+
+    /// Returns the index of the last frame that has completed.
+    #[must_use]
+    pub(crate) const fn completed(&self) -> u64 {
+        self.completed
+    }
+
+    /// Returns the number of objects waiting to be destroyed.
+    #[must_use]
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
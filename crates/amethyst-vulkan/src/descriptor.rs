@@ -0,0 +1,840 @@
+use crate::{
+    buffer::Buffer,
+    deletion::Deletion,
+    device::VulkanDevice,
+    hazard::{self, Access, ResourceUse},
+    image::Sampler,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// A binding of a descriptor set layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DescriptorBinding {
+    /// The binding number used in the shaders.
+    pub binding: u32,
+
+    /// The type of the resource bound.
+    pub kind: vk::DescriptorType,
+
+    /// The shader stages that can access the resource.
+    pub stages: vk::ShaderStageFlags,
+}
+
+/// A descriptor set layout, describing the resources bound in a descriptor set.
+#[derive(Debug)]
+pub struct DescriptorSetLayout {
+    device: Arc<VulkanDevice>,
+    inner: vk::DescriptorSetLayout,
+    bindings: Vec<DescriptorBinding>,
+}
+
+impl DescriptorSetLayout {
+    /// Create a new descriptor set layout with the given bindings.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, bindings: &[DescriptorBinding]) -> Self {
+        let layout_bindings = bindings
+            .iter()
+            .map(|binding| {
+                vk::DescriptorSetLayoutBinding::builder()
+                    .binding(binding.binding)
+                    .descriptor_type(binding.kind)
+                    .descriptor_count(1)
+                    .stage_flags(binding.stages)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let info = vk::DescriptorSetLayoutCreateInfo::builder().bindings(&layout_bindings);
+        let inner = unsafe {
+            device
+                .logical()
+                .create_descriptor_set_layout(&info, None)
+                .expect("Failed to create descriptor set layout")
+        };
+
+        Self {
+            device,
+            inner,
+            bindings: bindings.to_vec(),
+        }
+    }
+
+    /// Returns the bindings of the layout.
+    #[must_use]
+    pub fn bindings(&self) -> &[DescriptorBinding] {
+        &self.bindings
+    }
+
+    /// Returns the inner vulkan descriptor set layout.
+    #[must_use]
+    pub const fn inner(&self) -> vk::DescriptorSetLayout {
+        self.inner
+    }
+}
+
+impl Drop for DescriptorSetLayout {
+    fn drop(&mut self) {
+        unsafe {
+            self.device
+                .logical()
+                .destroy_descriptor_set_layout(self.inner, None);
+        }
+    }
+}
+
+/// A descriptor pool, from which descriptor sets are allocated. The descriptor sets are freed
+/// when the pool is destroyed.
+#[derive(Debug)]
+pub struct DescriptorPool {
+    device: Arc<VulkanDevice>,
+    inner: vk::DescriptorPool,
+}
+
+impl DescriptorPool {
+    /// Create a descriptor pool able to allocate `count` descriptor sets with the given layout.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, layout: &DescriptorSetLayout, count: u32) -> Self {
+        let sizes = layout
+            .bindings()
+            .iter()
+            .map(|binding| {
+                vk::DescriptorPoolSize::builder()
+                    .type_(binding.kind)
+                    .descriptor_count(count)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .pool_sizes(&sizes)
+            .max_sets(count);
+
+        let inner = unsafe {
+            device
+                .logical()
+                .create_descriptor_pool(&info, None)
+                .expect("Failed to create descriptor pool")
+        };
+
+        Self { device, inner }
+    }
+
+    /// Allocate a descriptor set with the given layout from the pool.
+    ///
+    /// # Panics
+    /// Panics if the pool does not have enough space left to allocate the descriptor set.
+    #[must_use]
+    pub fn allocate(&self, layout: &DescriptorSetLayout) -> DescriptorSet {
+        let layouts = [layout.inner()];
+        let info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(self.inner)
+            .set_layouts(&layouts);
+
+        let inner = unsafe {
+            self.device
+                .logical()
+                .allocate_descriptor_sets(&info)
+                .expect("Failed to allocate descriptor set")[0]
+        };
+
+        DescriptorSet {
+            device: self.device.clone(),
+            pool: self.inner,
+            inner,
+            uses: Mutex::default(),
+        }
+    }
+
+    /// Returns the inner vulkan descriptor pool.
+    #[must_use]
+    pub const fn inner(&self) -> vk::DescriptorPool {
+        self.inner
+    }
+}
+
+impl Drop for DescriptorPool {
+    fn drop(&mut self) {
+        self.device
+            .destroy_later(Deletion::DescriptorPool(self.inner));
+    }
+}
+
+/// A descriptor set, binding resources to the shaders. A descriptor set is owned by the pool
+/// it was allocated from and must not be used after the pool is destroyed.
+#[derive(Debug)]
+pub struct DescriptorSet {
+    device: Arc<VulkanDevice>,
+
+    /// The pool from which the descriptor set was allocated.
+    pool: vk::DescriptorPool,
+    inner: vk::DescriptorSet,
+
+    /// The resources written to each binding, recorded only when the hazard tracking is
+    /// enabled.
+    uses: Mutex<Vec<(u32, ResourceUse)>>,
+}
+
+impl DescriptorSet {
+    /// Wrap a descriptor set allocated from the given pool.
+    pub(crate) fn from_raw(
+        device: Arc<VulkanDevice>,
+        pool: vk::DescriptorPool,
+        inner: vk::DescriptorSet,
+    ) -> Self {
+        Self {
+            device,
+            pool,
+            inner,
+            uses: Mutex::default(),
+        }
+    }
+
+    /// Bind an image that will be sampled with the given sampler.
+    ///
+    /// # Important
+    /// The descriptor set must not be used by a command buffer that is pending execution.
+    pub fn write_sampled_image(
+        &self,
+        binding: u32,
+        view: vk::ImageView,
+        sampler: &Sampler,
+        layout: vk::ImageLayout,
+    ) {
+        let image_info = [vk::DescriptorImageInfo::builder()
+            .image_view(view)
+            .sampler(sampler.inner())
+            .image_layout(layout)
+            .build()];
+
+        self.track(binding, ResourceUse::view(view, Access::Read, layout));
+        self.write_images(
+            binding,
+            vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+            &image_info,
+        );
+    }
+
+    /// Bind an image that will be read and written by the shaders, for example by a compute
+    /// shader. The image must be in the `vk::ImageLayout::GENERAL` layout when used.
+    ///
+    /// # Important
+    /// The descriptor set must not be used by a command buffer that is pending execution.
+    pub fn write_storage_image(&self, binding: u32, view: vk::ImageView) {
+        let image_info = [vk::DescriptorImageInfo::builder()
+            .image_view(view)
+            .image_layout(vk::ImageLayout::GENERAL)
+            .build()];
+
+        let usage = ResourceUse::view(view, Access::ReadWrite, vk::ImageLayout::GENERAL);
+        self.track(binding, usage);
+        self.write_images(binding, vk::DescriptorType::STORAGE_IMAGE, &image_info);
+    }
+
+    /// Bind a whole buffer that will be read and written by the shaders, for example by a
+    /// compute shader.
+    ///
+    /// # Important
+    /// The descriptor set must not be used by a command buffer that is pending execution.
+    pub fn write_storage_buffer(&self, binding: u32, buffer: &Buffer) {
+        self.write_buffer(binding, vk::DescriptorType::STORAGE_BUFFER, buffer);
+    }
+
+    /// Bind a whole buffer with the given descriptor type, usually a uniform or a storage
+    /// buffer.
+    ///
+    /// # Important
+    /// The descriptor set must not be used by a command buffer that is pending execution.
+    pub fn write_buffer(&self, binding: u32, kind: vk::DescriptorType, buffer: &Buffer) {
+        self.write_buffer_range(binding, kind, buffer, 0, vk::WHOLE_SIZE);
+    }
+
+    /// Bind a range of a buffer with the given descriptor type, starting at the given offset
+    /// relative to the start of the buffer. With a dynamic descriptor type, the dynamic offset
+    /// given when binding the descriptor set is added to this offset, so a single descriptor
+    /// can address every slice of a [`RingBuffer`].
+    ///
+    /// # Important
+    /// The descriptor set must not be used by a command buffer that is pending execution.
+    ///
+    /// [`RingBuffer`]: crate::ring::RingBuffer
+    pub fn write_buffer_range(
+        &self,
+        binding: u32,
+        kind: vk::DescriptorType,
+        buffer: &Buffer,
+        offset: vk::DeviceSize,
+        range: vk::DeviceSize,
+    ) {
+        let buffer_info = [vk::DescriptorBufferInfo::builder()
+            .buffer(buffer.inner())
+            .offset(offset)
+            .range(range)
+            .build()];
+
+        let access = match kind {
+            vk::DescriptorType::UNIFORM_BUFFER | vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC => {
+                Access::Read
+            }
+            _ => Access::ReadWrite,
+        };
+        self.track(binding, Some(ResourceUse::buffer(buffer.inner(), access)));
+
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.inner)
+            .dst_binding(binding)
+            .descriptor_type(kind)
+            .buffer_info(&buffer_info);
+
+        unsafe {
+            self.device
+                .logical()
+                .update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+        }
+    }
+
+    /// Write image descriptors to the given binding.
+    fn write_images(
+        &self,
+        binding: u32,
+        kind: vk::DescriptorType,
+        infos: &[vk::DescriptorImageInfo],
+    ) {
+        let write = vk::WriteDescriptorSet::builder()
+            .dst_set(self.inner)
+            .dst_binding(binding)
+            .descriptor_type(kind)
+            .image_info(infos);
+
+        unsafe {
+            self.device
+                .logical()
+                .update_descriptor_sets(&[write], &[] as &[vk::CopyDescriptorSet]);
+        }
+    }
+
+    /// Record the resource written to a binding for the hazard tracking, replacing the
+    /// previous one.
+    fn track(&self, binding: u32, usage: Option<ResourceUse>) {
+        if !hazard::is_enabled() {
+            return;
+        }
+
+        let mut uses = self.uses.lock().expect("Descriptor set uses poisoned");
+        uses.retain(|(b, _)| *b != binding);
+        uses.extend(usage.map(|usage| (binding, usage)));
+    }
+
+    /// Returns the resources bound by the descriptor set, as recorded for the hazard tracking.
+    pub(crate) fn uses(&self) -> Vec<ResourceUse> {
+        self.uses
+            .lock()
+            .expect("Descriptor set uses poisoned")
+            .iter()
+            .map(|(_, usage)| *usage)
+            .collect()
+    }
+
+    /// Returns the inner vulkan descriptor set.
+    #[must_use]
+    pub const fn inner(&self) -> vk::DescriptorSet {
+        self.inner
+    }
+}
+
+/// A fixed number of descriptor sets allocated from their own pool and used in turn. This is
+/// useful for passes that bind different resources each time they are recorded: each recording
+/// takes the next descriptor set of the ring, so at most `count` recordings can be pending
+/// execution at once before a descriptor set is overwritten while still in use.
+#[derive(Debug)]
+pub struct DescriptorSetRing {
+    /// The descriptor sets of the ring.
+    sets: Vec<DescriptorSet>,
+
+    /// The index of the next descriptor set to use.
+    next: AtomicUsize,
+
+    /// The pool from which the descriptor sets are allocated.
+    pool: DescriptorPool,
+
+    /// The layout of the descriptor sets, kept alive since the sets cannot be written once
+    /// their layout is destroyed.
+    layout: Arc<DescriptorSetLayout>,
+}
+
+impl DescriptorSetRing {
+    /// Allocate a ring of `count` descriptor sets with the given layout.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, layout: &Arc<DescriptorSetLayout>, count: usize) -> Self {
+        let pool = DescriptorPool::new(device, layout, count as u32);
+        let sets = (0..count).map(|_| pool.allocate(layout)).collect();
+
+        Self {
+            next: AtomicUsize::new(0),
+            layout: layout.clone(),
+            pool,
+            sets,
+        }
+    }
+
+    /// Returns the next descriptor set of the ring.
+    #[must_use]
+    pub fn next(&self) -> &DescriptorSet {
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.sets.len();
+        &self.sets[index]
+    }
+
+    /// Returns the number of descriptor sets in the ring.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Returns whether the ring does not contain any descriptor set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// Returns the pool from which the descriptor sets are allocated.
+    #[must_use]
+    pub const fn pool(&self) -> &DescriptorPool {
+        &self.pool
+    }
+
+    /// Returns the layout of the descriptor sets.
+    #[must_use]
+    pub const fn layout(&self) -> &Arc<DescriptorSetLayout> {
+        &self.layout
+    }
+}
+
+/// The configuration of a [`DescriptorAllocator`].
+#[derive(Debug, Clone)]
+pub struct DescriptorAllocatorInfo {
+    /// The average number of descriptors of each type used by a descriptor set, used to size
+    /// the pools. A set using more descriptors of a type than this average still succeeds as
+    /// long as the pool has enough descriptors left.
+    pub ratios: Vec<(vk::DescriptorType, u32)>,
+
+    /// The number of descriptor sets of the first pool. Each new pool can hold twice as many
+    /// descriptor sets as the previous one, up to 4096.
+    pub sets_per_pool: u32,
+
+    /// Whether the descriptor sets only live during the frame they are allocated in. The pools
+    /// of a frame are reset at once when the frame is complete, instead of freeing each
+    /// descriptor set, see [`DescriptorAllocator::reset_per_frame`].
+    pub per_frame: bool,
+}
+
+impl Default for DescriptorAllocatorInfo {
+    fn default() -> Self {
+        Self {
+            ratios: vec![
+                (vk::DescriptorType::UNIFORM_BUFFER, 2),
+                (vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC, 1),
+                (vk::DescriptorType::COMBINED_IMAGE_SAMPLER, 4),
+                (vk::DescriptorType::STORAGE_BUFFER, 2),
+                (vk::DescriptorType::STORAGE_IMAGE, 1),
+            ],
+            sets_per_pool: 64,
+            per_frame: false,
+        }
+    }
+}
+
+/// Allocates descriptor sets of any layout, creating new pools when the existing ones are
+/// exhausted. Unlike a [`DescriptorPool`], the number of descriptor sets does not need to be
+/// known in advance.
+///
+/// By default, the descriptor sets live until they are given back with
+/// [`DescriptorAllocator::free`]. In the per-frame mode, the descriptor sets are transient:
+/// they are all released once the frame they were allocated in is complete.
+#[derive(Debug)]
+pub struct DescriptorAllocator {
+    device: Arc<VulkanDevice>,
+
+    /// The configuration of the allocator.
+    info: DescriptorAllocatorInfo,
+
+    /// The number of descriptor sets of the next pool created.
+    next_pool_sets: u32,
+
+    /// The pools that still have room for new descriptor sets. The last pool is the one the
+    /// descriptor sets are allocated from.
+    pools: Vec<vk::DescriptorPool>,
+
+    /// The exhausted pools of the per-frame mode, with the frame during which they were last
+    /// used. They are reset once their frame is complete.
+    full: Vec<(u64, vk::DescriptorPool)>,
+
+    /// The frame during which the descriptor sets are allocated, in the per-frame mode.
+    frame: u64,
+}
+
+impl DescriptorAllocator {
+    /// The maximum number of descriptor sets of a pool.
+    const MAX_SETS_PER_POOL: u32 = 4096;
+
+    /// Create a new descriptor allocator. No pool is created until the first descriptor set
+    /// is allocated.
+    ///
+    /// # Panics
+    /// Panics if the number of descriptor sets per pool is zero.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, info: DescriptorAllocatorInfo) -> Self {
+        assert!(
+            info.sets_per_pool > 0,
+            "A descriptor pool must hold at least one descriptor set"
+        );
+        Self {
+            device,
+            next_pool_sets: info.sets_per_pool,
+            info,
+            pools: Vec::new(),
+            full: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    /// Allocate a descriptor set with the given layout, from the first pool that has enough
+    /// room for it, or from a new pool if none has.
+    ///
+    /// # Panics
+    /// Panics if the allocation fails for another reason than an exhausted pool, or if a new
+    /// pool is too small for the layout.
+    #[must_use]
+    pub fn allocate(&mut self, layout: &DescriptorSetLayout) -> DescriptorSet {
+        // The per-frame mode only allocates from the last pool, the exhausted ones are reset
+        // once their frame is complete. Otherwise, freed descriptor sets may have made room
+        // in any pool.
+        let candidates = if self.info.per_frame {
+            self.pools.len().saturating_sub(1)..self.pools.len()
+        } else {
+            0..self.pools.len()
+        };
+        for index in candidates.rev() {
+            match self.try_allocate(self.pools[index], layout) {
+                Ok(set) => return set,
+                Err(vk::ErrorCode::OUT_OF_POOL_MEMORY | vk::ErrorCode::FRAGMENTED_POOL) => {
+                    if self.info.per_frame {
+                        let pool = self.pools.remove(index);
+                        self.full.push((self.frame, pool));
+                    }
+                }
+                Err(error) => panic!("Failed to allocate descriptor set: {error}"),
+            }
+        }
+
+        let pool = self.create_pool();
+        self.pools.push(pool);
+        self.try_allocate(pool, layout)
+            .expect("Failed to allocate descriptor set from a new pool")
+    }
+
+    /// Free a descriptor set allocated by this allocator, once the frames that may use it are
+    /// complete.
+    ///
+    /// # Panics
+    /// Panics if the allocator is in the per-frame mode, whose descriptor sets are released
+    /// when their frame is complete.
+    pub fn free(&mut self, set: DescriptorSet) {
+        assert!(
+            !self.info.per_frame,
+            "The descriptor sets of a per-frame allocator cannot be freed"
+        );
+        self.device
+            .destroy_later(Deletion::DescriptorSet(set.pool, set.inner));
+    }
+
+    /// Start allocating the descriptor sets of the given frame, as returned by
+    /// [`VulkanDevice::begin_frame`], and reset the pools of the frames that are complete.
+    /// This releases all the descriptor sets allocated during those frames, which must not be
+    /// used anymore.
+    ///
+    /// # Panics
+    /// Panics if the allocator is not in the per-frame mode.
+    pub fn reset_per_frame(&mut self, frame: u64) {
+        assert!(
+            self.info.per_frame,
+            "Only the pools of a per-frame allocator can be reset"
+        );
+
+        // The current pool may also hold descriptor sets of the previous frame.
+        if let Some(pool) = self.pools.pop() {
+            self.full.push((self.frame, pool));
+        }
+        self.frame = frame;
+
+        let completed = self.device.completed_frame();
+        let (reset, pending) = self
+            .full
+            .drain(..)
+            .partition::<Vec<_>, _>(|&(frame, _)| frame <= completed);
+        self.full = pending;
+
+        for (_, pool) in reset {
+            // SAFETY: The frames that used the descriptor sets of the pool are complete.
+            unsafe {
+                self.device
+                    .logical()
+                    .reset_descriptor_pool(pool, vk::DescriptorPoolResetFlags::empty())
+                    .expect("Failed to reset descriptor pool");
+            }
+            self.pools.push(pool);
+        }
+    }
+
+    /// Returns the number of pools created by the allocator.
+    #[must_use]
+    pub fn pools(&self) -> usize {
+        self.pools.len() + self.full.len()
+    }
+
+    /// Try to allocate a descriptor set with the given layout from the given pool.
+    fn try_allocate(
+        &self,
+        pool: vk::DescriptorPool,
+        layout: &DescriptorSetLayout,
+    ) -> Result<DescriptorSet, vk::ErrorCode> {
+        let layouts = [layout.inner()];
+        let info = vk::DescriptorSetAllocateInfo::builder()
+            .descriptor_pool(pool)
+            .set_layouts(&layouts);
+
+        let inner = unsafe { self.device.logical().allocate_descriptor_sets(&info)?[0] };
+        Ok(DescriptorSet {
+            device: self.device.clone(),
+            pool,
+            inner,
+            uses: Mutex::default(),
+        })
+    }
+
+    /// Create a new pool, larger than the previous one.
+    fn create_pool(&mut self) -> vk::DescriptorPool {
+        let sets = self.next_pool_sets;
+        self.next_pool_sets = (sets * 2).min(Self::MAX_SETS_PER_POOL);
+
+        let sizes = self
+            .info
+            .ratios
+            .iter()
+            .map(|&(kind, ratio)| {
+                vk::DescriptorPoolSize::builder()
+                    .type_(kind)
+                    .descriptor_count(ratio * sets)
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        let flags = if self.info.per_frame {
+            vk::DescriptorPoolCreateFlags::empty()
+        } else {
+            vk::DescriptorPoolCreateFlags::FREE_DESCRIPTOR_SET
+        };
+        let info = vk::DescriptorPoolCreateInfo::builder()
+            .flags(flags)
+            .pool_sizes(&sizes)
+            .max_sets(sets);
+
+        unsafe {
+            self.device
+                .logical()
+                .create_descriptor_pool(&info, None)
+                .expect("Failed to create descriptor pool")
+        }
+    }
+}
+
+impl Drop for DescriptorAllocator {
+    fn drop(&mut self) {
+        let full = self.full.drain(..).map(|(_, pool)| pool);
+        for pool in self.pools.drain(..).chain(full) {
+            self.device.destroy_later(Deletion::DescriptorPool(pool));
+        }
+    }
+}
+
+/// A resource written to a binding of a descriptor set cached by a [`DescriptorCache`].
+#[derive(Debug, Clone, Copy)]
+pub enum DescriptorWrite<'a> {
+    /// A whole buffer with the given descriptor type, see [`DescriptorSet::write_buffer`].
+    Buffer {
+        binding: u32,
+        kind: vk::DescriptorType,
+        buffer: &'a Buffer,
+    },
+
+    /// An image sampled with the given sampler, see [`DescriptorSet::write_sampled_image`].
+    SampledImage {
+        binding: u32,
+        view: vk::ImageView,
+        sampler: &'a Sampler,
+        layout: vk::ImageLayout,
+    },
+
+    /// An image read and written by the shaders, see [`DescriptorSet::write_storage_image`].
+    StorageImage { binding: u32, view: vk::ImageView },
+}
+
+impl DescriptorWrite<'_> {
+    /// Returns the Vulkan objects identifying the content of the write.
+    fn key(&self) -> DescriptorKey {
+        match *self {
+            Self::Buffer {
+                binding,
+                kind,
+                buffer,
+            } => DescriptorKey::Buffer(binding, kind, buffer.inner(), buffer.size()),
+            Self::SampledImage {
+                binding,
+                view,
+                sampler,
+                layout,
+            } => DescriptorKey::SampledImage(binding, view, sampler.inner(), layout),
+            Self::StorageImage { binding, view } => DescriptorKey::StorageImage(binding, view),
+        }
+    }
+
+    /// Write the resource to the given descriptor set.
+    fn apply(&self, set: &DescriptorSet) {
+        match *self {
+            Self::Buffer {
+                binding,
+                kind,
+                buffer,
+            } => set.write_buffer(binding, kind, buffer),
+            Self::SampledImage {
+                binding,
+                view,
+                sampler,
+                layout,
+            } => set.write_sampled_image(binding, view, sampler, layout),
+            Self::StorageImage { binding, view } => set.write_storage_image(binding, view),
+        }
+    }
+}
+
+/// The content of a [`DescriptorWrite`], as the Vulkan objects it binds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum DescriptorKey {
+    Buffer(u32, vk::DescriptorType, vk::Buffer, vk::DeviceSize),
+    SampledImage(u32, vk::ImageView, vk::Sampler, vk::ImageLayout),
+    StorageImage(u32, vk::ImageView),
+}
+
+impl DescriptorKey {
+    /// Returns the binding the resource is written to.
+    const fn binding(&self) -> u32 {
+        match *self {
+            Self::Buffer(binding, ..)
+            | Self::SampledImage(binding, ..)
+            | Self::StorageImage(binding, _) => binding,
+        }
+    }
+}
+
+/// Reuses the descriptor sets binding the same resources with the same layout. Systems that
+/// bind the same combinations of textures and buffers each frame get the descriptor set
+/// written the first time, instead of writing a new one every frame.
+///
+/// The cached descriptor sets are never written again, so they can be reused while the
+/// frames using them are still executing. A descriptor set that has not been used for a
+/// number of frames is freed, so the cache does not grow with the resources that are not
+/// used anymore.
+#[derive(Debug)]
+pub struct DescriptorCache {
+    /// The cached descriptor sets, with the frame during which they were last used.
+    sets: HashMap<(vk::DescriptorSetLayout, Vec<DescriptorKey>), (DescriptorSet, u64)>,
+
+    /// The allocator of the cached descriptor sets.
+    allocator: DescriptorAllocator,
+
+    /// The number of frames after which an unused descriptor set is freed.
+    max_unused_frames: u64,
+
+    /// The frame during which the descriptor sets are currently used.
+    frame: u64,
+}
+
+impl DescriptorCache {
+    /// Create an empty cache, freeing the descriptor sets that have not been used during the
+    /// given number of frames.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, max_unused_frames: u64) -> Self {
+        Self {
+            sets: HashMap::new(),
+            allocator: DescriptorAllocator::new(device, DescriptorAllocatorInfo::default()),
+            max_unused_frames,
+            frame: 0,
+        }
+    }
+
+    /// Returns a descriptor set with the given layout binding the given resources, writing a
+    /// new one only if no cached descriptor set binds exactly the same resources.
+    ///
+    /// The resources are identified by their Vulkan objects, so the writes must cover all the
+    /// bindings of the layout for the key to identify the content of the descriptor set.
+    #[must_use]
+    pub fn get(
+        &mut self,
+        layout: &DescriptorSetLayout,
+        writes: &[DescriptorWrite<'_>],
+    ) -> &DescriptorSet {
+        let mut keys = writes.iter().map(DescriptorWrite::key).collect::<Vec<_>>();
+        keys.sort_unstable_by_key(DescriptorKey::binding);
+
+        let frame = self.frame;
+        let allocator = &mut self.allocator;
+        let (set, last_used) = self.sets.entry((layout.inner(), keys)).or_insert_with(|| {
+            let set = allocator.allocate(layout);
+            for write in writes {
+                write.apply(&set);
+            }
+            (set, frame)
+        });
+        *last_used = frame;
+        set
+    }
+
+    /// Start using the descriptor sets in the given frame, as returned by
+    /// [`VulkanDevice::begin_frame`], and free the descriptor sets that have not been used
+    /// during the last frames. The freed descriptor sets are only destroyed once the frames
+    /// that may use them are complete.
+    pub fn begin_frame(&mut self, frame: u64) {
+        self.frame = frame;
+
+        let oldest = frame.saturating_sub(self.max_unused_frames);
+        let unused = self
+            .sets
+            .iter()
+            .filter(|(_, (_, last_used))| *last_used < oldest)
+            .map(|(key, _)| key.clone())
+            .collect::<Vec<_>>();
+
+        for key in unused {
+            if let Some((set, _)) = self.sets.remove(&key) {
+                self.allocator.free(set);
+            }
+        }
+    }
+
+    /// Returns the number of cached descriptor sets.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Returns whether the cache does not contain any descriptor set.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+}
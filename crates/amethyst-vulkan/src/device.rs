@@ -1,19 +1,42 @@
 use crate::{
-    context::{VulkanContext, ENABLE_VALIDATION, VALIDATION_LAYER},
+    context::{ValidationMode, VulkanContext, VALIDATION_LAYER},
+    deletion::{Deletion, DeletionQueue},
+    image::SampleCount,
+    profile::DeviceCapabilities,
+    renderpass::{create_render_pass, RenderPassLayout},
     swapchain::Surface,
 };
 use bevy::prelude::*;
-use std::collections::HashSet;
-use vk::KhrSurfaceExtension;
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::CString,
+    ops::Range,
+    sync::{Mutex, MutexGuard},
+};
+use vk::{ExtDebugUtilsExtension, KhrSurfaceExtension};
 use vulkanalia::prelude::v1_3::*;
 
 /// The device extensions required by Amethyst.
 const DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[vk::KHR_SWAPCHAIN_EXTENSION.name];
 
+/// The device extensions that Amethyst uses if they are available, but that are not required.
+/// Use [`VulkanDevice::is_extension_enabled`] to check whether one of them has been enabled.
+const OPTIONAL_DEVICE_EXTENSIONS: &[vk::ExtensionName] = &[
+    vk::EXT_MEMORY_BUDGET_EXTENSION.name,
+    vk::EXT_MEMORY_PRIORITY_EXTENSION.name,
+    vk::EXT_PAGEABLE_DEVICE_LOCAL_MEMORY_EXTENSION.name,
+    vk::KHR_FRAGMENT_SHADING_RATE_EXTENSION.name,
+    vk::EXT_CONSERVATIVE_RASTERIZATION_EXTENSION.name,
+];
+
 /// The Vulkan device. This contains the physical device chosen by Amethyst, the logical device
 /// created from the physical device, and information about the queues of the device.
 #[derive(Debug, Resource)]
 pub struct VulkanDevice {
+    /// The Vulkan instance from which the physical device was enumerated, used to query
+    /// its properties.
+    instance: Instance,
+
     /// The physical device chosen by Amethyst
     physical: vk::PhysicalDevice,
 
@@ -26,12 +49,105 @@ pub struct VulkanDevice {
     /// transfer and async compute queue families that support transfer and compute
     /// operations, respectively.
     queues_info: DeviceQueueInfo,
+
+    /// The optional extensions that are supported by the physical device and have been
+    /// enabled on the logical device, and the extensions required by the application.
+    optional_extensions: HashSet<vk::ExtensionName>,
+
+    /// The core features enabled on the logical device.
+    features: DeviceFeatures,
+
+    /// The capabilities of the physical device, including what is supported but not enabled.
+    capabilities: DeviceCapabilities,
+
+    /// Whether the Vulkan 1.3 dynamic rendering feature is enabled on the logical device. The
+    /// pipelines and the render pass instances use classic render passes otherwise.
+    dynamic_rendering: bool,
+
+    /// The features of the portability subset enabled on the logical device, if the physical
+    /// device is a portability implementation that does not support all of Vulkan.
+    portability_subset: Option<vk::PhysicalDevicePortabilitySubsetFeaturesKHR>,
+
+    /// The fragment shading rate features enabled on the logical device, all disabled if the
+    /// fragment shading rate extension is not supported.
+    shading_rate: vk::PhysicalDeviceFragmentShadingRateFeaturesKHR,
+
+    /// The fragment shading rate properties of the physical device, like the supported texel
+    /// sizes of the shading rate attachments.
+    shading_rate_properties: vk::PhysicalDeviceFragmentShadingRatePropertiesKHR,
+
+    /// The conservative rasterization properties of the physical device, all zero if the
+    /// conservative rasterization extension is not supported.
+    conservative_rasterization: vk::PhysicalDeviceConservativeRasterizationPropertiesEXT,
+
+    /// Whether the Vulkan 1.2 draw indirect count feature is enabled on the logical device.
+    draw_indirect_count: bool,
+
+    /// Whether the Vulkan 1.2 shader output layer feature is enabled on the logical device.
+    shader_output_layer: bool,
+
+    /// Whether the Vulkan 1.2 descriptor indexing features needed by bindless textures are
+    /// enabled on the logical device.
+    descriptor_indexing: bool,
+
+    /// The limits of the physical device.
+    limits: vk::PhysicalDeviceLimits,
+
+    /// Whether the debug utils extension is enabled on the instance, to name the objects and
+    /// label the commands.
+    debug_utils: bool,
+
+    /// The resources dropped while the GPU may still use them, destroyed once the frames
+    /// that may use them are complete.
+    deletion: Mutex<DeletionQueue>,
+
+    /// The render passes created for the pipelines and the render pass instances when dynamic
+    /// rendering is not supported, destroyed with the device.
+    render_passes: Mutex<HashMap<RenderPassLayout, vk::RenderPass>>,
 }
 
 impl VulkanDevice {
-    /// Choose the best physical device and create a logical device from it.
+    /// Choose the best physical device supporting the features and extensions required by
+    /// the given request, and create a logical device from it. The optional features and
+    /// extensions of the request are enabled if the chosen device supports them, which can be
+    /// checked afterwards with [`VulkanDevice::features`] and
+    /// [`VulkanDevice::is_extension_enabled`].
+    ///
+    /// # Panics
+    /// Panics if no physical device supports the required features and extensions.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn pick_best(
+        context: &VulkanContext,
+        surface: &Surface,
+        request: &DeviceFeatureRequest,
+    ) -> Self {
+        Self::create(context, Some(surface), request)
+    }
+
+    /// Choose the best physical device and create a logical device from it, without a surface
+    /// to present to. The swapchain extension is not required nor enabled, so the device can
+    /// only render to images, for example in tests, on servers or for batch rendering.
     #[must_use]
-    pub fn pick_best(context: &VulkanContext, surface: &Surface) -> Self {
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn pick_best_headless(context: &VulkanContext, request: &DeviceFeatureRequest) -> Self {
+        Self::create(context, None, request)
+    }
+
+    /// Choose the best physical device and create a logical device from it, able to present
+    /// to the given surface if any.
+    fn create(
+        context: &VulkanContext,
+        surface: Option<&Surface>,
+        request: &DeviceFeatureRequest,
+    ) -> Self {
+        // The swapchain extension is only needed to present to a surface.
+        let required_extensions = if surface.is_some() {
+            DEVICE_EXTENSIONS
+        } else {
+            &[]
+        };
+
         let physical = unsafe {
             let mut devices = context
                 .instance()
@@ -62,7 +178,14 @@ impl VulkanDevice {
             devices
                 .into_iter()
                 .find(|(device, properties, features)| {
-                    Self::suitable_device(context, device, properties, features)
+                    Self::suitable_device(
+                        context,
+                        device,
+                        properties,
+                        features,
+                        required_extensions,
+                        request,
+                    )
                 })
                 .expect("No suitable physical device found")
                 .0
@@ -103,29 +226,212 @@ impl VulkanDevice {
             .collect::<Vec<_>>();
 
         // Add the validation layer to the list of layers to enable if validation is enabled.
-        let layers_names = if ENABLE_VALIDATION {
+        let layers_names = if context.validation() != ValidationMode::Disabled {
             vec![VALIDATION_LAYER.as_ptr()]
         } else {
             vec![]
         };
 
+        // Find the optional extensions supported by the physical device, including the ones
+        // requested by the application, and enable them alongside the required extensions.
+        let capabilities = DeviceCapabilities::query(context, physical);
+        let mut optional_extensions = Self::supported_optional_extensions(context, physical);
+        optional_extensions.extend(
+            request
+                .optional_extensions
+                .iter()
+                .filter(|e| capabilities.extensions.contains(e)),
+        );
+        optional_extensions.extend(request.required_extensions.iter());
+
+        // Portability implementations like MoltenVK expose the portability subset extension,
+        // which must then be enabled, and report the features of Vulkan that they cannot
+        // emulate. All the features of the subset supported by the device are enabled.
+        let mut portability_subset = capabilities
+            .extensions
+            .contains(&vk::KHR_PORTABILITY_SUBSET_EXTENSION.name)
+            .then(|| {
+                let mut subset = vk::PhysicalDevicePortabilitySubsetFeaturesKHR::default();
+                let mut features = vk::PhysicalDeviceFeatures2::builder().push_next(&mut subset);
+                unsafe {
+                    context
+                        .instance()
+                        .get_physical_device_features2(physical, &mut features);
+                }
+                subset.next = std::ptr::null_mut();
+                subset
+            });
+        if let Some(subset) = &portability_subset {
+            optional_extensions.insert(vk::KHR_PORTABILITY_SUBSET_EXTENSION.name);
+            log_portability_caveats(subset);
+        }
+
+        // The fragment shading rate lowers the number of fragment shader invocations per pixel,
+        // per pipeline, per primitive, or per region of the framebuffer with a shading rate
+        // attachment. All the features of the extension supported by the device are enabled.
+        let fragment_shading_rate =
+            optional_extensions.contains(&vk::KHR_FRAGMENT_SHADING_RATE_EXTENSION.name);
+        let mut shading_rate = vk::PhysicalDeviceFragmentShadingRateFeaturesKHR::default();
+        let mut shading_rate_properties =
+            vk::PhysicalDeviceFragmentShadingRatePropertiesKHR::default();
+        if fragment_shading_rate {
+            unsafe {
+                let instance = context.instance();
+                let mut features =
+                    vk::PhysicalDeviceFeatures2::builder().push_next(&mut shading_rate);
+                instance.get_physical_device_features2(physical, &mut features);
+                let mut properties = vk::PhysicalDeviceProperties2::builder()
+                    .push_next(&mut shading_rate_properties);
+                instance.get_physical_device_properties2(physical, &mut properties);
+            }
+            shading_rate.next = std::ptr::null_mut();
+            shading_rate_properties.next = std::ptr::null_mut();
+        }
+
+        // Conservative rasterization has no feature to enable, only properties describing how
+        // much the primitives can be overestimated, and whether they can be underestimated.
+        let mut conservative_rasterization =
+            vk::PhysicalDeviceConservativeRasterizationPropertiesEXT::default();
+        if optional_extensions.contains(&vk::EXT_CONSERVATIVE_RASTERIZATION_EXTENSION.name) {
+            unsafe {
+                let mut properties = vk::PhysicalDeviceProperties2::builder()
+                    .push_next(&mut conservative_rasterization);
+                context
+                    .instance()
+                    .get_physical_device_properties2(physical, &mut properties);
+            }
+            conservative_rasterization.next = std::ptr::null_mut();
+        }
+
         // The list of extensions to enable for the logical device. This should include the
         // swapchain extension, as it is required for rendering to the screen. Then, create the
         // device create info with the queues, extensions, layers, and features.
-        let extensions = DEVICE_EXTENSIONS
+        let extensions = required_extensions
             .iter()
+            .chain(
+                optional_extensions
+                    .iter()
+                    .filter(|e| !required_extensions.contains(e)),
+            )
             .map(|e| e.as_ptr())
             .collect::<Vec<_>>();
-        let features = vk::PhysicalDeviceFeatures::builder().sampler_anisotropy(true);
-        let mut feature_1_3 = vk::PhysicalDeviceVulkan13Features::builder()
-            .dynamic_rendering(true)
-            .synchronization2(true);
-        let device_create_info = vk::DeviceCreateInfo::builder()
+        // Optional features are only enabled if the physical device supports them.
+        let supported = unsafe { context.instance().get_physical_device_features(physical) };
+        let features = vk::PhysicalDeviceFeatures::builder()
+            .texture_compression_bc(supported.texture_compression_bc == vk::TRUE)
+            .texture_compression_etc2(supported.texture_compression_etc2 == vk::TRUE)
+            .texture_compression_astc_ldr(supported.texture_compression_astc_ldr == vk::TRUE)
+            .multi_draw_indirect(supported.multi_draw_indirect == vk::TRUE)
+            .tessellation_shader(supported.tessellation_shader == vk::TRUE)
+            .multi_viewport(supported.multi_viewport == vk::TRUE)
+            .depth_bounds(supported.depth_bounds == vk::TRUE)
+            .fill_mode_non_solid(supported.fill_mode_non_solid == vk::TRUE)
+            .wide_lines(supported.wide_lines == vk::TRUE)
+            .large_points(supported.large_points == vk::TRUE)
+            .sample_rate_shading(supported.sample_rate_shading == vk::TRUE)
+            .depth_clamp(supported.depth_clamp == vk::TRUE)
+            .depth_bias_clamp(supported.depth_bias_clamp == vk::TRUE)
+            .sampler_anisotropy(true)
+            .build();
+
+        // The draw indirect count feature lets the GPU decide how many indirect draws are
+        // executed, for example after a culling compute shader. Timeline semaphores are always
+        // supported by Vulkan 1.2 devices.
+        let mut supported_1_2 = vk::PhysicalDeviceVulkan12Features::default();
+        unsafe {
+            let mut supported =
+                vk::PhysicalDeviceFeatures2::builder().push_next(&mut supported_1_2);
+            context
+                .instance()
+                .get_physical_device_features2(physical, &mut supported);
+        }
+        let draw_indirect_count = supported_1_2.draw_indirect_count == vk::TRUE;
+
+        // Layered rendering selects the layer of each primitive from the vertex shader, which
+        // avoids a geometry shader to render all the faces of a cube map in one pass.
+        let shader_output_layer = supported_1_2.shader_output_layer == vk::TRUE;
+
+        // Bindless textures index a large, partially bound array of samplers that is updated
+        // while the command buffers using it are pending, so they need all of these features.
+        let descriptor_indexing = [
+            supported_1_2.runtime_descriptor_array,
+            supported_1_2.descriptor_binding_partially_bound,
+            supported_1_2.descriptor_binding_variable_descriptor_count,
+            supported_1_2.descriptor_binding_sampled_image_update_after_bind,
+            supported_1_2.descriptor_binding_update_unused_while_pending,
+            supported_1_2.shader_sampled_image_array_non_uniform_indexing,
+        ]
+        .iter()
+        .all(|&supported| supported == vk::TRUE);
+        let feature_1_2 = vk::PhysicalDeviceVulkan12Features::builder()
+            .draw_indirect_count(draw_indirect_count)
+            .timeline_semaphore(true)
+            .shader_output_layer(shader_output_layer)
+            .runtime_descriptor_array(descriptor_indexing)
+            .descriptor_binding_partially_bound(descriptor_indexing)
+            .descriptor_binding_variable_descriptor_count(descriptor_indexing)
+            .descriptor_binding_sampled_image_update_after_bind(descriptor_indexing)
+            .descriptor_binding_update_unused_while_pending(descriptor_indexing)
+            .shader_sampled_image_array_non_uniform_indexing(descriptor_indexing);
+
+        // Dynamic rendering is only core since Vulkan 1.3, and the Vulkan 1.3 features must not
+        // be given to the drivers of older devices. Classic render passes are used instead.
+        let vulkan_1_3 = capabilities.properties.api_version >= vk::make_version(1, 3, 0);
+        let dynamic_rendering = capabilities.vulkan13.dynamic_rendering == vk::TRUE;
+        let feature_1_3 = vk::PhysicalDeviceVulkan13Features::builder()
+            .dynamic_rendering(dynamic_rendering)
+            .synchronization2(capabilities.vulkan13.synchronization2 == vk::TRUE);
+
+        // Add the features required by the application, which the device is known to support,
+        // and each group of optional features that the device fully supports.
+        // Multiview renders the same draws to several layers of the attachments at once, with
+        // a different view index in the shaders, for example for both eyes of a VR headset.
+        let feature_1_1 = vk::PhysicalDeviceVulkan11Features::builder()
+            .multiview(capabilities.vulkan11.multiview == vk::TRUE);
+        let mut enabled = DeviceFeatures {
+            features,
+            vulkan11: feature_1_1.build(),
+            vulkan12: feature_1_2.build(),
+            vulkan13: feature_1_3.build(),
+        };
+        enabled.insert(&request.required_features);
+        let available = DeviceFeatures::supported(&capabilities);
+        for group in &request.optional_features {
+            let mut features = DeviceFeatures::default();
+            group(&mut features);
+            if available.contains(&features) {
+                enabled.insert(&features);
+            }
+        }
+        let mut memory_priority =
+            vk::PhysicalDeviceMemoryPriorityFeaturesEXT::builder().memory_priority(true);
+        let mut pageable_memory = vk::PhysicalDevicePageableDeviceLocalMemoryFeaturesEXT::builder()
+            .pageable_device_local_memory(true);
+
+        let mut device_create_info = vk::DeviceCreateInfo::builder()
             .enabled_extension_names(&extensions)
             .enabled_layer_names(&layers_names)
             .queue_create_infos(&queues_create_info)
-            .enabled_features(&features)
-            .push_next(&mut feature_1_3);
+            .enabled_features(&enabled.features)
+            .push_next(&mut enabled.vulkan11)
+            .push_next(&mut enabled.vulkan12);
+
+        if vulkan_1_3 {
+            device_create_info = device_create_info.push_next(&mut enabled.vulkan13);
+        }
+        if let Some(subset) = &mut portability_subset {
+            device_create_info = device_create_info.push_next(subset);
+        }
+
+        if optional_extensions.contains(&vk::EXT_MEMORY_PRIORITY_EXTENSION.name) {
+            device_create_info = device_create_info.push_next(&mut memory_priority);
+        }
+        if optional_extensions.contains(&vk::EXT_PAGEABLE_DEVICE_LOCAL_MEMORY_EXTENSION.name) {
+            device_create_info = device_create_info.push_next(&mut pageable_memory);
+        }
+        if fragment_shading_rate {
+            device_create_info = device_create_info.push_next(&mut shading_rate);
+        }
 
         // Create the logical device from the physical device,
         // queue info, and device features.
@@ -136,20 +442,119 @@ impl VulkanDevice {
                 .expect("Failed to create logical device")
         };
 
-        Self {
+        // The structure was linked into the create info, so its pointer to the next structure
+        // would dangle.
+        if let Some(subset) = &mut portability_subset {
+            subset.next = std::ptr::null_mut();
+        }
+        shading_rate.next = std::ptr::null_mut();
+
+        let limits = unsafe {
+            context
+                .instance()
+                .get_physical_device_properties(physical)
+                .limits
+        };
+
+        let device = Self {
+            instance: context.instance().clone(),
             physical,
             logical,
             queues_info,
+            optional_extensions,
+            features: enabled,
+            capabilities,
+            dynamic_rendering,
+            portability_subset,
+            shading_rate,
+            shading_rate_properties,
+            conservative_rasterization,
+            draw_indirect_count,
+            shader_output_layer,
+            descriptor_indexing,
+            limits,
+            debug_utils: context.debug_utils(),
+            deletion: Mutex::default(),
+            render_passes: Mutex::default(),
+        };
+
+        // Name the queues so that the validation messages and the graphics debuggers tell
+        // them apart. When several queues share a family, they are the same queue and the
+        // last name wins.
+        let queues = VulkanQueues::fetch(&device);
+        let names = [
+            (Some(queues.main()), "main queue"),
+            (Some(queues.present()), "present queue"),
+            (queues.async_transfer(), "async transfer queue"),
+            (queues.async_compute(), "async compute queue"),
+        ];
+        for (queue, name) in names.into_iter().rev() {
+            if let Some(queue) = queue {
+                device.set_object_name(vk::ObjectType::QUEUE, queue.as_raw() as u64, name);
+            }
         }
+        device
+    }
+
+    /// Returns the optional extensions supported by the physical device. The memory priority
+    /// and pageable device local memory extensions only expose a single feature each, so they
+    /// are only considered as supported if their feature is supported too.
+    fn supported_optional_extensions(
+        context: &VulkanContext,
+        physical: vk::PhysicalDevice,
+    ) -> HashSet<vk::ExtensionName> {
+        let available = unsafe {
+            context
+                .instance()
+                .enumerate_device_extension_properties(physical, None)
+                .expect("Failed to enumerate device extensions")
+                .iter()
+                .map(|e| e.extension_name)
+                .collect::<HashSet<_>>()
+        };
+
+        let mut memory_priority = vk::PhysicalDeviceMemoryPriorityFeaturesEXT::default();
+        let mut pageable_memory = vk::PhysicalDevicePageableDeviceLocalMemoryFeaturesEXT::default();
+        let mut features = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut memory_priority)
+            .push_next(&mut pageable_memory);
+
+        unsafe {
+            context
+                .instance()
+                .get_physical_device_features2(physical, &mut features);
+        }
+
+        OPTIONAL_DEVICE_EXTENSIONS
+            .iter()
+            .filter(|e| available.contains(e))
+            .filter(|&&e| {
+                if e == vk::EXT_MEMORY_PRIORITY_EXTENSION.name {
+                    memory_priority.memory_priority == vk::TRUE
+                } else if e == vk::EXT_PAGEABLE_DEVICE_LOCAL_MEMORY_EXTENSION.name {
+                    // The pageable device local memory extension depends on the memory
+                    // priority extension.
+                    pageable_memory.pageable_device_local_memory == vk::TRUE
+                        && memory_priority.memory_priority == vk::TRUE
+                        && available.contains(&vk::EXT_MEMORY_PRIORITY_EXTENSION.name)
+                } else {
+                    true
+                }
+            })
+            .copied()
+            .collect()
     }
 
     /// Verify if the physical device is suitable for the application. This checks if the physical
-    /// device supports all the required features, capabilities, and extensions needed by Amethyst.
+    /// device supports all the required features, capabilities, and extensions needed by Amethyst,
+    /// the given required extensions, and the features and extensions required by the request.
     pub fn suitable_device(
         context: &VulkanContext,
         device: &vk::PhysicalDevice,
-        _properties: &vk::PhysicalDeviceProperties,
+        properties: &vk::PhysicalDeviceProperties,
         _features: &vk::PhysicalDeviceFeatures,
+        required_extensions: &[vk::ExtensionName],
+        request: &DeviceFeatureRequest,
     ) -> bool {
         // Get all the extensions supported by the physical device.
         let extensions = unsafe {
@@ -163,12 +568,23 @@ impl VulkanDevice {
         };
 
         // Check if the physical device supports all the required extensions.
-        if !DEVICE_EXTENSIONS.iter().all(|e| extensions.contains(e)) {
+        if !required_extensions
+            .iter()
+            .chain(request.required_extensions.iter())
+            .all(|e| extensions.contains(e))
+        {
+            return false;
+        }
+
+        // Check if the physical device supports all the features required by the application.
+        let capabilities = DeviceCapabilities::query(context, *device);
+        if !DeviceFeatures::supported(&capabilities).contains(&request.required_features) {
             return false;
         }
 
-        // TODO: Verify that extensions like dynamic rendering and synchronization2 are supported
-        true
+        // Timeline semaphores and the other Vulkan 1.2 features are always used, while dynamic
+        // rendering falls back to classic render passes on older devices.
+        properties.api_version >= vk::make_version(1, 2, 0)
     }
 
     /// Returns the vulkan physical device object.
@@ -183,21 +599,412 @@ impl VulkanDevice {
         &self.logical
     }
 
+    /// Returns the features enabled on the logical device. Optional features, like the BC
+    /// texture compression, must be checked here before being used.
+    #[must_use]
+    pub const fn enabled_features(&self) -> &vk::PhysicalDeviceFeatures {
+        &self.features.features
+    }
+
+    /// Returns all the core features enabled on the logical device, including the optional
+    /// features of the [`DeviceFeatureRequest`] that the device supports. Renderers can branch
+    /// on them to use a feature only where it is available.
+    #[must_use]
+    pub const fn features(&self) -> &DeviceFeatures {
+        &self.features
+    }
+
+    /// Returns the capabilities of the physical device: its properties, and all the features
+    /// and extensions that it supports, whether they are enabled or not.
+    #[must_use]
+    pub const fn capabilities(&self) -> &DeviceCapabilities {
+        &self.capabilities
+    }
+
+    /// Returns whether the dynamic rendering feature is enabled. Otherwise, the pipelines are
+    /// created against classic render passes, and
+    /// [`CommandBuffer::start_rendering`](crate::command::CommandBuffer::start_rendering) begins
+    /// a render pass with a framebuffer instead of a dynamic render pass instance.
+    #[must_use]
+    pub const fn supports_dynamic_rendering(&self) -> bool {
+        self.dynamic_rendering
+    }
+
+    /// Returns the features of the portability subset if the device is a portability
+    /// implementation, like MoltenVK on macOS and iOS, or `None` if it is a conformant
+    /// implementation. The features missing from the subset must not be used, for example
+    /// triangle fans, events or a LOD bias on the samplers.
+    #[must_use]
+    pub const fn portability_subset(
+        &self,
+    ) -> Option<&vk::PhysicalDevicePortabilitySubsetFeaturesKHR> {
+        self.portability_subset.as_ref()
+    }
+
+    /// Returns a render pass with the given attachments, created on the first call with
+    /// these attachments and destroyed with the device.
+    ///
+    /// # Panics
+    /// Panics if the render pass cache is poisoned.
+    #[must_use]
+    pub fn render_pass(&self, layout: &RenderPassLayout) -> vk::RenderPass {
+        let mut render_passes = self.render_passes.lock().expect("Render passes poisoned");
+        if let Some(&render_pass) = render_passes.get(layout) {
+            return render_pass;
+        }
+        let render_pass = create_render_pass(&self.logical, layout);
+        render_passes.insert(layout.clone(), render_pass);
+        render_pass
+    }
+
+    /// Returns whether the draw indirect count feature is enabled, which is required to draw
+    /// with a number of draws read from a buffer.
+    #[must_use]
+    pub const fn supports_draw_indirect_count(&self) -> bool {
+        self.draw_indirect_count
+    }
+
+    /// Returns the features supported by the physical device for the given format.
+    #[must_use]
+    pub fn format_properties(&self, format: vk::Format) -> vk::FormatProperties {
+        unsafe {
+            self.instance
+                .get_physical_device_format_properties(self.physical, format)
+        }
+    }
+
+    /// Returns whether images in the given format can be created with the optimal tiling and
+    /// sampled by the shaders, for example to check which compressed formats can be uploaded
+    /// as they are instead of being decompressed.
+    #[must_use]
+    pub fn supports_sampled_format(&self, format: vk::Format) -> bool {
+        self.format_properties(format)
+            .optimal_tiling_features
+            .contains(vk::FormatFeatureFlags::SAMPLED_IMAGE)
+    }
+
+    /// Returns whether the shader output layer feature is enabled, which is required to write
+    /// `gl_Layer` from a vertex shader for layered rendering.
+    #[must_use]
+    pub const fn supports_shader_output_layer(&self) -> bool {
+        self.shader_output_layer
+    }
+
+    /// Returns whether the descriptor indexing features are enabled, which are required by
+    /// [`BindlessTextures`](crate::bindless::BindlessTextures).
+    #[must_use]
+    pub const fn supports_descriptor_indexing(&self) -> bool {
+        self.descriptor_indexing
+    }
+
+    /// Returns whether the tessellation shader feature is enabled, which is required to create
+    /// pipelines with tessellation shaders and the `vk::PrimitiveTopology::PATCH_LIST`
+    /// topology.
+    #[must_use]
+    pub const fn supports_tessellation(&self) -> bool {
+        self.features.features.tessellation_shader == vk::TRUE
+    }
+
+    /// Returns whether the multiview feature is enabled, which is required to render with a
+    /// non-zero view mask, see [`RenderingInfo::view_mask`].
+    ///
+    /// [`RenderingInfo::view_mask`]: crate::command::RenderingInfo::view_mask
+    #[must_use]
+    pub const fn supports_multiview(&self) -> bool {
+        self.features.vulkan11.multiview == vk::TRUE
+    }
+
+    /// Returns whether the multiple viewports feature is enabled, which is required to create
+    /// pipelines with more than one viewport.
+    #[must_use]
+    pub const fn supports_multi_viewport(&self) -> bool {
+        self.features.features.multi_viewport == vk::TRUE
+    }
+
+    /// Returns whether the depth bounds feature is enabled, which is required to create
+    /// pipelines with [`PipelineCreateInfo::depth_bounds`].
+    ///
+    /// [`PipelineCreateInfo::depth_bounds`]: crate::pipeline::PipelineCreateInfo::depth_bounds
+    #[must_use]
+    pub const fn supports_depth_bounds(&self) -> bool {
+        self.features.features.depth_bounds == vk::TRUE
+    }
+
+    /// Returns whether the non-solid fill mode feature is enabled, which is required to create
+    /// pipelines drawing the edges or the vertices of the polygons, with the `LINE` and `POINT`
+    /// polygon modes.
+    #[must_use]
+    pub const fn supports_fill_mode_non_solid(&self) -> bool {
+        self.features.features.fill_mode_non_solid == vk::TRUE
+    }
+
+    /// Returns whether the wide lines feature is enabled, which is required to draw lines of a
+    /// width other than 1.0, within the line width range of the [limits](Self::limits).
+    #[must_use]
+    pub const fn supports_wide_lines(&self) -> bool {
+        self.features.features.wide_lines == vk::TRUE
+    }
+
+    /// Returns whether the large points feature is enabled, which is required to write a point
+    /// size other than 1.0 to `gl_PointSize`, within the point size range of the
+    /// [limits](Self::limits).
+    #[must_use]
+    pub const fn supports_large_points(&self) -> bool {
+        self.features.features.large_points == vk::TRUE
+    }
+
+    /// Returns whether the sample rate shading feature is enabled, which is required to create
+    /// pipelines with [`PipelineCreateInfo::min_sample_shading`].
+    ///
+    /// [`PipelineCreateInfo::min_sample_shading`]:
+    ///     crate::pipeline::PipelineCreateInfo::min_sample_shading
+    #[must_use]
+    pub const fn supports_sample_rate_shading(&self) -> bool {
+        self.features.features.sample_rate_shading == vk::TRUE
+    }
+
+    /// Returns whether the depth clamp feature is enabled, which is required to create
+    /// pipelines with [`PipelineCreateInfo::depth_clamp`].
+    ///
+    /// [`PipelineCreateInfo::depth_clamp`]: crate::pipeline::PipelineCreateInfo::depth_clamp
+    #[must_use]
+    pub const fn supports_depth_clamp(&self) -> bool {
+        self.features.features.depth_clamp == vk::TRUE
+    }
+
+    /// Returns whether the depth bias clamp feature is enabled, which is required to clamp the
+    /// depth bias to a value other than 0.
+    #[must_use]
+    pub const fn supports_depth_bias_clamp(&self) -> bool {
+        self.features.features.depth_bias_clamp == vk::TRUE
+    }
+
+    /// Returns whether the pipeline fragment shading rate feature is enabled, which is required
+    /// to create pipelines with a
+    /// [`FragmentShadingRate`](crate::pipeline::FragmentShadingRate).
+    #[must_use]
+    pub const fn supports_pipeline_shading_rate(&self) -> bool {
+        self.shading_rate.pipeline_fragment_shading_rate == vk::TRUE
+    }
+
+    /// Returns whether the primitive fragment shading rate feature is enabled, which is
+    /// required to write `gl_PrimitiveShadingRateEXT` from the vertex shaders.
+    #[must_use]
+    pub const fn supports_primitive_shading_rate(&self) -> bool {
+        self.shading_rate.primitive_fragment_shading_rate == vk::TRUE
+    }
+
+    /// Returns whether the attachment fragment shading rate feature is enabled and dynamic
+    /// rendering is supported, which are both required to render with a shading rate
+    /// attachment, see [`RenderingInfo::shading_rate_attachment`].
+    ///
+    /// [`RenderingInfo::shading_rate_attachment`]:
+    ///     crate::command::RenderingInfo::shading_rate_attachment
+    #[must_use]
+    pub const fn supports_attachment_shading_rate(&self) -> bool {
+        self.shading_rate.attachment_fragment_shading_rate == vk::TRUE && self.dynamic_rendering
+    }
+
+    /// Returns the fragment shading rate properties of the physical device, like the minimum
+    /// and maximum texel sizes of the shading rate attachments. They are all zero if the
+    /// fragment shading rate extension is not enabled.
+    #[must_use]
+    pub const fn shading_rate_properties(
+        &self,
+    ) -> &vk::PhysicalDeviceFragmentShadingRatePropertiesKHR {
+        &self.shading_rate_properties
+    }
+
+    /// Returns whether the conservative rasterization extension is enabled, which is required
+    /// to create pipelines with [`PipelineCreateInfo::conservative_rasterization`].
+    ///
+    /// [`PipelineCreateInfo::conservative_rasterization`]:
+    ///     crate::pipeline::PipelineCreateInfo::conservative_rasterization
+    #[must_use]
+    pub fn supports_conservative_rasterization(&self) -> bool {
+        self.is_extension_enabled(vk::EXT_CONSERVATIVE_RASTERIZATION_EXTENSION.name)
+    }
+
+    /// Returns the conservative rasterization properties of the physical device, like the
+    /// maximum extra overestimation size and whether underestimation is supported. They are
+    /// all zero if the conservative rasterization extension is not enabled.
+    #[must_use]
+    pub const fn conservative_rasterization_properties(
+        &self,
+    ) -> &vk::PhysicalDeviceConservativeRasterizationPropertiesEXT {
+        &self.conservative_rasterization
+    }
+
+    /// Returns the limits of the physical device.
+    #[must_use]
+    pub const fn limits(&self) -> &vk::PhysicalDeviceLimits {
+        &self.limits
+    }
+
+    /// Returns the highest number of samples supported by both the color and the depth
+    /// attachments, to choose the level of MSAA.
+    #[must_use]
+    pub fn max_sample_count(&self) -> SampleCount {
+        let supported = self.limits.framebuffer_color_sample_counts
+            & self.limits.framebuffer_depth_sample_counts;
+        [SampleCount::Eight, SampleCount::Four, SampleCount::Two]
+            .into_iter()
+            .find(|&samples| supported.contains(samples.into()))
+            .unwrap_or(SampleCount::One)
+    }
+
+    /// Give a name to a Vulkan object created from this device, displayed in the validation
+    /// messages and by graphics debuggers like RenderDoc or Nsight instead of its raw handle.
+    /// This does nothing if the debug utils extension is not enabled.
+    ///
+    /// # Panics
+    /// Panics if the name contains a null byte.
+    pub fn set_object_name(&self, object_type: vk::ObjectType, handle: u64, name: &str) {
+        if !self.debug_utils {
+            return;
+        }
+
+        let name = CString::new(name).expect("Object names must not contain null bytes");
+        let info = vk::DebugUtilsObjectNameInfoEXT::builder()
+            .object_type(object_type)
+            .object_handle(handle)
+            .object_name(name.as_bytes_with_nul());
+
+        // Naming an object is only a debugging aid, so a failure is not worth a panic.
+        unsafe {
+            if let Err(error) = self
+                .instance
+                .set_debug_utils_object_name_ext(self.logical.handle(), &info)
+            {
+                log::warn!("Failed to name {object_type:?} object: {error}");
+            }
+        }
+    }
+
+    /// Returns whether the debug utils extension is enabled, in which case the objects can be
+    /// named and the commands labelled.
+    #[must_use]
+    pub const fn debug_utils(&self) -> bool {
+        self.debug_utils
+    }
+
+    /// Returns the Vulkan instance from which the device was created, to call the instance
+    /// level commands that take the device as a parameter.
+    #[must_use]
+    pub(crate) const fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// Returns whether the given optional extension is supported by the physical device and
+    /// has been enabled on the logical device. This includes the extensions of the
+    /// [`DeviceFeatureRequest`] given when the device was created.
+    #[must_use]
+    pub fn is_extension_enabled(&self, extension: vk::ExtensionName) -> bool {
+        self.optional_extensions.contains(&extension)
+    }
+
     /// Returns the device queues information.
     #[must_use]
     pub const fn queues_info(&self) -> &DeviceQueueInfo {
         &self.queues_info
     }
+
+    /// Begin a new frame, and returns its index. The resources dropped from now on are only
+    /// destroyed once [`VulkanDevice::end_frame`] is called with this index or a later one,
+    /// since the commands of the frame may still use them.
+    pub fn begin_frame(&self) -> u64 {
+        self.deletion_queue().begin_frame()
+    }
+
+    /// Mark the given frame and all the previous ones as complete, once the fence of the frame
+    /// has signaled or its queue is idle, and destroy the resources dropped during them.
+    pub fn end_frame(&self, frame: u64) {
+        self.deletion_queue().end_frame(&self.logical, frame);
+    }
+
+    /// Returns the index of the last frame marked as complete with
+    /// [`VulkanDevice::end_frame`], or zero if no frame has completed yet.
+    #[must_use]
+    pub fn completed_frame(&self) -> u64 {
+        self.deletion_queue().completed()
+    }
+
+    /// Returns the number of resources dropped during frames that are not complete yet, and
+    /// that are waiting to be destroyed.
+    #[must_use]
+    pub fn pending_deletions(&self) -> usize {
+        self.deletion_queue().pending()
+    }
+
+    /// Destroy the given object once the frames that may use it are complete.
+    pub(crate) fn destroy_later(&self, deletion: Deletion) {
+        self.deletion_queue().push(&self.logical, deletion);
+    }
+
+    /// Lock the deletion queue of the device.
+    fn deletion_queue(&self) -> MutexGuard<'_, DeletionQueue> {
+        self.deletion.lock().expect("Deletion queue poisoned")
+    }
 }
 
 impl Drop for VulkanDevice {
     fn drop(&mut self) {
         unsafe {
+            self.logical
+                .device_wait_idle()
+                .expect("Failed to wait for device idle");
+            self.deletion
+                .get_mut()
+                .expect("Deletion queue poisoned")
+                .flush(&self.logical);
+            let render_passes = self
+                .render_passes
+                .get_mut()
+                .expect("Render passes poisoned");
+            for (_, render_pass) in render_passes.drain() {
+                self.logical.destroy_render_pass(render_pass, None);
+            }
             self.logical.destroy_device(None);
         }
     }
 }
 
+/// Warn about the features of Vulkan that are not supported by a portability implementation,
+/// among the ones a renderer is likely to rely on.
+fn log_portability_caveats(subset: &vk::PhysicalDevicePortabilitySubsetFeaturesKHR) {
+    let missing = [
+        (
+            "constant alpha blend factors",
+            subset.constant_alpha_color_blend_factors,
+        ),
+        ("events", subset.events),
+        ("image view swizzles", subset.image_view_format_swizzle),
+        ("multisampled array images", subset.multisample_array_image),
+        (
+            "mutable comparison samplers",
+            subset.mutable_comparison_samplers,
+        ),
+        ("sampler LOD bias", subset.sampler_mip_lod_bias),
+        ("separate stencil masks", subset.separate_stencil_mask_ref),
+        ("triangle fans", subset.triangle_fans),
+        (
+            "vertex attributes beyond the stride",
+            subset.vertex_attribute_access_beyond_stride,
+        ),
+    ]
+    .into_iter()
+    .filter(|&(_, supported)| supported != vk::TRUE)
+    .map(|(name, _)| name)
+    .collect::<Vec<_>>();
+
+    if !missing.is_empty() {
+        log::warn!(
+            "The device is a Vulkan portability implementation without: {}",
+            missing.join(", ")
+        );
+    }
+}
+
 /// The device queues. This contains the main queue family that supports graphics, compute, and
 /// transfer operations, and optional async transfer and async compute queue families that support
 /// transfer and compute operations, respectively.
@@ -213,8 +1020,13 @@ impl DeviceQueueInfo {
     /// Create a new set of device queues from the physical device. This will find the main queue
     /// that supports graphics, compute, and transfer operations, and try to find async transfer
     /// and async compute queues that support transfer and compute operations, respectively.
+    /// Without a surface, nothing is presented and the present family is the main family.
     #[must_use]
-    pub fn new(context: &VulkanContext, device: vk::PhysicalDevice, surface: &Surface) -> Self {
+    pub fn new(
+        context: &VulkanContext,
+        device: vk::PhysicalDevice,
+        surface: Option<&Surface>,
+    ) -> Self {
         let families = unsafe {
             context
                 .instance()
@@ -243,16 +1055,19 @@ impl DeviceQueueInfo {
         // Find a queue family that supports presenting to the surface. This is used for
         // presenting the rendered images to the screen. It may be the same as the main queue,
         // but this does not really matter for most applications.
-        let present = families
-            .iter()
-            .find(|(index, _)| unsafe {
-                context
-                    .instance()
-                    .get_physical_device_surface_support_khr(device, *index, surface.inner())
-                    .expect("Failed to get surface support")
-            })
-            .map(|(index, _)| *index)
-            .expect("No present queue family found");
+        let present = match surface {
+            Some(surface) => families
+                .iter()
+                .find(|(index, _)| unsafe {
+                    context
+                        .instance()
+                        .get_physical_device_surface_support_khr(device, *index, surface.inner())
+                        .expect("Failed to get surface support")
+                })
+                .map(|(index, _)| *index)
+                .expect("No present queue family found"),
+            None => *main,
+        };
 
         // Try to find a queue family that supports transfer operations, but is not the main queue
         // family. This is used for async transfer operations alongside graphics and compute
@@ -425,3 +1240,205 @@ impl VulkanQueues {
         self.async_compute
     }
 }
+
+/// Returns the range of bytes of a feature structure holding its boolean fields, from the first
+/// to the last given field.
+macro_rules! feature_flags {
+    ($kind:ty, $first:ident, $last:ident) => {
+        std::mem::offset_of!($kind, $first)
+            ..std::mem::offset_of!($kind, $last) + std::mem::size_of::<vk::Bool32>()
+    };
+}
+
+/// Returns the boolean fields of a feature structure in the given range of bytes.
+///
+/// # Safety
+/// The range must only cover `vk::Bool32` fields of the structure. This holds for the ranges
+/// returned by `feature_flags!`, since the feature structures are `#[repr(C)]` and only
+/// contain `vk::Bool32` fields after their header.
+unsafe fn flags<T>(features: &T, range: Range<usize>) -> &[vk::Bool32] {
+    let start = std::ptr::from_ref(features).cast::<u8>().add(range.start);
+    let len = range.len() / std::mem::size_of::<vk::Bool32>();
+    std::slice::from_raw_parts(start.cast(), len)
+}
+
+/// Returns the boolean fields of a feature structure in the given range of bytes, mutably.
+///
+/// # Safety
+/// Same as [`flags`].
+unsafe fn flags_mut<T>(features: &mut T, range: Range<usize>) -> &mut [vk::Bool32] {
+    let start = std::ptr::from_mut(features).cast::<u8>().add(range.start);
+    let len = range.len() / std::mem::size_of::<vk::Bool32>();
+    std::slice::from_raw_parts_mut(start.cast(), len)
+}
+
+/// The core features of a device, from Vulkan 1.0 to 1.3. This is used both to request the
+/// features to enable on a device, and to query the features enabled on it.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceFeatures {
+    /// The Vulkan 1.0 features.
+    pub features: vk::PhysicalDeviceFeatures,
+
+    /// The Vulkan 1.1 features.
+    pub vulkan11: vk::PhysicalDeviceVulkan11Features,
+
+    /// The Vulkan 1.2 features.
+    pub vulkan12: vk::PhysicalDeviceVulkan12Features,
+
+    /// The Vulkan 1.3 features.
+    pub vulkan13: vk::PhysicalDeviceVulkan13Features,
+}
+
+impl DeviceFeatures {
+    /// Returns the features supported by a physical device.
+    #[must_use]
+    pub fn supported(capabilities: &DeviceCapabilities) -> Self {
+        Self {
+            features: capabilities.features,
+            vulkan11: capabilities.vulkan11,
+            vulkan12: capabilities.vulkan12,
+            vulkan13: capabilities.vulkan13,
+        }
+    }
+
+    /// Returns whether all the features enabled in `other` are also enabled in `self`.
+    #[must_use]
+    pub fn contains(&self, other: &Self) -> bool {
+        self.flags()
+            .into_iter()
+            .flatten()
+            .zip(other.flags().into_iter().flatten())
+            .all(|(&this, &other)| this == vk::TRUE || other != vk::TRUE)
+    }
+
+    /// Enable all the features enabled in `other`.
+    pub fn insert(&mut self, other: &Self) {
+        let enabled = self.flags_mut().into_iter().flatten();
+        for (this, &other) in enabled.zip(other.flags().into_iter().flatten()) {
+            if other == vk::TRUE {
+                *this = vk::TRUE;
+            }
+        }
+    }
+
+    /// Returns the boolean fields of each feature structure.
+    fn flags(&self) -> [&[vk::Bool32]; 4] {
+        let [features, vulkan11, vulkan12, vulkan13] = Self::ranges();
+
+        // SAFETY: The ranges only cover the boolean fields of the structures.
+        unsafe {
+            [
+                flags(&self.features, features),
+                flags(&self.vulkan11, vulkan11),
+                flags(&self.vulkan12, vulkan12),
+                flags(&self.vulkan13, vulkan13),
+            ]
+        }
+    }
+
+    /// Returns the boolean fields of each feature structure, mutably.
+    fn flags_mut(&mut self) -> [&mut [vk::Bool32]; 4] {
+        let [features, vulkan11, vulkan12, vulkan13] = Self::ranges();
+
+        // SAFETY: The ranges only cover the boolean fields of the structures.
+        unsafe {
+            [
+                flags_mut(&mut self.features, features),
+                flags_mut(&mut self.vulkan11, vulkan11),
+                flags_mut(&mut self.vulkan12, vulkan12),
+                flags_mut(&mut self.vulkan13, vulkan13),
+            ]
+        }
+    }
+
+    /// Returns the range of bytes of the boolean fields of each feature structure.
+    fn ranges() -> [Range<usize>; 4] {
+        [
+            feature_flags!(
+                vk::PhysicalDeviceFeatures,
+                robust_buffer_access,
+                inherited_queries
+            ),
+            feature_flags!(
+                vk::PhysicalDeviceVulkan11Features,
+                storage_buffer_16bit_access,
+                shader_draw_parameters
+            ),
+            feature_flags!(
+                vk::PhysicalDeviceVulkan12Features,
+                sampler_mirror_clamp_to_edge,
+                subgroup_broadcast_dynamic_id
+            ),
+            feature_flags!(
+                vk::PhysicalDeviceVulkan13Features,
+                robust_image_access,
+                maintenance4
+            ),
+        ]
+    }
+}
+
+/// The features and extensions that an application needs from the device, in addition to the
+/// ones used by Amethyst itself. The required ones exclude the physical devices that do not
+/// support them, while the optional ones are only enabled where they are supported, so that
+/// the renderer can branch on [`VulkanDevice::features`] and
+/// [`VulkanDevice::is_extension_enabled`].
+///
+/// The features are set with functions writing to a [`DeviceFeatures`], for example:
+///
+/// ```ignore
+/// let request = DeviceFeatureRequest::default()
+///     .require_features(|f| f.vulkan12.buffer_device_address = vk::TRUE)
+///     .optional_extension(vk::EXT_MESH_SHADER_EXTENSION.name);
+/// ```
+///
+/// Only the core features can be requested this way: the features of an extension, like the
+/// ones of the ray tracing or mesh shader extensions, are not enabled by enabling the
+/// extension alone.
+#[derive(Debug, Clone, Default, Resource)]
+pub struct DeviceFeatureRequest {
+    /// The extensions that the device must support.
+    required_extensions: Vec<vk::ExtensionName>,
+
+    /// The extensions enabled only if the device supports them.
+    optional_extensions: Vec<vk::ExtensionName>,
+
+    /// The features that the device must support.
+    required_features: DeviceFeatures,
+
+    /// The groups of features enabled only if the device supports all the features of the
+    /// group.
+    optional_features: Vec<fn(&mut DeviceFeatures)>,
+}
+
+impl DeviceFeatureRequest {
+    /// Require an extension, which the chosen device must support.
+    #[must_use]
+    pub fn require_extension(mut self, extension: vk::ExtensionName) -> Self {
+        self.required_extensions.push(extension);
+        self
+    }
+
+    /// Request an extension, enabled only if the chosen device supports it.
+    #[must_use]
+    pub fn optional_extension(mut self, extension: vk::ExtensionName) -> Self {
+        self.optional_extensions.push(extension);
+        self
+    }
+
+    /// Require the features enabled by the given function, which the chosen device must all
+    /// support.
+    #[must_use]
+    pub fn require_features(mut self, features: fn(&mut DeviceFeatures)) -> Self {
+        features(&mut self.required_features);
+        self
+    }
+
+    /// Request the group of features enabled by the given function. The features of a group
+    /// are enabled together, and only if the chosen device supports all of them.
+    #[must_use]
+    pub fn optional_features(mut self, features: fn(&mut DeviceFeatures)) -> Self {
+        self.optional_features.push(features);
+        self
+    }
+}
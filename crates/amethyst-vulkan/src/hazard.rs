@@ -0,0 +1,259 @@
+//! An opt-in debug mode tracking the use of images and buffers in each command buffer, and
+//! warning when a resource is read after being written, or used in another layout than its
+//! current one, without a barrier in between. This is much cheaper than the synchronization
+//! validation layer and reports the labels given to the commands with
+//! [`CommandBuffer::label`](crate::command::CommandBuffer::label), but it is also much less
+//! precise:
+//! - Whole resources are tracked, regardless of their mip levels, array layers or ranges.
+//! - The stages and access masks of the barriers are ignored: any barrier on a resource is
+//!   considered to cover all its previous uses.
+//! - Storage images and buffers are considered both read and written by every dispatch or draw
+//!   that binds them.
+//! - Each command buffer is tracked independently, and the layout of an image is unknown until
+//!   its first use in the command buffer.
+use crate::command::PipelineBarrierInfo;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex, OnceLock,
+    },
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// Whether the hazards of the command buffers are tracked.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The image of each image view, since descriptor sets and render passes only reference views
+/// while barriers reference images.
+static VIEWS: OnceLock<Mutex<HashMap<vk::ImageView, vk::Image>>> = OnceLock::new();
+
+/// Enable or disable the tracking of hazards. Only the command buffers allocated while the
+/// tracking is enabled are tracked.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether the tracking of hazards is enabled.
+#[must_use]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+fn views() -> &'static Mutex<HashMap<vk::ImageView, vk::Image>> {
+    VIEWS.get_or_init(Mutex::default)
+}
+
+/// Register the image of a view, so uses of the view can be tracked as uses of the image.
+pub(crate) fn register_view(view: vk::ImageView, image: vk::Image) {
+    if is_enabled() {
+        views()
+            .lock()
+            .expect("Hazard tracking views poisoned")
+            .insert(view, image);
+    }
+}
+
+/// Forget a view that is about to be destroyed, since its handle may be reused.
+pub(crate) fn unregister_view(view: vk::ImageView) {
+    if let Some(views) = VIEWS.get() {
+        views
+            .lock()
+            .expect("Hazard tracking views poisoned")
+            .remove(&view);
+    }
+}
+
+/// Returns the image of a view, if the view was registered.
+pub(crate) fn image_of(view: vk::ImageView) -> Option<vk::Image> {
+    VIEWS
+        .get()?
+        .lock()
+        .expect("Hazard tracking views poisoned")
+        .get(&view)
+        .copied()
+}
+
+/// A resource tracked by the hazard tracker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Resource {
+    Image(vk::Image),
+    Buffer(vk::Buffer),
+}
+
+/// How a resource is accessed by a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    const fn reads(self) -> bool {
+        matches!(self, Access::Read | Access::ReadWrite)
+    }
+
+    const fn writes(self) -> bool {
+        matches!(self, Access::Write | Access::ReadWrite)
+    }
+}
+
+/// A use of a resource by a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct ResourceUse {
+    pub resource: Resource,
+    pub access: Access,
+
+    /// The layout the image must be in, or `None` for buffers.
+    pub layout: Option<vk::ImageLayout>,
+}
+
+impl ResourceUse {
+    /// Returns the use of the image of a view, or `None` if the view was not registered.
+    pub fn view(view: vk::ImageView, access: Access, layout: vk::ImageLayout) -> Option<Self> {
+        Some(Self {
+            resource: Resource::Image(image_of(view)?),
+            access,
+            layout: Some(layout),
+        })
+    }
+
+    /// Returns the use of an image.
+    pub const fn image(image: vk::Image, access: Access, layout: vk::ImageLayout) -> Self {
+        Self {
+            resource: Resource::Image(image),
+            access,
+            layout: Some(layout),
+        }
+    }
+
+    /// Returns the use of a buffer.
+    pub const fn buffer(buffer: vk::Buffer, access: Access) -> Self {
+        Self {
+            resource: Resource::Buffer(buffer),
+            access,
+            layout: None,
+        }
+    }
+}
+
+/// The state of a resource in a command buffer.
+#[derive(Debug, Default)]
+struct ResourceState {
+    /// The current layout of the image, if known.
+    layout: Option<vk::ImageLayout>,
+
+    /// The label of the last command that wrote the resource, if no barrier was recorded
+    /// since.
+    pending_write: Option<String>,
+}
+
+/// The hazard tracker of a command buffer.
+#[derive(Debug, Default)]
+pub(crate) struct HazardTracker {
+    /// The label of the commands being recorded.
+    label: String,
+
+    /// The state of each resource used by the command buffer.
+    states: HashMap<Resource, ResourceState>,
+
+    /// The resources of the descriptor sets bound at each set index of each bind point.
+    bound: HashMap<(vk::PipelineBindPoint, u32), Vec<ResourceUse>>,
+}
+
+impl HazardTracker {
+    /// Create a tracker if the tracking is enabled.
+    pub fn new() -> Option<Box<Self>> {
+        is_enabled().then(Box::default)
+    }
+
+    /// Set the label of the next commands.
+    pub fn set_label(&mut self, label: &str) {
+        label.clone_into(&mut self.label);
+    }
+
+    /// Record a pipeline barrier, which synchronizes the previous writes of its resources and
+    /// transitions the layout of its images.
+    pub fn barrier(&mut self, info: &PipelineBarrierInfo) {
+        for barrier in &info.images_barriers {
+            let resource = Resource::Image(barrier.image);
+            let state = self.states.entry(resource).or_default();
+            if let Some(layout) = state.layout {
+                if barrier.old_layout != vk::ImageLayout::UNDEFINED && barrier.old_layout != layout
+                {
+                    log::warn!(
+                        "`{}`: barrier transitions {:?} from {:?}, but it is in {:?}",
+                        self.label,
+                        resource,
+                        barrier.old_layout,
+                        layout
+                    );
+                }
+            }
+            state.layout = Some(barrier.new_layout);
+            state.pending_write = None;
+        }
+
+        for barrier in &info.buffers_barriers {
+            let state = self
+                .states
+                .entry(Resource::Buffer(barrier.buffer))
+                .or_default();
+            state.pending_write = None;
+        }
+    }
+
+    /// Record the use of a resource by a command, warning about the hazards it causes.
+    pub fn use_resource(&mut self, usage: ResourceUse) {
+        let state = self.states.entry(usage.resource).or_default();
+
+        if usage.access.reads() {
+            if let Some(writer) = &state.pending_write {
+                log::warn!(
+                    "`{}`: {:?} is read after being written by `{}` without a barrier",
+                    self.label,
+                    usage.resource,
+                    writer
+                );
+            }
+        }
+
+        if let Some(expected) = usage.layout {
+            match state.layout {
+                Some(layout) if layout != expected => log::warn!(
+                    "`{}`: {:?} is used in {:?}, but it is in {:?}",
+                    self.label,
+                    usage.resource,
+                    expected,
+                    layout
+                ),
+                Some(_) => (),
+                None => state.layout = Some(expected),
+            }
+        }
+
+        if usage.access.writes() {
+            state.pending_write = Some(self.label.clone());
+        }
+    }
+
+    /// Record the binding of a descriptor set with the given resources.
+    pub fn bind(&mut self, bind_point: vk::PipelineBindPoint, set: u32, uses: Vec<ResourceUse>) {
+        self.bound.insert((bind_point, set), uses);
+    }
+
+    /// Record a dispatch or a draw, which uses all the resources bound to the bind point.
+    pub fn execute(&mut self, bind_point: vk::PipelineBindPoint) {
+        let uses = self
+            .bound
+            .iter()
+            .filter(|((point, _), _)| *point == bind_point)
+            .flat_map(|(_, uses)| uses.iter().copied())
+            .collect::<Vec<_>>();
+
+        for usage in uses {
+            self.use_resource(usage);
+        }
+    }
+}
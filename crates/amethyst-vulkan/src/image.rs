@@ -0,0 +1,1282 @@
+use crate::{
+    buffer::{
+        Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo, MemoryPriority,
+    },
+    command::{CommandBuffer, CommandPool, SubmitInfo},
+    deletion::Deletion,
+    device::{VulkanDevice, VulkanQueues},
+    hazard,
+};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// The usages allowed for transient images, which only live during a render pass.
+const TRANSIENT_USAGE: vk::ImageUsageFlags = vk::ImageUsageFlags::from_bits_truncate(
+    vk::ImageUsageFlags::COLOR_ATTACHMENT.bits()
+        | vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT.bits()
+        | vk::ImageUsageFlags::INPUT_ATTACHMENT.bits()
+        | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT.bits(),
+);
+
+/// A 2D image allocated in device local memory, with a view covering all its mip levels and
+/// array layers. The image can also be an array of 2D images, or a cube map.
+#[derive(Debug)]
+pub struct Image {
+    /// The device that owns the image view.
+    device: Arc<VulkanDevice>,
+
+    /// The allocator that allocated the image memory.
+    allocator: Arc<BufferAllocator>,
+
+    /// The allocation of the image memory.
+    allocation: vma::Allocation,
+
+    /// The vulkan image object.
+    inner: vk::Image,
+
+    /// A view covering the whole image.
+    view: vk::ImageView,
+
+    /// The format of the image.
+    format: vk::Format,
+
+    /// The extent of the first mip level of the image.
+    extent: vk::Extent2D,
+
+    /// The number of mip levels of the image.
+    mip_levels: u32,
+
+    /// The number of array layers of the image.
+    array_layers: u32,
+
+    /// The number of samples per pixel of the image.
+    samples: SampleCount,
+
+    /// Whether the image is a transient attachment, whose content only lives during a render
+    /// pass and may be backed by lazily allocated memory.
+    transient: bool,
+
+    /// The current layout of each mip level and array layer of the image, indexed by
+    /// `mip_level * array_layers + array_layer`. The layouts are updated when the transitions
+    /// are recorded, so the command buffers must be submitted in the order they were recorded.
+    layouts: Mutex<Vec<vk::ImageLayout>>,
+}
+
+impl Image {
+    /// Create a new image with the given allocator. The content of the image is undefined
+    /// and its layout is `vk::ImageLayout::UNDEFINED`.
+    ///
+    /// # Panics
+    /// Panics if the image is a cube map and does not have a multiple of 6 array layers, if a
+    /// multisampled image has several mip levels or is a cube map, or if a transient image is
+    /// used for anything else than an attachment.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        allocator: Arc<BufferAllocator>,
+        info: ImageCreateInfo,
+    ) -> Self {
+        assert!(
+            !info.cube || info.array_layers % 6 == 0,
+            "Cube map images must have a multiple of 6 array layers"
+        );
+        assert!(
+            info.samples == SampleCount::One || (info.mip_levels == 1 && !info.cube),
+            "Multisampled images must have a single mip level and cannot be cube maps"
+        );
+        assert!(
+            !info.transient || (TRANSIENT_USAGE | info.usage) == TRANSIENT_USAGE,
+            "Transient images can only be used as color, depth or input attachments"
+        );
+
+        let usage = if info.transient {
+            info.usage | vk::ImageUsageFlags::TRANSIENT_ATTACHMENT
+        } else {
+            info.usage
+        };
+
+        let flags = if info.cube {
+            vk::ImageCreateFlags::CUBE_COMPATIBLE
+        } else {
+            vk::ImageCreateFlags::empty()
+        };
+
+        let image_info = vk::ImageCreateInfo::builder()
+            .flags(flags)
+            .image_type(vk::ImageType::_2D)
+            .format(info.format)
+            .extent(vk::Extent3D {
+                width: info.extent.width,
+                height: info.extent.height,
+                depth: 1,
+            })
+            .mip_levels(info.mip_levels)
+            .array_layers(info.array_layers)
+            .samples(info.samples.into())
+            .tiling(vk::ImageTiling::OPTIMAL)
+            .usage(usage)
+            .sharing_mode(vk::SharingMode::EXCLUSIVE)
+            .initial_layout(vk::ImageLayout::UNDEFINED);
+
+        // Tile based GPUs keep the content of transient attachments in their on-chip memory
+        // during a render pass, so their lazily allocated memory is never actually committed.
+        // Other devices usually have no such memory type, and fall back to device local memory.
+        let preferred_flags = if info.transient {
+            vk::MemoryPropertyFlags::LAZILY_ALLOCATED
+        } else {
+            vk::MemoryPropertyFlags::empty()
+        };
+        let allocation_info = vma::AllocationOptions {
+            usage: vma::MemoryUsage::AutoPreferDevice,
+            preferred_flags,
+            priority: info.priority.value(),
+            ..Default::default()
+        };
+
+        let (inner, allocation) = unsafe {
+            allocator
+                .inner()
+                .create_image(image_info, &allocation_info)
+                .expect("Failed to create image")
+        };
+
+        let view_type = match (info.cube, info.array_layers) {
+            (true, 6) => vk::ImageViewType::CUBE,
+            (true, _) => vk::ImageViewType::CUBE_ARRAY,
+            (false, 1) => vk::ImageViewType::_2D,
+            (false, _) => vk::ImageViewType::_2D_ARRAY,
+        };
+        let view = create_view(
+            &device,
+            inner,
+            view_type,
+            info.format,
+            subresource_range(info.format, info.mip_levels, info.array_layers),
+        );
+
+        if let Some(name) = info.name {
+            device.set_object_name(vk::ObjectType::IMAGE, inner.as_raw(), name);
+            device.set_object_name(vk::ObjectType::IMAGE_VIEW, view.as_raw(), name);
+        }
+
+        Self {
+            device,
+            allocator,
+            allocation,
+            inner,
+            view,
+            format: info.format,
+            extent: info.extent,
+            mip_levels: info.mip_levels,
+            array_layers: info.array_layers,
+            samples: info.samples,
+            transient: info.transient,
+            layouts: Mutex::new(vec![
+                vk::ImageLayout::UNDEFINED;
+                (info.mip_levels * info.array_layers) as usize
+            ]),
+        }
+    }
+
+    /// Create a view covering the given mip levels and array layers of the image, for example
+    /// to render into a single face of a cube map or to write a single mip level from a
+    /// compute shader.
+    ///
+    /// # Panics
+    /// Panics if the ranges are empty or exceed the mip levels or array layers of the image.
+    #[must_use]
+    pub fn create_view(
+        &self,
+        view_type: vk::ImageViewType,
+        mip_levels: Range<u32>,
+        array_layers: Range<u32>,
+    ) -> ImageView {
+        let aspect_mask = format_aspects(self.format);
+        self.create_aspect_view(view_type, mip_levels, array_layers, aspect_mask)
+    }
+
+    /// Create a view covering the given aspects, mip levels and array layers of the image. The
+    /// views of a depth-stencil image cover both its aspects by default, so they can be used
+    /// as attachments but not sampled: a view of its depth or its stencil aspect alone must be
+    /// created to read it from the shaders.
+    ///
+    /// # Panics
+    /// Panics if the ranges are empty or exceed the mip levels or array layers of the image,
+    /// or if the image does not have the given aspects.
+    #[must_use]
+    pub fn create_aspect_view(
+        &self,
+        view_type: vk::ImageViewType,
+        mip_levels: Range<u32>,
+        array_layers: Range<u32>,
+        aspect_mask: vk::ImageAspectFlags,
+    ) -> ImageView {
+        assert!(
+            format_aspects(self.format).contains(aspect_mask),
+            "Images in {:?} do not have the {aspect_mask:?} aspects",
+            self.format
+        );
+        assert!(
+            !mip_levels.is_empty() && mip_levels.end <= self.mip_levels,
+            "Invalid mip level range"
+        );
+        assert!(
+            !array_layers.is_empty() && array_layers.end <= self.array_layers,
+            "Invalid array layer range"
+        );
+
+        let range = vk::ImageSubresourceRange {
+            aspect_mask,
+            base_mip_level: mip_levels.start,
+            level_count: mip_levels.len() as u32,
+            base_array_layer: array_layers.start,
+            layer_count: array_layers.len() as u32,
+        };
+
+        ImageView {
+            inner: create_view(&self.device, self.inner, view_type, self.format, range),
+            device: self.device.clone(),
+        }
+    }
+
+    /// Returns the subresource range covering the whole image, including both the depth and
+    /// the stencil aspects of a depth-stencil image.
+    #[must_use]
+    pub fn subresource_range(&self) -> vk::ImageSubresourceRange {
+        subresource_range(self.format, self.mip_levels, self.array_layers)
+    }
+
+    /// Returns the aspect of the image copied to or from buffers. A copy only addresses a
+    /// single aspect, so only the depth of a depth-stencil image is copied.
+    pub(crate) fn copy_aspect(&self) -> vk::ImageAspectFlags {
+        let aspects = format_aspects(self.format);
+        if aspects.contains(vk::ImageAspectFlags::DEPTH) {
+            vk::ImageAspectFlags::DEPTH
+        } else {
+            aspects
+        }
+    }
+
+    /// Returns the inner vulkan image object.
+    #[must_use]
+    pub const fn inner(&self) -> vk::Image {
+        self.inner
+    }
+
+    /// Returns the view covering the whole image.
+    #[must_use]
+    pub const fn view(&self) -> vk::ImageView {
+        self.view
+    }
+
+    /// Returns the format of the image.
+    #[must_use]
+    pub const fn format(&self) -> vk::Format {
+        self.format
+    }
+
+    /// Returns the extent of the first mip level of the image.
+    #[must_use]
+    pub const fn extent(&self) -> vk::Extent2D {
+        self.extent
+    }
+
+    /// Returns the number of mip levels of the image.
+    #[must_use]
+    pub const fn mip_levels(&self) -> u32 {
+        self.mip_levels
+    }
+
+    /// Returns the extent of the given mip level of the image.
+    #[must_use]
+    pub fn mip_extent(&self, mip_level: u32) -> vk::Extent2D {
+        vk::Extent2D {
+            width: (self.extent.width >> mip_level).max(1),
+            height: (self.extent.height >> mip_level).max(1),
+        }
+    }
+
+    /// Returns the number of array layers of the image.
+    #[must_use]
+    pub const fn array_layers(&self) -> u32 {
+        self.array_layers
+    }
+
+    /// Returns the number of samples per pixel of the image.
+    #[must_use]
+    pub const fn samples(&self) -> SampleCount {
+        self.samples
+    }
+
+    /// Returns whether the image is a transient attachment. Its content is undefined outside
+    /// of the render passes, so it must be loaded with `vk::AttachmentLoadOp::CLEAR` or
+    /// `DONT_CARE` and stored with `vk::AttachmentStoreOp::DONT_CARE`.
+    #[must_use]
+    pub const fn is_transient(&self) -> bool {
+        self.transient
+    }
+
+    /// Create a cube map from the texels of its six faces, in the order +X, -X, +Y, -Y, +Z,
+    /// -Z, for example a skybox or an environment map. The texels of each face must be tightly
+    /// packed. The faces are copied through a staging buffer on the main queue, and this
+    /// waits for the copy to complete. The cube map has a single mip level, and is left in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout.
+    ///
+    /// # Panics
+    /// Panics if the format has no known texel size, or if a face does not have the size of a
+    /// `size` x `size` image in that format.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn cube_from_faces(
+        device: Arc<VulkanDevice>,
+        allocator: Arc<BufferAllocator>,
+        format: vk::Format,
+        size: u32,
+        faces: [&[u8]; 6],
+    ) -> Self {
+        let texel_size =
+            texel_size(format).unwrap_or_else(|| panic!("Cannot upload cube maps in {format:?}"));
+        let face_size = (size * size * texel_size) as usize;
+        assert!(
+            faces.iter().all(|face| face.len() == face_size),
+            "Each face of the cube map must be {size}x{size} texels"
+        );
+
+        let image = Self::new(
+            device.clone(),
+            allocator.clone(),
+            ImageCreateInfo {
+                extent: vk::Extent2D {
+                    width: size,
+                    height: size,
+                },
+                format,
+                array_layers: 6,
+                cube: true,
+                ..Default::default()
+            },
+        );
+
+        let texels = faces.concat();
+        let staging = Buffer::new(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Source,
+                    access: BufferAccess::Sequential,
+                    usage: BufferUsage::None,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Slice(&texels),
+                ..Default::default()
+            },
+        );
+
+        let pool = CommandPool::new(
+            device.clone(),
+            device.queues_info().main_family(),
+            vk::CommandPoolCreateFlags::TRANSIENT,
+        );
+        let layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        CommandBuffer::new(&pool)
+            .start_recording()
+            .label("cube map upload")
+            .transition_subresources(&image, 0..1, 0..6, layout)
+            .copy_buffer_to_image_layers(&staging, &image, 0..6, layout)
+            .transition_subresources(
+                &image,
+                0..1,
+                0..6,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .stop_recording()
+            .submit_and_wait(SubmitInfo {
+                queue: VulkanQueues::fetch(&device).main(),
+                ..Default::default()
+            });
+
+        image
+    }
+
+    /// Create an image from the data of all its mip levels, starting with the largest one,
+    /// for example a texture whose mip chain was generated offline. The data of each level
+    /// holds the images of all the array layers, tightly packed one after the other, and may
+    /// be block-compressed. The levels are copied through a staging buffer on the main queue,
+    /// and this waits for the copy to complete. The image is left in the
+    /// `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` layout.
+    ///
+    /// # Panics
+    /// Panics if the number of levels is not the number of mip levels of the image, if the
+    /// size of the blocks of the format is not known, or if the data of a level does not have
+    /// the size of the level in that format.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn from_levels(
+        device: Arc<VulkanDevice>,
+        allocator: Arc<BufferAllocator>,
+        info: ImageCreateInfo,
+        levels: &[&[u8]],
+    ) -> Self {
+        assert_eq!(
+            levels.len(),
+            info.mip_levels as usize,
+            "The data of each mip level must be given"
+        );
+        let block = format_block(info.format)
+            .unwrap_or_else(|| panic!("Cannot upload images in {:?}", info.format));
+
+        let image = Self::new(device.clone(), allocator.clone(), info);
+        for (level, data) in levels.iter().enumerate() {
+            let size = block.image_size(image.mip_extent(level as u32));
+            assert_eq!(
+                data.len(),
+                size * info.array_layers as usize,
+                "Invalid size for mip level {level}"
+            );
+        }
+
+        let data = levels.concat();
+        let staging = Buffer::new(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Source,
+                    access: BufferAccess::Sequential,
+                    usage: BufferUsage::None,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Slice(&data),
+                ..Default::default()
+            },
+        );
+
+        let pool = CommandPool::new(
+            device.clone(),
+            device.queues_info().main_family(),
+            vk::CommandPoolCreateFlags::TRANSIENT,
+        );
+        let mip_levels = 0..info.mip_levels;
+        let array_layers = 0..info.array_layers;
+        let layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        CommandBuffer::new(&pool)
+            .start_recording()
+            .label("mip chain upload")
+            .transition_subresources(&image, mip_levels.clone(), array_layers.clone(), layout)
+            .copy_buffer_to_image_mips(&staging, &image, mip_levels.clone(), layout)
+            .transition_subresources(
+                &image,
+                mip_levels,
+                array_layers,
+                vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            )
+            .stop_recording()
+            .submit_and_wait(SubmitInfo {
+                queue: VulkanQueues::fetch(&device).main(),
+                ..Default::default()
+            });
+
+        image
+    }
+
+    /// Read the first mip level and array layer of the image back to the host, with tightly
+    /// packed rows. The image is copied to a host visible buffer on the main queue, and this
+    /// waits for the copy to complete. The image is transitioned for the copy and transitioned
+    /// back to its previous layout afterwards, unless its content was undefined.
+    ///
+    /// # Panics
+    /// Panics if the image is multisampled, if its format has no known texel size or if it
+    /// was not created with the `TRANSFER_SRC` usage.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn read_to_vec(&self) -> Vec<u8> {
+        assert_eq!(
+            self.samples,
+            SampleCount::One,
+            "Multisampled images must be resolved before being read back"
+        );
+        let texel_size = texel_size(self.format)
+            .unwrap_or_else(|| panic!("Cannot read back images in {:?}", self.format));
+        let size = (self.extent.width * self.extent.height * texel_size) as usize;
+
+        let buffer = Buffer::new(
+            self.allocator.clone(),
+            BufferCreateInfo::<u8> {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Destination,
+                    access: BufferAccess::Random,
+                    usage: BufferUsage::None,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Uninitialized(size),
+                ..Default::default()
+            },
+        );
+
+        let previous = self.layout(0, 0);
+        let region = vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: self.extent,
+        };
+
+        let pool = CommandPool::new(
+            self.device.clone(),
+            self.device.queues_info().main_family(),
+            vk::CommandPoolCreateFlags::TRANSIENT,
+        );
+        let mut command = CommandBuffer::new(&pool)
+            .start_recording()
+            .label("image readback")
+            .transition_subresources(self, 0..1, 0..1, vk::ImageLayout::TRANSFER_SRC_OPTIMAL)
+            .copy_image_to_buffer(self, vk::ImageLayout::TRANSFER_SRC_OPTIMAL, region, &buffer);
+        if previous != vk::ImageLayout::UNDEFINED {
+            command = command.transition_subresources(self, 0..1, 0..1, previous);
+        }
+        command.stop_recording().submit_and_wait(SubmitInfo {
+            queue: VulkanQueues::fetch(&self.device).main(),
+            ..Default::default()
+        });
+
+        // SAFETY: The buffer is host visible, and the copy has completed.
+        let mut pixels = unsafe { buffer.read::<u8>() };
+        pixels.truncate(size);
+        pixels
+    }
+
+    /// Returns the layout of the given mip level and array layer, as of the last transition
+    /// recorded with [`CommandBuffer::transition_image`].
+    ///
+    /// [`CommandBuffer::transition_image`]: crate::command::CommandBuffer::transition_image
+    #[must_use]
+    pub fn layout(&self, mip_level: u32, array_layer: u32) -> vk::ImageLayout {
+        let layouts = self.layouts.lock().expect("Image layouts poisoned");
+        layouts[(mip_level * self.array_layers + array_layer) as usize]
+    }
+
+    /// Set the layout of the whole image without recording a transition. This must be called
+    /// after the image is transitioned with a raw pipeline barrier, so the next automatic
+    /// transitions start from the right layout.
+    pub fn assume_layout(&self, layout: vk::ImageLayout) {
+        let mut layouts = self.layouts.lock().expect("Image layouts poisoned");
+        layouts.fill(layout);
+    }
+
+    /// Set the layout of the given mip levels and array layers, and returns the subresource
+    /// ranges that must be transitioned with their previous layout. Consecutive array layers
+    /// of a mip level sharing the same previous layout are transitioned together, and a range
+    /// already in the new layout is skipped unless the layout allows writes, since a barrier is
+    /// still needed to synchronize the writes.
+    pub(crate) fn record_transition(
+        &self,
+        mip_levels: Range<u32>,
+        array_layers: Range<u32>,
+        layout: vk::ImageLayout,
+    ) -> Vec<(vk::ImageLayout, vk::ImageSubresourceRange)> {
+        assert!(
+            !mip_levels.is_empty() && mip_levels.end <= self.mip_levels,
+            "Invalid mip level range"
+        );
+        assert!(
+            !array_layers.is_empty() && array_layers.end <= self.array_layers,
+            "Invalid array layer range"
+        );
+
+        let writable = !(layout_access(layout).1
+            & (vk::AccessFlags::SHADER_WRITE
+                | vk::AccessFlags::TRANSFER_WRITE
+                | vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE
+                | vk::AccessFlags::MEMORY_WRITE))
+            .is_empty();
+
+        let mut layouts = self.layouts.lock().expect("Image layouts poisoned");
+        let mut transitions = Vec::new();
+        for mip in mip_levels {
+            let mut start = array_layers.start;
+            for layer in array_layers.clone() {
+                let index = (mip * self.array_layers + layer) as usize;
+                let next = layer + 1;
+                let old = layouts[index];
+                let end_of_run = next == array_layers.end
+                    || layouts[(mip * self.array_layers + next) as usize] != old;
+                layouts[index] = layout;
+
+                if end_of_run {
+                    if old != layout || writable {
+                        transitions.push((
+                            old,
+                            vk::ImageSubresourceRange {
+                                base_mip_level: mip,
+                                level_count: 1,
+                                base_array_layer: start,
+                                layer_count: next - start,
+                                ..self.subresource_range()
+                            },
+                        ));
+                    }
+                    start = next;
+                }
+            }
+        }
+        transitions
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        hazard::unregister_view(self.view);
+        self.device.destroy_later(Deletion::ImageView(self.view));
+        self.device.destroy_later(Deletion::Image(
+            self.allocator.shared_inner(),
+            self.inner,
+            self.allocation,
+        ));
+    }
+}
+
+/// Information required to create an image.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageCreateInfo<'a> {
+    /// The extent of the first mip level of the image.
+    pub extent: vk::Extent2D,
+
+    /// The format of the image.
+    pub format: vk::Format,
+
+    /// How the image will be used.
+    pub usage: vk::ImageUsageFlags,
+
+    /// The number of mip levels of the image.
+    pub mip_levels: u32,
+
+    /// The number of array layers of the image. Cube maps have 6 layers per cube, in the
+    /// order +X, -X, +Y, -Y, +Z, -Z.
+    pub array_layers: u32,
+
+    /// Whether the image is a cube map, or an array of cube maps.
+    pub cube: bool,
+
+    /// The priority of the image memory. Render targets and frequently used textures should
+    /// use a high priority.
+    pub priority: MemoryPriority,
+
+    /// The number of samples per pixel. Multisampled images are used as MSAA render targets,
+    /// and must be resolved into a single sampled image before being sampled or presented.
+    pub samples: SampleCount,
+
+    /// Whether the image is a transient attachment, like a depth buffer or a MSAA target that
+    /// is only used during a render pass, or resolved at its end. Its content is never loaded
+    /// from nor stored to memory, which lets tile based GPUs back it with lazily allocated
+    /// memory that does not consume any real memory. The usage must only contain attachment
+    /// usages.
+    pub transient: bool,
+
+    /// The name of the image and of its view, displayed in the validation messages and by
+    /// graphics debuggers.
+    pub name: Option<&'a str>,
+}
+
+impl Default for ImageCreateInfo<'_> {
+    fn default() -> Self {
+        Self {
+            extent: vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+            format: vk::Format::R8G8B8A8_UNORM,
+            usage: vk::ImageUsageFlags::SAMPLED | vk::ImageUsageFlags::TRANSFER_DST,
+            mip_levels: 1,
+            array_layers: 1,
+            cube: false,
+            priority: MemoryPriority::default(),
+            samples: SampleCount::One,
+            transient: false,
+            name: None,
+        }
+    }
+}
+
+/// The number of samples per pixel of an image, or rasterized by a pipeline.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SampleCount {
+    /// A single sample, without multisampling.
+    #[default]
+    One,
+
+    /// 2x MSAA.
+    Two,
+
+    /// 4x MSAA, supported by all the devices.
+    Four,
+
+    /// 8x MSAA.
+    Eight,
+}
+
+impl SampleCount {
+    /// Returns the number of samples per pixel.
+    #[must_use]
+    pub const fn count(self) -> u32 {
+        match self {
+            Self::One => 1,
+            Self::Two => 2,
+            Self::Four => 4,
+            Self::Eight => 8,
+        }
+    }
+}
+
+impl From<SampleCount> for vk::SampleCountFlags {
+    fn from(samples: SampleCount) -> Self {
+        match samples {
+            SampleCount::One => Self::_1,
+            SampleCount::Two => Self::_2,
+            SampleCount::Four => Self::_4,
+            SampleCount::Eight => Self::_8,
+        }
+    }
+}
+
+/// Returns whether the given format contains a depth component.
+#[must_use]
+pub fn is_depth_format(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM
+            | vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT
+            | vk::Format::D32_SFLOAT_S8_UINT
+            | vk::Format::X8_D24_UNORM_PACK32
+    )
+}
+
+/// Returns whether the given format contains a stencil component.
+#[must_use]
+pub fn has_stencil_component(format: vk::Format) -> bool {
+    matches!(
+        format,
+        vk::Format::D16_UNORM_S8_UINT
+            | vk::Format::D24_UNORM_S8_UINT
+            | vk::Format::D32_SFLOAT_S8_UINT
+            | vk::Format::S8_UINT
+    )
+}
+
+/// Returns the aspects of the images in the given format: the depth and the stencil aspects
+/// of the depth-stencil formats, or the color aspect of the other formats.
+#[must_use]
+pub fn format_aspects(format: vk::Format) -> vk::ImageAspectFlags {
+    let mut aspects = vk::ImageAspectFlags::empty();
+    if is_depth_format(format) {
+        aspects |= vk::ImageAspectFlags::DEPTH;
+    }
+    if has_stencil_component(format) {
+        aspects |= vk::ImageAspectFlags::STENCIL;
+    }
+    if aspects.is_empty() {
+        aspects = vk::ImageAspectFlags::COLOR;
+    }
+    aspects
+}
+
+/// Returns the formats of the depth and the stencil attachments of dynamic rendering for a
+/// depth-stencil attachment in the given format, which is used for both aspects that it has.
+/// The format of an aspect that it does not have is `vk::Format::UNDEFINED`.
+#[must_use]
+pub(crate) fn depth_stencil_formats(format: vk::Format) -> (vk::Format, vk::Format) {
+    let aspect = |has_aspect: bool| {
+        if has_aspect {
+            format
+        } else {
+            vk::Format::UNDEFINED
+        }
+    };
+    (
+        aspect(is_depth_format(format)),
+        aspect(has_stencil_component(format)),
+    )
+}
+
+/// Returns the size in bytes of a texel of the given uncompressed color or depth format, or
+/// `None` if the format is compressed or not supported.
+#[must_use]
+pub fn texel_size(format: vk::Format) -> Option<u32> {
+    let size = match format {
+        vk::Format::R8_UNORM | vk::Format::R8_UINT | vk::Format::S8_UINT => 1,
+        vk::Format::R8G8_UNORM | vk::Format::R16_SFLOAT | vk::Format::D16_UNORM => 2,
+        vk::Format::R8G8B8A8_UNORM
+        | vk::Format::R8G8B8A8_SRGB
+        | vk::Format::B8G8R8A8_UNORM
+        | vk::Format::B8G8R8A8_SRGB
+        | vk::Format::A2B10G10R10_UNORM_PACK32
+        | vk::Format::B10G11R11_UFLOAT_PACK32
+        | vk::Format::R16G16_SFLOAT
+        | vk::Format::R32_UINT
+        | vk::Format::R32_SFLOAT
+        | vk::Format::D32_SFLOAT => 4,
+        vk::Format::R16G16B16A16_SFLOAT | vk::Format::R32G32_UINT | vk::Format::R32G32_SFLOAT => 8,
+        vk::Format::R32G32B32A32_SFLOAT | vk::Format::R32G32B32A32_UINT => 16,
+        _ => return None,
+    };
+    Some(size)
+}
+
+/// The blocks of texels in which the images of a format are stored. Uncompressed formats have
+/// blocks of a single texel, while block-compressed formats encode blocks of 4x4 texels or
+/// more in a fixed number of bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FormatBlock {
+    /// The width of a block, in texels.
+    pub width: u32,
+
+    /// The height of a block, in texels.
+    pub height: u32,
+
+    /// The size of a block, in bytes.
+    pub size: u32,
+}
+
+impl FormatBlock {
+    /// Returns the size in bytes of a tightly packed image of the given extent. The extent
+    /// does not need to be a multiple of the block size: partial blocks are stored whole.
+    #[must_use]
+    pub const fn image_size(&self, extent: vk::Extent2D) -> usize {
+        let columns = extent.width.div_ceil(self.width) as usize;
+        let rows = extent.height.div_ceil(self.height) as usize;
+        columns * rows * self.size as usize
+    }
+}
+
+/// Returns the blocks of the given format, or `None` if the format is not supported. This
+/// covers the uncompressed formats known by [`texel_size`], and the BCn, ETC2, EAC and ASTC
+/// compressed formats.
+#[must_use]
+pub fn format_block(format: vk::Format) -> Option<FormatBlock> {
+    let (width, height, size) = match format {
+        vk::Format::BC1_RGB_UNORM_BLOCK
+        | vk::Format::BC1_RGB_SRGB_BLOCK
+        | vk::Format::BC1_RGBA_UNORM_BLOCK
+        | vk::Format::BC1_RGBA_SRGB_BLOCK
+        | vk::Format::BC4_UNORM_BLOCK
+        | vk::Format::BC4_SNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A1_SRGB_BLOCK
+        | vk::Format::EAC_R11_UNORM_BLOCK
+        | vk::Format::EAC_R11_SNORM_BLOCK => (4, 4, 8),
+        vk::Format::BC2_UNORM_BLOCK
+        | vk::Format::BC2_SRGB_BLOCK
+        | vk::Format::BC3_UNORM_BLOCK
+        | vk::Format::BC3_SRGB_BLOCK
+        | vk::Format::BC5_UNORM_BLOCK
+        | vk::Format::BC5_SNORM_BLOCK
+        | vk::Format::BC6H_UFLOAT_BLOCK
+        | vk::Format::BC6H_SFLOAT_BLOCK
+        | vk::Format::BC7_UNORM_BLOCK
+        | vk::Format::BC7_SRGB_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_UNORM_BLOCK
+        | vk::Format::ETC2_R8G8B8A8_SRGB_BLOCK
+        | vk::Format::EAC_R11G11_UNORM_BLOCK
+        | vk::Format::EAC_R11G11_SNORM_BLOCK => (4, 4, 16),
+        vk::Format::ASTC_4X4_UNORM_BLOCK | vk::Format::ASTC_4X4_SRGB_BLOCK => (4, 4, 16),
+        vk::Format::ASTC_5X4_UNORM_BLOCK | vk::Format::ASTC_5X4_SRGB_BLOCK => (5, 4, 16),
+        vk::Format::ASTC_5X5_UNORM_BLOCK | vk::Format::ASTC_5X5_SRGB_BLOCK => (5, 5, 16),
+        vk::Format::ASTC_6X5_UNORM_BLOCK | vk::Format::ASTC_6X5_SRGB_BLOCK => (6, 5, 16),
+        vk::Format::ASTC_6X6_UNORM_BLOCK | vk::Format::ASTC_6X6_SRGB_BLOCK => (6, 6, 16),
+        vk::Format::ASTC_8X5_UNORM_BLOCK | vk::Format::ASTC_8X5_SRGB_BLOCK => (8, 5, 16),
+        vk::Format::ASTC_8X6_UNORM_BLOCK | vk::Format::ASTC_8X6_SRGB_BLOCK => (8, 6, 16),
+        vk::Format::ASTC_8X8_UNORM_BLOCK | vk::Format::ASTC_8X8_SRGB_BLOCK => (8, 8, 16),
+        vk::Format::ASTC_10X5_UNORM_BLOCK | vk::Format::ASTC_10X5_SRGB_BLOCK => (10, 5, 16),
+        vk::Format::ASTC_10X6_UNORM_BLOCK | vk::Format::ASTC_10X6_SRGB_BLOCK => (10, 6, 16),
+        vk::Format::ASTC_10X8_UNORM_BLOCK | vk::Format::ASTC_10X8_SRGB_BLOCK => (10, 8, 16),
+        vk::Format::ASTC_10X10_UNORM_BLOCK | vk::Format::ASTC_10X10_SRGB_BLOCK => (10, 10, 16),
+        vk::Format::ASTC_12X10_UNORM_BLOCK | vk::Format::ASTC_12X10_SRGB_BLOCK => (12, 10, 16),
+        vk::Format::ASTC_12X12_UNORM_BLOCK | vk::Format::ASTC_12X12_SRGB_BLOCK => (12, 12, 16),
+        _ => (1, 1, texel_size(format)?),
+    };
+    Some(FormatBlock {
+        width,
+        height,
+        size,
+    })
+}
+
+/// Returns whether the given format is block-compressed.
+#[must_use]
+pub fn is_compressed_format(format: vk::Format) -> bool {
+    format_block(format).is_some_and(|block| block.width > 1 || block.height > 1)
+}
+
+/// Returns the pipeline stages and the memory accesses that can use an image in the given
+/// layout, used to deduce the synchronization of automatic layout transitions. The stages are
+/// conservative: an image in `SHADER_READ_ONLY_OPTIMAL` can be read by any shader stage, and
+/// an image in `GENERAL` by any command.
+#[must_use]
+pub fn layout_access(layout: vk::ImageLayout) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+    match layout {
+        vk::ImageLayout::UNDEFINED => (
+            vk::PipelineStageFlags::TOP_OF_PIPE,
+            vk::AccessFlags::empty(),
+        ),
+        vk::ImageLayout::PREINITIALIZED => {
+            (vk::PipelineStageFlags::HOST, vk::AccessFlags::HOST_WRITE)
+        }
+        vk::ImageLayout::TRANSFER_SRC_OPTIMAL => (
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_READ,
+        ),
+        vk::ImageLayout::TRANSFER_DST_OPTIMAL => (
+            vk::PipelineStageFlags::TRANSFER,
+            vk::AccessFlags::TRANSFER_WRITE,
+        ),
+        vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL => (
+            vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            vk::AccessFlags::COLOR_ATTACHMENT_READ | vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+        ),
+        vk::ImageLayout::DEPTH_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL
+        | vk::ImageLayout::DEPTH_ATTACHMENT_STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_READ_ONLY_STENCIL_ATTACHMENT_OPTIMAL => (
+            vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS
+                | vk::PipelineStageFlags::LATE_FRAGMENT_TESTS,
+            vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_READ
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
+        ),
+        vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::DEPTH_STENCIL_READ_ONLY_OPTIMAL
+        | vk::ImageLayout::READ_ONLY_OPTIMAL => (
+            vk::PipelineStageFlags::VERTEX_SHADER
+                | vk::PipelineStageFlags::FRAGMENT_SHADER
+                | vk::PipelineStageFlags::COMPUTE_SHADER,
+            vk::AccessFlags::SHADER_READ,
+        ),
+        vk::ImageLayout::PRESENT_SRC_KHR => (
+            vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            vk::AccessFlags::empty(),
+        ),
+        vk::ImageLayout::FRAGMENT_SHADING_RATE_ATTACHMENT_OPTIMAL_KHR => (
+            vk::PipelineStageFlags::FRAGMENT_SHADING_RATE_ATTACHMENT_KHR,
+            vk::AccessFlags::FRAGMENT_SHADING_RATE_ATTACHMENT_READ_KHR,
+        ),
+        _ => (
+            vk::PipelineStageFlags::ALL_COMMANDS,
+            vk::AccessFlags::MEMORY_READ | vk::AccessFlags::MEMORY_WRITE,
+        ),
+    }
+}
+
+/// The format of the shading rate attachments, the only one that all the devices supporting
+/// the attachment fragment shading rate must support. Each texel holds the rate of a region
+/// of the framebuffer, as encoded by [`shading_rate_texel`].
+pub const SHADING_RATE_FORMAT: vk::Format = vk::Format::R8_UINT;
+
+/// Returns the texel of a shading rate attachment for the given fragment size, whose width and
+/// height must be 1, 2 or 4. For example, a 2x2 fragment size is encoded as `0b0101`.
+#[must_use]
+pub const fn shading_rate_texel(fragment_size: vk::Extent2D) -> u8 {
+    let width = fragment_size.width.trailing_zeros() as u8 & 0b11;
+    let height = fragment_size.height.trailing_zeros() as u8 & 0b11;
+    (width << 2) | height
+}
+
+/// Returns the subresource range covering all the mip levels and array layers of an image with
+/// the given format.
+fn subresource_range(
+    format: vk::Format,
+    mip_levels: u32,
+    array_layers: u32,
+) -> vk::ImageSubresourceRange {
+    vk::ImageSubresourceRange {
+        aspect_mask: format_aspects(format),
+        base_array_layer: 0,
+        base_mip_level: 0,
+        level_count: mip_levels,
+        layer_count: array_layers,
+    }
+}
+
+/// Create a view of the given subresource range of an image.
+fn create_view(
+    device: &VulkanDevice,
+    image: vk::Image,
+    view_type: vk::ImageViewType,
+    format: vk::Format,
+    range: vk::ImageSubresourceRange,
+) -> vk::ImageView {
+    let info = vk::ImageViewCreateInfo::builder()
+        .subresource_range(range)
+        .view_type(view_type)
+        .format(format)
+        .image(image);
+
+    let view = unsafe {
+        device
+            .logical()
+            .create_image_view(&info, None)
+            .expect("Failed to create image view")
+    };
+    hazard::register_view(view, image);
+    view
+}
+
+/// A view of a part of an [`Image`], created with [`Image::create_view`]. The view must be
+/// dropped before the image.
+#[derive(Debug)]
+pub struct ImageView {
+    device: Arc<VulkanDevice>,
+    inner: vk::ImageView,
+}
+
+impl ImageView {
+    /// Returns the inner vulkan image view.
+    #[must_use]
+    pub const fn inner(&self) -> vk::ImageView {
+        self.inner
+    }
+}
+
+impl Drop for ImageView {
+    fn drop(&mut self) {
+        hazard::unregister_view(self.inner);
+        self.device.destroy_later(Deletion::ImageView(self.inner));
+    }
+}
+
+/// A sampler, describing how an image is read by a shader.
+#[derive(Debug)]
+pub struct Sampler {
+    device: Arc<VulkanDevice>,
+    inner: vk::Sampler,
+}
+
+impl Sampler {
+    /// Create a new sampler with the given filter and address mode.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        filter: vk::Filter,
+        address: vk::SamplerAddressMode,
+    ) -> Self {
+        Self::with_info(device, &SamplerInfo::new(filter, address))
+    }
+
+    /// Create a new sampler with the given configuration. The anisotropy level is clamped to
+    /// the maximum supported by the device.
+    #[must_use]
+    pub fn with_info(device: Arc<VulkanDevice>, info: &SamplerInfo) -> Self {
+        let max_anisotropy = info
+            .max_anisotropy
+            .map(|level| level.clamp(1.0, device.limits().max_sampler_anisotropy));
+
+        let info = vk::SamplerCreateInfo::builder()
+            .mag_filter(info.mag_filter)
+            .min_filter(info.min_filter)
+            .mipmap_mode(info.mipmap_mode)
+            .address_mode_u(info.address[0])
+            .address_mode_v(info.address[1])
+            .address_mode_w(info.address[2])
+            .border_color(info.border_color)
+            .anisotropy_enable(max_anisotropy.is_some())
+            .max_anisotropy(max_anisotropy.unwrap_or(1.0))
+            .compare_enable(info.compare.is_some())
+            .compare_op(info.compare.unwrap_or(vk::CompareOp::ALWAYS))
+            .min_lod(0.0)
+            .max_lod(info.max_lod);
+
+        let inner = unsafe {
+            device
+                .logical()
+                .create_sampler(&info, None)
+                .expect("Failed to create sampler")
+        };
+
+        Self { device, inner }
+    }
+
+    /// Returns the inner vulkan sampler.
+    #[must_use]
+    pub const fn inner(&self) -> vk::Sampler {
+        self.inner
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.device.destroy_later(Deletion::Sampler(self.inner));
+    }
+}
+
+/// The configuration of a [`Sampler`].
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerInfo {
+    /// The filter used when the image is magnified.
+    pub mag_filter: vk::Filter,
+
+    /// The filter used when the image is minified.
+    pub min_filter: vk::Filter,
+
+    /// The filter used between the mip levels.
+    pub mipmap_mode: vk::SamplerMipmapMode,
+
+    /// The address mode of the U, V and W coordinates outside of the `[0, 1]` range.
+    pub address: [vk::SamplerAddressMode; 3],
+
+    /// The color returned outside of the image with the `CLAMP_TO_BORDER` address mode.
+    pub border_color: vk::BorderColor,
+
+    /// The anisotropy level, usually between 1 and 16, or `None` to disable the anisotropic
+    /// filtering.
+    pub max_anisotropy: Option<f32>,
+
+    /// The comparison applied to the fetched depth, for example to sample shadow maps with
+    /// hardware percentage-closer filtering, or `None` to return the texels themselves.
+    pub compare: Option<vk::CompareOp>,
+
+    /// The highest mip level that can be sampled.
+    pub max_lod: f32,
+}
+
+impl SamplerInfo {
+    /// A sampler using the same filter for the magnification, the minification and the mip
+    /// levels, and the same address mode for all the coordinates.
+    #[must_use]
+    pub fn new(filter: vk::Filter, address: vk::SamplerAddressMode) -> Self {
+        let mipmap_mode = match filter {
+            vk::Filter::NEAREST => vk::SamplerMipmapMode::NEAREST,
+            _ => vk::SamplerMipmapMode::LINEAR,
+        };
+
+        Self {
+            mag_filter: filter,
+            min_filter: filter,
+            mipmap_mode,
+            address: [address; 3],
+            ..Self::default()
+        }
+    }
+
+    /// A sampler comparing the depth of a shadow map with the given operation, returning the
+    /// filtered result of the comparison. Outside of the shadow map, the comparison is made
+    /// against the farthest depth, so the fragments are not shadowed.
+    #[must_use]
+    pub fn shadow(compare: vk::CompareOp) -> Self {
+        Self {
+            address: [vk::SamplerAddressMode::CLAMP_TO_BORDER; 3],
+            border_color: vk::BorderColor::FLOAT_OPAQUE_WHITE,
+            compare: Some(compare),
+            ..Self::new(vk::Filter::LINEAR, vk::SamplerAddressMode::CLAMP_TO_BORDER)
+        }
+    }
+
+    /// Returns the fields of the configuration that identify it, with the floating point
+    /// values compared by their bits.
+    #[allow(clippy::type_complexity)]
+    fn key(
+        &self,
+    ) -> (
+        [vk::Filter; 2],
+        vk::SamplerMipmapMode,
+        [vk::SamplerAddressMode; 3],
+        vk::BorderColor,
+        Option<u32>,
+        Option<vk::CompareOp>,
+        u32,
+    ) {
+        (
+            [self.mag_filter, self.min_filter],
+            self.mipmap_mode,
+            self.address,
+            self.border_color,
+            self.max_anisotropy.map(f32::to_bits),
+            self.compare,
+            self.max_lod.to_bits(),
+        )
+    }
+}
+
+impl Default for SamplerInfo {
+    fn default() -> Self {
+        Self {
+            mag_filter: vk::Filter::LINEAR,
+            min_filter: vk::Filter::LINEAR,
+            mipmap_mode: vk::SamplerMipmapMode::LINEAR,
+            address: [vk::SamplerAddressMode::REPEAT; 3],
+            border_color: vk::BorderColor::FLOAT_TRANSPARENT_BLACK,
+            max_anisotropy: None,
+            compare: None,
+            max_lod: vk::LOD_CLAMP_NONE,
+        }
+    }
+}
+
+impl PartialEq for SamplerInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for SamplerInfo {}
+
+impl Hash for SamplerInfo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key().hash(state);
+    }
+}
+
+/// Deduplicates the samplers of a device: the passes that need a sampler with the same
+/// configuration share the same Vulkan sampler, instead of each creating its own. Devices
+/// only support a limited number of samplers, as low as 4000 on some drivers.
+#[derive(Debug)]
+pub struct SamplerCache {
+    device: Arc<VulkanDevice>,
+    samplers: Mutex<HashMap<SamplerInfo, Arc<Sampler>>>,
+}
+
+impl SamplerCache {
+    /// Create an empty sampler cache for the given device.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Self {
+        Self {
+            device,
+            samplers: Mutex::default(),
+        }
+    }
+
+    /// Returns the sampler with the given configuration, creating it if no pass requested it
+    /// before.
+    #[must_use]
+    pub fn get(&self, info: &SamplerInfo) -> Arc<Sampler> {
+        self.samplers
+            .lock()
+            .expect("Sampler cache poisoned")
+            .entry(*info)
+            .or_insert_with(|| Arc::new(Sampler::with_info(self.device.clone(), info)))
+            .clone()
+    }
+
+    /// Returns the number of distinct samplers created by the cache.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.samplers.lock().expect("Sampler cache poisoned").len()
+    }
+
+    /// Returns whether the cache has not created any sampler yet.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
@@ -1,15 +1,54 @@
+pub mod bindless;
 pub mod buffer;
 pub mod command;
+pub mod compute;
 pub mod context;
+mod deletion;
+pub mod descriptor;
 pub mod device;
+pub mod hazard;
+pub mod image;
 pub mod pipeline;
+pub mod pool;
+pub mod profile;
+pub mod query;
+pub mod recorder;
+pub mod reflect;
+pub mod renderpass;
+pub mod ring;
 pub mod semaphore;
 pub mod shader;
 pub mod swapchain;
+pub mod upload;
 
 pub mod vk {
     pub use vulkanalia::prelude::v1_3::vk::*;
 }
 
+/// The items used by the exported macros, which are not part of the public API.
+#[doc(hidden)]
+pub mod __private {
+    #[cfg(feature = "trace")]
+    pub use tracing;
+}
+
+/// Enter a `tracing` span that lasts until the end of the current scope. This expands to nothing
+/// when the `trace` feature of this crate is disabled, so it can be used freely in hot paths.
+#[cfg(feature = "trace")]
+#[macro_export]
+macro_rules! trace_span {
+    ($name:expr) => {
+        let _span = $crate::__private::tracing::info_span!($name).entered();
+    };
+}
+
+/// Enter a `tracing` span that lasts until the end of the current scope. This expands to nothing
+/// when the `trace` feature of this crate is disabled, so it can be used freely in hot paths.
+#[cfg(not(feature = "trace"))]
+#[macro_export]
+macro_rules! trace_span {
+    ($name:expr) => {};
+}
+
 /// The maximum number of frames that can be in flight at once.
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
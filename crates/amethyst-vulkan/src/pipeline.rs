@@ -1,9 +1,15 @@
 use crate::{
+    deletion::Deletion,
+    descriptor::DescriptorSetLayout,
     device::VulkanDevice,
-    shader::{ShaderModule, ShaderType},
+    image::{depth_stencil_formats, SampleCount},
+    reflect::PipelineReflection,
+    renderpass::RenderPassLayout,
+    shader::{ShaderModule, ShaderType, SpecializationData},
     swapchain::VulkanSwapchain,
 };
-use std::sync::Arc;
+use bevy::tasks::{AsyncComputeTaskPool, Task, TaskPool};
+use std::{marker::PhantomData, ops::Range, sync::Arc};
 use vulkanalia::prelude::v1_3::*;
 
 /// A pipeline object.
@@ -12,12 +18,33 @@ pub struct Pipeline {
     device: Arc<VulkanDevice>,
     layout: vk::PipelineLayout,
     inner: vk::Pipeline,
+
+    /// The layouts of the descriptor sets bound to the pipeline, given in the create info or
+    /// derived from the shaders. The pipeline keeps them alive, so the descriptor sets bound to
+    /// it can be allocated with them.
+    descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
 }
 
 impl Pipeline {
     /// Creates a new pipeline object. The generic parameter `T` is the type of the vertex
     /// data that will be passed to the vertex shader.
+    ///
+    /// Unless [`PipelineCreateInfo::reflect`] is disabled, the shaders are reflected to derive
+    /// the descriptor set layouts and the push constant ranges that are left empty in the
+    /// create info, and a warning is logged for each input of the vertex shader that is not
+    /// provided by the attributes of `T`.
+    ///
+    /// # Panics
+    /// Panics if the tessellation shaders and the patch list topology are not used together,
+    /// if primitive restart is used with a list topology, if the device does not support
+    /// tessellation, multiview, multiple viewports, the fragment shading rates, the depth
+    /// bounds test, the depth clamp, the depth bias clamp, wide lines, the non-solid fill modes,
+    /// sample shading or the conservative rasterization mode when they are used, or if the
+    /// number of control points per patch, of viewports, the fragment size, the depth bounds,
+    /// the line width, the minimum sample shading or the extra overestimation size are not
+    /// supported.
     #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
     pub fn new<T>(
         device: Arc<VulkanDevice>,
         swapchain: &VulkanSwapchain,
@@ -26,132 +53,772 @@ impl Pipeline {
     where
         T: VertexAttributeDescription + VertexBindingDescription,
     {
-        // Create the pipeline layout.
-        let layout = unsafe {
+        let target = (swapchain.format(), swapchain.extent());
+        Self::create(device, Some(target), VertexLayout::of::<T>(), info)
+    }
+
+    /// Creates a new pipeline object rendering to images only, without a swapchain, like
+    /// [`Pipeline::new`]. If no color format is given, the pipeline has no color attachment,
+    /// which is useful for depth-only passes.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn offscreen<T>(device: Arc<VulkanDevice>, info: PipelineCreateInfo) -> Self
+    where
+        T: VertexAttributeDescription + VertexBindingDescription,
+    {
+        Self::create(device, None, VertexLayout::of::<T>(), info)
+    }
+
+    fn create(
+        device: Arc<VulkanDevice>,
+        target: Option<(vk::Format, vk::Extent2D)>,
+        vertex: VertexLayout,
+        info: PipelineCreateInfo,
+    ) -> Self {
+        let mut state = GraphicsPipelineState::new(&device, target, vertex, info);
+        let inner = unsafe {
             device
                 .logical()
-                .create_pipeline_layout(&vk::PipelineLayoutCreateInfo::builder().build(), None)
-                .expect("Failed to create pipeline layout")
+                .create_graphics_pipelines(vk::PipelineCache::null(), &[state.create_info()], None)
+                .expect("Failed to create graphics pipeline")
+                .0[0]
         };
+        state.into_pipeline(device, inner)
+    }
 
-        // Create a pipeline shader stage create info for each shader
-        let stages = info
-            .shaders
-            .iter()
-            .map(|shader| {
-                let stage = match shader.kind() {
-                    ShaderType::Fragment => vk::ShaderStageFlags::FRAGMENT,
-                    ShaderType::Compute => vk::ShaderStageFlags::COMPUTE,
-                    ShaderType::Vertex => vk::ShaderStageFlags::VERTEX,
-                };
-
-                vk::PipelineShaderStageCreateInfo::builder()
-                    .module(shader.inner())
-                    .name(b"main\0")
-                    .stage(stage)
-                    .build()
+    /// Returns the pipeline layout used by the pipeline.
+    #[must_use]
+    pub fn layout(&self) -> vk::PipelineLayout {
+        self.layout
+    }
+
+    /// Returns the layouts of the descriptor sets bound to the pipeline, in the order of their
+    /// set number, to allocate the descriptor sets bound to it. They are the layouts given in
+    /// the create info, or the layouts derived from the shaders if none was given.
+    #[must_use]
+    pub fn descriptor_set_layouts(&self) -> &[Arc<DescriptorSetLayout>] {
+        &self.descriptor_set_layouts
+    }
+
+    /// Returns the inner pipeline handle.
+    #[must_use]
+    pub fn inner(&self) -> vk::Pipeline {
+        self.inner
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        self.device
+            .destroy_later(Deletion::Pipeline(self.inner, self.layout));
+    }
+}
+
+/// A batch of graphics pipelines created together with a single call to the driver, for
+/// example all the variants of a material. The pipelines of a batch can derive from a parent
+/// pipeline of the same batch, which lets some drivers create the variants of a pipeline
+/// faster. The batch can also be built on the async compute task pool with
+/// [`PipelineBatch::build_async`], so compiling the pipelines does not stall the frame.
+pub struct PipelineBatch<'a> {
+    device: Arc<VulkanDevice>,
+
+    /// The format and the extent of the swapchain the pipelines render to, if any.
+    target: Option<(vk::Format, vk::Extent2D)>,
+
+    /// The pipelines to create, with their vertex layout and the index of their parent.
+    pipelines: Vec<(PipelineCreateInfo<'a>, VertexLayout, Option<usize>)>,
+}
+
+impl<'a> PipelineBatch<'a> {
+    /// Creates an empty batch of pipelines rendering to the given swapchain, like
+    /// [`Pipeline::new`], or to images only, like [`Pipeline::offscreen`], without a swapchain.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, swapchain: Option<&VulkanSwapchain>) -> Self {
+        Self {
+            device,
+            target: swapchain.map(|swapchain| (swapchain.format(), swapchain.extent())),
+            pipelines: Vec::new(),
+        }
+    }
+
+    /// Adds a pipeline to the batch, and returns its index in the pipelines returned by
+    /// [`PipelineBatch::build`]. The generic parameter `T` is the type of the vertex data, like
+    /// with [`Pipeline::new`].
+    pub fn add<T>(&mut self, info: PipelineCreateInfo<'a>) -> usize
+    where
+        T: VertexAttributeDescription + VertexBindingDescription,
+    {
+        self.pipelines.push((info, VertexLayout::of::<T>(), None));
+        self.pipelines.len() - 1
+    }
+
+    /// Adds a pipeline deriving from the pipeline of the batch at the given index, usually a
+    /// variant of it with different specialization constants or states, and returns its index.
+    ///
+    /// # Panics
+    /// Panics if there is no pipeline at the given index in the batch.
+    pub fn add_derivative<T>(&mut self, parent: usize, info: PipelineCreateInfo<'a>) -> usize
+    where
+        T: VertexAttributeDescription + VertexBindingDescription,
+    {
+        assert!(
+            parent < self.pipelines.len(),
+            "The parent pipeline must be added to the batch first"
+        );
+        self.pipelines
+            .push((info, VertexLayout::of::<T>(), Some(parent)));
+        self.pipelines.len() - 1
+    }
+
+    /// Returns the number of pipelines in the batch.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    /// Returns whether the batch has no pipeline.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pipelines.is_empty()
+    }
+
+    /// Creates all the pipelines of the batch at once, in the order they were added.
+    ///
+    /// # Panics
+    /// Panics if a pipeline cannot be created, for the same reasons as [`Pipeline::new`].
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn build(self) -> Vec<Pipeline> {
+        if self.pipelines.is_empty() {
+            return Vec::new();
+        }
+
+        let mut parents = vec![false; self.pipelines.len()];
+        let mut states = self
+            .pipelines
+            .into_iter()
+            .map(|(info, vertex, parent)| {
+                if let Some(parent) = parent {
+                    parents[parent] = true;
+                }
+                let state = GraphicsPipelineState::new(&self.device, self.target, vertex, info);
+                (state, parent)
+            })
+            .collect::<Vec<_>>();
+
+        // The derivatives reference their parent by its index in the same call, and the
+        // parents must allow them.
+        let infos = states
+            .iter_mut()
+            .zip(parents)
+            .map(|((state, parent), is_parent)| {
+                let mut info = state.create_info();
+                if is_parent {
+                    info.flags |= vk::PipelineCreateFlags::ALLOW_DERIVATIVES;
+                }
+                if let Some(parent) = parent {
+                    info.flags |= vk::PipelineCreateFlags::DERIVATIVE;
+                    info.base_pipeline_index = *parent as i32;
+                }
+                info
             })
             .collect::<Vec<_>>();
 
-        // Create the vertex input state and the vertex binding description from the vertex
-        // type passed in the generic parameter and then create the vertex input state.
-        let attribute_descriptions = T::attribute_descriptions();
-        let binding_descriptions = T::binding_description();
-        let vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
-            .vertex_attribute_descriptions(&attribute_descriptions)
-            .vertex_binding_descriptions(&binding_descriptions);
+        let pipelines = unsafe {
+            self.device
+                .logical()
+                .create_graphics_pipelines(vk::PipelineCache::null(), &infos, None)
+                .expect("Failed to create graphics pipelines")
+                .0
+        };
+        states
+            .into_iter()
+            .zip(pipelines)
+            .map(|((state, _), inner)| state.into_pipeline(self.device.clone(), inner))
+            .collect()
+    }
+}
+
+impl PipelineBatch<'static> {
+    /// Creates all the pipelines of the batch on the async compute task pool, and returns the
+    /// task that completes with them, in the order they were added. The task can be polled
+    /// each frame, and the pipelines used once it completes.
+    #[must_use]
+    pub fn build_async(self) -> Task<Vec<Pipeline>> {
+        AsyncComputeTaskPool::get_or_init(TaskPool::default).spawn(async move { self.build() })
+    }
+}
 
-        // Create the input assembly state
+/// The vertex bindings and attributes of a pipeline, gathered from its vertex type.
+#[derive(Debug, Clone)]
+struct VertexLayout {
+    attributes: Vec<vk::VertexInputAttributeDescription>,
+    bindings: Vec<vk::VertexInputBindingDescription>,
+}
+
+impl VertexLayout {
+    /// Returns the layout of the vertex type `T`.
+    fn of<T>() -> Self
+    where
+        T: VertexAttributeDescription + VertexBindingDescription,
+    {
+        Self {
+            attributes: T::attribute_descriptions(),
+            bindings: T::binding_description(),
+        }
+    }
+}
+
+/// Everything needed to create a graphics pipeline: the pipeline layout, the shaders, and all
+/// the structures referenced by its create info. The create info only points to them, so the
+/// state must stay in place until the pipeline is created.
+struct GraphicsPipelineState<'a> {
+    layout: vk::PipelineLayout,
+    descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
+    name: Option<&'a str>,
+    shaders: Vec<ShaderModule>,
+    specializations: Vec<SpecializationData>,
+    specialization_infos: Vec<Option<vk::SpecializationInfo>>,
+    stages: Vec<vk::PipelineShaderStageCreateInfo>,
+    vertex: VertexLayout,
+    vertex_input_state: vk::PipelineVertexInputStateCreateInfo,
+    input_assembly_state: vk::PipelineInputAssemblyStateCreateInfo,
+    tessellation_state: Option<vk::PipelineTessellationStateCreateInfo>,
+    viewports: Vec<vk::Viewport>,
+    scissors: Vec<vk::Rect2D>,
+    viewport_state: vk::PipelineViewportStateCreateInfo,
+    rasterization_state: vk::PipelineRasterizationStateCreateInfo,
+    conservative_state: Option<vk::PipelineRasterizationConservativeStateCreateInfoEXT>,
+    multisample_state: vk::PipelineMultisampleStateCreateInfo,
+    formats: Vec<vk::Format>,
+    attachments: Vec<vk::PipelineColorBlendAttachmentState>,
+    color_blend_state: vk::PipelineColorBlendStateCreateInfo,
+    depth_stencil_state: vk::PipelineDepthStencilStateCreateInfo,
+    shading_rate_state: Option<vk::PipelineFragmentShadingRateStateCreateInfoKHR>,
+    dynamic_states: Vec<vk::DynamicState>,
+    dynamic_state: vk::PipelineDynamicStateCreateInfo,
+    view_mask: u32,
+    depth_format: vk::Format,
+    rendering_info: vk::PipelineRenderingCreateInfo,
+    render_pass: Option<vk::RenderPass>,
+    flags: vk::PipelineCreateFlags,
+}
+
+impl<'a> GraphicsPipelineState<'a> {
+    /// Validate the create info and gather everything needed to create the pipeline, with
+    /// the format and the extent of the swapchain it renders to, if any. The pipeline layout
+    /// is created here.
+    fn new(
+        device: &Arc<VulkanDevice>,
+        target: Option<(vk::Format, vk::Extent2D)>,
+        vertex: VertexLayout,
+        info: PipelineCreateInfo<'a>,
+    ) -> Self {
+        let mut descriptor_set_layouts = info.descriptor_set_layouts;
+        let mut push_constant_ranges = info.push_constant_ranges;
+        if info.reflect {
+            let reflection = PipelineReflection::new(&info.shaders);
+            if descriptor_set_layouts.is_empty() {
+                descriptor_set_layouts = reflection
+                    .sets
+                    .iter()
+                    .map(|bindings| Arc::new(DescriptorSetLayout::new(device.clone(), bindings)))
+                    .collect();
+            }
+            if push_constant_ranges.is_empty() {
+                push_constant_ranges = reflection.push_constant_ranges.clone();
+            }
+            for input in reflection.missing_vertex_inputs(&vertex.attributes) {
+                log::warn!("The vertex shader input at {input} is not provided by the vertices");
+            }
+        }
+
+        // Create the pipeline layout from the descriptor set layouts and the push
+        // constants used by the shaders.
+        let layout = create_layout(device, &descriptor_set_layouts, &push_constant_ranges);
+
+        // The values of the specialization constants of each shader.
+        let specializations = info
+            .shaders
+            .iter()
+            .map(SpecializationData::new)
+            .collect::<Vec<_>>();
+
+        // Create the input assembly state. Primitive restart is only allowed for the strips and
+        // the fans, which it splits at each special index.
+        assert!(
+            !info.primitive_restart
+                || matches!(
+                    info.topology,
+                    vk::PrimitiveTopology::LINE_STRIP
+                        | vk::PrimitiveTopology::TRIANGLE_STRIP
+                        | vk::PrimitiveTopology::TRIANGLE_FAN
+                        | vk::PrimitiveTopology::LINE_STRIP_WITH_ADJACENCY
+                        | vk::PrimitiveTopology::TRIANGLE_STRIP_WITH_ADJACENCY
+                ),
+            "Primitive restart can only be used with strip and fan topologies"
+        );
         let input_assembly_state = vk::PipelineInputAssemblyStateCreateInfo::builder()
-            .topology(vk::PrimitiveTopology::TRIANGLE_LIST)
-            .primitive_restart_enable(false);
+            .topology(info.topology)
+            .primitive_restart_enable(info.primitive_restart)
+            .build();
+
+        // Tessellated pipelines draw patches of control points, that are subdivided between
+        // the tessellation control and evaluation shaders.
+        let patches = info.topology == vk::PrimitiveTopology::PATCH_LIST;
+        let tessellated = info.shaders.iter().any(|shader| {
+            matches!(
+                shader.kind(),
+                ShaderType::TessellationControl | ShaderType::TessellationEvaluation
+            )
+        });
+        assert_eq!(
+            patches, tessellated,
+            "Tessellation shaders must be used with the patch list topology"
+        );
+        if patches {
+            assert!(
+                device.supports_tessellation(),
+                "The device does not support tessellation"
+            );
+            assert!(
+                (1..=device.limits().max_tessellation_patch_size)
+                    .contains(&info.patch_control_points),
+                "Unsupported number of control points per patch"
+            );
+        }
+        let tessellation_state = patches.then(|| {
+            vk::PipelineTessellationStateCreateInfo::builder()
+                .patch_control_points(info.patch_control_points)
+                .build()
+        });
 
-        // Configure the static viewport
+        // Configure the initial viewport. The viewport and the scissor are dynamic states, so
+        // those values are ignored and are set when starting a render pass instead. This allows
+        // the same pipeline to be reused when the swapchain is recreated with a different size.
+        let extent = target.map_or(
+            vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+            |(_, extent)| extent,
+        );
         let viewport = vk::Viewport::builder()
-            .height(swapchain.extent().height as f32)
-            .width(swapchain.extent().width as f32)
+            .height(extent.height as f32)
+            .width(extent.width as f32)
             .min_depth(0.0)
             .max_depth(1.0)
             .x(0.0)
             .y(0.0);
 
-        // Configure the static scissor
+        // Configure the initial scissor
         let scissor = vk::Rect2D::builder()
             .offset(vk::Offset2D { x: 0, y: 0 })
-            .extent(swapchain.extent());
+            .extent(extent);
 
-        // Create the viewport state
-        let viewports = &[viewport];
-        let scissors = &[scissor];
-        let viewport_state = vk::PipelineViewportStateCreateInfo::builder()
-            .viewports(viewports)
-            .scissors(scissors);
+        // Create the viewport state. With several viewports, the shaders select the viewport
+        // of each primitive, and they are all set with `CommandBuffer::set_viewports`.
+        assert!(
+            info.viewport_count == 1
+                || (device.supports_multi_viewport()
+                    && info.viewport_count <= device.limits().max_viewports),
+            "Unsupported number of viewports"
+        );
+        let viewports = vec![viewport.build(); info.viewport_count as usize];
+        let scissors = vec![scissor.build(); info.viewport_count as usize];
 
         // Configure the rasterization state
+        assert!(
+            info.fill_mode == vk::PolygonMode::FILL || device.supports_fill_mode_non_solid(),
+            "The device does not support the line and point polygon modes"
+        );
+        let [min_width, max_width] = device.limits().line_width_range;
+        assert!(
+            info.line_width == 1.0
+                || (device.supports_wide_lines()
+                    && (min_width..=max_width).contains(&info.line_width)),
+            "Unsupported line width"
+        );
+        assert!(
+            !info.depth_clamp || device.supports_depth_clamp(),
+            "The device does not support depth clamping"
+        );
+        let depth_bias = info.depth_bias.unwrap_or_default();
+        assert!(
+            depth_bias.clamp == 0.0 || device.supports_depth_bias_clamp(),
+            "The device does not support clamping the depth bias"
+        );
         let rasterization_state = vk::PipelineRasterizationStateCreateInfo::builder()
             .polygon_mode(info.fill_mode.into())
             .front_face(info.front_face.into())
             .cull_mode(info.cull_mode.into())
             .rasterizer_discard_enable(false)
-            .depth_clamp_enable(false)
-            .depth_bias_enable(false)
-            .line_width(1.0);
+            .depth_clamp_enable(info.depth_clamp)
+            .depth_bias_enable(info.depth_bias.is_some() || info.dynamic_depth_bias)
+            .depth_bias_constant_factor(depth_bias.constant_factor)
+            .depth_bias_slope_factor(depth_bias.slope_factor)
+            .depth_bias_clamp(depth_bias.clamp)
+            .line_width(info.line_width)
+            .build();
+
+        // Conservative rasterization is chained to the rasterization state when the create
+        // info is built, since it is referenced by a pointer.
+        let conservative = info.conservative_rasterization;
+        let properties = device.conservative_rasterization_properties();
+        let conservative_state = (conservative != vk::ConservativeRasterizationModeEXT::DISABLED)
+            .then(|| {
+                assert!(
+                    device.supports_conservative_rasterization(),
+                    "The device does not support conservative rasterization"
+                );
+                assert!(
+                    conservative != vk::ConservativeRasterizationModeEXT::UNDERESTIMATE
+                        || properties.primitive_underestimation == vk::TRUE,
+                    "The device does not support underestimating the primitives"
+                );
+                assert!(
+                    (0.0..=properties.max_extra_primitive_overestimation_size)
+                        .contains(&info.extra_overestimation_size),
+                    "Unsupported extra overestimation size"
+                );
+                vk::PipelineRasterizationConservativeStateCreateInfoEXT::builder()
+                    .conservative_rasterization_mode(conservative)
+                    .extra_primitive_overestimation_size(info.extra_overestimation_size)
+                    .build()
+            });
 
         // Configure the multisample state
+        assert!(
+            info.min_sample_shading.is_none() || device.supports_sample_rate_shading(),
+            "The device does not support sample shading"
+        );
+        let min_sample_shading = info.min_sample_shading.unwrap_or(0.0);
+        assert!(
+            (0.0..=1.0).contains(&min_sample_shading),
+            "The minimum fraction of sample shading must be within 0.0..=1.0"
+        );
         let multisample_state = vk::PipelineMultisampleStateCreateInfo::builder()
-            .rasterization_samples(vk::SampleCountFlags::_1)
-            .sample_shading_enable(false);
+            .rasterization_samples(info.samples.into())
+            .sample_shading_enable(info.min_sample_shading.is_some())
+            .min_sample_shading(min_sample_shading)
+            .alpha_to_coverage_enable(info.alpha_to_coverage)
+            .build();
 
-        let attachment = vk::PipelineColorBlendAttachmentState::builder()
-            .color_write_mask(vk::ColorComponentFlags::all())
-            .blend_enable(false);
+        // The formats of the color attachments. If no color format is given,
+        // the pipeline renders to the swapchain, if any.
+        let formats = if info.color_formats.is_empty() {
+            target.map(|(format, _)| format).into_iter().collect()
+        } else {
+            info.color_formats.clone()
+        };
 
-        let attachments = &[attachment];
-        let color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
-            .blend_constants([0.0, 0.0, 0.0, 0.0])
-            .logic_op(vk::LogicOp::COPY)
-            .logic_op_enable(false)
-            .attachments(attachments);
+        // Each color attachment has its own blend state, since the targets of a G-buffer
+        // usually don't blend the same way. Blending is disabled for all of them by default.
+        let attachments = if info.color_blends.is_empty() {
+            let attachment = vk::PipelineColorBlendAttachmentState::builder()
+                .color_write_mask(vk::ColorComponentFlags::all())
+                .blend_enable(false)
+                .build();
+            vec![attachment; formats.len()]
+        } else {
+            assert_eq!(
+                info.color_blends.len(),
+                formats.len(),
+                "A blend state must be given for each color attachment"
+            );
+            info.color_blends.clone()
+        };
 
+        assert!(
+            info.depth_bounds.is_none() || device.supports_depth_bounds(),
+            "The device does not support the depth bounds test"
+        );
+        let depth_bounds = info.depth_bounds.clone().unwrap_or(0.0..1.0);
+        assert!(
+            (0.0..=1.0).contains(&depth_bounds.start) && (0.0..=1.0).contains(&depth_bounds.end),
+            "The depth bounds must be within 0.0..=1.0"
+        );
         let depth_stencil_state = vk::PipelineDepthStencilStateCreateInfo::builder()
             .depth_write_enable(info.depth_write)
             .depth_test_enable(info.depth_test)
-            .depth_bounds_test_enable(false)
-            .depth_compare_op(vk::CompareOp::LESS)
-            .stencil_test_enable(false);
+            .depth_compare_op(info.depth_compare_op)
+            .depth_bounds_test_enable(info.depth_bounds.is_some())
+            .min_depth_bounds(depth_bounds.start)
+            .max_depth_bounds(depth_bounds.end)
+            .stencil_test_enable(info.stencil_test)
+            .front(info.stencil_front)
+            .back(info.stencil_back)
+            .build();
+
+        // Configure the fragment shading rate, if any. The pipelines reading the rate from an
+        // attachment must be flagged as such when they are used with dynamic rendering.
+        let mut flags = vk::PipelineCreateFlags::empty();
+        if let Some(shading_rate) = info.shading_rate {
+            let size = shading_rate.fragment_size;
+            assert!(
+                device.supports_pipeline_shading_rate(),
+                "The device does not support the pipeline fragment shading rate"
+            );
+            assert!(
+                [1, 2, 4].contains(&size.width) && [1, 2, 4].contains(&size.height),
+                "Unsupported fragment size"
+            );
+            assert!(
+                shading_rate.combiner_ops[0] == vk::FragmentShadingRateCombinerOpKHR::KEEP
+                    || device.supports_primitive_shading_rate(),
+                "The device does not support the primitive fragment shading rate"
+            );
+            if shading_rate.uses_attachment() {
+                assert!(
+                    device.supports_attachment_shading_rate(),
+                    "The device does not support the attachment fragment shading rate"
+                );
+                flags |= vk::PipelineCreateFlags::RENDERING_FRAGMENT_SHADING_RATE_ATTACHMENT_KHR;
+            }
+        }
+        let shading_rate_state = info.shading_rate.map(|shading_rate| {
+            vk::PipelineFragmentShadingRateStateCreateInfoKHR::builder()
+                .fragment_size(shading_rate.fragment_size)
+                .combiner_ops(shading_rate.combiner_ops)
+                .build()
+        });
+
+        let mut dynamic_states = vec![vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        if info.dynamic_depth_bias {
+            dynamic_states.push(vk::DynamicState::DEPTH_BIAS);
+        }
+        if info.dynamic_line_width {
+            dynamic_states.push(vk::DynamicState::LINE_WIDTH);
+        }
+
+        // The rendering info is given when dynamic rendering is supported, since it is not
+        // included in the base pipeline create info struct. Otherwise, the pipeline is created
+        // against a render pass compatible with the attachments.
+        assert!(
+            info.view_mask == 0 || device.supports_multiview(),
+            "The device does not support multiview"
+        );
+        let render_pass = (!device.supports_dynamic_rendering()).then(|| {
+            let layout = RenderPassLayout::compatible(&formats, info.depth_format, info.samples)
+                .with_view_mask(info.view_mask);
+            device.render_pass(&layout)
+        });
+
+        Self {
+            layout,
+            descriptor_set_layouts,
+            name: info.name,
+            shaders: info.shaders,
+            specializations,
+            specialization_infos: Vec::new(),
+            stages: Vec::new(),
+            vertex,
+            vertex_input_state: vk::PipelineVertexInputStateCreateInfo::default(),
+            input_assembly_state,
+            tessellation_state,
+            viewports,
+            scissors,
+            viewport_state: vk::PipelineViewportStateCreateInfo::default(),
+            rasterization_state,
+            conservative_state,
+            multisample_state,
+            formats,
+            attachments,
+            color_blend_state: vk::PipelineColorBlendStateCreateInfo::default(),
+            depth_stencil_state,
+            shading_rate_state,
+            dynamic_states,
+            dynamic_state: vk::PipelineDynamicStateCreateInfo::default(),
+            view_mask: info.view_mask,
+            depth_format: info.depth_format,
+            rendering_info: vk::PipelineRenderingCreateInfo::default(),
+            render_pass,
+            flags,
+        }
+    }
 
-        // Create the rendering info struct, since we use dynamic rendering
-        // which is not included in the base pipeline create info struct.
-        let format = [swapchain.format()];
-        let mut rendering_info = vk::PipelineRenderingCreateInfo::builder()
-            .depth_attachment_format(info.depth_format.into())
-            .color_attachment_formats(&format);
+    /// Returns the create info of the pipeline, pointing to the structures of the state, which
+    /// must not be moved nor dropped until the pipeline is created.
+    fn create_info(&mut self) -> vk::GraphicsPipelineCreateInfo {
+        // Create a pipeline shader stage create info for each shader, with the values of
+        // its specialization constants.
+        self.specialization_infos = self
+            .specializations
+            .iter()
+            .map(SpecializationData::info)
+            .collect();
+        self.stages = self
+            .shaders
+            .iter()
+            .zip(&self.specialization_infos)
+            .map(|(shader, specialization)| {
+                let mut stage = vk::PipelineShaderStageCreateInfo::builder()
+                    .module(shader.inner())
+                    .name(b"main\0")
+                    .stage(shader.kind().into());
+                if let Some(specialization) = specialization {
+                    stage = stage.specialization_info(specialization);
+                }
+                stage.build()
+            })
+            .collect();
+
+        self.vertex_input_state = vk::PipelineVertexInputStateCreateInfo::builder()
+            .vertex_attribute_descriptions(&self.vertex.attributes)
+            .vertex_binding_descriptions(&self.vertex.bindings)
+            .build();
+        self.viewport_state = vk::PipelineViewportStateCreateInfo::builder()
+            .viewports(&self.viewports)
+            .scissors(&self.scissors)
+            .build();
+        self.color_blend_state = vk::PipelineColorBlendStateCreateInfo::builder()
+            .blend_constants([0.0, 0.0, 0.0, 0.0])
+            .logic_op(vk::LogicOp::COPY)
+            .logic_op_enable(false)
+            .attachments(&self.attachments)
+            .build();
+        self.dynamic_state = vk::PipelineDynamicStateCreateInfo::builder()
+            .dynamic_states(&self.dynamic_states)
+            .build();
+        if let Some(conservative_state) = &self.conservative_state {
+            let next: *const vk::PipelineRasterizationConservativeStateCreateInfoEXT =
+                conservative_state;
+            self.rasterization_state.next = next.cast();
+        }
+        let (depth_format, stencil_format) = depth_stencil_formats(self.depth_format);
+        self.rendering_info = vk::PipelineRenderingCreateInfo::builder()
+            .view_mask(self.view_mask)
+            .depth_attachment_format(depth_format)
+            .stencil_attachment_format(stencil_format)
+            .color_attachment_formats(&self.formats)
+            .build();
 
         // Register all the previous structs into the pipeline create infos
-        let creat_info = vk::GraphicsPipelineCreateInfo::builder()
-            .input_assembly_state(&input_assembly_state)
-            .rasterization_state(&rasterization_state)
-            .depth_stencil_state(&depth_stencil_state)
-            .vertex_input_state(&vertex_input_state)
-            .multisample_state(&multisample_state)
-            .color_blend_state(&color_blend_state)
-            .viewport_state(&viewport_state)
-            .stages(&stages)
-            .layout(layout)
-            .push_next(&mut rendering_info);
+        let mut info = vk::GraphicsPipelineCreateInfo::builder()
+            .input_assembly_state(&self.input_assembly_state)
+            .rasterization_state(&self.rasterization_state)
+            .depth_stencil_state(&self.depth_stencil_state)
+            .vertex_input_state(&self.vertex_input_state)
+            .multisample_state(&self.multisample_state)
+            .color_blend_state(&self.color_blend_state)
+            .viewport_state(&self.viewport_state)
+            .dynamic_state(&self.dynamic_state)
+            .stages(&self.stages)
+            .layout(self.layout)
+            .flags(self.flags);
+
+        if let Some(tessellation_state) = &self.tessellation_state {
+            info = info.tessellation_state(tessellation_state);
+        }
+        if let Some(shading_rate_state) = &mut self.shading_rate_state {
+            info = info.push_next(shading_rate_state);
+        }
+        match self.render_pass {
+            Some(render_pass) => info = info.render_pass(render_pass).subpass(0),
+            None => info = info.push_next(&mut self.rendering_info),
+        }
+        info.build()
+    }
+
+    /// Returns the pipeline created from this state, and names it.
+    fn into_pipeline(self, device: Arc<VulkanDevice>, inner: vk::Pipeline) -> Pipeline {
+        if let Some(name) = self.name {
+            device.set_object_name(vk::ObjectType::PIPELINE, inner.as_raw(), name);
+            device.set_object_name(vk::ObjectType::PIPELINE_LAYOUT, self.layout.as_raw(), name);
+        }
+
+        Pipeline {
+            layout: self.layout,
+            device,
+            inner,
+            descriptor_set_layouts: self.descriptor_set_layouts,
+        }
+    }
+}
+
+/// Create a pipeline layout from the given descriptor set layouts and push constant ranges.
+fn create_layout(
+    device: &VulkanDevice,
+    descriptor_set_layouts: &[Arc<DescriptorSetLayout>],
+    push_constant_ranges: &[vk::PushConstantRange],
+) -> vk::PipelineLayout {
+    let set_layouts = descriptor_set_layouts
+        .iter()
+        .map(|layout| layout.inner())
+        .collect::<Vec<_>>();
+    let layout_info = vk::PipelineLayoutCreateInfo::builder()
+        .set_layouts(&set_layouts)
+        .push_constant_ranges(push_constant_ranges);
+    unsafe {
+        device
+            .logical()
+            .create_pipeline_layout(&layout_info, None)
+            .expect("Failed to create pipeline layout")
+    }
+}
+
+/// A compute pipeline object.
+#[derive(Debug)]
+pub struct ComputePipeline {
+    device: Arc<VulkanDevice>,
+    layout: vk::PipelineLayout,
+    inner: vk::Pipeline,
+
+    /// The layouts of the descriptor sets bound to the pipeline, kept alive by the pipeline.
+    descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
+}
+
+impl ComputePipeline {
+    /// Creates a new compute pipeline object.
+    ///
+    /// # Panics
+    /// Panics if the given shader is not a compute shader.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn new(device: Arc<VulkanDevice>, info: ComputePipelineCreateInfo) -> Self {
+        assert_eq!(
+            info.shader.kind(),
+            ShaderType::Compute,
+            "A compute pipeline requires a compute shader"
+        );
+
+        let layout = create_layout(
+            &device,
+            &info.descriptor_set_layouts,
+            &info.push_constant_ranges,
+        );
+
+        let specialization = SpecializationData::new(&info.shader);
+        let specialization_info = specialization.info();
+        let mut stage = vk::PipelineShaderStageCreateInfo::builder()
+            .stage(vk::ShaderStageFlags::COMPUTE)
+            .module(info.shader.inner())
+            .name(b"main\0");
+        if let Some(specialization_info) = &specialization_info {
+            stage = stage.specialization_info(specialization_info);
+        }
+
+        let create_info = vk::ComputePipelineCreateInfo::builder()
+            .stage(stage)
+            .layout(layout);
 
         let inner = unsafe {
             device
                 .logical()
-                .create_graphics_pipelines(vk::PipelineCache::null(), &[creat_info], None)
-                .expect("Failed to create graphics pipeline")
+                .create_compute_pipelines(vk::PipelineCache::null(), &[create_info], None)
+                .expect("Failed to create compute pipeline")
                 .0[0]
         };
+        if let Some(name) = info.name {
+            device.set_object_name(vk::ObjectType::PIPELINE, inner.as_raw(), name);
+            device.set_object_name(vk::ObjectType::PIPELINE_LAYOUT, layout.as_raw(), name);
+        }
 
         Self {
-            layout,
             device,
+            layout,
             inner,
+            descriptor_set_layouts: info.descriptor_set_layouts,
         }
     }
 
@@ -161,6 +828,13 @@ impl Pipeline {
         self.layout
     }
 
+    /// Returns the layouts of the descriptor sets bound to the pipeline, in the order of their
+    /// set number.
+    #[must_use]
+    pub fn descriptor_set_layouts(&self) -> &[Arc<DescriptorSetLayout>] {
+        &self.descriptor_set_layouts
+    }
+
     /// Returns the inner pipeline handle.
     #[must_use]
     pub fn inner(&self) -> vk::Pipeline {
@@ -168,18 +842,33 @@ impl Pipeline {
     }
 }
 
-impl Drop for Pipeline {
+impl Drop for ComputePipeline {
     fn drop(&mut self) {
-        unsafe {
-            let device = &self.device.logical();
-            device.destroy_pipeline_layout(self.layout, None);
-            device.destroy_pipeline(self.inner, None);
-        }
+        self.device
+            .destroy_later(Deletion::Pipeline(self.inner, self.layout));
     }
 }
 
+/// A struct containing the information needed to create a compute pipeline.
+#[derive(Debug)]
+pub struct ComputePipelineCreateInfo<'a> {
+    /// The compute shader run by the pipeline.
+    pub shader: ShaderModule,
+
+    /// The layouts of the descriptor sets used by the shader, in the order of their set
+    /// number. The pipeline keeps them alive.
+    pub descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
+
+    /// The push constant ranges used by the shader.
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+
+    /// The name of the pipeline, displayed in the validation messages and by graphics
+    /// debuggers.
+    pub name: Option<&'a str>,
+}
+
 /// A struct containing the information needed to create a pipeline.
-pub struct PipelineCreateInfo {
+pub struct PipelineCreateInfo<'a> {
     /// A list of shaders to use for the pipeline.
     pub shaders: Vec<ShaderModule>,
 
@@ -187,13 +876,58 @@ pub struct PipelineCreateInfo {
     /// when culling.
     pub front_face: vk::FrontFace,
 
-    /// The fill mode to use for the pipeline.
+    /// The fill mode to use for the pipeline. The `LINE` and `POINT` modes, for example to
+    /// draw wireframes, require the non-solid fill mode feature of the device.
     pub fill_mode: vk::PolygonMode,
 
+    /// The primitive topology of the vertices, for example `LINE_LIST` for debug lines or
+    /// `POINT_LIST` for point clouds, whose point size is written to `gl_PointSize` by the
+    /// vertex shader. Pipelines with tessellation shaders must use
+    /// `vk::PrimitiveTopology::PATCH_LIST`, and no other pipeline can use it.
+    pub topology: vk::PrimitiveTopology,
+
+    /// Whether the special index `0xFFFF` or `0xFFFFFFFF`, depending on the index type,
+    /// restarts the strip or the fan in indexed draws. It can only be enabled with the strip
+    /// and fan topologies.
+    pub primitive_restart: bool,
+
+    /// The width of the rasterized lines, in pixels. Widths other than 1.0 require the wide
+    /// lines feature of the device, and must be within its line width range.
+    pub line_width: f32,
+
+    /// Whether the line width is set per draw with [`CommandBuffer::set_line_width`] instead
+    /// of being fixed in the pipeline, in which case [`PipelineCreateInfo::line_width`] is
+    /// ignored.
+    ///
+    /// [`CommandBuffer::set_line_width`]: crate::command::CommandBuffer::set_line_width
+    pub dynamic_line_width: bool,
+
+    /// The number of control points per patch, when the topology is
+    /// `vk::PrimitiveTopology::PATCH_LIST`. It is ignored otherwise.
+    pub patch_control_points: u32,
+
     /// The cull mode to use for the pipeline.
     pub cull_mode: vk::CullModeFlags,
 
-    /// The format of the depth buffer.
+    /// The layouts of the descriptor sets used by the shaders, in the order of their set
+    /// number. The pipeline keeps them alive.
+    pub descriptor_set_layouts: Vec<Arc<DescriptorSetLayout>>,
+
+    /// The push constant ranges used by the shaders.
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+
+    /// The formats of the color attachments. If empty, the pipeline renders to a single
+    /// color attachment with the format of the swapchain, or to no color attachment at all
+    /// for offscreen pipelines. The attachments are written by the
+    /// fragment shader outputs with the same location, and must be given in the same order to
+    /// [`CommandBuffer::start_rendering`](crate::command::CommandBuffer::start_rendering).
+    pub color_formats: Vec<vk::Format>,
+
+    /// The blend state of each color attachment. If empty, blending is disabled for all the
+    /// attachments; otherwise there must be one state per color attachment.
+    pub color_blends: Vec<vk::PipelineColorBlendAttachmentState>,
+
+    /// The format of the depth buffer, which may also have a stencil aspect.
     pub depth_format: vk::Format,
 
     /// Whether or not to enable depth writing.
@@ -201,22 +935,208 @@ pub struct PipelineCreateInfo {
 
     /// Whether or not to enable depth testing.
     pub depth_test: bool,
+
+    /// The comparison of the depth of the fragments with the depth buffer by the depth test,
+    /// for example `GREATER` with a reversed depth buffer.
+    pub depth_compare_op: vk::CompareOp,
+
+    /// The range of depths of the depth buffer outside of which the fragments are discarded,
+    /// or `None` to disable the depth bounds test. It requires the depth bounds feature of the
+    /// device, and must be within `0.0..=1.0`.
+    pub depth_bounds: Option<Range<f32>>,
+
+    /// The depth bias added to the depth of the fragments, for example to avoid the shadow
+    /// acne of shadow maps, or `None` to disable it.
+    pub depth_bias: Option<DepthBias>,
+
+    /// Whether the depth bias is enabled and set per draw with
+    /// [`CommandBuffer::set_depth_bias`] instead of being fixed in the pipeline, in which case
+    /// [`PipelineCreateInfo::depth_bias`] is ignored.
+    ///
+    /// [`CommandBuffer::set_depth_bias`]: crate::command::CommandBuffer::set_depth_bias
+    pub dynamic_depth_bias: bool,
+
+    /// Whether the depth of the fragments is clamped to the depth range of the viewport instead
+    /// of clipping the primitives at the near and far planes, for example to keep the shadow
+    /// casters behind the near plane of a shadow map. It requires the depth clamp feature.
+    pub depth_clamp: bool,
+
+    /// The conservative rasterization mode. With `OVERESTIMATE`, every pixel touched by a
+    /// primitive is rasterized, even partially, for example to voxelize a scene or to test
+    /// occluders against a coarse depth buffer without missing thin geometry. With
+    /// `UNDERESTIMATE`, only the pixels fully covered by a primitive are rasterized. Both
+    /// require the conservative rasterization extension.
+    pub conservative_rasterization: vk::ConservativeRasterizationModeEXT,
+
+    /// The size in pixels by which the primitives are further extended with the
+    /// `OVERESTIMATE` conservative rasterization mode, up to the maximum extra overestimation
+    /// size of the device.
+    pub extra_overestimation_size: f32,
+
+    /// Whether or not to enable stencil testing, which requires a depth format with a stencil
+    /// aspect.
+    pub stencil_test: bool,
+
+    /// The stencil operations, masks and reference of the front-facing primitives.
+    pub stencil_front: vk::StencilOpState,
+
+    /// The stencil operations, masks and reference of the back-facing primitives.
+    pub stencil_back: vk::StencilOpState,
+
+    /// The number of samples rasterized per pixel, which must match the samples of the
+    /// attachments rendered to.
+    pub samples: SampleCount,
+
+    /// The minimum fraction of the samples of each pixel shaded by a distinct fragment shader
+    /// invocation with MSAA, or `None` to shade each pixel once. 1.0 shades every sample,
+    /// which smooths the edges of alpha-tested geometry and of the details inside the
+    /// primitives. It requires the sample rate shading feature of the device.
+    pub min_sample_shading: Option<f32>,
+
+    /// Whether the alpha of the first color output of the fragment shader gives the coverage
+    /// of the samples with MSAA, which blends the edges of alpha-tested foliage or fences
+    /// without sorting them.
+    pub alpha_to_coverage: bool,
+
+    /// The views rendered with multiview, which must match the view mask of the render passes
+    /// in which the pipeline is used. See [`RenderingInfo::view_mask`].
+    ///
+    /// [`RenderingInfo::view_mask`]: crate::command::RenderingInfo::view_mask
+    pub view_mask: u32,
+
+    /// The number of viewports of the pipeline. More than one viewport requires the multiple
+    /// viewports feature of the device.
+    pub viewport_count: u32,
+
+    /// The fragment shading rate of the pipeline, or `None` to shade each pixel, or each
+    /// sample, once. See [`FragmentShadingRate`].
+    pub shading_rate: Option<FragmentShadingRate>,
+
+    /// Whether to reflect the shaders to derive the descriptor set layouts and the push
+    /// constant ranges that are left empty. Disable it to keep full manual control over the
+    /// layout of the pipeline, for example to share descriptor sets between pipelines.
+    pub reflect: bool,
+
+    /// The name of the pipeline, displayed in the validation messages and by graphics
+    /// debuggers.
+    pub name: Option<&'a str>,
 }
 
-impl Default for PipelineCreateInfo {
+/// The depth bias of a pipeline, added to the depth of the fragments before the depth test.
+/// The bias is `constant_factor` times the smallest resolvable difference of depth plus
+/// `slope_factor` times the depth slope of the primitive, and is clamped to `clamp` unless it
+/// is 0.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DepthBias {
+    /// The constant depth bias, in units of the smallest resolvable difference of depth.
+    pub constant_factor: f32,
+
+    /// The depth bias relative to the slope of the primitives, which offsets the surfaces
+    /// almost parallel to the light or the view direction the most.
+    pub slope_factor: f32,
+
+    /// The maximum, or minimum if negative, depth bias. Clamping requires the depth bias clamp
+    /// feature of the device.
+    pub clamp: f32,
+}
+
+/// The stencil state that always passes and keeps the stencil buffer unchanged, with all the
+/// bits of the masks set.
+const DEFAULT_STENCIL_STATE: vk::StencilOpState = vk::StencilOpState {
+    fail_op: vk::StencilOp::KEEP,
+    pass_op: vk::StencilOp::KEEP,
+    depth_fail_op: vk::StencilOp::KEEP,
+    compare_op: vk::CompareOp::ALWAYS,
+    compare_mask: 0xFF,
+    write_mask: 0xFF,
+    reference: 0,
+};
+
+impl Default for PipelineCreateInfo<'_> {
     fn default() -> Self {
         Self {
             front_face: vk::FrontFace::COUNTER_CLOCKWISE,
             cull_mode: vk::CullModeFlags::BACK,
             fill_mode: vk::PolygonMode::FILL,
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            primitive_restart: false,
+            line_width: 1.0,
+            dynamic_line_width: false,
+            patch_control_points: 3,
             depth_format: vk::Format::UNDEFINED,
+            color_formats: Vec::new(),
+            color_blends: Vec::new(),
+            descriptor_set_layouts: Vec::new(),
+            push_constant_ranges: Vec::new(),
             depth_write: false,
             depth_test: false,
+            depth_compare_op: vk::CompareOp::LESS,
+            depth_bounds: None,
+            depth_bias: None,
+            dynamic_depth_bias: false,
+            depth_clamp: false,
+            conservative_rasterization: vk::ConservativeRasterizationModeEXT::DISABLED,
+            extra_overestimation_size: 0.0,
+            stencil_test: false,
+            stencil_front: DEFAULT_STENCIL_STATE,
+            stencil_back: DEFAULT_STENCIL_STATE,
+            samples: SampleCount::One,
+            min_sample_shading: None,
+            alpha_to_coverage: false,
+            view_mask: 0,
+            viewport_count: 1,
+            shading_rate: None,
+            reflect: true,
             shaders: Vec::new(),
+            name: None,
+        }
+    }
+}
+
+/// The fragment shading rate of a pipeline, to shade a block of pixels with a single fragment
+/// shader invocation where the full resolution is not needed, for example at the edge of the
+/// field of view with foveated rendering, or everywhere to trade quality for performance.
+///
+/// The rate of the pipeline is combined with the rate written by the vertex shaders to
+/// `gl_PrimitiveShadingRateEXT`, then with the rate read from the shading rate attachment of
+/// the render pass, see [`RenderingInfo::shading_rate_attachment`]. With the default `KEEP`
+/// operations, the rates of the primitives and the attachment are ignored.
+///
+/// [`RenderingInfo::shading_rate_attachment`]:
+///     crate::command::RenderingInfo::shading_rate_attachment
+#[derive(Debug, Clone, Copy)]
+pub struct FragmentShadingRate {
+    /// The size of the blocks of pixels shaded by a single invocation. The width and the height
+    /// must be 1, 2 or 4.
+    pub fragment_size: vk::Extent2D,
+
+    /// How the rate of the pipeline is combined with the rate of the primitives, then how the
+    /// result is combined with the rate of the attachment. Operations other than `KEEP` require
+    /// the primitive and the attachment fragment shading rate features, respectively.
+    pub combiner_ops: [vk::FragmentShadingRateCombinerOpKHR; 2],
+}
+
+impl Default for FragmentShadingRate {
+    fn default() -> Self {
+        Self {
+            fragment_size: vk::Extent2D {
+                width: 1,
+                height: 1,
+            },
+            combiner_ops: [vk::FragmentShadingRateCombinerOpKHR::KEEP; 2],
         }
     }
 }
 
+impl FragmentShadingRate {
+    /// Returns whether the rate of the shading rate attachment is used, in which case the
+    /// pipeline must be created for render passes with a shading rate attachment.
+    #[must_use]
+    pub fn uses_attachment(&self) -> bool {
+        self.combiner_ops[1] != vk::FragmentShadingRateCombinerOpKHR::KEEP
+    }
+}
+
 /// Specify the spacing between vertex data and and whether the data is per-vertex
 /// or per-instance (instancing)
 ///
@@ -253,3 +1173,50 @@ unsafe impl VertexAttributeDescription for NoVertex {
         Vec::new()
     }
 }
+
+/// The combination of a vertex type `V` read per vertex from the binding 0, and an instance
+/// type `I` read per instance from the binding 1, for instanced drawing. Both types describe
+/// their attributes at the binding 0: the bindings and input rate of `I` are rewritten. The
+/// attributes of `I` must use locations that are not used by `V`.
+#[derive(Debug, Clone, Copy)]
+pub struct Instanced<V, I>(PhantomData<(V, I)>);
+
+unsafe impl<V, I> VertexBindingDescription for Instanced<V, I>
+where
+    V: VertexBindingDescription,
+    I: VertexBindingDescription,
+{
+    fn binding_description() -> Vec<vk::VertexInputBindingDescription> {
+        let instances =
+            I::binding_description()
+                .into_iter()
+                .map(|binding| vk::VertexInputBindingDescription {
+                    binding: 1,
+                    input_rate: vk::VertexInputRate::INSTANCE,
+                    ..binding
+                });
+        V::binding_description()
+            .into_iter()
+            .chain(instances)
+            .collect()
+    }
+}
+
+unsafe impl<V, I> VertexAttributeDescription for Instanced<V, I>
+where
+    V: VertexAttributeDescription,
+    I: VertexAttributeDescription,
+{
+    fn attribute_descriptions() -> Vec<vk::VertexInputAttributeDescription> {
+        let instances = I::attribute_descriptions().into_iter().map(|attribute| {
+            vk::VertexInputAttributeDescription {
+                binding: 1,
+                ..attribute
+            }
+        });
+        V::attribute_descriptions()
+            .into_iter()
+            .chain(instances)
+            .collect()
+    }
+}
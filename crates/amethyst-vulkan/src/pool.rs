@@ -0,0 +1,314 @@
+//! Suballocation of small buffers. Creating a `vk::Buffer` and a memory allocation for each
+//! mesh or each small uniform block quickly exceeds the allocation limits of the drivers in
+//! scenes with many objects. A [`BufferPool`] instead allocates a few large buffers, and carves
+//! the [`SubBuffer`]s out of them with the alignment required by their usage.
+//!
+//! The ranges of the dropped sub-buffers are returned to their buffer through the deletion
+//! queue of the device, so that they are not reused while the frames that may use them are
+//! still executing.
+use crate::{
+    buffer::{
+        Buffer, BufferAllocator, BufferCreateInfo, BufferDataInfo, BufferUsage, BufferUsageInfo,
+        SubBuffer,
+    },
+    deletion::Deletion,
+    device::VulkanDevice,
+};
+use std::{
+    ops::Range,
+    sync::{Arc, Mutex},
+};
+use vulkanalia::prelude::v1_3::*;
+
+/// The default size of the buffers allocated by a [`BufferPool`], 16 MiB.
+pub const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 16 * 1024 * 1024;
+
+/// A pool of large buffers from which sub-buffers are suballocated. All the buffers of the
+/// pool share the same usage, so a pool is usually created for each kind of data, for example
+/// one for the vertices and one for the indices of the meshes.
+///
+/// The sub-buffers of a pool in host visible memory can be written directly with
+/// [`SubBuffer::write`]. The sub-buffers of a pool in device local memory are written by
+/// copying a staging buffer into them with
+/// [`CommandBuffer::copy_to_sub_buffer`](crate::command::CommandBuffer::copy_to_sub_buffer),
+/// so the usage of the pool must allow the buffers to be transfer destinations.
+#[derive(Debug)]
+pub struct BufferPool {
+    /// The allocator of the buffers of the pool.
+    allocator: Arc<BufferAllocator>,
+
+    /// The usage of the buffers of the pool.
+    usage: BufferUsageInfo,
+
+    /// The size of the buffers of the pool. Sub-buffers larger than this size get a buffer
+    /// of their own.
+    block_size: vk::DeviceSize,
+
+    /// The minimum alignment of the sub-buffers required by the device for the usage of the
+    /// pool.
+    alignment: vk::DeviceSize,
+
+    /// The buffers of the pool.
+    blocks: Vec<PoolBlock>,
+}
+
+impl BufferPool {
+    /// Create a new pool whose buffers have the given usage and size. No buffer is allocated
+    /// until the first sub-buffer is.
+    ///
+    /// # Panics
+    /// Panics if the block size is zero.
+    #[must_use]
+    pub fn new(
+        allocator: Arc<BufferAllocator>,
+        usage: BufferUsageInfo,
+        block_size: vk::DeviceSize,
+    ) -> Self {
+        assert!(block_size > 0, "The block size of a pool must not be zero");
+        let alignment = offset_alignment(allocator.device(), usage.usage);
+        Self {
+            allocator,
+            usage,
+            block_size,
+            alignment,
+            blocks: Vec::new(),
+        }
+    }
+
+    /// Allocate a sub-buffer of `len` elements of type `T`, in the first buffer of the pool
+    /// with enough free space, or in a new buffer if none has. The offset of the sub-buffer is
+    /// a multiple of the size of `T`, so that it can be addressed with the index of its first
+    /// element when its buffer is bound as a whole.
+    ///
+    /// # Panics
+    /// Panics if `len` is zero.
+    #[must_use]
+    pub fn allocate<T>(&mut self, len: usize) -> SubBuffer<T> {
+        assert!(len > 0, "Cannot allocate an empty sub-buffer");
+        let size = (len * std::mem::size_of::<T>()).max(1) as vk::DeviceSize;
+        let alignment = lcm(
+            self.alignment
+                .max(std::mem::align_of::<T>() as vk::DeviceSize),
+            std::mem::size_of::<T>().max(1) as vk::DeviceSize,
+        );
+
+        let allocated = self.blocks.iter().enumerate().find_map(|(index, block)| {
+            let mut free = block.free.lock().expect("Free list poisoned");
+            free.allocate(size, alignment).map(|range| (index, range))
+        });
+        let (index, range) = allocated.unwrap_or_else(|| {
+            let block = self.create_block(size);
+            let range = block
+                .free
+                .lock()
+                .expect("Free list poisoned")
+                .allocate(size, alignment)
+                .expect("Failed to allocate from a new pool block");
+            self.blocks.push(block);
+            (self.blocks.len() - 1, range)
+        });
+
+        let block = &self.blocks[index];
+        let suballocation = Suballocation {
+            device: self.allocator.device().clone(),
+            free: block.free.clone(),
+            range,
+        };
+        SubBuffer::suballocated(block.buffer.clone(), suballocation, len)
+    }
+
+    /// Release the buffers of the pool that do not contain any sub-buffer anymore.
+    pub fn trim(&mut self) {
+        self.blocks
+            .retain(|block| Arc::strong_count(&block.buffer) > 1);
+    }
+
+    /// Returns the number of buffers allocated by the pool.
+    #[must_use]
+    pub fn blocks(&self) -> usize {
+        self.blocks.len()
+    }
+
+    /// Returns the size of the buffers of the pool.
+    #[must_use]
+    pub const fn block_size(&self) -> vk::DeviceSize {
+        self.block_size
+    }
+
+    /// Allocate a new buffer large enough to hold a sub-buffer of the given size.
+    fn create_block(&self, size: vk::DeviceSize) -> PoolBlock {
+        let size = self.block_size.max(size);
+        let buffer = Buffer::new::<u8>(
+            self.allocator.clone(),
+            BufferCreateInfo {
+                usage: self.usage,
+                alignment: self.alignment as usize,
+                data: BufferDataInfo::Uninitialized(size as usize),
+                name: Some("buffer pool block"),
+            },
+        );
+
+        PoolBlock {
+            buffer: Arc::new(buffer),
+            free: Arc::new(Mutex::new(FreeList::new(size))),
+        }
+    }
+}
+
+/// A buffer of a [`BufferPool`], with the ranges that are not allocated.
+#[derive(Debug)]
+struct PoolBlock {
+    buffer: Arc<Buffer>,
+    free: Arc<Mutex<FreeList>>,
+}
+
+/// The free ranges of a pooled buffer, sorted by offset. Adjacent ranges are always merged.
+#[derive(Debug)]
+pub(crate) struct FreeList {
+    ranges: Vec<Range<vk::DeviceSize>>,
+}
+
+impl FreeList {
+    /// Create a free list for a buffer of the given size, which is entirely free.
+    fn new(size: vk::DeviceSize) -> Self {
+        Self {
+            ranges: vec![0..size],
+        }
+    }
+
+    /// Reserve a range of the given size, whose start is a multiple of the given alignment, in
+    /// the first free range large enough to hold it.
+    fn allocate(
+        &mut self,
+        size: vk::DeviceSize,
+        alignment: vk::DeviceSize,
+    ) -> Option<Range<vk::DeviceSize>> {
+        let (index, start) = self.ranges.iter().enumerate().find_map(|(index, free)| {
+            let start = free.start.next_multiple_of(alignment);
+            (start + size <= free.end).then_some((index, start))
+        })?;
+
+        // Keep the padding before the aligned start and the space after the allocation free.
+        let free = self.ranges[index].clone();
+        let end = start + size;
+        let remaining = [free.start..start, end..free.end]
+            .into_iter()
+            .filter(|range| !range.is_empty());
+        self.ranges.splice(index..=index, remaining);
+        Some(start..end)
+    }
+
+    /// Return the given range to the free list, merging it with its free neighbours.
+    pub(crate) fn free(&mut self, range: Range<vk::DeviceSize>) {
+        let index = self.ranges.partition_point(|free| free.start < range.start);
+        self.ranges.insert(index, range);
+
+        if index + 1 < self.ranges.len() && self.ranges[index].end == self.ranges[index + 1].start {
+            self.ranges[index].end = self.ranges.remove(index + 1).end;
+        }
+        if index > 0 && self.ranges[index - 1].end == self.ranges[index].start {
+            self.ranges[index - 1].end = self.ranges.remove(index).end;
+        }
+    }
+}
+
+/// A range of a pooled buffer reserved for a sub-buffer, returned to the free list of the
+/// buffer once the frames that may use it are complete.
+#[derive(Debug)]
+pub(crate) struct Suballocation {
+    device: Arc<VulkanDevice>,
+    free: Arc<Mutex<FreeList>>,
+    range: Range<vk::DeviceSize>,
+}
+
+impl Suballocation {
+    /// Returns the reserved range of the buffer, in bytes.
+    #[must_use]
+    pub(crate) fn range(&self) -> Range<vk::DeviceSize> {
+        self.range.clone()
+    }
+}
+
+impl Drop for Suballocation {
+    fn drop(&mut self) {
+        self.device.destroy_later(Deletion::Suballocation(
+            self.free.clone(),
+            self.range.clone(),
+        ));
+    }
+}
+
+/// Returns the minimum alignment required by the device for the offset of a range of a buffer
+/// with the given usage, bound to a descriptor or used as a vertex or an index buffer.
+pub(crate) fn offset_alignment(device: &VulkanDevice, usage: BufferUsage) -> vk::DeviceSize {
+    let limits = device.limits();
+    let alignment = match usage {
+        BufferUsage::Uniforms => limits.min_uniform_buffer_offset_alignment,
+        BufferUsage::Storage | BufferUsage::Indirect => limits.min_storage_buffer_offset_alignment,
+        BufferUsage::Unbounded => limits
+            .min_uniform_buffer_offset_alignment
+            .max(limits.min_storage_buffer_offset_alignment)
+            .max(limits.min_texel_buffer_offset_alignment),
+        BufferUsage::None | BufferUsage::Vertices | BufferUsage::Indices => 1,
+    };
+    alignment.max(1)
+}
+
+/// Returns the least common multiple of two non-zero numbers.
+pub(crate) const fn lcm(a: vk::DeviceSize, b: vk::DeviceSize) -> vk::DeviceSize {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn least_common_multiple() {
+        assert_eq!(lcm(4, 6), 12);
+        assert_eq!(lcm(256, 12), 768);
+        assert_eq!(lcm(64, 16), 64);
+        assert_eq!(lcm(1, 7), 7);
+    }
+
+    #[test]
+    fn allocate_aligned_ranges() {
+        let mut free = FreeList::new(1024);
+        assert_eq!(free.allocate(100, 64), Some(0..100));
+        assert_eq!(free.allocate(10, 64), Some(128..138));
+
+        // The padding before an aligned range stays free.
+        assert_eq!(free.ranges, [100..128, 138..1024]);
+        assert_eq!(free.allocate(28, 4), Some(100..128));
+        assert_eq!(free.ranges, [138..1024]);
+    }
+
+    #[test]
+    fn allocate_too_large_ranges() {
+        let mut free = FreeList::new(256);
+        assert_eq!(free.allocate(257, 1), None);
+        assert_eq!(free.allocate(200, 64), Some(0..200));
+        assert_eq!(free.allocate(50, 64), None);
+        assert_eq!(free.allocate(50, 8), Some(200..250));
+    }
+
+    #[test]
+    fn free_merges_the_neighbours() {
+        let mut free = FreeList::new(300);
+        let a = free.allocate(100, 1).unwrap();
+        let b = free.allocate(100, 1).unwrap();
+        let c = free.allocate(100, 1).unwrap();
+        assert!(free.ranges.is_empty());
+
+        free.free(a);
+        free.free(c);
+        assert_eq!(free.ranges, [0..100, 200..300]);
+
+        free.free(b);
+        assert_eq!(free.ranges, [0..300]);
+    }
+}
@@ -0,0 +1,351 @@
+//! Conformance checks of a physical device against a profile: a Vulkan version and a set of
+//! extensions, features and limits that an application relies on. A device that silently lacks
+//! one of them may render garbage or crash deep inside a driver, so the device is checked once
+//! when the renderer starts and a readable report lists everything that is missing.
+use crate::context::VulkanContext;
+use bevy::prelude::*;
+use std::{collections::HashSet, fmt};
+use vulkanalia::prelude::v1_3::*;
+
+/// Declare a requirement on a boolean feature of the given feature structure of
+/// [`DeviceCapabilities`].
+macro_rules! feature {
+    ($group:ident . $name:ident) => {
+        Requirement {
+            description: concat!(stringify!($group), ".", stringify!($name)),
+            check: |caps| {
+                if caps.$group.$name == vk::TRUE {
+                    Ok(())
+                } else {
+                    Err(String::from("not supported"))
+                }
+            },
+        }
+    };
+}
+
+/// Declare a requirement on a limit of the physical device, which must be at least or at most
+/// the given value.
+macro_rules! limit {
+    ($name:ident >= $value:expr) => {
+        limit!(@check $name, >=, $value)
+    };
+    ($name:ident <= $value:expr) => {
+        limit!(@check $name, <=, $value)
+    };
+    (@check $name:ident, $op:tt, $value:expr) => {
+        Requirement {
+            description: concat!(
+                "limits.", stringify!($name), " ", stringify!($op), " ", stringify!($value)
+            ),
+            check: |caps| {
+                let value = caps.properties.limits.$name;
+                if value $op $value {
+                    Ok(())
+                } else {
+                    Err(format!("found {value}"))
+                }
+            },
+        }
+    };
+}
+
+/// The capabilities of a physical device, as queried from the driver.
+#[derive(Debug, Clone)]
+pub struct DeviceCapabilities {
+    /// The properties and limits of the device.
+    pub properties: vk::PhysicalDeviceProperties,
+
+    /// The Vulkan 1.0 features of the device.
+    pub features: vk::PhysicalDeviceFeatures,
+
+    /// The Vulkan 1.1 features of the device.
+    pub vulkan11: vk::PhysicalDeviceVulkan11Features,
+
+    /// The Vulkan 1.2 features of the device.
+    pub vulkan12: vk::PhysicalDeviceVulkan12Features,
+
+    /// The Vulkan 1.3 features of the device.
+    pub vulkan13: vk::PhysicalDeviceVulkan13Features,
+
+    /// The extensions supported by the device.
+    pub extensions: HashSet<vk::ExtensionName>,
+}
+
+impl DeviceCapabilities {
+    /// Query the capabilities of the given physical device.
+    #[must_use]
+    pub fn query(context: &VulkanContext, physical: vk::PhysicalDevice) -> Self {
+        let instance = context.instance();
+        let properties = unsafe { instance.get_physical_device_properties(physical) };
+        let mut vulkan11 = vk::PhysicalDeviceVulkan11Features::default();
+        let mut vulkan12 = vk::PhysicalDeviceVulkan12Features::default();
+        let mut vulkan13 = vk::PhysicalDeviceVulkan13Features::default();
+        let mut features = vk::PhysicalDeviceFeatures2::builder()
+            .push_next(&mut vulkan11)
+            .push_next(&mut vulkan12);
+
+        // The Vulkan 1.3 features are left unsupported on older devices, whose drivers must not
+        // be given their structure.
+        if properties.api_version >= vk::make_version(1, 3, 0) {
+            features = features.push_next(&mut vulkan13);
+        }
+
+        let extensions = unsafe {
+            instance.get_physical_device_features2(physical, &mut features);
+            instance
+                .enumerate_device_extension_properties(physical, None)
+                .expect("Failed to enumerate device extensions")
+                .iter()
+                .map(|e| e.extension_name)
+                .collect::<HashSet<_>>()
+        };
+        let features = features.features;
+
+        // The structures are copied out of the chain, so their pointers to the next structure
+        // would dangle.
+        vulkan11.next = std::ptr::null_mut();
+        vulkan12.next = std::ptr::null_mut();
+        vulkan13.next = std::ptr::null_mut();
+
+        Self {
+            properties,
+            features,
+            vulkan11,
+            vulkan12,
+            vulkan13,
+            extensions,
+        }
+    }
+
+    /// Returns the name of the device, as reported by the driver.
+    #[must_use]
+    pub fn device_name(&self) -> String {
+        self.properties.device_name.to_string_lossy().into_owned()
+    }
+}
+
+/// A single requirement of a profile on a feature or a limit of the device.
+#[derive(Debug, Clone, Copy)]
+pub struct Requirement {
+    /// A short description of the requirement, shown in the report when it is not met.
+    pub description: &'static str,
+
+    /// Returns whether the device meets the requirement, or a description of what the device
+    /// supports instead.
+    pub check: fn(&DeviceCapabilities) -> Result<(), String>,
+}
+
+/// A profile: the Vulkan version, extensions, features and limits required by an application.
+#[derive(Debug, Clone)]
+pub struct DeviceProfile {
+    /// The name of the profile, shown in the report.
+    pub name: &'static str,
+
+    /// The minimum Vulkan version supported by the device, as `(major, minor)`.
+    pub api_version: (u32, u32),
+
+    /// The extensions that must be supported by the device.
+    pub extensions: Vec<vk::ExtensionName>,
+
+    /// The features and limits that the device must support.
+    pub requirements: Vec<Requirement>,
+}
+
+impl DeviceProfile {
+    /// The baseline required by Amethyst itself: Vulkan 1.2, since dynamic rendering falls back
+    /// to classic render passes, and the features and limits used by the built-in passes, like cube map arrays for the
+    /// reflection probes or 256 invocations per work group for the auto exposure.
+    #[must_use]
+    pub fn amethyst_baseline() -> Self {
+        Self {
+            name: "Amethyst baseline",
+            api_version: (1, 2),
+            extensions: vec![vk::KHR_SWAPCHAIN_EXTENSION.name],
+            requirements: vec![
+                feature!(vulkan12.timeline_semaphore),
+                feature!(features.image_cube_array),
+                limit!(max_push_constants_size >= 128),
+                limit!(max_compute_work_group_invocations >= 256),
+                limit!(max_bound_descriptor_sets >= 4),
+                limit!(max_image_dimension_2d >= 4096),
+            ],
+        }
+    }
+
+    /// The Khronos `VP_KHR_roadmap_2022` profile, describing the features expected from
+    /// mid-to-high-end devices of 2022. This only checks the features and limits that matter
+    /// to a renderer, not the whole profile.
+    #[must_use]
+    pub fn roadmap_2022() -> Self {
+        Self {
+            name: "VP_KHR_roadmap_2022",
+            api_version: (1, 3),
+            extensions: Vec::new(),
+            requirements: vec![
+                feature!(features.full_draw_index_uint32),
+                feature!(features.image_cube_array),
+                feature!(features.independent_blend),
+                feature!(features.sample_rate_shading),
+                feature!(features.draw_indirect_first_instance),
+                feature!(features.depth_clamp),
+                feature!(features.depth_bias_clamp),
+                feature!(features.sampler_anisotropy),
+                feature!(features.occlusion_query_precise),
+                feature!(features.fragment_stores_and_atomics),
+                feature!(features.shader_storage_image_extended_formats),
+                feature!(features.shader_uniform_buffer_array_dynamic_indexing),
+                feature!(features.shader_sampled_image_array_dynamic_indexing),
+                feature!(features.shader_storage_buffer_array_dynamic_indexing),
+                feature!(features.shader_storage_image_array_dynamic_indexing),
+                feature!(vulkan11.sampler_ycbcr_conversion),
+                feature!(vulkan12.sampler_mirror_clamp_to_edge),
+                feature!(vulkan12.descriptor_indexing),
+                feature!(vulkan12.descriptor_binding_partially_bound),
+                feature!(vulkan12.descriptor_binding_variable_descriptor_count),
+                feature!(vulkan12.runtime_descriptor_array),
+                feature!(vulkan12.scalar_block_layout),
+                feature!(vulkan13.robust_image_access),
+                feature!(vulkan13.shader_terminate_invocation),
+                feature!(vulkan13.subgroup_size_control),
+                feature!(vulkan13.compute_full_subgroups),
+                feature!(vulkan13.synchronization2),
+                feature!(vulkan13.shader_zero_initialize_workgroup_memory),
+                feature!(vulkan13.dynamic_rendering),
+                feature!(vulkan13.maintenance4),
+                limit!(max_image_dimension_1d >= 8192),
+                limit!(max_image_dimension_2d >= 8192),
+                limit!(max_image_dimension_cube >= 8192),
+                limit!(max_image_array_layers >= 2048),
+                limit!(max_uniform_buffer_range >= 65536),
+                limit!(buffer_image_granularity <= 4096),
+                limit!(max_per_stage_descriptor_samplers >= 64),
+                limit!(max_per_stage_descriptor_uniform_buffers >= 15),
+                limit!(max_per_stage_descriptor_storage_buffers >= 30),
+                limit!(max_per_stage_descriptor_sampled_images >= 200),
+                limit!(max_per_stage_descriptor_storage_images >= 16),
+                limit!(max_per_stage_resources >= 200),
+                limit!(max_descriptor_set_samplers >= 576),
+                limit!(max_descriptor_set_uniform_buffers >= 90),
+                limit!(max_descriptor_set_storage_buffers >= 96),
+                limit!(max_descriptor_set_sampled_images >= 1800),
+                limit!(max_descriptor_set_storage_images >= 144),
+                limit!(max_fragment_combined_output_resources >= 16),
+                limit!(max_compute_work_group_invocations >= 256),
+                limit!(sub_texel_precision_bits >= 8),
+                limit!(mipmap_precision_bits >= 6),
+                limit!(max_sampler_lod_bias >= 14.0),
+                limit!(max_color_attachments >= 7),
+            ],
+        }
+    }
+
+    /// Check the given capabilities against the profile.
+    #[must_use]
+    pub fn check(&self, capabilities: &DeviceCapabilities) -> ProfileReport {
+        let mut missing = Vec::new();
+
+        let version = capabilities.properties.api_version;
+        let found = (vk::version_major(version), vk::version_minor(version));
+        if found < self.api_version {
+            missing.push(format!(
+                "Vulkan {}.{} (found {}.{})",
+                self.api_version.0, self.api_version.1, found.0, found.1
+            ));
+        }
+
+        missing.extend(
+            self.extensions
+                .iter()
+                .filter(|e| !capabilities.extensions.contains(e))
+                .map(|e| format!("extension {e}")),
+        );
+
+        missing.extend(self.requirements.iter().filter_map(|requirement| {
+            (requirement.check)(capabilities)
+                .err()
+                .map(|found| format!("{} ({found})", requirement.description))
+        }));
+
+        ProfileReport {
+            profile: self.name,
+            device: capabilities.device_name(),
+            missing,
+        }
+    }
+
+    /// Query the capabilities of the given physical device and check them against the profile.
+    #[must_use]
+    pub fn check_device(
+        &self,
+        context: &VulkanContext,
+        physical: vk::PhysicalDevice,
+    ) -> ProfileReport {
+        self.check(&DeviceCapabilities::query(context, physical))
+    }
+}
+
+/// The result of the check of a device against a profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileReport {
+    /// The name of the profile.
+    pub profile: &'static str,
+
+    /// The name of the device.
+    pub device: String,
+
+    /// A description of each requirement that the device does not meet.
+    pub missing: Vec<String>,
+}
+
+impl ProfileReport {
+    /// Returns whether the device meets all the requirements of the profile.
+    #[must_use]
+    pub fn is_conformant(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+impl fmt::Display for ProfileReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_conformant() {
+            return write!(
+                f,
+                "{} conforms to the {} profile",
+                self.device, self.profile
+            );
+        }
+
+        write!(
+            f,
+            "{} does not conform to the {} profile, {} requirement(s) missing:",
+            self.device,
+            self.profile,
+            self.missing.len()
+        )?;
+        for missing in &self.missing {
+            write!(f, "\n  - {missing}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The profile that the device chosen by the renderer is checked against when it starts.
+#[derive(Debug, Clone, Resource)]
+pub struct RequiredProfile {
+    /// The profile required by the application.
+    pub profile: DeviceProfile,
+
+    /// Whether the application must stop when the device does not conform to the profile.
+    /// Otherwise, the report is only logged as an error.
+    pub abort: bool,
+}
+
+impl Default for RequiredProfile {
+    fn default() -> Self {
+        Self {
+            profile: DeviceProfile::amethyst_baseline(),
+            abort: false,
+        }
+    }
+}
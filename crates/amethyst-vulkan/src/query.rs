@@ -0,0 +1,316 @@
+//! Queries and GPU profiling. The time spent by the GPU on each part of a frame cannot be
+//! measured from the host, since the commands are executed long after they are recorded:
+//! timestamps are instead written by the GPU into a [`QueryPool`] while it executes the
+//! commands, and read back once the submission has completed.
+//!
+//! The [`GpuProfiler`] builds on timestamp queries to measure named scopes of a frame.
+use crate::{
+    command::{Capability, CommandBuffer, Recording},
+    deletion::Deletion,
+    device::VulkanDevice,
+};
+use std::{ops::Range, sync::Arc, time::Duration};
+use vulkanalia::prelude::v1_3::*;
+
+/// The maximum number of scopes measured by a [`GpuProfiler`] in a single frame.
+pub const MAX_PROFILER_SCOPES: u32 = 64;
+
+/// A pool of queries of the same type.
+#[derive(Debug)]
+pub struct QueryPool {
+    device: Arc<VulkanDevice>,
+    inner: vk::QueryPool,
+    kind: vk::QueryType,
+    count: u32,
+}
+
+impl QueryPool {
+    /// Creates a new pool of `count` queries of the given type.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, kind: vk::QueryType, count: u32) -> Self {
+        let info = vk::QueryPoolCreateInfo::builder()
+            .query_type(kind)
+            .query_count(count);
+        let inner = unsafe {
+            device
+                .logical()
+                .create_query_pool(&info, None)
+                .expect("Failed to create query pool")
+        };
+
+        Self {
+            device,
+            inner,
+            kind,
+            count,
+        }
+    }
+
+    /// Creates a new pool of `count` timestamp queries.
+    #[must_use]
+    pub fn timestamps(device: Arc<VulkanDevice>, count: u32) -> Self {
+        Self::new(device, vk::QueryType::TIMESTAMP, count)
+    }
+
+    /// Read the results of the given queries, or `None` if some of them are not available
+    /// yet. This does not wait for the queries to complete.
+    ///
+    /// # Panics
+    /// Panics if the range is out of the bounds of the pool.
+    #[must_use]
+    pub fn results(&self, queries: Range<u32>) -> Option<Vec<u64>> {
+        assert!(
+            queries.end <= self.count,
+            "Queries out of the bounds of the pool"
+        );
+
+        let mut results = vec![0u64; queries.len()];
+        let status = unsafe {
+            self.device
+                .logical()
+                .get_query_pool_results(
+                    self.inner,
+                    queries.start,
+                    queries.end - queries.start,
+                    bytemuck::cast_slice_mut(&mut results),
+                    std::mem::size_of::<u64>() as vk::DeviceSize,
+                    vk::QueryResultFlags::_64,
+                )
+                .expect("Failed to read the query results")
+        };
+
+        match status {
+            vk::SuccessCode::SUCCESS => Some(results),
+            vk::SuccessCode::NOT_READY => None,
+            _ => panic!("Unexpected query pool status: {:?}", status),
+        }
+    }
+
+    /// Return the type of the queries of the pool.
+    #[must_use]
+    pub const fn kind(&self) -> vk::QueryType {
+        self.kind
+    }
+
+    /// Return the number of queries in the pool.
+    #[must_use]
+    pub const fn count(&self) -> u32 {
+        self.count
+    }
+
+    /// Return the inner vulkan query pool.
+    #[must_use]
+    pub const fn inner(&self) -> vk::QueryPool {
+        self.inner
+    }
+}
+
+impl Drop for QueryPool {
+    fn drop(&mut self) {
+        self.device.destroy_later(Deletion::QueryPool(self.inner));
+    }
+}
+
+/// The time spent by the GPU in a scope of a frame.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuScopeTiming {
+    /// The name of the scope.
+    pub name: &'static str,
+
+    /// The number of scopes enclosing this one.
+    pub depth: u32,
+
+    /// The time between the start and the end of the scope on the GPU.
+    pub duration: Duration,
+}
+
+/// A scope recorded in the current frame, with the indices of its timestamp queries.
+#[derive(Debug)]
+struct Scope {
+    name: &'static str,
+    depth: u32,
+    start: Option<u32>,
+    end: Option<u32>,
+    open: bool,
+}
+
+/// Measures the time spent by the GPU in named scopes of a frame. The scopes are opened and
+/// closed while the commands of the frame are recorded, and their durations are resolved
+/// once the submission of the frame has completed:
+///
+/// ```ignore
+/// let recording = profiler.begin_frame(recording);
+/// let recording = profiler.begin_scope(recording, "shadows");
+/// // ... record the shadow pass ...
+/// let recording = profiler.end_scope(recording);
+/// // ... submit the commands and wait for them ...
+/// let timings = profiler.resolve();
+/// ```
+///
+/// Scopes can be nested, but must be closed in the reverse order they were opened. When the
+/// device does not support timestamps on its graphics and compute queues, nothing is
+/// recorded and no timings are resolved.
+#[derive(Debug)]
+pub struct GpuProfiler {
+    pool: Option<QueryPool>,
+    scopes: Vec<Scope>,
+    queries: u32,
+    period: f64,
+}
+
+impl GpuProfiler {
+    /// Creates a new profiler measuring at most [`MAX_PROFILER_SCOPES`] scopes per frame.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Self {
+        let limits = device.limits();
+        let period = f64::from(limits.timestamp_period);
+        let supported = limits.timestamp_compute_and_graphics == vk::TRUE;
+        if !supported {
+            log::warn!("The device does not support timestamps, GPU profiling is disabled");
+        }
+
+        Self {
+            pool: supported.then(|| QueryPool::timestamps(device, MAX_PROFILER_SCOPES * 2)),
+            scopes: Vec::new(),
+            queries: 0,
+            period,
+        }
+    }
+
+    /// Returns whether the device supports timestamps, and therefore whether the profiler
+    /// measures anything.
+    #[must_use]
+    pub const fn is_supported(&self) -> bool {
+        self.pool.is_some()
+    }
+
+    /// Start a new frame, discarding the scopes of the previous one. This must be recorded
+    /// outside of a render pass, before any scope of the frame.
+    #[must_use]
+    pub fn begin_frame<'pool, Q: Capability>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording, Q>,
+    ) -> CommandBuffer<'pool, Recording, Q> {
+        self.scopes.clear();
+        self.queries = 0;
+        match &self.pool {
+            Some(pool) => command.reset_query_pool(pool, 0..pool.count()),
+            None => command,
+        }
+    }
+
+    /// Open a scope with the given name. If the maximum number of scopes of the frame is
+    /// reached, the scope is not measured.
+    #[must_use]
+    pub fn begin_scope<'pool, Q: Capability>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording, Q>,
+        name: &'static str,
+    ) -> CommandBuffer<'pool, Recording, Q> {
+        if self.pool.is_none() {
+            return command;
+        }
+
+        let depth = self.scopes.iter().filter(|scope| scope.open).count() as u32;
+        let start = self.next_query();
+        self.scopes.push(Scope {
+            name,
+            depth,
+            start,
+            end: None,
+            open: true,
+        });
+
+        match start {
+            Some(start) => self.write(command, vk::PipelineStageFlags::TOP_OF_PIPE, start),
+            None => command,
+        }
+    }
+
+    /// Close the innermost open scope.
+    ///
+    /// # Panics
+    /// Panics if no scope is open.
+    #[must_use]
+    pub fn end_scope<'pool, Q: Capability>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording, Q>,
+    ) -> CommandBuffer<'pool, Recording, Q> {
+        if self.pool.is_none() {
+            return command;
+        }
+
+        let index = self
+            .scopes
+            .iter()
+            .rposition(|scope| scope.open)
+            .expect("No GPU profiler scope to end");
+
+        // A scope whose start was not measured is not measured at all.
+        let end = match self.scopes[index].start {
+            Some(_) => self.next_query(),
+            None => None,
+        };
+        self.scopes[index].end = end;
+        self.scopes[index].open = false;
+
+        match end {
+            Some(end) => self.write(command, vk::PipelineStageFlags::BOTTOM_OF_PIPE, end),
+            None => command,
+        }
+    }
+
+    /// Resolve the durations of the scopes of the frame, in the order they were opened. This
+    /// must be called once the commands of the frame have completed, and returns `None` if
+    /// the timestamps are not available.
+    #[must_use]
+    pub fn resolve(&self) -> Option<Vec<GpuScopeTiming>> {
+        let pool = self.pool.as_ref()?;
+        if self.queries == 0 {
+            return Some(Vec::new());
+        }
+
+        let timestamps = pool.results(0..self.queries)?;
+        let timings = self
+            .scopes
+            .iter()
+            .filter_map(|scope| {
+                let start = timestamps[scope.start? as usize];
+                let end = timestamps[scope.end? as usize];
+                let nanos = end.saturating_sub(start) as f64 * self.period;
+                Some(GpuScopeTiming {
+                    name: scope.name,
+                    depth: scope.depth,
+                    duration: Duration::from_nanos(nanos as u64),
+                })
+            })
+            .collect();
+        Some(timings)
+    }
+
+    /// Reserve the next timestamp query of the frame, or returns `None` if there are no
+    /// queries left.
+    fn next_query(&mut self) -> Option<u32> {
+        let pool = self.pool.as_ref()?;
+        if self.queries == pool.count() {
+            log::warn!("Too many GPU profiler scopes in a single frame");
+            return None;
+        }
+
+        self.queries += 1;
+        Some(self.queries - 1)
+    }
+
+    /// Write a timestamp into the given query of the pool.
+    fn write<'pool, Q: Capability>(
+        &self,
+        command: CommandBuffer<'pool, Recording, Q>,
+        stage: vk::PipelineStageFlags,
+        query: u32,
+    ) -> CommandBuffer<'pool, Recording, Q> {
+        match &self.pool {
+            Some(pool) => command.write_timestamp(stage, pool, query),
+            None => command,
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! Multi-threaded recording of command buffers. Command pools cannot be shared between
+//! threads, so recording the draws of a large scene from several threads requires one pool
+//! per thread. The [`ThreadedCommandRecorder`] owns one pool per worker, records a secondary
+//! command buffer on each worker from a slice of the work, and hands the buffers back so that
+//! a primary command buffer executes them with
+//! [`CommandBuffer::execute_commands`](crate::command::CommandBuffer::execute_commands).
+use crate::{
+    command::{CommandBuffer, CommandPool, InheritanceInfo, Recording},
+    device::VulkanDevice,
+};
+use bevy::tasks::{ComputeTaskPool, TaskPool};
+use std::{marker::PhantomData, sync::Arc};
+use vulkanalia::prelude::v1_3::*;
+
+/// Records secondary command buffers in parallel on the compute task pool, with one command
+/// pool per worker.
+///
+/// The command buffers of a recording are only reused once the next recording starts, so the
+/// GPU must have finished executing them by then, like a [`CommandPool`] that is reset.
+#[derive(Debug)]
+pub struct ThreadedCommandRecorder {
+    /// The command pool of each worker, allocated from the main queue family.
+    pools: Vec<CommandPool>,
+}
+
+impl ThreadedCommandRecorder {
+    /// Creates a new recorder with the given number of workers. Using more workers than
+    /// the number of threads of the compute task pool does not speed up the recording.
+    ///
+    /// # Panics
+    /// Panics if there are no workers.
+    #[must_use]
+    pub fn new(device: &Arc<VulkanDevice>, workers: usize) -> Self {
+        assert!(workers > 0, "A recorder must have at least one worker");
+        let pools = (0..workers)
+            .map(|_| {
+                CommandPool::new(
+                    device.clone(),
+                    device.queues_info().main_family(),
+                    vk::CommandPoolCreateFlags::TRANSIENT,
+                )
+            })
+            .collect();
+
+        Self { pools }
+    }
+
+    /// Returns the number of workers of the recorder.
+    #[must_use]
+    pub fn workers(&self) -> usize {
+        self.pools.len()
+    }
+
+    /// Split the items into one contiguous chunk per worker, and record each chunk in a
+    /// secondary command buffer executed inside the render pass described by `info`. The
+    /// secondary command buffers are returned in the order of the chunks, so executing them
+    /// in that order preserves the order of the items.
+    #[must_use]
+    pub fn record<T, F>(
+        &mut self,
+        info: &InheritanceInfo,
+        items: &[T],
+        record: F,
+    ) -> SecondaryCommands<'_>
+    where
+        T: Sync,
+        F: for<'pool> Fn(CommandBuffer<'pool, Recording>, &[T]) -> CommandBuffer<'pool, Recording>
+            + Sync,
+    {
+        if items.is_empty() {
+            return SecondaryCommands::default();
+        }
+
+        let chunk_size = items.len().div_ceil(self.pools.len());
+        let record = &record;
+        let handles = ComputeTaskPool::get_or_init(TaskPool::default).scope(|scope| {
+            for (pool, chunk) in self.pools.iter_mut().zip(items.chunks(chunk_size)) {
+                scope.spawn(async move {
+                    // The GPU has finished executing the command buffers of the previous
+                    // recording, since the caller waits for it before starting a new one.
+                    pool.reset(vk::CommandPoolResetFlags::empty());
+                    let command = CommandBuffer::secondary(pool).start_secondary_recording(info);
+                    record(command, chunk).stop_recording().inner()
+                });
+            }
+        });
+
+        SecondaryCommands {
+            handles,
+            recorder: PhantomData,
+        }
+    }
+}
+
+/// The secondary command buffers of a recording of a [`ThreadedCommandRecorder`]. They borrow
+/// the recorder, so that no new recording can reuse them while they are still used to record
+/// a primary command buffer.
+#[derive(Debug, Default)]
+pub struct SecondaryCommands<'recorder> {
+    handles: Vec<vk::CommandBuffer>,
+    recorder: PhantomData<&'recorder ThreadedCommandRecorder>,
+}
+
+impl SecondaryCommands<'_> {
+    /// Returns the vulkan command buffer objects, in the order they must be executed.
+    #[must_use]
+    pub fn handles(&self) -> &[vk::CommandBuffer] {
+        &self.handles
+    }
+}
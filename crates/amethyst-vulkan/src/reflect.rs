@@ -0,0 +1,217 @@
+//! Reflection of the SPIR-V bytecode of the shaders with `spirv-reflect`, to derive the
+//! descriptor set layouts, the push constant ranges and the vertex inputs of a pipeline from its
+//! shaders instead of declaring them by hand, where they can silently drift from the shaders.
+use crate::{descriptor::DescriptorBinding, shader::ShaderModule};
+use spirv_reflect::types::{ReflectDecorationFlags, ReflectDescriptorType, ReflectFormat};
+use vulkanalia::prelude::v1_3::*;
+
+/// The resources used by a single shader, as declared in its bytecode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderReflection {
+    /// The stage of the shader.
+    pub stage: vk::ShaderStageFlags,
+
+    /// The descriptor bindings used by the shader, with the number of their set.
+    pub bindings: Vec<(u32, DescriptorBinding)>,
+
+    /// The range of the push constant block used by the shader, if any.
+    pub push_constants: Option<vk::PushConstantRange>,
+
+    /// The location and format of each input of the shader, excluding the built-in ones.
+    pub inputs: Vec<(u32, vk::Format)>,
+}
+
+impl ShaderReflection {
+    /// Reflect the bytecode of the given shader.
+    ///
+    /// # Panics
+    /// Panics if the bytecode cannot be parsed, or if the shader uses a descriptor array or a
+    /// kind of descriptor that is not supported.
+    #[must_use]
+    pub fn new(shader: &ShaderModule) -> Self {
+        let stage = vk::ShaderStageFlags::from(shader.kind());
+        let module = spirv_reflect::ShaderModule::load_u32_data(shader.bytecode())
+            .expect("Failed to parse the SPIR-V bytecode");
+
+        let bindings = module
+            .enumerate_descriptor_bindings(None)
+            .expect("Failed to reflect the descriptor bindings")
+            .iter()
+            .map(|binding| {
+                assert!(
+                    binding.count <= 1,
+                    "Descriptor arrays are not supported by the reflection (set {}, binding {})",
+                    binding.set,
+                    binding.binding
+                );
+
+                let kind = descriptor_type(binding.descriptor_type);
+                (
+                    binding.set,
+                    DescriptorBinding {
+                        binding: binding.binding,
+                        kind,
+                        stages: stage,
+                    },
+                )
+            })
+            .collect();
+
+        // The offset and the size of the block are computed from its members, since a block
+        // may skip the bytes used by the blocks of the other stages.
+        let push_constants = module
+            .enumerate_push_constant_blocks(None)
+            .expect("Failed to reflect the push constant blocks")
+            .iter()
+            .filter_map(|block| {
+                let start = block.members.iter().map(|member| member.offset).min()?;
+                let end = block
+                    .members
+                    .iter()
+                    .map(|member| member.offset + member.size)
+                    .max()?;
+                Some((start, end))
+            })
+            .reduce(|(start, end), (other_start, other_end)| {
+                (start.min(other_start), end.max(other_end))
+            })
+            .map(|(start, end)| vk::PushConstantRange {
+                stage_flags: stage,
+                offset: start,
+                size: end - start,
+            });
+
+        let mut inputs = module
+            .enumerate_input_variables(None)
+            .expect("Failed to reflect the shader inputs")
+            .iter()
+            .filter(|input| {
+                !input
+                    .decoration_flags
+                    .contains(ReflectDecorationFlags::BUILT_IN)
+            })
+            .map(|input| (input.location, format(input.format)))
+            .collect::<Vec<_>>();
+        inputs.sort_unstable_by_key(|&(location, _)| location);
+
+        Self {
+            stage,
+            bindings,
+            push_constants,
+            inputs,
+        }
+    }
+}
+
+/// The resources used by all the shaders of a pipeline.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct PipelineReflection {
+    /// The bindings of each descriptor set, indexed by set number. A set that is not used by
+    /// any shader but precedes a used one has no bindings.
+    pub sets: Vec<Vec<DescriptorBinding>>,
+
+    /// The push constant range of each stage that uses push constants.
+    pub push_constant_ranges: Vec<vk::PushConstantRange>,
+
+    /// The location and format of each input of the vertex shader.
+    pub vertex_inputs: Vec<(u32, vk::Format)>,
+}
+
+impl PipelineReflection {
+    /// Reflect the given shaders and merge their resources. A binding used by several stages
+    /// is visible to all of them.
+    ///
+    /// # Panics
+    /// Panics if a shader cannot be reflected, or if two shaders declare different kinds of
+    /// descriptors for the same binding.
+    #[must_use]
+    pub fn new(shaders: &[ShaderModule]) -> Self {
+        let mut reflection = Self::default();
+
+        for shader in shaders.iter().map(ShaderReflection::new) {
+            for (set, binding) in shader.bindings {
+                let set = set as usize;
+                if reflection.sets.len() <= set {
+                    reflection.sets.resize_with(set + 1, Vec::new);
+                }
+
+                let bindings = &mut reflection.sets[set];
+                match bindings.iter_mut().find(|b| b.binding == binding.binding) {
+                    Some(existing) => {
+                        assert_eq!(
+                            existing.kind, binding.kind,
+                            "Set {set}, binding {} is declared with different kinds",
+                            binding.binding
+                        );
+                        existing.stages |= binding.stages;
+                    }
+                    None => bindings.push(binding),
+                }
+            }
+
+            reflection
+                .push_constant_ranges
+                .extend(shader.push_constants);
+            if shader.stage == vk::ShaderStageFlags::VERTEX {
+                reflection.vertex_inputs = shader.inputs;
+            }
+        }
+
+        for bindings in &mut reflection.sets {
+            bindings.sort_unstable_by_key(|binding| binding.binding);
+        }
+        reflection
+    }
+
+    /// Check the given vertex attributes against the inputs of the vertex shader, and returns
+    /// a description of each input that no attribute provides. The formats are not compared,
+    /// since a normalized or packed attribute can legitimately feed a floating point input.
+    #[must_use]
+    pub fn missing_vertex_inputs(
+        &self,
+        attributes: &[vk::VertexInputAttributeDescription],
+    ) -> Vec<String> {
+        self.vertex_inputs
+            .iter()
+            .filter(|&&(location, _)| !attributes.iter().any(|a| a.location == location))
+            .map(|(location, format)| format!("location {location} ({format:?})"))
+            .collect()
+    }
+}
+
+/// Convert a reflected descriptor type to its Vulkan equivalent.
+fn descriptor_type(kind: ReflectDescriptorType) -> vk::DescriptorType {
+    match kind {
+        ReflectDescriptorType::Sampler => vk::DescriptorType::SAMPLER,
+        ReflectDescriptorType::CombinedImageSampler => vk::DescriptorType::COMBINED_IMAGE_SAMPLER,
+        ReflectDescriptorType::SampledImage => vk::DescriptorType::SAMPLED_IMAGE,
+        ReflectDescriptorType::StorageImage => vk::DescriptorType::STORAGE_IMAGE,
+        ReflectDescriptorType::UniformTexelBuffer => vk::DescriptorType::UNIFORM_TEXEL_BUFFER,
+        ReflectDescriptorType::StorageTexelBuffer => vk::DescriptorType::STORAGE_TEXEL_BUFFER,
+        ReflectDescriptorType::UniformBuffer => vk::DescriptorType::UNIFORM_BUFFER,
+        ReflectDescriptorType::StorageBuffer => vk::DescriptorType::STORAGE_BUFFER,
+        ReflectDescriptorType::UniformBufferDynamic => vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC,
+        ReflectDescriptorType::StorageBufferDynamic => vk::DescriptorType::STORAGE_BUFFER_DYNAMIC,
+        ReflectDescriptorType::InputAttachment => vk::DescriptorType::INPUT_ATTACHMENT,
+        kind => panic!("Unsupported descriptor type {kind:?}"),
+    }
+}
+
+/// Convert a reflected input format to its Vulkan equivalent.
+fn format(format: ReflectFormat) -> vk::Format {
+    match format {
+        ReflectFormat::R32_UINT => vk::Format::R32_UINT,
+        ReflectFormat::R32_SINT => vk::Format::R32_SINT,
+        ReflectFormat::R32_SFLOAT => vk::Format::R32_SFLOAT,
+        ReflectFormat::R32G32_UINT => vk::Format::R32G32_UINT,
+        ReflectFormat::R32G32_SINT => vk::Format::R32G32_SINT,
+        ReflectFormat::R32G32_SFLOAT => vk::Format::R32G32_SFLOAT,
+        ReflectFormat::R32G32B32_UINT => vk::Format::R32G32B32_UINT,
+        ReflectFormat::R32G32B32_SINT => vk::Format::R32G32B32_SINT,
+        ReflectFormat::R32G32B32_SFLOAT => vk::Format::R32G32B32_SFLOAT,
+        ReflectFormat::R32G32B32A32_UINT => vk::Format::R32G32B32A32_UINT,
+        ReflectFormat::R32G32B32A32_SINT => vk::Format::R32G32B32A32_SINT,
+        ReflectFormat::R32G32B32A32_SFLOAT => vk::Format::R32G32B32A32_SFLOAT,
+        ReflectFormat::Undefined => vk::Format::UNDEFINED,
+    }
+}
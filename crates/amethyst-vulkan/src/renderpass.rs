@@ -0,0 +1,396 @@
+//! Classic render passes, used on devices without dynamic rendering. Dynamic rendering is only
+//! core since Vulkan 1.3, and many drivers of older or mobile GPUs still stop at Vulkan 1.2.
+//! On those devices, the graphics pipelines are created against a render pass instead of the
+//! formats of their attachments, and [`CommandBuffer::start_rendering`] begins a render pass
+//! with a framebuffer of the attachment views instead of a dynamic render pass instance.
+//!
+//! The choice is made automatically from [`VulkanDevice::supports_dynamic_rendering`], so the
+//! renderers record the same commands on both paths. The render passes only have a single
+//! subpass, whose attachments stay in the layout given in the [`RenderingInfo`] before, during
+//! and after the render pass, so the barriers recorded around the render pass are the same as
+//! with dynamic rendering.
+//!
+//! [`CommandBuffer::start_rendering`]: crate::command::CommandBuffer::start_rendering
+use crate::{
+    command::RenderingInfo,
+    deletion::Deletion,
+    device::VulkanDevice,
+    image::{format_aspects, has_stencil_component, SampleCount},
+};
+use std::sync::Arc;
+use vulkanalia::prelude::v1_3::*;
+
+/// The attachments of a render pass with a single subpass. Two render passes with the same
+/// formats and samples are compatible, so a pipeline created against one of them can be used
+/// in the other, whatever their load and store operations and layouts.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct RenderPassLayout {
+    /// The color attachments, in the order of the fragment shader outputs.
+    pub color_attachments: Vec<AttachmentLayout>,
+
+    /// The depth attachment, if any.
+    pub depth_attachment: Option<AttachmentLayout>,
+
+    /// The views rendered by the subpass with multiview, as a bit mask of the array layers of
+    /// the attachments, or 0 without multiview.
+    pub view_mask: u32,
+}
+
+impl RenderPassLayout {
+    /// Returns the layout of a render pass compatible with the pipelines rendering to
+    /// attachments of the given formats, used to create those pipelines. A depth format of
+    /// `vk::Format::UNDEFINED` means there is no depth attachment.
+    #[must_use]
+    pub fn compatible(
+        color_formats: &[vk::Format],
+        depth_format: vk::Format,
+        samples: SampleCount,
+    ) -> Self {
+        let attachment = |format, layout| AttachmentLayout {
+            format,
+            samples,
+            layout,
+            load_op: vk::AttachmentLoadOp::DONT_CARE,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            resolve: None,
+        };
+        Self {
+            color_attachments: color_formats
+                .iter()
+                .map(|&format| attachment(format, vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL))
+                .collect(),
+            depth_attachment: (depth_format != vk::Format::UNDEFINED).then(|| {
+                attachment(
+                    depth_format,
+                    vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+                )
+            }),
+            view_mask: 0,
+        }
+    }
+
+    /// Returns the same layout, rendering the views of the given mask with multiview.
+    #[must_use]
+    pub fn with_view_mask(self, view_mask: u32) -> Self {
+        Self { view_mask, ..self }
+    }
+
+    /// Returns the layout of the render pass equivalent to the given dynamic render pass.
+    ///
+    /// # Panics
+    /// Panics if a format is not given for each color attachment, or if the depth attachment
+    /// has no format.
+    #[must_use]
+    pub fn from_rendering(info: &RenderingInfo) -> Self {
+        assert_eq!(
+            info.color_formats.len(),
+            info.colors_attachements.len(),
+            "A format must be given for each color attachment"
+        );
+        assert!(
+            info.depth_attachment.is_none() || info.depth_format != vk::Format::UNDEFINED,
+            "A format must be given for the depth attachment"
+        );
+
+        let attachment = |attachment: &vk::RenderingAttachmentInfo, format| AttachmentLayout {
+            format,
+            samples: info.samples,
+            layout: attachment.image_layout,
+            load_op: attachment.load_op,
+            store_op: attachment.store_op,
+            resolve: is_resolved(attachment)
+                .then_some((attachment.resolve_mode, attachment.resolve_image_layout)),
+        };
+        Self {
+            color_attachments: info
+                .colors_attachements
+                .iter()
+                .zip(&info.color_formats)
+                .map(|(color, &format)| attachment(color, format))
+                .collect(),
+            depth_attachment: info
+                .depth_attachment
+                .as_ref()
+                .map(|depth| attachment(depth, info.depth_format)),
+            view_mask: info.view_mask,
+        }
+    }
+}
+
+/// An attachment of a [`RenderPassLayout`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AttachmentLayout {
+    /// The format of the attachment.
+    pub format: vk::Format,
+
+    /// The number of samples per pixel of the attachment.
+    pub samples: SampleCount,
+
+    /// The layout of the attachment before, during and after the render pass.
+    pub layout: vk::ImageLayout,
+
+    /// How the content of the attachment is initialized at the start of the render pass.
+    pub load_op: vk::AttachmentLoadOp,
+
+    /// Whether the content of the attachment is kept at the end of the render pass.
+    pub store_op: vk::AttachmentStoreOp,
+
+    /// The mode and the layout of the resolve of the attachment to a single-sampled image at
+    /// the end of the render pass, if it is resolved. Color attachments are always resolved
+    /// by averaging their samples, whatever the mode.
+    pub resolve: Option<(vk::ResolveModeFlags, vk::ImageLayout)>,
+}
+
+/// A render pass object, with a single subpass rendering to the attachments of its layout.
+#[derive(Debug)]
+pub struct RenderPass {
+    device: Arc<VulkanDevice>,
+    inner: vk::RenderPass,
+    layout: RenderPassLayout,
+}
+
+impl RenderPass {
+    /// Create a render pass with the given attachments. The render passes used by the
+    /// pipelines and by [`CommandBuffer::start_rendering`] are cached by the device instead,
+    /// see [`VulkanDevice::render_pass`].
+    ///
+    /// [`CommandBuffer::start_rendering`]: crate::command::CommandBuffer::start_rendering
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, layout: RenderPassLayout) -> Self {
+        let inner = create_render_pass(device.logical(), &layout);
+        Self {
+            device,
+            inner,
+            layout,
+        }
+    }
+
+    /// Returns the vulkan render pass object.
+    #[must_use]
+    pub const fn inner(&self) -> vk::RenderPass {
+        self.inner
+    }
+
+    /// Returns the attachments of the render pass.
+    #[must_use]
+    pub const fn layout(&self) -> &RenderPassLayout {
+        &self.layout
+    }
+}
+
+impl Drop for RenderPass {
+    fn drop(&mut self) {
+        self.device.destroy_later(Deletion::RenderPass(self.inner));
+    }
+}
+
+/// A framebuffer, binding image views to the attachments of a render pass.
+#[derive(Debug)]
+pub struct Framebuffer {
+    device: Arc<VulkanDevice>,
+    inner: vk::Framebuffer,
+}
+
+impl Framebuffer {
+    /// Create a framebuffer binding the given views to the attachments of a render pass, in
+    /// the order of its attachment descriptions: the color attachments, the resolve images of
+    /// the resolved color attachments, then the depth attachment and its resolve image. The
+    /// views must cover at least `layers` array layers.
+    #[must_use]
+    pub fn new(
+        device: Arc<VulkanDevice>,
+        render_pass: vk::RenderPass,
+        views: &[vk::ImageView],
+        extent: vk::Extent2D,
+        layers: u32,
+    ) -> Self {
+        let info = vk::FramebufferCreateInfo::builder()
+            .render_pass(render_pass)
+            .attachments(views)
+            .width(extent.width)
+            .height(extent.height)
+            .layers(layers);
+
+        let inner = unsafe {
+            device
+                .logical()
+                .create_framebuffer(&info, None)
+                .expect("Failed to create framebuffer")
+        };
+        Self { device, inner }
+    }
+
+    /// Returns the vulkan framebuffer object.
+    #[must_use]
+    pub const fn inner(&self) -> vk::Framebuffer {
+        self.inner
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        self.device.destroy_later(Deletion::Framebuffer(self.inner));
+    }
+}
+
+/// Returns the views and the clear values of the attachments of the render pass equivalent to
+/// the given dynamic render pass, in the order expected by [`Framebuffer::new`].
+pub(crate) fn attachments(info: &RenderingInfo) -> Vec<(vk::ImageView, vk::ClearValue)> {
+    let resolve = |attachment: &vk::RenderingAttachmentInfo| {
+        is_resolved(attachment)
+            .then_some((attachment.resolve_image_view, vk::ClearValue::default()))
+    };
+    let colors = info
+        .colors_attachements
+        .iter()
+        .map(|color| (color.image_view, color.clear_value));
+    let depth = info
+        .depth_attachment
+        .iter()
+        .flat_map(|depth| [Some((depth.image_view, depth.clear_value)), resolve(depth)])
+        .flatten();
+
+    colors
+        .chain(info.colors_attachements.iter().filter_map(resolve))
+        .chain(depth)
+        .collect()
+}
+
+/// Returns whether an attachment of a dynamic render pass is resolved.
+fn is_resolved(attachment: &vk::RenderingAttachmentInfo) -> bool {
+    attachment.resolve_mode != vk::ResolveModeFlags::NONE
+        && !attachment.resolve_image_view.is_null()
+}
+
+/// Returns the single-sampled attachment an attachment is resolved to, if it is resolved.
+fn resolved(attachment: &AttachmentLayout) -> Option<AttachmentLayout> {
+    attachment.resolve.map(|(_, layout)| AttachmentLayout {
+        samples: SampleCount::One,
+        layout,
+        load_op: vk::AttachmentLoadOp::DONT_CARE,
+        store_op: vk::AttachmentStoreOp::STORE,
+        resolve: None,
+        ..*attachment
+    })
+}
+
+/// Add the description of an attachment to a render pass, and returns a reference to it.
+fn describe(
+    descriptions: &mut Vec<vk::AttachmentDescription2>,
+    attachment: &AttachmentLayout,
+    aspect_mask: vk::ImageAspectFlags,
+) -> vk::AttachmentReference2 {
+    // The stencil aspect of a depth-stencil attachment is loaded and stored like its depth.
+    let (stencil_load_op, stencil_store_op) = if has_stencil_component(attachment.format) {
+        (attachment.load_op, attachment.store_op)
+    } else {
+        (
+            vk::AttachmentLoadOp::DONT_CARE,
+            vk::AttachmentStoreOp::DONT_CARE,
+        )
+    };
+    descriptions.push(
+        vk::AttachmentDescription2::builder()
+            .format(attachment.format)
+            .samples(attachment.samples.into())
+            .load_op(attachment.load_op)
+            .store_op(attachment.store_op)
+            .stencil_load_op(stencil_load_op)
+            .stencil_store_op(stencil_store_op)
+            .initial_layout(attachment.layout)
+            .final_layout(attachment.layout)
+            .build(),
+    );
+    vk::AttachmentReference2::builder()
+        .attachment(descriptions.len() as u32 - 1)
+        .layout(attachment.layout)
+        .aspect_mask(aspect_mask)
+        .build()
+}
+
+/// Create a render pass with a single subpass rendering to the attachments of the layout.
+pub(crate) fn create_render_pass(device: &Device, layout: &RenderPassLayout) -> vk::RenderPass {
+    // The attachments are described in the order of the views returned by `attachments`.
+    let mut descriptions = Vec::new();
+    let colors = layout
+        .color_attachments
+        .iter()
+        .map(|color| describe(&mut descriptions, color, vk::ImageAspectFlags::COLOR))
+        .collect::<Vec<_>>();
+
+    // The resolve attachments are given for all the color attachments, the ones that are not
+    // resolved being unused.
+    let resolves = layout
+        .color_attachments
+        .iter()
+        .map(|color| match resolved(color) {
+            Some(resolved) => describe(&mut descriptions, &resolved, vk::ImageAspectFlags::COLOR),
+            None => vk::AttachmentReference2::builder()
+                .attachment(vk::ATTACHMENT_UNUSED)
+                .build(),
+        })
+        .collect::<Vec<_>>();
+
+    let depth = layout.depth_attachment.map(|depth| {
+        let aspects = format_aspects(depth.format);
+        debug_assert!(
+            aspects.intersects(vk::ImageAspectFlags::DEPTH | vk::ImageAspectFlags::STENCIL),
+            "Invalid depth format"
+        );
+        let reference = describe(&mut descriptions, &depth, aspects);
+        let resolve = resolved(&depth).map(|resolved| {
+            let mode = depth
+                .resolve
+                .map_or(vk::ResolveModeFlags::NONE, |(mode, _)| mode);
+            let reference = describe(&mut descriptions, &resolved, aspects);
+            (mode, reference)
+        });
+        (reference, resolve)
+    });
+
+    let mut subpass = vk::SubpassDescription2::builder()
+        .pipeline_bind_point(vk::PipelineBindPoint::GRAPHICS)
+        .view_mask(layout.view_mask)
+        .color_attachments(&colors);
+    if layout
+        .color_attachments
+        .iter()
+        .any(|color| color.resolve.is_some())
+    {
+        subpass = subpass.resolve_attachments(&resolves);
+    }
+
+    // The depth attachment is resolved with the depth stencil resolve of Vulkan 1.2, since the
+    // original render passes can only resolve color attachments. Its stencil aspect, if any,
+    // is resolved with the same mode, like with dynamic rendering.
+    let mut depth_resolve = vk::SubpassDescriptionDepthStencilResolve::builder();
+    if let Some((reference, resolve)) = &depth {
+        subpass = subpass.depth_stencil_attachment(reference);
+        if let Some((mode, resolve)) = resolve {
+            let aspects = reference.aspect_mask;
+            let aspect_mode = |aspect| {
+                if aspects.contains(aspect) {
+                    *mode
+                } else {
+                    vk::ResolveModeFlags::NONE
+                }
+            };
+            depth_resolve = depth_resolve
+                .depth_resolve_mode(aspect_mode(vk::ImageAspectFlags::DEPTH))
+                .stencil_resolve_mode(aspect_mode(vk::ImageAspectFlags::STENCIL))
+                .depth_stencil_resolve_attachment(resolve);
+            subpass = subpass.push_next(&mut depth_resolve);
+        }
+    }
+
+    let subpasses = [subpass];
+    let info = vk::RenderPassCreateInfo2::builder()
+        .attachments(&descriptions)
+        .subpasses(&subpasses);
+
+    unsafe {
+        device
+            .create_render_pass2(&info, None)
+            .expect("Failed to create render pass")
+    }
+}
@@ -0,0 +1,220 @@
+//! Transient per-frame data. Uniforms and dynamic vertices change every frame, but rewriting a
+//! single buffer while the previous frames may still read it requires waiting for them, and
+//! `vkCmdUpdateBuffer` is limited to 64 KiB. A [`RingBuffer`] is a persistently mapped buffer
+//! from which each frame takes new slices, written directly by the host. The slices of a frame
+//! are reclaimed once the frame is marked as complete with [`VulkanDevice::end_frame`].
+use crate::{
+    buffer::{
+        Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo, SubBuffer,
+    },
+    device::VulkanDevice,
+    pool::{lcm, offset_alignment},
+};
+use std::{collections::VecDeque, sync::Arc};
+use vulkanalia::prelude::v1_3::*;
+
+/// A persistently mapped buffer in host visible memory, from which transient slices are
+/// allocated in a circular fashion. Each slice lives until the end of the frame during which
+/// it was allocated, so its memory is only reused once that frame is complete.
+///
+/// A uniform ring is usually bound with a single `vk::DescriptorType::UNIFORM_BUFFER_DYNAMIC`
+/// descriptor covering one slice, see
+/// [`DescriptorSet::write_buffer_range`](crate::descriptor::DescriptorSet::write_buffer_range),
+/// and each slice is then selected with the dynamic offset given when the set is bound.
+#[derive(Debug)]
+pub struct RingBuffer {
+    /// The device whose frames delimit the lifetime of the slices.
+    device: Arc<VulkanDevice>,
+
+    /// The mapped buffer from which the slices are allocated.
+    buffer: Arc<Buffer>,
+
+    /// The size of the ring, in bytes.
+    capacity: vk::DeviceSize,
+
+    /// The minimum alignment of the slices required by the device for the usage of the ring.
+    alignment: vk::DeviceSize,
+
+    /// The total number of bytes allocated since the creation of the ring. The position of the
+    /// next slice in the buffer is this value modulo the capacity.
+    head: u64,
+
+    /// The total number of bytes that are reclaimed. The bytes between the tail and the head
+    /// may still be used by frames that are not complete.
+    tail: u64,
+
+    /// The frame during which the slices are currently allocated.
+    frame: u64,
+
+    /// The previous frames that are not complete yet, with the head at their end.
+    in_flight: VecDeque<(u64, u64)>,
+}
+
+impl RingBuffer {
+    /// Create a new ring buffer of the given size, in bytes, whose slices have the given usage.
+    ///
+    /// # Panics
+    /// Panics if the capacity is zero.
+    #[must_use]
+    pub fn new(
+        allocator: Arc<BufferAllocator>,
+        usage: BufferUsage,
+        capacity: vk::DeviceSize,
+    ) -> Self {
+        assert!(
+            capacity > 0,
+            "The capacity of a ring buffer must not be zero"
+        );
+        let device = allocator.device().clone();
+        let alignment = offset_alignment(&device, usage);
+        let buffer = Buffer::new::<u8>(
+            allocator,
+            BufferCreateInfo {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Source,
+                    access: BufferAccess::Sequential,
+                    usage,
+                    ..Default::default()
+                },
+                alignment: alignment as usize,
+                data: BufferDataInfo::Uninitialized(capacity as usize),
+                name: Some("ring buffer"),
+            },
+        );
+
+        Self {
+            device,
+            buffer: Arc::new(buffer),
+            capacity,
+            alignment,
+            head: 0,
+            tail: 0,
+            frame: 0,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    /// Start allocating the slices of the given frame, as returned by
+    /// [`VulkanDevice::begin_frame`], and reclaim the slices of the frames that are complete.
+    pub fn begin_frame(&mut self, frame: u64) {
+        self.in_flight.push_back((self.frame, self.head));
+        self.frame = frame;
+
+        let completed = self.device.completed_frame();
+        while let Some(&(_, end)) = self
+            .in_flight
+            .front()
+            .filter(|&&(frame, _)| frame <= completed)
+        {
+            self.tail = end;
+            self.in_flight.pop_front();
+        }
+    }
+
+    /// Allocate a slice holding the given data in the current frame. The offset of the slice
+    /// is a multiple of the size of `T`, and of the alignment required by the device for the
+    /// usage of the ring.
+    ///
+    /// Returns `None` if the ring does not have enough free space, because the frames that
+    /// are still executing use too much of it. The capacity of the ring must be large enough
+    /// to hold the data of [`MAX_FRAMES_IN_FLIGHT`](crate::MAX_FRAMES_IN_FLIGHT) frames.
+    ///
+    /// # Panics
+    /// Panics if the data is empty.
+    #[must_use]
+    pub fn push<T: Copy>(&mut self, data: &[T]) -> Option<SubBuffer<T>> {
+        assert!(!data.is_empty(), "Cannot allocate an empty slice");
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        let alignment = lcm(
+            self.alignment
+                .max(std::mem::align_of::<T>() as vk::DeviceSize),
+            std::mem::size_of::<T>().max(1) as vk::DeviceSize,
+        );
+
+        let (head, offset) = place(self.head, self.tail, self.capacity, size, alignment)?;
+        self.head = head;
+
+        let slice = SubBuffer::new(self.buffer.clone(), offset, data.len());
+
+        // SAFETY: The slice does not overlap the slices of the frames that are not complete,
+        // so the GPU is not reading it.
+        unsafe { slice.write(data) };
+        Some(slice)
+    }
+
+    /// Returns the buffer from which the slices are allocated.
+    #[must_use]
+    pub const fn buffer(&self) -> &Arc<Buffer> {
+        &self.buffer
+    }
+
+    /// Returns the size of the ring, in bytes.
+    #[must_use]
+    pub const fn capacity(&self) -> vk::DeviceSize {
+        self.capacity
+    }
+
+    /// Returns the number of bytes used by the frames that are not complete, including the
+    /// current one.
+    #[must_use]
+    pub const fn used(&self) -> vk::DeviceSize {
+        self.head - self.tail
+    }
+}
+
+/// Place a slice of the given size and alignment after the head of a ring of the given capacity,
+/// and returns the new head and the offset of the slice in the buffer, or `None` if the slice
+/// would overwrite bytes after the tail that are not reclaimed yet.
+///
+/// A slice never wraps around the end of the buffer: if it does not fit before the end, it is
+/// placed at the start of the buffer instead, and the bytes skipped at the end are only
+/// reclaimed along with the slice.
+fn place(
+    head: u64,
+    tail: u64,
+    capacity: vk::DeviceSize,
+    size: vk::DeviceSize,
+    alignment: vk::DeviceSize,
+) -> Option<(u64, vk::DeviceSize)> {
+    let position = head % capacity;
+    let offset = position.next_multiple_of(alignment);
+    let (start, offset) = if offset + size <= capacity {
+        (head + (offset - position), offset)
+    } else {
+        (head + (capacity - position), 0)
+    };
+
+    let end = start + size;
+    (end - tail <= capacity).then_some((end, offset))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn place_aligned_slices() {
+        assert_eq!(place(0, 0, 256, 100, 64), Some((100, 0)));
+        assert_eq!(place(100, 0, 256, 20, 64), Some((148, 128)));
+        assert_eq!(place(148, 0, 256, 16, 1), Some((164, 148)));
+    }
+
+    #[test]
+    fn wrap_around_to_the_start() {
+        // The slice does not fit before the end, so the bytes up to the end are skipped.
+        assert_eq!(place(228, 200, 256, 100, 64), Some((356, 0)));
+
+        // The offsets keep wrapping on the following laps.
+        assert_eq!(place(356, 300, 256, 40, 64), Some((424, 128)));
+    }
+
+    #[test]
+    fn fail_to_overwrite_the_frames_in_flight() {
+        assert_eq!(place(228, 0, 256, 100, 64), None);
+        assert_eq!(place(228, 99, 256, 100, 64), None);
+        assert_eq!(place(228, 100, 256, 100, 64), Some((356, 0)));
+        assert_eq!(place(0, 0, 256, 257, 1), None);
+    }
+}
@@ -1,5 +1,9 @@
-use crate::device::VulkanDevice;
-use std::sync::Arc;
+use crate::{deletion::Deletion, device::VulkanDevice};
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 use vulkanalia::prelude::v1_3::*;
 
 /// A binary semaphore. It is a GPU-GPU synchronization primitive that can be
@@ -43,6 +47,109 @@ impl Drop for Semaphore {
     }
 }
 
+/// A timeline semaphore. Unlike a binary semaphore, it holds a monotonically
+/// increasing 64-bit value: queue operations and the host signal it by setting
+/// a new value, and wait until it reaches a given value. A single timeline
+/// semaphore can therefore replace a fence and a binary semaphore per frame in
+/// flight, and synchronize work between queues without a round trip through
+/// the host.
+#[derive(Debug)]
+pub struct TimelineSemaphore {
+    device: Arc<VulkanDevice>,
+    inner: vk::Semaphore,
+}
+
+impl TimelineSemaphore {
+    /// Creates a new timeline semaphore with the given initial value.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, initial_value: u64) -> Self {
+        let mut kind = vk::SemaphoreTypeCreateInfo::builder()
+            .semaphore_type(vk::SemaphoreType::TIMELINE)
+            .initial_value(initial_value);
+        let info = vk::SemaphoreCreateInfo::builder().push_next(&mut kind);
+        let inner = unsafe {
+            device
+                .logical()
+                .create_semaphore(&info, None)
+                .expect("Failed to create timeline semaphore")
+        };
+
+        Self { device, inner }
+    }
+
+    /// Return the current value of the semaphore.
+    #[must_use]
+    pub fn value(&self) -> u64 {
+        unsafe {
+            self.device
+                .logical()
+                .get_semaphore_counter_value(self.inner)
+                .expect("Failed to query timeline semaphore value")
+        }
+    }
+
+    /// Signal the semaphore from the host, setting its value to the given
+    /// one. The value must be greater than the current value of the semaphore
+    /// and than the value of any pending signal operation.
+    pub fn signal(&self, value: u64) {
+        let info = vk::SemaphoreSignalInfo::builder()
+            .semaphore(self.inner)
+            .value(value);
+        unsafe {
+            self.device
+                .logical()
+                .signal_semaphore(&info)
+                .expect("Failed to signal timeline semaphore");
+        }
+    }
+
+    /// Wait for the semaphore to reach the given value. This function will
+    /// block the current thread until the value is reached without a timeout.
+    pub fn wait(&self, value: u64) {
+        let reached = self.wait_timeout(value, Duration::MAX);
+        debug_assert!(reached);
+    }
+
+    /// Wait for the semaphore to reach the given value, for at most the given
+    /// duration. Returns `false` if the timeout expired before the value was
+    /// reached.
+    pub fn wait_timeout(&self, value: u64, timeout: Duration) -> bool {
+        let semaphores = [self.inner];
+        let values = [value];
+        let info = vk::SemaphoreWaitInfo::builder()
+            .semaphores(&semaphores)
+            .values(&values);
+        let timeout = u64::try_from(timeout.as_nanos()).unwrap_or(u64::MAX);
+
+        let status = unsafe {
+            self.device
+                .logical()
+                .wait_semaphores(&info, timeout)
+                .expect("Failed to wait for timeline semaphore")
+        };
+
+        match status {
+            vk::SuccessCode::SUCCESS => true,
+            vk::SuccessCode::TIMEOUT => false,
+            _ => panic!("Unexpected timeline semaphore status: {:?}", status),
+        }
+    }
+
+    /// Return the inner vulkan semaphore.
+    #[must_use]
+    pub const fn inner(&self) -> vk::Semaphore {
+        self.inner
+    }
+}
+
+impl Drop for TimelineSemaphore {
+    fn drop(&mut self) {
+        unsafe {
+            self.device.logical().destroy_semaphore(self.inner, None);
+        }
+    }
+}
+
 /// A fence is a CPU-GPU synchronization primitive that can be used to insert a
 /// dependency from a queue to the host.
 #[derive(Debug)]
@@ -124,3 +231,103 @@ pub enum FenceStatus {
     Unsignaled,
     Signaled,
 }
+
+/// An event is a fine-grained synchronization primitive between the commands of a single
+/// queue, or between the host and a queue. Unlike a pipeline barrier, the dependency is split
+/// in two: the event is set once the commands before it have completed some stages, and the
+/// commands after a wait on the event are blocked until it is set. The commands recorded
+/// between the set and the wait can therefore overlap with the work being synchronized,
+/// which is useful in long command buffers.
+#[derive(Debug)]
+pub struct Event {
+    device: Arc<VulkanDevice>,
+    inner: vk::Event,
+}
+
+impl Event {
+    /// Creates a new event, in the unsignaled state.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>) -> Self {
+        let info = vk::EventCreateInfo::builder();
+        let inner = unsafe {
+            device
+                .logical()
+                .create_event(&info, None)
+                .expect("Failed to create event")
+        };
+
+        Self { device, inner }
+    }
+
+    /// Returns whether the event is signaled.
+    #[must_use]
+    pub fn is_set(&self) -> bool {
+        let status = unsafe {
+            self.device
+                .logical()
+                .get_event_status(self.inner)
+                .expect("Failed to query event status")
+        };
+
+        match status {
+            vk::SuccessCode::EVENT_SET => true,
+            vk::SuccessCode::EVENT_RESET => false,
+            _ => panic!("Unexpected event status: {:?}", status),
+        }
+    }
+
+    /// Signal the event from the host. The commands waiting for the event must not have been
+    /// submitted before, otherwise the queue may wait for the host forever.
+    pub fn set(&self) {
+        unsafe {
+            self.device
+                .logical()
+                .set_event(self.inner)
+                .expect("Failed to set event");
+        }
+    }
+
+    /// Reset the event to the unsignaled state from the host. The event must not be used by
+    /// a pending command.
+    pub fn reset(&self) {
+        unsafe {
+            self.device
+                .logical()
+                .reset_event(self.inner)
+                .expect("Failed to reset event");
+        }
+    }
+
+    /// Wait on the host for the event to be signaled. Vulkan cannot block the host on an
+    /// event, so this polls its status, yielding the thread in between.
+    pub fn wait(&self) {
+        let signaled = self.wait_timeout(Duration::MAX);
+        debug_assert!(signaled);
+    }
+
+    /// Wait on the host for the event to be signaled, for at most the given duration. Returns
+    /// `false` if the timeout expired before the event was signaled.
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let start = Instant::now();
+        while !self.is_set() {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+            thread::yield_now();
+        }
+        true
+    }
+
+    /// Return the inner vulkan event.
+    #[must_use]
+    pub const fn inner(&self) -> vk::Event {
+        self.inner
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        // The event may still be set or waited on by the commands of the last frames.
+        self.device.destroy_later(Deletion::Event(self.inner));
+    }
+}
@@ -1,7 +1,24 @@
 use crate::device::VulkanDevice;
-use std::sync::Arc;
+use std::{borrow::Cow, sync::Arc};
+#[cfg(feature = "glsl")]
+use std::{fmt, path::PathBuf};
 use vulkanalia::prelude::v1_3::*;
 
+/// The magic number starting every SPIR-V module.
+const SPIRV_MAGIC: u32 = 0x0723_0203;
+
+/// The source of a shader module.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ShaderSource {
+    /// GLSL source code, compiled at runtime with shaderc. This requires the `glsl` feature.
+    #[cfg(feature = "glsl")]
+    Glsl(String),
+
+    /// Precompiled SPIR-V bytecode, for example embedded with `include_bytes!`. The bytecode
+    /// does not need to be aligned, and can be in either endianness.
+    Spirv(Cow<'static, [u8]>),
+}
+
 /// A shader module. Shader modules are just a thin wrapper around the shader bytecode
 /// and the functions defined in it. The compilation and linking of the SPIR-V bytecode
 /// to machine code for execution by the GPU doesn't happen until the graphics pipeline
@@ -14,24 +31,91 @@ pub struct ShaderModule {
     device: Arc<VulkanDevice>,
     inner: vk::ShaderModule,
     kind: ShaderType,
+    bytecode: Vec<u32>,
+    specialization: Vec<(u32, SpecializationConstant)>,
 }
 
 impl ShaderModule {
-    /// Compiles the given GLSL code into a shader module.
+    /// Create a shader module from the given source.
+    ///
+    /// # Panics
+    /// This method panics if the shader compilation fails, or if the SPIR-V bytecode is not
+    /// valid.
+    #[must_use]
+    pub fn new(device: Arc<VulkanDevice>, kind: ShaderType, source: ShaderSource) -> Self {
+        match source {
+            #[cfg(feature = "glsl")]
+            ShaderSource::Glsl(code) => Self::compile_glsl(device, kind, code),
+            ShaderSource::Spirv(bytes) => Self::from_spirv(device, kind, &bytes),
+        }
+    }
+
+    /// Create a shader module from precompiled SPIR-V bytecode.
+    ///
+    /// # Panics
+    /// This method panics if the bytecode is not a whole number of 32-bit words or does not
+    /// start with the SPIR-V magic number.
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn from_spirv(device: Arc<VulkanDevice>, kind: ShaderType, bytes: &[u8]) -> Self {
+        assert!(
+            bytes.len() % 4 == 0,
+            "SPIR-V bytecode must be a whole number of 32-bit words"
+        );
+
+        // The bytes may not be aligned to a word, so they are copied into words. The magic
+        // number tells the endianness of the module.
+        let mut words = bytes
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect::<Vec<_>>();
+        match words.first() {
+            Some(&SPIRV_MAGIC) => (),
+            Some(&magic) if magic.swap_bytes() == SPIRV_MAGIC => {
+                words.iter_mut().for_each(|word| *word = word.swap_bytes());
+            }
+            _ => panic!("Invalid SPIR-V magic number"),
+        }
+
+        Self::from_words(device, kind, &words)
+    }
+
+    /// Compiles the given GLSL code into a shader module, with the default compile options.
     ///
     /// # Panics
     /// This method panics if the shader compilation fails.
     #[must_use]
+    #[cfg(feature = "glsl")]
     pub fn compile_glsl(device: Arc<VulkanDevice>, kind: ShaderType, code: String) -> Self {
-        let options = shaderc::CompileOptions::new().unwrap();
+        Self::compile_glsl_with(device, kind, &code, &ShaderCompileOptions::default())
+    }
+
+    /// Compiles the given GLSL code into a shader module with the given compile options.
+    ///
+    /// # Panics
+    /// This method panics if the shader compilation fails, for example because an included
+    /// file cannot be found.
+    #[must_use]
+    #[cfg(feature = "glsl")]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn compile_glsl_with(
+        device: Arc<VulkanDevice>,
+        kind: ShaderType,
+        code: &str,
+        options: &ShaderCompileOptions,
+    ) -> Self {
         let compiler = shaderc::Compiler::new().expect("Failed to create shader compiler");
-        let provenance = "(no provenance)";
+        let name = options.source_name.as_deref().unwrap_or("(no provenance)");
 
         let artefact = compiler
-            .compile_into_spirv(&code, kind.into(), provenance, "main", Some(&options))
-            .expect("Failed to compile the shader");
+            .compile_into_spirv(code, kind.into(), name, "main", Some(&options.to_shaderc()))
+            .unwrap_or_else(|error| panic!("Failed to compile the shader {name}: {error}"));
 
-        let bytecode = artefact.as_binary();
+        Self::from_words(device, kind, artefact.as_binary())
+    }
+
+    /// Create a shader module from SPIR-V words in the native endianness.
+    fn from_words(device: Arc<VulkanDevice>, kind: ShaderType, bytecode: &[u32]) -> Self {
         let create_info = vk::ShaderModuleCreateInfo::builder()
             .code_size(bytecode.len() * 4)
             .code(bytecode)
@@ -48,7 +132,31 @@ impl ShaderModule {
             device,
             inner,
             kind,
+            bytecode: bytecode.to_vec(),
+            specialization: Vec::new(),
+        }
+    }
+
+    /// Set the value of the specialization constant with the given id, declared in GLSL with
+    /// `layout(constant_id = ...) const`. The value is baked into the pipelines created with
+    /// this module, which lets a single shader be compiled into several variants, for example
+    /// with a different number of lights or with a feature disabled, and lets the driver
+    /// optimize each variant as if the value was a literal. Constants that are not set keep
+    /// the default value given in the shader.
+    #[must_use]
+    pub fn specialize(mut self, id: u32, value: impl Into<SpecializationConstant>) -> Self {
+        let value = value.into();
+        match self.specialization.iter_mut().find(|(i, _)| *i == id) {
+            Some((_, constant)) => *constant = value,
+            None => self.specialization.push((id, value)),
         }
+        self
+    }
+
+    /// Returns the specialization constants set on the module, with their id.
+    #[must_use]
+    pub fn specialization(&self) -> &[(u32, SpecializationConstant)] {
+        &self.specialization
     }
 
     /// Returns the raw Vulkan handle of the shader module.
@@ -62,6 +170,215 @@ impl ShaderModule {
     pub fn kind(&self) -> ShaderType {
         self.kind
     }
+
+    /// Returns the SPIR-V bytecode of the shader, in the native endianness. The bytecode is
+    /// kept after the creation of the module so that it can be reflected when creating a
+    /// pipeline.
+    #[must_use]
+    pub fn bytecode(&self) -> &[u32] {
+        &self.bytecode
+    }
+}
+
+/// A callback resolving an `#include` directive, given the requested file and the name of the
+/// file containing the directive. It returns the resolved name of the file, used to resolve
+/// the relative includes it contains, and its content, or `None` if it cannot resolve it.
+#[cfg(feature = "glsl")]
+pub type IncludeCallback = Arc<dyn Fn(&str, &str) -> Option<(String, String)> + Send + Sync>;
+
+/// The options given to shaderc to compile GLSL code.
+#[cfg(feature = "glsl")]
+#[derive(Clone, Default)]
+pub struct ShaderCompileOptions {
+    /// The preprocessor macros defined before compiling the code, with their optional value,
+    /// like `#define NAME VALUE`.
+    pub defines: Vec<(String, Option<String>)>,
+
+    /// The optimization level of the SPIR-V bytecode. Without one, the code is not optimized,
+    /// which is fine since the driver optimizes the shaders anyway when creating pipelines.
+    pub optimization: Option<shaderc::OptimizationLevel>,
+
+    /// Whether to emit debug information in the bytecode, which lets graphics debuggers like
+    /// RenderDoc display and step through the GLSL source of the shaders.
+    pub debug_info: bool,
+
+    /// The version of SPIR-V to generate. Defaults to the version of the Vulkan version
+    /// targeted by shaderc, which is SPIR-V 1.0 for Vulkan 1.0.
+    pub spirv_version: Option<shaderc::SpirvVersion>,
+
+    /// The name of the compiled source, used in the error messages and to resolve the
+    /// relative includes of the source. This is usually its path.
+    pub source_name: Option<String>,
+
+    /// The directories in which the included files are searched. Files included with quotes
+    /// are first searched relative to the directory of the file including them.
+    pub include_paths: Vec<PathBuf>,
+
+    /// A callback resolving the included files, for example from files embedded in the
+    /// executable. It is tried before the include paths.
+    pub include_callback: Option<IncludeCallback>,
+}
+
+#[cfg(feature = "glsl")]
+impl ShaderCompileOptions {
+    /// Define a preprocessor macro with the given value.
+    #[must_use]
+    pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.defines.push((name.into(), Some(value.into())));
+        self
+    }
+
+    /// Convert the options into shaderc compile options.
+    fn to_shaderc(&self) -> shaderc::CompileOptions<'static> {
+        let mut options =
+            shaderc::CompileOptions::new().expect("Failed to create shader compile options");
+        for (name, value) in &self.defines {
+            options.add_macro_definition(name, value.as_deref());
+        }
+        if let Some(level) = self.optimization {
+            options.set_optimization_level(level);
+        }
+        if self.debug_info {
+            options.set_generate_debug_info();
+        }
+        if let Some(version) = self.spirv_version {
+            options.set_target_spirv(version);
+        }
+
+        let paths = self.include_paths.clone();
+        let callback = self.include_callback.clone();
+        options.set_include_callback(move |requested, kind, requesting, _depth| {
+            if let Some((resolved_name, content)) = callback
+                .as_ref()
+                .and_then(|resolve| resolve(requested, requesting))
+            {
+                return Ok(shaderc::ResolvedInclude {
+                    resolved_name,
+                    content,
+                });
+            }
+
+            let relative = PathBuf::from(requesting)
+                .parent()
+                .map(|parent| parent.join(requested))
+                .filter(|_| kind == shaderc::IncludeType::Relative);
+            relative
+                .into_iter()
+                .chain(paths.iter().map(|path| path.join(requested)))
+                .find_map(|path| {
+                    let content = std::fs::read_to_string(&path).ok()?;
+                    Some(shaderc::ResolvedInclude {
+                        resolved_name: path.to_string_lossy().into_owned(),
+                        content,
+                    })
+                })
+                .ok_or_else(|| format!("Cannot find included file {requested}"))
+        });
+        options
+    }
+}
+
+#[cfg(feature = "glsl")]
+impl fmt::Debug for ShaderCompileOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShaderCompileOptions")
+            .field("defines", &self.defines)
+            .field("optimization", &self.optimization)
+            .field("debug_info", &self.debug_info)
+            .field("spirv_version", &self.spirv_version)
+            .field("source_name", &self.source_name)
+            .field("include_paths", &self.include_paths)
+            .field("include_callback", &self.include_callback.is_some())
+            .finish()
+    }
+}
+
+/// The value of a specialization constant. All the supported types are 32 bits wide, and
+/// booleans are given to Vulkan as a `VkBool32`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpecializationConstant {
+    Bool(bool),
+    Int(i32),
+    UInt(u32),
+    Float(f32),
+}
+
+impl SpecializationConstant {
+    /// Returns the bytes of the constant, as read by the shader.
+    #[must_use]
+    pub fn to_ne_bytes(self) -> [u8; 4] {
+        match self {
+            Self::Bool(value) => u32::from(value).to_ne_bytes(),
+            Self::Int(value) => value.to_ne_bytes(),
+            Self::UInt(value) => value.to_ne_bytes(),
+            Self::Float(value) => value.to_ne_bytes(),
+        }
+    }
+}
+
+impl From<bool> for SpecializationConstant {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i32> for SpecializationConstant {
+    fn from(value: i32) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<u32> for SpecializationConstant {
+    fn from(value: u32) -> Self {
+        Self::UInt(value)
+    }
+}
+
+impl From<f32> for SpecializationConstant {
+    fn from(value: f32) -> Self {
+        Self::Float(value)
+    }
+}
+
+/// The map entries and the data of the specialization constants of a shader module, which
+/// must outlive the `vk::SpecializationInfo` pointing to them.
+#[derive(Debug, Default)]
+pub(crate) struct SpecializationData {
+    entries: Vec<vk::SpecializationMapEntry>,
+    data: Vec<u8>,
+}
+
+impl SpecializationData {
+    /// Pack the specialization constants of the given module.
+    pub fn new(shader: &ShaderModule) -> Self {
+        let entries = shader
+            .specialization
+            .iter()
+            .enumerate()
+            .map(|(index, &(id, _))| vk::SpecializationMapEntry {
+                constant_id: id,
+                offset: index as u32 * 4,
+                size: 4,
+            })
+            .collect();
+        let data = shader
+            .specialization
+            .iter()
+            .flat_map(|(_, value)| value.to_ne_bytes())
+            .collect();
+        Self { entries, data }
+    }
+
+    /// Returns the specialization info to give to the shader stage, or `None` if the module
+    /// has no specialization constants.
+    pub fn info(&self) -> Option<vk::SpecializationInfo> {
+        (!self.entries.is_empty()).then(|| {
+            vk::SpecializationInfo::builder()
+                .map_entries(&self.entries)
+                .data(&self.data)
+                .build()
+        })
+    }
 }
 
 impl Drop for ShaderModule {
@@ -80,25 +397,51 @@ pub enum ShaderType {
     Vertex,
     Fragment,
     Compute,
+
+    /// The tessellation control shader, which sets how finely each patch is subdivided. This
+    /// requires the tessellation shader feature of the device.
+    TessellationControl,
+
+    /// The tessellation evaluation shader, which computes the vertices generated by the
+    /// subdivision of the patches. This requires the tessellation shader feature of the device.
+    TessellationEvaluation,
+}
+
+impl From<ShaderType> for vk::ShaderStageFlags {
+    fn from(kind: ShaderType) -> Self {
+        match kind {
+            ShaderType::Fragment => Self::FRAGMENT,
+            ShaderType::Compute => Self::COMPUTE,
+            ShaderType::Vertex => Self::VERTEX,
+            ShaderType::TessellationControl => Self::TESSELLATION_CONTROL,
+            ShaderType::TessellationEvaluation => Self::TESSELLATION_EVALUATION,
+        }
+    }
 }
 
+#[cfg(feature = "glsl")]
 impl From<shaderc::ShaderKind> for ShaderType {
     fn from(kind: shaderc::ShaderKind) -> Self {
         match kind {
             shaderc::ShaderKind::Fragment => Self::Fragment,
             shaderc::ShaderKind::Compute => Self::Compute,
             shaderc::ShaderKind::Vertex => Self::Vertex,
+            shaderc::ShaderKind::TessControl => Self::TessellationControl,
+            shaderc::ShaderKind::TessEvaluation => Self::TessellationEvaluation,
             _ => panic!("Unsupported shader type"),
         }
     }
 }
 
+#[cfg(feature = "glsl")]
 impl From<ShaderType> for shaderc::ShaderKind {
     fn from(kind: ShaderType) -> Self {
         match kind {
             ShaderType::Fragment => Self::Fragment,
             ShaderType::Compute => Self::Compute,
             ShaderType::Vertex => Self::Vertex,
+            ShaderType::TessellationControl => Self::TessControl,
+            ShaderType::TessellationEvaluation => Self::TessEvaluation,
         }
     }
 }
@@ -1,4 +1,5 @@
-use crate::{context::VulkanContext, device::VulkanDevice, semaphore::Semaphore};
+use crate::{context::VulkanContext, device::VulkanDevice, hazard, semaphore::Semaphore};
+use bevy::{math::Mat4, prelude::Resource};
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
 use std::sync::Arc;
 use vk::{KhrSurfaceExtension, KhrSwapchainExtension};
@@ -56,12 +57,22 @@ pub struct VulkanSwapchain {
     /// Information about the swapchain, such as supported formats and present modes.
     support: VulkanSwapchainSupport,
 
+    /// The format and color space requested for the swapchain images.
+    preference: SurfaceFormatPreference,
+
     /// The format of the swapchain images.
     format: vk::Format,
 
+    /// The color space in which the swapchain images are presented.
+    color_space: vk::ColorSpaceKHR,
+
     /// The extent of the swapchain images.
     extent: vk::Extent2D,
 
+    /// The transform applied by the presentation engine to the images before displaying them,
+    /// which the rendering must compensate for.
+    transform: vk::SurfaceTransformFlagsKHR,
+
     /// The present mode of the swapchain.
     present_mode: vk::PresentModeKHR,
 
@@ -76,9 +87,61 @@ pub struct VulkanSwapchain {
 }
 
 impl VulkanSwapchain {
+    /// Create a swapchain presenting 8-bit sRGB images to the surface.
     #[must_use]
     pub fn new(context: Arc<VulkanContext>, device: Arc<VulkanDevice>, surface: Surface) -> Self {
+        Self::with_format_preference(context, device, surface, SurfaceFormatPreference::Srgb)
+    }
+
+    /// Create a swapchain presenting images with the preferred format and color space, for
+    /// example to drive a HDR display. If the surface does not support them, the swapchain
+    /// falls back to [`SurfaceFormatPreference::Srgb`], which is reflected by
+    /// [`VulkanSwapchain::color_space`].
+    #[must_use]
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn with_format_preference(
+        context: Arc<VulkanContext>,
+        device: Arc<VulkanDevice>,
+        surface: Surface,
+        preference: SurfaceFormatPreference,
+    ) -> Self {
         let support = VulkanSwapchainSupport::new(&context, &device, &surface);
+        let mut swapchain = Self {
+            device,
+            surface,
+            support,
+            preference,
+            format: vk::Format::UNDEFINED,
+            color_space: vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            extent: vk::Extent2D::default(),
+            transform: vk::SurfaceTransformFlagsKHR::IDENTITY,
+            present_mode: vk::PresentModeKHR::FIFO,
+            images: Vec::new(),
+            views: Vec::new(),
+            inner: vk::SwapchainKHR::null(),
+        };
+        swapchain.build();
+        swapchain
+    }
+
+    /// Recreate the swapchain for the current size and properties of the surface. This must be
+    /// called when the swapchain is out of date or suboptimal, usually after the window has
+    /// been resized or moved to another display.
+    ///
+    /// # Important
+    /// The images of the swapchain must not be in use by the GPU anymore, for example by
+    /// waiting for the device to be idle before calling this function.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn recreate(&mut self, context: &VulkanContext) {
+        self.support = VulkanSwapchainSupport::new(context, &self.device, &self.surface);
+        self.build();
+    }
+
+    /// Create the swapchain objects from the current surface support, replacing the previous
+    /// swapchain if there is one.
+    fn build(&mut self) {
+        let support = &self.support;
+        let device = &self.device;
 
         // Choose the swapchain present mode. By default, we use the FIFO present mode as it is
         // guaranteed to be supported by all devices that support the swapchain extension.
@@ -86,47 +149,44 @@ impl VulkanSwapchain {
 
         // Choose the swapchain extent. This is the resolution of the swapchain images. By default,
         // we use the current extent of the surface provided by the surface capabilities.
-        let extent = unsafe {
-            context
-                .instance()
-                .get_physical_device_surface_capabilities_khr(device.physical(), surface.inner())
-                .expect("Failed to get physical device surface capabilities")
-                .current_extent
+        //
+        // When the display is rotated, like on Android phones held in landscape, the images are
+        // pre-rotated by the application instead of being rotated by the compositor at each
+        // presentation. The images then keep the orientation of the display, so the extent of
+        // the surface is swapped for a rotation of 90 or 270 degrees, and the rendering rotates
+        // its clip space with `pre_rotation`.
+        let transform = support.capabilities().current_transform;
+        let current = support.capabilities().current_extent;
+        let extent = if is_quarter_turn(transform) {
+            vk::Extent2D {
+                width: current.height,
+                height: current.width,
+            }
+        } else {
+            current
         };
 
-        // Choose the swapchain format. By default, we use the B8G8R8A8_SRGB format as it is
-        // a common format that is supported by most devices with good color accuracy. If this
-        // format is not supported, we fallback to the first supported format.
-        let format = support
-            .formats()
-            .iter()
-            .find(|f| f.format == vk::Format::B8G8R8A8_SRGB)
-            .map(|f| f.format)
+        // Choose the swapchain format and color space from the preference. HDR and wide gamut
+        // color spaces are only supported by some displays, so the swapchain falls back to
+        // 8-bit sRGB, which is supported by almost every surface. If even sRGB is not
+        // supported, the first supported format is used.
+        let preferred = support.surface_format(self.preference);
+        if preferred.is_none() && self.inner.is_null() {
+            log::warn!(
+                "The surface does not support the {:?} format preference, falling back to sRGB",
+                self.preference
+            );
+        }
+        let vk::SurfaceFormatKHR {
+            format,
+            color_space,
+        } = preferred
+            .or_else(|| support.surface_format(SurfaceFormatPreference::Srgb))
             .unwrap_or_else(|| {
-                support
+                *support
                     .formats()
                     .first()
                     .expect("No supported formats found")
-                    .format
-            });
-
-        // Choose the swapchain color space. By default, we use the SRGB_NONLINEAR color space as
-        // it is a common color space that is supported by most devices with good color accuracy.
-        // If this color space is not supported with the chosen format, we fallback to the first
-        // supported color space that is compatible with the chosen format.
-        let color_space = support
-            .formats()
-            .iter()
-            .filter(|f| f.format == format)
-            .find(|f| f.color_space == vk::ColorSpaceKHR::SRGB_NONLINEAR)
-            .map(|f| f.color_space)
-            .unwrap_or_else(|| {
-                support
-                    .formats()
-                    .iter()
-                    .find(|f| f.format == format)
-                    .expect("No supported formats found")
-                    .color_space
             });
 
         // Get the queue family that are allowed to present to the surface.
@@ -146,10 +206,14 @@ impl VulkanSwapchain {
             vk::SharingMode::EXCLUSIVE
         };
 
+        // The images can also be copied from when the surface allows it, to take screenshots.
+        let usage = vk::ImageUsageFlags::COLOR_ATTACHMENT
+            | (support.capabilities().supported_usage_flags & vk::ImageUsageFlags::TRANSFER_SRC);
+
         // Build the swapchain create info.
         let swapchain_create_info = vk::SwapchainCreateInfoKHR::builder()
-            .image_usage(vk::ImageUsageFlags::COLOR_ATTACHMENT)
-            .pre_transform(support.capabilities().current_transform)
+            .image_usage(usage)
+            .pre_transform(transform)
             .composite_alpha(vk::CompositeAlphaFlagsKHR::OPAQUE)
             .queue_family_indices(&queue_family_indices)
             .min_image_count(support.clamp_image_count(2))
@@ -159,7 +223,8 @@ impl VulkanSwapchain {
             .image_extent(extent)
             .image_array_layers(1)
             .present_mode(present_mode)
-            .surface(surface.inner())
+            .surface(self.surface.inner())
+            .old_swapchain(self.inner)
             .clipped(true);
 
         // Create the swapchain.
@@ -209,60 +274,70 @@ impl VulkanSwapchain {
                     .format(format)
                     .image(image);
 
-                unsafe {
+                let view = unsafe {
                     device
                         .logical()
                         .create_image_view(&view_create_info, None)
                         .expect("Failed to create image view")
-                }
+                };
+                hazard::register_view(view, image);
+                view
             })
             .collect();
 
-        Self {
-            device,
-            surface,
-            support,
-            format,
-            extent,
-            present_mode,
-            images,
-            views,
-            inner: swapchain,
-        }
-    }
-
-    /// Acquire an image from the swapchain, and return its image index. The
-    /// index can be used to retrieve the image/image view from the swapchain
-    /// images/images views using the `images()` method.
-    /// If no image is available, this function will block indefinitely until
-    /// an image is available.
-    #[must_use]
-    pub fn acquire_next_image_index(&self, semaphore: &Semaphore) -> u32 {
+        // Destroy the previous swapchain and its views, now that the new swapchain has been
+        // created from it.
         unsafe {
-            self.device
-                .logical()
-                .acquire_next_image_khr(
-                    self.inner,
-                    std::u64::MAX,
-                    semaphore.inner(),
-                    vk::Fence::null(),
-                )
-                .expect("Failed to acquire next image")
-                .0
+            for view in self.views.drain(..) {
+                hazard::unregister_view(view);
+                device.logical().destroy_image_view(view, None);
+            }
+            device.logical().destroy_swapchain_khr(self.inner, None);
         }
+
+        self.format = format;
+        self.color_space = color_space;
+        self.extent = extent;
+        self.transform = transform;
+        self.present_mode = present_mode;
+        self.images = images;
+        self.views = views;
+        self.inner = swapchain;
     }
 
-    /// Acquire an image from the swapchain, and return the image, its image view, and
-    /// its index. The image and image view can be used to render to the image, and the
-    /// index can be used to present the image to the surface.
+    /// Acquire an image from the swapchain. The image can be rendered to, and its index is
+    /// used to present it to the surface. If the swapchain is out of date, no image is acquired
+    /// and the swapchain must be recreated with [`VulkanSwapchain::recreate`].
     /// If no image is available, this function will block indefinitely until an image
     /// is available.
+    ///
+    /// # Panics
+    /// Panics if acquiring the image fails for another reason, like a lost device.
     #[must_use]
-    pub fn acquire_next_image(&self, semaphore: &Semaphore) -> (u32, vk::Image, vk::ImageView) {
-        let index = self.acquire_next_image_index(semaphore);
-        let image = self.images[index as usize];
-        let view = self.views[index as usize];
-        (index, image, view)
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn acquire_next_image(&self, semaphore: &Semaphore) -> AcquireResult {
+        let result = unsafe {
+            self.device.logical().acquire_next_image_khr(
+                self.inner,
+                u64::MAX,
+                semaphore.inner(),
+                vk::Fence::null(),
+            )
+        };
+
+        let image = |index: u32| AcquiredImage {
+            index,
+            image: self.images[index as usize],
+            view: self.views[index as usize],
+        };
+
+        match result {
+            Ok((index, vk::SuccessCode::SUBOPTIMAL_KHR)) => AcquireResult::Suboptimal(image(index)),
+            Ok((index, _)) => AcquireResult::Optimal(image(index)),
+            Err(vk::ErrorCode::OUT_OF_DATE_KHR) => AcquireResult::OutOfDate,
+            Err(vk::ErrorCode::SURFACE_LOST_KHR) => AcquireResult::SurfaceLost,
+            Err(error) => panic!("Failed to acquire next image: {error}"),
+        }
     }
 
     /// Present an image to the surface. The image is identified by its index
@@ -274,7 +349,17 @@ impl VulkanSwapchain {
     /// queue. The actual presentation may not have been completed yet. To ensure that
     /// the presentation is completed, you can use a fence or a semaphore to wait for
     /// the presentation to be completed.
-    pub fn present_image(&self, queue: vk::Queue, image_index: u32, wait: &Semaphore) {
+    ///
+    /// # Panics
+    /// Panics if the presentation fails for another reason than an out of date swapchain or a
+    /// lost surface, like a lost device.
+    #[cfg_attr(feature = "trace", tracing::instrument(skip_all))]
+    pub fn present_image(
+        &self,
+        queue: vk::Queue,
+        image_index: u32,
+        wait: &Semaphore,
+    ) -> PresentResult {
         let wait_semaphores = [wait.inner()];
         let image_indices = [image_index];
         let swapchains = [self.inner];
@@ -284,11 +369,18 @@ impl VulkanSwapchain {
             .image_indices(&image_indices)
             .swapchains(&swapchains);
 
-        unsafe {
+        let result = unsafe {
             self.device
                 .logical()
                 .queue_present_khr(queue, &present_info)
-                .expect("Failed to present image");
+        };
+
+        match result {
+            Ok(vk::SuccessCode::SUBOPTIMAL_KHR) => PresentResult::Suboptimal,
+            Ok(_) => PresentResult::Presented,
+            Err(vk::ErrorCode::OUT_OF_DATE_KHR) => PresentResult::OutOfDate,
+            Err(vk::ErrorCode::SURFACE_LOST_KHR) => PresentResult::SurfaceLost,
+            Err(error) => panic!("Failed to present image: {error}"),
         }
     }
 
@@ -310,6 +402,88 @@ impl VulkanSwapchain {
         self.extent
     }
 
+    /// Returns the extent of the swapchain images as displayed, which is the extent of the
+    /// images with the width and the height swapped when they are pre-rotated by a quarter
+    /// turn. This is the extent to compare with the size of the window, and from which the
+    /// aspect ratio of the projection is computed.
+    #[must_use]
+    pub const fn window_extent(&self) -> vk::Extent2D {
+        if is_quarter_turn(self.transform) {
+            vk::Extent2D {
+                width: self.extent.height,
+                height: self.extent.width,
+            }
+        } else {
+            self.extent
+        }
+    }
+
+    /// Returns the transform applied by the presentation engine to the images before
+    /// displaying them.
+    #[must_use]
+    pub const fn transform(&self) -> vk::SurfaceTransformFlagsKHR {
+        self.transform
+    }
+
+    /// Returns the rotation to apply to the clip space coordinates so that the images are
+    /// displayed upright once the presentation engine has applied the transform of the
+    /// swapchain. The projection matrices must be premultiplied by it, and it is the identity
+    /// when the display is not rotated.
+    #[must_use]
+    pub fn pre_rotation(&self) -> Mat4 {
+        let degrees: f32 = if self.transform == vk::SurfaceTransformFlagsKHR::ROTATE_90 {
+            90.0
+        } else if self.transform == vk::SurfaceTransformFlagsKHR::ROTATE_180 {
+            180.0
+        } else if self.transform == vk::SurfaceTransformFlagsKHR::ROTATE_270 {
+            270.0
+        } else {
+            0.0
+        };
+        Mat4::from_rotation_z(degrees.to_radians())
+    }
+
+    /// Returns the color space in which the swapchain images are presented. The renderer can
+    /// use it to choose how to tonemap and encode the final image, see
+    /// [`SurfaceFormatPreference::from_color_space`].
+    #[must_use]
+    pub const fn color_space(&self) -> vk::ColorSpaceKHR {
+        self.color_space
+    }
+
+    /// Returns the format and color space requested when the swapchain was created. The
+    /// swapchain may have fallen back to sRGB if the surface does not support them.
+    #[must_use]
+    pub const fn format_preference(&self) -> SurfaceFormatPreference {
+        self.preference
+    }
+
+    /// Returns whether the swapchain images are presented in a HDR color space, in which
+    /// case the rendering must not be tonemapped to the standard dynamic range.
+    #[must_use]
+    pub fn is_hdr(&self) -> bool {
+        SurfaceFormatPreference::from_color_space(self.color_space)
+            .is_some_and(SurfaceFormatPreference::is_hdr)
+    }
+
+    /// Returns whether the swapchain images can be copied to a buffer, which is not supported
+    /// by every surface.
+    #[must_use]
+    pub fn supports_readback(&self) -> bool {
+        self.support
+            .capabilities()
+            .supported_usage_flags
+            .contains(vk::ImageUsageFlags::TRANSFER_SRC)
+    }
+
+    /// Returns whether the swapchain images have a null width or height. This happens when
+    /// the swapchain was created while the window was minimized, and such a swapchain cannot
+    /// be used for rendering.
+    #[must_use]
+    pub const fn is_zero_sized(&self) -> bool {
+        self.extent.width == 0 || self.extent.height == 0
+    }
+
     /// Returns the present mode of the swapchain.
     #[must_use]
     pub const fn present_mode(&self) -> vk::PresentModeKHR {
@@ -338,6 +512,7 @@ impl Drop for VulkanSwapchain {
     fn drop(&mut self) {
         unsafe {
             for view in self.views.drain(..) {
+                hazard::unregister_view(view);
                 self.device.logical().destroy_image_view(view, None);
             }
 
@@ -348,6 +523,151 @@ impl Drop for VulkanSwapchain {
     }
 }
 
+/// An image acquired from the swapchain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AcquiredImage {
+    /// The index of the image in the swapchain, used to present it.
+    pub index: u32,
+
+    /// The swapchain image.
+    pub image: vk::Image,
+
+    /// The view of the swapchain image.
+    pub view: vk::ImageView,
+}
+
+/// The result of [`VulkanSwapchain::acquire_next_image`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AcquireResult {
+    /// An image has been acquired, and the swapchain matches the surface.
+    Optimal(AcquiredImage),
+
+    /// An image has been acquired and can be rendered to and presented, but the swapchain does
+    /// not match the surface exactly anymore. It should be recreated after the frame.
+    Suboptimal(AcquiredImage),
+
+    /// The swapchain is not compatible with the surface anymore and no image has been
+    /// acquired. The swapchain must be recreated before rendering.
+    OutOfDate,
+
+    /// The surface has been destroyed by the system, for example when an Android application
+    /// is sent to the background, and no image has been acquired. The swapchain and the
+    /// surface must both be recreated from the current window handle.
+    SurfaceLost,
+}
+
+impl AcquireResult {
+    /// Returns the acquired image, if any.
+    #[must_use]
+    pub const fn image(&self) -> Option<AcquiredImage> {
+        match self {
+            AcquireResult::Optimal(image) | AcquireResult::Suboptimal(image) => Some(*image),
+            AcquireResult::OutOfDate | AcquireResult::SurfaceLost => None,
+        }
+    }
+
+    /// Returns whether the swapchain should be recreated.
+    #[must_use]
+    pub const fn needs_recreation(&self) -> bool {
+        !matches!(self, AcquireResult::Optimal(_))
+    }
+}
+
+/// The result of [`VulkanSwapchain::present_image`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresentResult {
+    /// The image has been presented, and the swapchain matches the surface.
+    Presented,
+
+    /// The image has been presented, but the swapchain does not match the surface exactly
+    /// anymore. It should be recreated before the next frame.
+    Suboptimal,
+
+    /// The swapchain is not compatible with the surface anymore and must be recreated before
+    /// the next frame.
+    OutOfDate,
+
+    /// The surface has been destroyed by the system. The swapchain and the surface must both
+    /// be recreated from the current window handle.
+    SurfaceLost,
+}
+
+impl PresentResult {
+    /// Returns whether the swapchain should be recreated.
+    #[must_use]
+    pub const fn needs_recreation(&self) -> bool {
+        !matches!(self, PresentResult::Presented)
+    }
+}
+
+/// The format and the color space in which the swapchain images are presented. Anything else
+/// than sRGB requires a display supporting it, and the swapchain color space extension.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Resource)]
+pub enum SurfaceFormatPreference {
+    /// 8-bit images in the sRGB color space, supported by almost every display.
+    #[default]
+    Srgb,
+
+    /// 16-bit floating point images in the extended sRGB color space with a linear transfer
+    /// function, where 1.0 is the white of a SDR display at 80 nits and values above are
+    /// brighter. This is the usual HDR format on Windows.
+    ScRgb,
+
+    /// 10-bit images in the BT.2020 color space with the PQ transfer function of HDR10, which
+    /// the shaders must encode themselves.
+    Hdr10,
+
+    /// 8 or 10-bit images in the Display P3 color space with the sRGB transfer function, used
+    /// by the wide gamut displays of Apple devices.
+    DisplayP3,
+}
+
+impl SurfaceFormatPreference {
+    /// Returns the color space of the preference.
+    #[must_use]
+    pub const fn color_space(self) -> vk::ColorSpaceKHR {
+        match self {
+            Self::Srgb => vk::ColorSpaceKHR::SRGB_NONLINEAR,
+            Self::ScRgb => vk::ColorSpaceKHR::EXTENDED_SRGB_LINEAR_EXT,
+            Self::Hdr10 => vk::ColorSpaceKHR::HDR10_ST2084_EXT,
+            Self::DisplayP3 => vk::ColorSpaceKHR::DISPLAY_P3_NONLINEAR_EXT,
+        }
+    }
+
+    /// Returns the formats that can be used with the preference, from the most to the least
+    /// preferred one.
+    #[must_use]
+    pub const fn formats(self) -> &'static [vk::Format] {
+        match self {
+            Self::Srgb => &[vk::Format::B8G8R8A8_SRGB, vk::Format::R8G8B8A8_SRGB],
+            Self::ScRgb => &[vk::Format::R16G16B16A16_SFLOAT],
+            Self::Hdr10 => &[
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::Format::A2R10G10B10_UNORM_PACK32,
+            ],
+            Self::DisplayP3 => &[
+                vk::Format::A2B10G10R10_UNORM_PACK32,
+                vk::Format::B8G8R8A8_SRGB,
+                vk::Format::R8G8B8A8_SRGB,
+            ],
+        }
+    }
+
+    /// Returns the preference presenting in the given color space, if any.
+    #[must_use]
+    pub fn from_color_space(color_space: vk::ColorSpaceKHR) -> Option<Self> {
+        [Self::Srgb, Self::ScRgb, Self::Hdr10, Self::DisplayP3]
+            .into_iter()
+            .find(|preference| preference.color_space() == color_space)
+    }
+
+    /// Returns whether the preference presents high dynamic range images.
+    #[must_use]
+    pub const fn is_hdr(self) -> bool {
+        matches!(self, Self::ScRgb | Self::Hdr10)
+    }
+}
+
 /// Information about the supported formats, present modes, and capabilities of a Vulkan swapchain.
 /// This information can be used to create a swapchain with the best possible settings that are
 /// supported by the device.
@@ -402,6 +722,34 @@ impl VulkanSwapchainSupport {
         self.formats.iter().any(|f| f.format == format)
     }
 
+    /// Returns the first supported surface format matching the given preference, or `None`
+    /// if the surface does not support the preference at all.
+    #[must_use]
+    pub fn surface_format(
+        &self,
+        preference: SurfaceFormatPreference,
+    ) -> Option<vk::SurfaceFormatKHR> {
+        preference
+            .formats()
+            .iter()
+            .map(|&format| vk::SurfaceFormatKHR {
+                format,
+                color_space: preference.color_space(),
+            })
+            .find(|candidate| {
+                self.formats
+                    .iter()
+                    .any(|f| f.format == candidate.format && f.color_space == candidate.color_space)
+            })
+    }
+
+    /// Returns whether the surface supports the given format preference, for example to only
+    /// offer a HDR option in the settings of a game when the display supports it.
+    #[must_use]
+    pub fn support_format_preference(&self, preference: SurfaceFormatPreference) -> bool {
+        self.surface_format(preference).is_some()
+    }
+
     /// Clamps the given image count to the supported range of the swapchain. This guarantees that
     /// the returned image count is within the supported range.
     #[must_use]
@@ -429,3 +777,14 @@ impl VulkanSwapchainSupport {
         &self.present_modes
     }
 }
+
+/// Returns whether a surface transform rotates the images by a quarter turn, which swaps their
+/// width and their height.
+const fn is_quarter_turn(transform: vk::SurfaceTransformFlagsKHR) -> bool {
+    transform.intersects(
+        vk::SurfaceTransformFlagsKHR::ROTATE_90
+            .union(vk::SurfaceTransformFlagsKHR::ROTATE_270)
+            .union(vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_90)
+            .union(vk::SurfaceTransformFlagsKHR::HORIZONTAL_MIRROR_ROTATE_270),
+    )
+}
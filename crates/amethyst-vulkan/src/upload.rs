@@ -0,0 +1,339 @@
+//! Asynchronous uploads on the async transfer queue. Uploading the initial data of a buffer
+//! with [`Buffer::new`] blocks until the copy is complete, which is fine when loading a level
+//! but causes hitches when assets are streamed while the game is running. The [`UploadQueue`]
+//! instead submits the copies without waiting for them, and signals a timeline semaphore once
+//! they are complete, so that the graphics queue waits for the uploaded resources on the GPU.
+//!
+//! When the device has a dedicated transfer queue family, the uploaded resources are owned by
+//! the transfer family after the copy. Their ownership is released to the main queue family at
+//! the end of the upload, and must be acquired by the main queue with [`UploadQueue::acquire`]
+//! before they are used.
+use crate::{
+    buffer::{
+        Buffer, BufferAccess, BufferAllocator, BufferCreateInfo, BufferDataInfo,
+        BufferMemoryLocation, BufferTransfert, BufferUsage, BufferUsageInfo,
+    },
+    command::{
+        Capability, CommandBuffer, CommandPool, PipelineBarrierInfo, Recording, SubmitInfo,
+        Transfer,
+    },
+    device::{VulkanDevice, VulkanQueues},
+    image::{layout_access, texel_size, Image},
+    semaphore::TimelineSemaphore,
+};
+use std::{collections::VecDeque, sync::Arc};
+use vulkanalia::prelude::v1_3::*;
+
+/// Uploads buffers and images on the async transfer queue, or on the main queue if the device
+/// does not have one. The queue must not be used by another thread at the same time, which
+/// includes the uploads of the [`BufferAllocator`].
+///
+/// Each upload returns the value the [semaphore](UploadQueue::semaphore) reaches once it is
+/// complete. A submission using the uploaded resources must wait for the semaphore to reach
+/// this value, and must record the barriers of [`UploadQueue::acquire`] first.
+///
+/// # Important
+/// The order of fields in the struct is important to ensure that the staging buffers are
+/// destroyed before the command pool and the semaphore.
+#[derive(Debug)]
+pub struct UploadQueue {
+    /// The staging buffers of the uploads still executed by the GPU, with the value of the
+    /// semaphore signaled once they are complete.
+    in_flight: VecDeque<(u64, Buffer)>,
+
+    /// The barriers acquiring the ownership of the buffers uploaded since the last call to
+    /// [`UploadQueue::acquire`].
+    acquire_buffers: Vec<vk::BufferMemoryBarrier>,
+
+    /// The barriers acquiring the ownership of the images uploaded since the last call to
+    /// [`UploadQueue::acquire`].
+    acquire_images: Vec<vk::ImageMemoryBarrier>,
+
+    /// The command pool used to record the uploads.
+    pool: CommandPool<Transfer>,
+
+    /// The semaphore signaled with the value of each upload once it is complete.
+    semaphore: TimelineSemaphore,
+
+    /// The value signaled by the last submitted upload.
+    value: u64,
+
+    /// The queue on which the uploads are submitted.
+    queue: vk::Queue,
+
+    /// The transfer and the main queue families, or `None` if the uploads are done on the
+    /// main queue family and no ownership transfer is needed.
+    families: Option<[u32; 2]>,
+
+    /// The allocator of the staging buffers.
+    allocator: Arc<BufferAllocator>,
+}
+
+impl UploadQueue {
+    /// Create a new upload queue, allocating its staging buffers with the given allocator.
+    #[must_use]
+    pub fn new(device: &Arc<VulkanDevice>, allocator: Arc<BufferAllocator>) -> Self {
+        let queues = VulkanQueues::fetch(device);
+        let main_family = device.queues_info().main_family();
+        let families = device
+            .queues_info()
+            .async_transfer_family()
+            .map(|family| [family, main_family]);
+
+        Self {
+            in_flight: VecDeque::new(),
+            acquire_buffers: Vec::new(),
+            acquire_images: Vec::new(),
+            pool: CommandPool::transfer(device.clone(), vk::CommandPoolCreateFlags::TRANSIENT),
+            semaphore: TimelineSemaphore::new(device.clone(), 0),
+            value: 0,
+            queue: queues.async_transfer().unwrap_or(queues.main()),
+            families,
+            allocator,
+        }
+    }
+
+    /// Copy the given data to the start of a buffer, and returns the value of the semaphore
+    /// signaled once the copy is complete. The buffer must be a transfer destination, and
+    /// must not be shared between queue families, which is the case of the buffers created
+    /// without initial data.
+    #[must_use]
+    pub fn upload_buffer<T: Copy>(&mut self, destination: &Buffer, data: &[T]) -> u64 {
+        self.collect();
+        let staging = self.staging(data);
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+
+        let mut command = CommandBuffer::new(&self.pool)
+            .start_recording()
+            .label("async buffer upload")
+            .copy_buffer(&staging, destination, size);
+
+        if let Some([transfer, main]) = self.families {
+            let release = vk::BufferMemoryBarrier::builder()
+                .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                .dst_access_mask(vk::AccessFlags::empty())
+                .src_queue_family_index(transfer)
+                .dst_queue_family_index(main)
+                .buffer(destination.inner())
+                .offset(0)
+                .size(size)
+                .build();
+
+            command = command.pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                dst_stage_mask: vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                buffers_barriers: vec![release],
+                ..Default::default()
+            });
+            self.acquire_buffers.push(vk::BufferMemoryBarrier {
+                src_access_mask: vk::AccessFlags::empty(),
+                dst_access_mask: vk::AccessFlags::MEMORY_READ,
+                ..release
+            });
+        }
+
+        self.value += 1;
+        command.stop_recording().submit(
+            SubmitInfo {
+                queue: self.queue,
+                signal_semaphores: vec![self.semaphore.inner()],
+                signal_values: vec![self.value],
+                ..Default::default()
+            },
+            None,
+        );
+        self.in_flight.push_back((self.value, staging));
+        self.value
+    }
+
+    /// Copy the given texels to the first mip level and array layer of an image, transitioned
+    /// to `vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL` once the copy is complete, and returns
+    /// the value of the semaphore signaled once the upload is complete. The texels must be
+    /// tightly packed, and the image must be a transfer destination that is not used by the
+    /// GPU anymore.
+    ///
+    /// # Panics
+    /// Panics if the size of the texels of the image is known and the data does not cover
+    /// the whole first mip level.
+    #[must_use]
+    pub fn upload_image(&mut self, destination: &Image, data: &[u8]) -> u64 {
+        if let Some(size) = texel_size(destination.format()) {
+            let extent = destination.extent();
+            assert_eq!(
+                data.len(),
+                (size * extent.width * extent.height) as usize,
+                "The data does not match the size of the image"
+            );
+        }
+
+        self.collect();
+        let staging = self.staging(data);
+        let layout = vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL;
+
+        // The previous content of the image is discarded, since the transfer queue cannot
+        // synchronize with the stages of the main queue that may have used it.
+        let copy_layout = vk::ImageLayout::TRANSFER_DST_OPTIMAL;
+        let discard = destination
+            .record_transition(0..1, 0..1, copy_layout)
+            .into_iter()
+            .map(|(_, range)| {
+                vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::empty())
+                    .dst_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .old_layout(vk::ImageLayout::UNDEFINED)
+                    .new_layout(copy_layout)
+                    .subresource_range(range)
+                    .image(destination.inner())
+                    .build()
+            })
+            .collect();
+
+        let command = CommandBuffer::new(&self.pool)
+            .start_recording()
+            .label("async image upload")
+            .pipeline_barrier(PipelineBarrierInfo {
+                src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
+                dst_stage_mask: vk::PipelineStageFlags::TRANSFER,
+                images_barriers: discard,
+                ..Default::default()
+            })
+            .copy_buffer_to_image(&staging, destination, copy_layout);
+
+        // The layout transition is done by the release barrier, and repeated by the acquire
+        // barrier when the ownership is transferred.
+        let (transfer, main) = match self.families {
+            Some([transfer, main]) => (transfer, main),
+            None => (vk::QUEUE_FAMILY_IGNORED, vk::QUEUE_FAMILY_IGNORED),
+        };
+        let release = destination
+            .record_transition(0..1, 0..1, layout)
+            .into_iter()
+            .map(|(old, range)| {
+                vk::ImageMemoryBarrier::builder()
+                    .src_access_mask(vk::AccessFlags::TRANSFER_WRITE)
+                    .dst_access_mask(vk::AccessFlags::empty())
+                    .old_layout(old)
+                    .new_layout(layout)
+                    .src_queue_family_index(transfer)
+                    .dst_queue_family_index(main)
+                    .subresource_range(range)
+                    .image(destination.inner())
+                    .build()
+            })
+            .collect::<Vec<_>>();
+
+        if self.families.is_some() {
+            let (_, dst_access) = layout_access(layout);
+            self.acquire_images
+                .extend(release.iter().map(|&barrier| vk::ImageMemoryBarrier {
+                    src_access_mask: vk::AccessFlags::empty(),
+                    dst_access_mask: dst_access,
+                    ..barrier
+                }));
+        }
+
+        let command = command.pipeline_barrier(PipelineBarrierInfo {
+            src_stage_mask: vk::PipelineStageFlags::TRANSFER,
+            dst_stage_mask: vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+            images_barriers: release,
+            ..Default::default()
+        });
+
+        self.value += 1;
+        command.stop_recording().submit(
+            SubmitInfo {
+                queue: self.queue,
+                signal_semaphores: vec![self.semaphore.inner()],
+                signal_values: vec![self.value],
+                ..Default::default()
+            },
+            None,
+        );
+        self.in_flight.push_back((self.value, staging));
+        self.value
+    }
+
+    /// Record the barriers acquiring the ownership of the resources uploaded since the last
+    /// call, on a command buffer of the main queue family. The submission of this command
+    /// buffer must wait for the semaphore to reach the [value](UploadQueue::value) of the
+    /// last upload.
+    #[must_use]
+    pub fn acquire<'pool, Q: Capability>(
+        &mut self,
+        command: CommandBuffer<'pool, Recording, Q>,
+    ) -> CommandBuffer<'pool, Recording, Q> {
+        if self.acquire_buffers.is_empty() && self.acquire_images.is_empty() {
+            return command;
+        }
+
+        command.pipeline_barrier(PipelineBarrierInfo {
+            src_stage_mask: vk::PipelineStageFlags::TOP_OF_PIPE,
+            dst_stage_mask: vk::PipelineStageFlags::ALL_COMMANDS,
+            buffers_barriers: std::mem::take(&mut self.acquire_buffers),
+            images_barriers: std::mem::take(&mut self.acquire_images),
+        })
+    }
+
+    /// Returns whether the upload that returned the given value is complete.
+    #[must_use]
+    pub fn is_complete(&self, value: u64) -> bool {
+        self.semaphore.value() >= value
+    }
+
+    /// Returns the value of the semaphore signaled once all the uploads submitted so far are
+    /// complete.
+    #[must_use]
+    pub const fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns the timeline semaphore signaled by the uploads.
+    #[must_use]
+    pub const fn semaphore(&self) -> &TimelineSemaphore {
+        &self.semaphore
+    }
+
+    /// Destroy the staging buffers of the completed uploads, and reuse the command buffers
+    /// once all the uploads are complete. This is done before each upload, but can also be
+    /// called regularly to free the staging memory sooner.
+    pub fn collect(&mut self) {
+        let completed = self.semaphore.value();
+        while self
+            .in_flight
+            .front()
+            .is_some_and(|&(value, _)| value <= completed)
+        {
+            self.in_flight.pop_front();
+        }
+
+        if self.in_flight.is_empty() {
+            self.pool.reset(vk::CommandPoolResetFlags::empty());
+        }
+    }
+
+    /// Create a host visible staging buffer holding the given data.
+    fn staging<T>(&self, data: &[T]) -> Buffer {
+        Buffer::new(
+            self.allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsageInfo {
+                    location: BufferMemoryLocation::PreferHostVisible,
+                    transfer: BufferTransfert::Source,
+                    access: BufferAccess::Sequential,
+                    usage: BufferUsage::None,
+                    ..Default::default()
+                },
+                data: BufferDataInfo::Slice(data),
+                ..Default::default()
+            },
+        )
+    }
+}
+
+impl Drop for UploadQueue {
+    fn drop(&mut self) {
+        // The staging buffers and the command buffers must not be destroyed while the GPU is
+        // still executing the uploads.
+        self.semaphore.wait(self.value);
+    }
+}
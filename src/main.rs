@@ -1,4 +1,4 @@
-use amethyst::{prelude::*, render::AmethystRender};
+use amethyst::{prelude::*, render::AmethystRender, vulkan::context::ApplicationInfo};
 use bevy::prelude::*;
 
 /// This example illustrates how to create a simple amethyst application with a
@@ -15,6 +15,10 @@ pub fn main() {
             }),
             ..default()
         }))
+        .insert_resource(ApplicationInfo {
+            name: "Amesthyst".to_string(),
+            version: (0, 1, 0),
+        })
         .add_plugins(PlayerPlugin)
         .add_plugins(AmethystRender)
         .run();